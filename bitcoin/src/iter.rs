@@ -204,6 +204,7 @@ mod tests {
         trait BitcoinCoreApi {
             async fn wait_for_block(&self, height: u32, delay: Duration, num_confirmations: u32) -> Result<BlockHash, Error>;
             async fn get_block_count(&self) -> Result<u64, Error>;
+            async fn get_balance(&self) -> Result<u64, Error>;
             async fn get_raw_tx_for(&self, txid: &Txid, block_hash: &BlockHash) -> Result<Vec<u8>, Error>;
             async fn get_proof_for(&self, txid: Txid, block_hash: &BlockHash) -> Result<Vec<u8>, Error>;
            async  fn get_block_hash_for(&self, height: u32) -> Result<BlockHash, Error>;
@@ -214,7 +215,9 @@ mod tests {
                 &self,
                 public_key: P,
                 secret_key: Vec<u8>,
-            ) -> Result<(), Error>;
+                rescan: bool,
+            ) -> Result<bool, Error>;
+            async fn rescan_blockchain(&self, start_height: u32) -> Result<(), Error>;
             async fn get_best_block_hash(&self) -> Result<BlockHash, Error>;
             async fn get_block(&self, hash: &BlockHash) -> Result<Block, Error>;
             async fn get_block_info(&self, hash: &BlockHash) -> Result<GetBlockResult, Error>;
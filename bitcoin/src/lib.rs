@@ -61,6 +61,9 @@ pub trait BitcoinCoreApi {
 
     async fn get_block_count(&self) -> Result<u64, Error>;
 
+    /// Returns the confirmed balance of the wallet in satoshis.
+    async fn get_balance(&self) -> Result<u64, Error>;
+
     async fn get_raw_tx_for(&self, txid: &Txid, block_hash: &BlockHash) -> Result<Vec<u8>, Error>;
 
     async fn get_proof_for(&self, txid: Txid, block_hash: &BlockHash) -> Result<Vec<u8>, Error>;
@@ -75,11 +78,18 @@ pub trait BitcoinCoreApi {
         &self,
     ) -> Result<P, Error>;
 
+    /// Imports the deposit key unless the wallet already has it, returns whether it was
+    /// imported. Without `rescan`, payments made to the key before the import are only
+    /// found by a later `rescan_blockchain`.
     async fn add_new_deposit_key<P: Into<[u8; PUBLIC_KEY_SIZE]> + Send + Sync + 'static>(
         &self,
         public_key: P,
         secret_key: Vec<u8>,
-    ) -> Result<(), Error>;
+        rescan: bool,
+    ) -> Result<bool, Error>;
+
+    /// Rescans the blocks from the given height for transactions of the wallet.
+    async fn rescan_blockchain(&self, start_height: u32) -> Result<(), Error>;
 
     async fn get_best_block_hash(&self) -> Result<BlockHash, Error>;
 
@@ -138,13 +148,22 @@ pub trait BitcoinCoreApi {
 
 pub struct LockedTransaction {
     pub transaction: Transaction,
-    _lock: OwnedMutexGuard<()>,
+    _lock: Option<OwnedMutexGuard<()>>,
 }
 impl LockedTransaction {
     pub fn new(transaction: Transaction, lock: OwnedMutexGuard<()>) -> Self {
         LockedTransaction {
             transaction,
-            _lock: lock,
+            _lock: Some(lock),
+        }
+    }
+
+    /// Wrap a transaction that was funded and signed earlier, e.g. to broadcast it again.
+    /// No lock is needed since its inputs were selected already.
+    pub fn signed(transaction: Transaction) -> Self {
+        LockedTransaction {
+            transaction,
+            _lock: None,
         }
     }
 }
@@ -257,6 +276,10 @@ impl BitcoinCoreApi for BitcoinCore {
         Ok(self.rpc.get_block_count()?)
     }
 
+    async fn get_balance(&self) -> Result<u64, Error> {
+        Ok(self.rpc.get_balance(None, None)?.as_sat())
+    }
+
     /// Get the raw transaction identified by `Txid` and stored
     /// in the specified block.
     ///
@@ -331,12 +354,14 @@ impl BitcoinCoreApi for BitcoinCore {
         Ok(P::from(public_key.key.serialize()))
     }
 
-    /// Derive and import the private key for the master public key and public secret
+    /// Derive and import the private key for the master public key and public secret,
+    /// unless the wallet already has it
     async fn add_new_deposit_key<P: Into<[u8; PUBLIC_KEY_SIZE]> + Send + Sync + 'static>(
         &self,
         public_key: P,
         secret_key: Vec<u8>,
-    ) -> Result<(), Error> {
+        rescan: bool,
+    ) -> Result<bool, Error> {
         let address = Address::p2wpkh(&PublicKey::from_slice(&public_key.into())?, self.network)
             .map_err(|err| ConversionError::from(err))?;
         let private_key = self.rpc.dump_private_key(&address)?;
@@ -344,15 +369,28 @@ impl BitcoinCoreApi for BitcoinCore {
             private_key.key,
             SecretKey::from_slice(&secret_key)?,
         )?;
-        self.rpc.import_private_key(
-            &PrivateKey {
-                compressed: private_key.compressed,
-                network: self.network,
-                key: deposit_secret_key,
-            },
-            None,
-            None,
-        )?;
+        let deposit_key = PrivateKey {
+            compressed: private_key.compressed,
+            network: self.network,
+            key: deposit_secret_key,
+        };
+
+        let deposit_address = Address::p2wpkh(
+            &deposit_key.public_key(&secp256k1::Secp256k1::new()),
+            self.network,
+        )
+        .map_err(|err| ConversionError::from(err))?;
+        if self.rpc.get_address_info(&deposit_address)?.is_mine == Some(true) {
+            return Ok(false);
+        }
+        self.rpc
+            .import_private_key(&deposit_key, None, Some(rescan))?;
+        Ok(true)
+    }
+
+    async fn rescan_blockchain(&self, start_height: u32) -> Result<(), Error> {
+        self.rpc
+            .rescan_blockchain(Some(start_height as usize), None)?;
         Ok(())
     }
 
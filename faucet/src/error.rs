@@ -2,6 +2,7 @@ use chrono::ParseError;
 use jsonrpc_http_server::jsonrpc_core::Error as JsonRpcError;
 use kv::Error as KVError;
 use parity_scale_codec::Error as CodecError;
+use runtime::json::{ApiErrorData, ApiErrorKind};
 use runtime::Error as RuntimeError;
 use std::net::AddrParseError;
 use thiserror::Error;
@@ -25,3 +26,16 @@ pub enum Error {
     #[error("Mathematical operation error")]
     MathError,
 }
+
+impl From<&Error> for ApiErrorData {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::RuntimeError(err) => err.into(),
+            Error::JsonRpcError(_) | Error::CodecError(_) => {
+                ApiErrorData::new(ApiErrorKind::InvalidParams)
+            }
+            Error::FaucetOveruseError => ApiErrorData::new(ApiErrorKind::Rejected),
+            _ => ApiErrorData::new(ApiErrorKind::Internal),
+        }
+    }
+}
@@ -10,8 +10,10 @@ use kv::*;
 use log::error;
 use parity_scale_codec::{Decode, Encode};
 use runtime::{
+    json::{self, MethodSchema},
     AccountId, DotBalancesPallet, PolkaBtcProvider, SecurityPallet, VaultRegistryPallet,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer};
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -26,13 +28,17 @@ fn parse_params<T: Decode>(params: Params) -> Result<T, Error> {
     Ok(req)
 }
 
+fn log_error<T>(resp: Result<T, Error>) -> Result<T, Error> {
+    if let Err(err) = &resp {
+        error!("Error: {}", err.to_string());
+    }
+    resp
+}
+
 fn handle_resp<T: Encode>(resp: Result<T, Error>) -> Result<Value, JsonRpcError> {
-    match resp {
+    match log_error(resp) {
         Ok(data) => Ok(format!("0x{}", hex::encode(data.encode())).into()),
-        Err(err) => {
-            error!("Error: {}", err.to_string());
-            Err(JsonRpcError::invalid_request())
-        }
+        Err(err) => Err(json::json_rpc_error(err)),
     }
 }
 
@@ -54,8 +60,10 @@ fn _system_health(api: &Arc<PolkaBtcProvider>) -> Result<(), Error> {
     Ok(())
 }
 
-#[derive(Encode, Decode, Debug, Clone)]
+#[derive(Encode, Decode, Deserialize, JsonSchema, Debug, Clone)]
 struct FundAccountJsonRpcRequest {
+    #[serde(with = "json::account_id")]
+    #[schemars(with = "String")]
     pub account_id: AccountId,
 }
 
@@ -70,6 +78,30 @@ async fn _fund_account_raw(
     fund_account(api, req, store, user_allowance, vault_allowance).await
 }
 
+async fn _fund_account_json(
+    api: &Arc<PolkaBtcProvider>,
+    params: Params,
+    store: Store,
+    user_allowance: u128,
+    vault_allowance: u128,
+) -> Result<(), Error> {
+    let req: FundAccountJsonRpcRequest = json::parse_json_params::<_, Error>(params)?;
+    fund_account(api, req, store, user_allowance, vault_allowance).await
+}
+
+fn _json_schema() -> Result<Vec<MethodSchema>, Error> {
+    Ok(vec![
+        MethodSchema::new::<(), ()>(
+            "json_system_health",
+            "Fails if the parachain is unreachable.",
+        ),
+        MethodSchema::new::<FundAccountJsonRpcRequest, ()>(
+            "json_fund_account",
+            "Transfer the faucet allowance to the account, at most once per cooldown period.",
+        ),
+    ])
+}
+
 async fn get_faucet_amount(
     provider: &Arc<PolkaBtcProvider>,
     id: AccountId,
@@ -168,18 +200,26 @@ pub async fn start(
 ) {
     let mut io = IoHandler::default();
     let api = api.clone();
+    let store = Store::new(Config::new("./kv")).expect("Unable to open kv store");
     {
         let api = api.clone();
         io.add_sync_method("system_health", move |_| handle_resp(_system_health(&api)));
     }
     {
         let api = api.clone();
+        io.add_sync_method("json_system_health", move |_| {
+            json::handle_json_resp(log_error(_system_health(&api)))
+        });
+    }
+    {
+        let api = api.clone();
+        let store = store.clone();
 
         // an async closure is only FnOnce, so we need this workaround
         io.add_method("fund_account", move |params| {
             let api = api.clone();
+            let store = store.clone();
             async move {
-                let store = Store::new(Config::new("./kv")).expect("Unable to open kv store");
                 let result =
                     _fund_account_raw(&api.clone(), params, store, user_allowance, vault_allowance)
                         .await;
@@ -187,6 +227,22 @@ pub async fn start(
             }
         });
     };
+    {
+        let api = api.clone();
+        let store = store.clone();
+        io.add_method("json_fund_account", move |params| {
+            let api = api.clone();
+            let store = store.clone();
+            async move {
+                let result =
+                    _fund_account_json(&api, params, store, user_allowance, vault_allowance).await;
+                json::handle_json_resp(log_error(result))
+            }
+        });
+    }
+    io.add_sync_method("json_schema", move |_| {
+        json::handle_json_resp(log_error(_json_schema()))
+    });
 
     let server = ServerBuilder::new(io)
         .health_api(("/health", "system_health"))
@@ -248,7 +304,7 @@ mod tests {
             author: "Interlay Ltd",
             copyright_start_year: 2020,
             db: DatabaseConfig::ParityDb {
-                path: tmp.path().join("db")
+                path: tmp.path().join("db"),
             },
             keystore: KeystoreConfig::Path {
                 path: tmp.path().join("keystore"),
@@ -3,8 +3,7 @@ mod http;
 
 use clap::Clap;
 use error::Error;
-use runtime::substrate_subxt::PairSigner;
-use runtime::{PolkaBtcProvider, PolkaBtcRuntime};
+use runtime::PolkaBtcProvider;
 use std::sync::Arc;
 
 /// DOT faucet for enabling users to test PolkaBTC
@@ -41,8 +40,7 @@ async fn main() -> Result<(), Error> {
     env_logger::init();
     let opts: Opts = Opts::parse();
 
-    let (key_pair, _) = opts.account_info.get_key_pair()?;
-    let signer = PairSigner::<PolkaBtcRuntime, _>::new(key_pair);
+    let (signer, _) = opts.account_info.get_signer().await?;
     let provider = Arc::new(PolkaBtcProvider::from_url(opts.polka_btc_url, signer).await?);
 
     let http_addr = opts.http_addr.parse()?;
@@ -0,0 +1,365 @@
+use crate::{json, Error};
+use jsonrpc_core::{
+    futures::{
+        future::{self, Either},
+        Future, FutureExt,
+    },
+    Call, FutureOutput, FutureResponse, Metadata, Middleware, Output,
+};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sp_core::hashing::blake2_256;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Role of an api token. Readers may only call read-only methods, admins every method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Read,
+    Admin,
+}
+
+/// The authenticated client of an api request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Caller {
+    /// Name of the token, used in the audit log.
+    pub name: String,
+    pub role: Role,
+}
+
+#[derive(Deserialize)]
+struct TokenEntry {
+    token: String,
+    role: Role,
+}
+
+/// Bearer tokens that grant access to an http api.
+pub struct ApiAuth {
+    /// Callers by the hash of their token, None if authentication is disabled.
+    tokens: Option<HashMap<[u8; 32], Caller>>,
+}
+
+impl ApiAuth {
+    /// Allow every request with the admin role.
+    pub fn disabled() -> Self {
+        Self { tokens: None }
+    }
+
+    /// Load the tokens from a json file mapping names to tokens and their role, e.g.
+    /// `{ "ops": { "token": "<secret>", "role": "admin" } }`.
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let entries: HashMap<String, TokenEntry> = serde_json::from_reader(File::open(path)?)?;
+        Ok(Self::from_entries(entries))
+    }
+
+    fn from_entries(entries: HashMap<String, TokenEntry>) -> Self {
+        Self {
+            tokens: Some(
+                entries
+                    .into_iter()
+                    .map(|(name, entry)| {
+                        (
+                            // only the hash is compared, which does not leak the token
+                            blake2_256(entry.token.as_bytes()),
+                            Caller {
+                                name,
+                                role: entry.role,
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.tokens.is_some()
+    }
+
+    /// Identify the caller from the value of the `Authorization` header, which has to
+    /// contain `Bearer <token>`. Returns None if the token is missing or unknown.
+    pub fn authenticate(&self, authorization: Option<&str>) -> Option<Caller> {
+        let tokens = match &self.tokens {
+            Some(tokens) => tokens,
+            None => {
+                return Some(Caller {
+                    name: "anonymous".to_string(),
+                    role: Role::Admin,
+                })
+            }
+        };
+        let token = authorization?.strip_prefix("Bearer ")?.trim();
+        tokens.get(&blake2_256(token.as_bytes())).cloned()
+    }
+}
+
+/// Check that the caller may call a method that requires the given role, where None means
+/// the method is public.
+pub fn authorize(caller: Option<&Caller>, required: Option<Role>) -> Result<(), Error> {
+    match (required, caller) {
+        (None, _) => Ok(()),
+        (Some(_), None) => Err(Error::Unauthorized),
+        (Some(required), Some(caller)) if caller.role < required => Err(Error::Forbidden(required)),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    /// Seconds since the unix epoch.
+    timestamp: u64,
+    caller: &'a str,
+    method: &'a str,
+    params: &'a Value,
+    /// Message of the error if the call failed.
+    error: Option<&'a str>,
+}
+
+/// Record of the state-changing api calls. Every entry is logged with the target `audit`
+/// and optionally appended to a file as a json line.
+#[derive(Default)]
+pub struct AuditLog {
+    file: Option<Mutex<File>>,
+}
+
+impl AuditLog {
+    pub fn open(path: Option<&str>) -> Result<Self, Error> {
+        Ok(Self {
+            file: match path {
+                Some(path) => Some(Mutex::new(
+                    OpenOptions::new().create(true).append(true).open(path)?,
+                )),
+                None => None,
+            },
+        })
+    }
+
+    pub fn record(&self, caller: &str, method: &str, params: &Value, result: Result<(), &str>) {
+        let entry = AuditEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or_default(),
+            caller,
+            method,
+            params,
+            error: result.err(),
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to encode audit log entry: {}", e);
+                return;
+            }
+        };
+        info!(target: "audit", "{}", line);
+        if let Some(file) = &self.file {
+            if let Err(e) = writeln!(file.lock().unwrap(), "{}", line) {
+                error!("Failed to write audit log: {}", e);
+            }
+        }
+    }
+}
+
+/// Caller of an api request, None if its bearer token is missing or unknown.
+#[derive(Clone, Default)]
+pub struct Meta(pub Option<Caller>);
+
+impl Metadata for Meta {}
+
+/// Rejects calls that the caller is not allowed to make and records the calls that
+/// require the admin role in the audit log.
+pub struct AuthMiddleware {
+    required_role: fn(&str) -> Option<Role>,
+    audit: Arc<AuditLog>,
+}
+
+impl AuthMiddleware {
+    /// `required_role` returns the role required to call a method, None if it is public.
+    pub fn new(required_role: fn(&str) -> Option<Role>, audit: AuditLog) -> Self {
+        Self {
+            required_role,
+            audit: Arc::new(audit),
+        }
+    }
+}
+
+impl Middleware<Meta> for AuthMiddleware {
+    type Future = FutureResponse;
+    type CallFuture = FutureOutput;
+
+    fn on_call<F, X>(&self, call: Call, meta: Meta, next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, Meta) -> X + Send + Sync,
+        X: Future<Output = Option<Output>> + Send + 'static,
+    {
+        let (method, params) = match &call {
+            Call::MethodCall(call) => (call.method.clone(), call.params.clone()),
+            Call::Notification(notification) => {
+                (notification.method.clone(), notification.params.clone())
+            }
+            Call::Invalid { .. } => return Either::Right(next(call, meta)),
+        };
+        let required = (self.required_role)(&method);
+        if let Err(err) = authorize(meta.0.as_ref(), required) {
+            let output = match call {
+                Call::MethodCall(call) => Some(Output::from(
+                    Err(json::json_rpc_error(err)),
+                    call.id,
+                    call.jsonrpc,
+                )),
+                _ => None,
+            };
+            return Either::Left(Box::pin(future::ready(output)));
+        }
+
+        let caller = match (&meta.0, required) {
+            (Some(caller), Some(Role::Admin)) => caller.name.clone(),
+            _ => return Either::Right(next(call, meta)),
+        };
+        let audit = self.audit.clone();
+        Either::Left(Box::pin(next(call, meta).map(move |output| {
+            let params = serde_json::to_value(params).unwrap_or_default();
+            let result = match &output {
+                Some(Output::Failure(failure)) => Err(failure.error.message.as_str()),
+                _ => Ok(()),
+            };
+            audit.record(&caller, &method, &params, result);
+            output
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::ApiErrorKind;
+    use jsonrpc_core::{Error as JsonRpcError, MetaIoHandler};
+    use tempdir::TempDir;
+
+    fn auth() -> ApiAuth {
+        ApiAuth::from_entries(
+            serde_json::from_value(serde_json::json!({
+                "dashboard": { "token": "read-secret", "role": "read" },
+                "ops": { "token": "admin-secret", "role": "admin" },
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let auth = auth();
+        assert_eq!(
+            auth.authenticate(Some("Bearer admin-secret")),
+            Some(Caller {
+                name: "ops".to_string(),
+                role: Role::Admin
+            })
+        );
+        assert_eq!(auth.authenticate(Some("Bearer wrong")), None);
+        assert_eq!(auth.authenticate(Some("admin-secret")), None);
+        assert_eq!(auth.authenticate(None), None);
+        assert!(ApiAuth::disabled().authenticate(None).is_some());
+    }
+
+    #[test]
+    fn test_authorize() {
+        let auth = auth();
+        let reader = auth.authenticate(Some("Bearer read-secret"));
+        let admin = auth.authenticate(Some("Bearer admin-secret"));
+        assert!(authorize(None, None).is_ok());
+        assert!(matches!(
+            authorize(None, Some(Role::Read)),
+            Err(Error::Unauthorized)
+        ));
+        assert!(authorize(reader.as_ref(), Some(Role::Read)).is_ok());
+        assert!(matches!(
+            authorize(reader.as_ref(), Some(Role::Admin)),
+            Err(Error::Forbidden(Role::Admin))
+        ));
+        assert!(authorize(admin.as_ref(), Some(Role::Admin)).is_ok());
+    }
+
+    fn required_role(method: &str) -> Option<Role> {
+        match method {
+            "health" => None,
+            "balance" => Some(Role::Read),
+            _ => Some(Role::Admin),
+        }
+    }
+
+    fn io_handler(audit: AuditLog) -> MetaIoHandler<Meta, AuthMiddleware> {
+        let mut io = MetaIoHandler::with_middleware(AuthMiddleware::new(required_role, audit));
+        io.add_sync_method("health", |_| Ok(Value::Null));
+        io.add_sync_method("balance", |_| Ok(Value::Null));
+        io.add_sync_method("withdraw", |_| Ok(Value::Null));
+        io.add_sync_method("register", |_| Err(JsonRpcError::invalid_params("bad key")));
+        io
+    }
+
+    /// Call the method as the caller with the given token, returns the error code if it failed.
+    fn call(
+        io: &MetaIoHandler<Meta, AuthMiddleware>,
+        token: Option<&str>,
+        method: &str,
+    ) -> Option<i64> {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": { "amount": "100" },
+        });
+        let meta = Meta(auth().authenticate(token));
+        let response = io.handle_request_sync(&request.to_string(), meta).unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+        response["error"]["code"].as_i64()
+    }
+
+    #[test]
+    fn test_middleware_authorizes_calls() {
+        let io = io_handler(AuditLog::default());
+        let unauthorized = Some(ApiErrorKind::Unauthorized.code());
+        let forbidden = Some(ApiErrorKind::Forbidden.code());
+
+        // public methods need no token
+        assert_eq!(call(&io, None, "health"), None);
+        assert_eq!(call(&io, None, "balance"), unauthorized);
+        assert_eq!(call(&io, Some("Bearer wrong"), "balance"), unauthorized);
+        assert_eq!(call(&io, Some("read-secret"), "balance"), unauthorized);
+        assert_eq!(call(&io, Some("Bearer read-secret"), "balance"), None);
+        assert_eq!(call(&io, Some("Bearer read-secret"), "withdraw"), forbidden);
+        assert_eq!(call(&io, Some("Bearer admin-secret"), "withdraw"), None);
+    }
+
+    #[test]
+    fn test_middleware_records_admin_calls() {
+        let tmp_dir = TempDir::new("audit-log").unwrap();
+        let path = tmp_dir.path().join("audit.log");
+        let io = io_handler(AuditLog::open(path.to_str()).unwrap());
+
+        call(&io, Some("Bearer admin-secret"), "balance");
+        call(&io, Some("Bearer admin-secret"), "withdraw");
+        // rejected calls are not executed, so they are not recorded
+        call(&io, Some("Bearer read-secret"), "withdraw");
+        call(&io, Some("Bearer admin-secret"), "register");
+
+        let entries = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["caller"], "ops");
+        assert_eq!(entries[0]["method"], "withdraw");
+        assert_eq!(entries[0]["params"], serde_json::json!({ "amount": "100" }));
+        assert_eq!(entries[0]["error"], Value::Null);
+        assert_eq!(entries[1]["method"], "register");
+        assert_eq!(entries[1]["error"], "bad key");
+    }
+}
@@ -0,0 +1,97 @@
+//! Submission of several calls in a single extrinsic, through `utility.batch_all`.
+
+use crate::pallets::utility::BatchAllCall;
+use crate::{Error, ExtrinsicFuncs, PolkaBtcProvider, PolkaBtcRuntime, XtError};
+use core::marker::PhantomData;
+use substrate_subxt::{Call, Encoded};
+
+struct BatchedCall {
+    module: &'static str,
+    function: &'static str,
+    encoded: Encoded,
+}
+
+/// Builder for a batch of calls that are dispatched atomically, see `PolkaBtcProvider::batch`.
+pub struct Batch<'a> {
+    provider: &'a PolkaBtcProvider,
+    /// The first encoding error, if any, is returned on submission.
+    calls: Result<Vec<BatchedCall>, Error>,
+}
+
+impl<'a> Batch<'a> {
+    pub(crate) fn new(provider: &'a PolkaBtcProvider) -> Self {
+        Self {
+            provider,
+            calls: Ok(vec![]),
+        }
+    }
+
+    /// Append the call to the batch, calls are dispatched in the order they are added.
+    pub fn add<C: Call<PolkaBtcRuntime>>(mut self, call: C) -> Self {
+        let provider = self.provider;
+        self.calls = self.calls.and_then(|mut calls| {
+            calls.push(BatchedCall {
+                module: C::MODULE,
+                function: C::FUNCTION,
+                encoded: Encoded(provider.encode_call(call)?.0),
+            });
+            Ok(calls)
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.as_ref().map(Vec::len).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Submit the batch and wait until it is included in a block. Either all calls succeed,
+    /// or none of them take effect. Submitting an empty batch does nothing.
+    ///
+    /// # Errors
+    /// * `BatchCallFailed` - if one of the calls failed to dispatch
+    pub async fn submit(self) -> Result<(), Error> {
+        let calls = self.calls?;
+        if calls.is_empty() {
+            return Ok(());
+        }
+
+        match self.provider.watch(batch_all(&calls)).await {
+            Err(Error::XtError(XtError::Runtime(err))) => {
+                Err(find_failed_call(self.provider, &calls)
+                    .await
+                    .unwrap_or_else(|| {
+                        // the state changed since the batch failed, so report the original error
+                        XtError::Runtime(err).into()
+                    }))
+            }
+            result => result,
+        }
+    }
+}
+
+fn batch_all(calls: &[BatchedCall]) -> BatchAllCall<PolkaBtcRuntime> {
+    BatchAllCall {
+        calls: calls.iter().map(|call| call.encoded.clone()).collect(),
+        _runtime: PhantomData,
+    }
+}
+
+/// `batch_all` does not report which call failed, so dry run increasingly long prefixes of
+/// the batch to find the first call that fails.
+async fn find_failed_call(provider: &PolkaBtcProvider, calls: &[BatchedCall]) -> Option<Error> {
+    for end in 1..=calls.len() {
+        if let Err(err) = provider.dry_run(batch_all(&calls[..end])).await {
+            let call = &calls[end - 1];
+            return Some(Error::BatchCallFailed {
+                index: end - 1,
+                call: format!("{}.{}", call.module, call.function),
+                source: Box::new(err),
+            });
+        }
+    }
+    None
+}
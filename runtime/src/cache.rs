@@ -0,0 +1,123 @@
+use parity_scale_codec::{Decode, Encode};
+use schemars::JsonSchema;
+use serde::Serialize;
+use sp_core::H256;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// Hit and miss counters of a `StorageCache`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Encode, Decode, Serialize, JsonSchema)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Default)]
+struct CachedBlock {
+    number: u32,
+    hash: H256,
+    /// Encoded values by storage key.
+    entries: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+/// Storage values as of the latest finalized parachain block. All entries are dropped when a
+/// newer block is finalized, see `set_finalized_block`.
+#[derive(Default)]
+pub struct StorageCache {
+    block: RwLock<Option<CachedBlock>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl StorageCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the hash of the block whose state is cached, None until the first block is
+    /// finalized.
+    pub fn block_hash(&self) -> Option<H256> {
+        self.block.read().unwrap().as_ref().map(|block| block.hash)
+    }
+
+    /// Cache the state of the given block from now on, unless a newer block was set already.
+    pub fn set_finalized_block(&self, number: u32, hash: H256) {
+        let mut block = self.block.write().unwrap();
+        if matches!(&*block, Some(cached) if cached.number >= number) {
+            return;
+        }
+        *block = Some(CachedBlock {
+            number,
+            hash,
+            entries: HashMap::new(),
+        });
+    }
+
+    /// Returns the encoded value of the storage key in the given block, if cached.
+    pub(crate) fn get(&self, block_hash: H256, key: &[u8]) -> Option<Vec<u8>> {
+        let value = match &*self.block.read().unwrap() {
+            Some(block) if block.hash == block_hash => block.entries.get(key).cloned(),
+            _ => None,
+        };
+        let counter = if value.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// Cache the encoded value of the storage key in the given block. Ignored if another
+    /// block was finalized in the meantime.
+    pub(crate) fn insert(&self, block_hash: H256, key: Vec<u8>, value: Vec<u8>) {
+        if let Some(block) = &mut *self.block.write().unwrap() {
+            if block.hash == block_hash {
+                block.entries.insert(key, value);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_block_invalidates_entries() {
+        let cache = StorageCache::new();
+        let key = vec![1, 2, 3];
+
+        // nothing is cached before the first block is finalized
+        cache.insert(H256::zero(), key.clone(), vec![4]);
+        assert_eq!(cache.get(H256::zero(), &key), None);
+
+        cache.set_finalized_block(1, H256::repeat_byte(1));
+        cache.insert(H256::repeat_byte(1), key.clone(), vec![4]);
+        assert_eq!(cache.get(H256::repeat_byte(1), &key), Some(vec![4]));
+
+        cache.set_finalized_block(2, H256::repeat_byte(2));
+        assert_eq!(cache.get(H256::repeat_byte(2), &key), None);
+        // values fetched from an older block are not cached
+        cache.insert(H256::repeat_byte(1), key.clone(), vec![4]);
+        assert_eq!(cache.get(H256::repeat_byte(2), &key), None);
+
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 3 });
+    }
+
+    #[test]
+    fn test_older_block_is_ignored() {
+        let cache = StorageCache::new();
+        cache.set_finalized_block(2, H256::repeat_byte(2));
+        cache.set_finalized_block(1, H256::repeat_byte(1));
+        assert_eq!(cache.block_hash(), Some(H256::repeat_byte(2)));
+    }
+}
@@ -1,10 +1,19 @@
+use crate::auth::{ApiAuth, AuditLog};
 use crate::error::{Error, KeyLoadingError};
+use crate::keystore::EncryptedAccount;
+use crate::signer::{PolkaBtcSigner, RemoteSigner};
+use crate::PolkaBtcRuntime;
 use clap::Clap;
 
+use serde::Deserialize;
+use sp_core::crypto::Ss58Codec;
 use sp_core::sr25519::Pair;
 use sp_core::Pair as _;
 use sp_keyring::AccountKeyring;
 use std::collections::HashMap;
+use std::io::BufRead;
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+use substrate_subxt::{PairSigner, Signer};
 
 #[derive(Clap, Debug, Clone)]
 pub struct ProviderUserOpts {
@@ -14,30 +23,124 @@ pub struct ProviderUserOpts {
 
     /// Path to the json file containing key pairs in a map.
     /// Valid content of this file is e.g.
-    /// `{ "MyUser1": "<Polkadot Account Mnemonic>", "MyUser2": "<Polkadot Account Mnemonic>" }`.
+    /// `{ "MyUser1": "<Polkadot Account Mnemonic>", "MyUser2": <polkadot-js JSON export> }`.
     #[clap(long, conflicts_with = "keyring", requires = "keyname")]
     pub keyfile: Option<String>,
 
     /// The name of the account from the keyfile to use.
     #[clap(long, conflicts_with = "keyring", requires = "keyfile")]
     pub keyname: Option<String>,
+
+    /// Path to an account exported from polkadot-js, encrypted with a password.
+    /// Mutually exclusive with keyring and keyfile.
+    #[clap(long, conflicts_with_all = &["keyring", "keyfile"])]
+    pub keystore: Option<String>,
+
+    /// Name of the environment variable containing the password of the encrypted account.
+    /// If neither this nor the password file descriptor is set, the password is prompted for.
+    #[clap(long, conflicts_with = "password-fd")]
+    pub password_env: Option<String>,
+
+    /// File descriptor to read the password of the encrypted account from,
+    /// e.g. `--password-fd 3 3< password.txt`.
+    #[clap(long)]
+    pub password_fd: Option<RawFd>,
+
+    /// Path to the unix socket of an external signer, which holds the key instead of this
+    /// client. Mutually exclusive with keyring, keyfile and keystore.
+    #[clap(long, conflicts_with_all = &["keyring", "keyfile", "keystore"])]
+    pub remote_signer: Option<String>,
 }
 
 impl ProviderUserOpts {
     /// Get the key pair and the username, the latter of which is used for wallet selection.
     pub fn get_key_pair(&self) -> Result<(Pair, String), Error> {
         // load parachain credentials
-        let (pair, user_name) = match (self.keyfile.as_ref(), self.keyname.as_ref(), &self.keyring)
-        {
-            (Some(file_path), Some(keyname), None) => (
-                get_credentials_from_file(&file_path, &keyname)?,
+        let (pair, user_name) = match (
+            self.keyfile.as_ref(),
+            self.keyname.as_ref(),
+            &self.keyring,
+            self.keystore.as_ref(),
+        ) {
+            (Some(file_path), Some(keyname), None, None) => (
+                get_credentials_from_file(&file_path, &keyname, || self.get_password())?,
                 keyname.to_string(),
             ),
-            (None, None, Some(keyring)) => (keyring.pair(), format!("{}", keyring)),
-            _ => panic!("Invalid arguments"), // should never occur, due to clap constraints
+            (None, None, Some(keyring), None) => (keyring.pair(), format!("{}", keyring)),
+            (None, None, None, Some(file_path)) => {
+                let account = EncryptedAccount::from_file(file_path)?;
+                let pair = account.decrypt(&self.get_password()?)?;
+                let user_name = account.name().unwrap_or(&account.address).to_string();
+                (pair, user_name)
+            }
+            (None, None, None, None) if self.remote_signer.is_some() => {
+                return Err(KeyLoadingError::RemoteSignerUnsupported.into())
+            }
+            _ => return Err(KeyLoadingError::NoKey.into()),
         };
         Ok((pair, user_name))
     }
+
+    /// Get the signer and the username, the latter of which is used for wallet selection.
+    /// Unlike `get_key_pair`, this also supports the remote signer.
+    pub async fn get_signer(&self) -> Result<(PolkaBtcSigner, String), Error> {
+        match &self.remote_signer {
+            Some(socket_path) => {
+                let signer = RemoteSigner::connect(socket_path).await?;
+                let user_name = signer.account_id().to_ss58check();
+                Ok((signer.into(), user_name))
+            }
+            None => {
+                let (pair, user_name) = self.get_key_pair()?;
+                Ok((
+                    PairSigner::<PolkaBtcRuntime, _>::new(pair).into(),
+                    user_name,
+                ))
+            }
+        }
+    }
+
+    /// Get the password of an encrypted account from the configured source.
+    fn get_password(&self) -> Result<String, KeyLoadingError> {
+        if let Some(var) = &self.password_env {
+            std::env::var(var).map_err(|_| KeyLoadingError::PasswordNotSet(var.clone()))
+        } else if let Some(fd) = self.password_fd {
+            read_password_from_fd(fd)
+        } else {
+            Ok(rpassword::read_password_from_tty(Some("Password: "))?)
+        }
+    }
+}
+
+#[derive(Clap, Debug, Clone)]
+pub struct ApiAuthOpts {
+    /// Path to the json file containing the bearer tokens of the api and their role,
+    /// either "read" or "admin", e.g.
+    /// `{ "dashboard": { "token": "<secret>", "role": "read" }, "ops": { "token": "<secret>", "role": "admin" } }`.
+    /// If not set, the api is unauthenticated and should only listen on localhost.
+    #[clap(long)]
+    pub rpc_auth_file: Option<String>,
+
+    /// Path to a file to which every state-changing api call is appended as a json line.
+    #[clap(long)]
+    pub rpc_audit_log: Option<String>,
+}
+
+impl ApiAuthOpts {
+    pub fn load(&self) -> Result<(ApiAuth, AuditLog), Error> {
+        let auth = match &self.rpc_auth_file {
+            Some(path) => ApiAuth::from_file(path)?,
+            None => ApiAuth::disabled(),
+        };
+        Ok((auth, AuditLog::open(self.rpc_audit_log.as_deref())?))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyfileEntry {
+    Mnemonic(String),
+    Encrypted(EncryptedAccount),
 }
 
 /// Loads the credentials for the given user from the keyfile
@@ -46,12 +149,97 @@ impl ProviderUserOpts {
 ///
 /// * `file_path` - path to the json file containing the credentials
 /// * `keyname` - name of the key to get
-fn get_credentials_from_file(file_path: &str, keyname: &str) -> Result<Pair, KeyLoadingError> {
+/// * `get_password` - called if the key is encrypted
+fn get_credentials_from_file<F>(
+    file_path: &str,
+    keyname: &str,
+    get_password: F,
+) -> Result<Pair, KeyLoadingError>
+where
+    F: FnOnce() -> Result<String, KeyLoadingError>,
+{
     let file = std::fs::File::open(file_path)?;
     let reader = std::io::BufReader::new(file);
-    let map: HashMap<String, String> = serde_json::from_reader(reader)?;
-    let pair_str = map.get(keyname).ok_or(KeyLoadingError::KeyNotFound)?;
-    let pair =
-        Pair::from_string(pair_str, None).map_err(|e| KeyLoadingError::SecretStringError(e))?;
-    Ok(pair)
+    let map: HashMap<String, KeyfileEntry> = serde_json::from_reader(reader)?;
+    match map.get(keyname).ok_or(KeyLoadingError::KeyNotFound)? {
+        KeyfileEntry::Mnemonic(pair_str) => {
+            Pair::from_string(pair_str, None).map_err(KeyLoadingError::SecretStringError)
+        }
+        KeyfileEntry::Encrypted(account) => account.decrypt(&get_password()?),
+    }
+}
+
+/// Reads the first line from the given file descriptor. The descriptor itself is left open,
+/// so calling this twice is safe, although the second call continues where the first stopped.
+fn read_password_from_fd(fd: RawFd) -> Result<String, KeyLoadingError> {
+    // safety: ownership is released again below, the line is read from a duplicate
+    // of the descriptor so that it is never closed twice
+    let file = unsafe { std::fs::File::from_raw_fd(fd) };
+    let duplicate = file.try_clone();
+    let _ = file.into_raw_fd();
+    let mut password = String::new();
+    std::io::BufReader::new(duplicate?).read_line(&mut password)?;
+    Ok(password.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keystore::tests::export_json;
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+    use tempdir::TempDir;
+
+    fn write_keyfile(dir: &TempDir, content: serde_json::Value) -> String {
+        let path = dir.path().join("keyfile.json");
+        std::fs::write(&path, content.to_string()).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_get_encrypted_credentials_from_file() {
+        let dir = TempDir::new("keyfile").unwrap();
+        let pair = AccountKeyring::Bob.pair();
+        let path = write_keyfile(
+            &dir,
+            serde_json::json!({ "vault": export_json(&pair, "correct horse") }),
+        );
+
+        let loaded =
+            get_credentials_from_file(&path, "vault", || Ok("correct horse".to_string())).unwrap();
+        assert_eq!(loaded.public(), pair.public());
+
+        assert!(matches!(
+            get_credentials_from_file(&path, "vault", || Ok("battery staple".to_string())),
+            Err(KeyLoadingError::DecryptionFailure)
+        ));
+    }
+
+    #[test]
+    fn test_get_mnemonic_credentials_from_file_without_password() {
+        let dir = TempDir::new("keyfile").unwrap();
+        let path = write_keyfile(&dir, serde_json::json!({ "vault": "//Alice" }));
+
+        let loaded =
+            get_credentials_from_file(&path, "vault", || panic!("password requested")).unwrap();
+        assert_eq!(loaded.public(), AccountKeyring::Alice.pair().public());
+    }
+
+    #[test]
+    fn test_read_password_from_fd_leaves_fd_open() {
+        let dir = TempDir::new("password").unwrap();
+        let path = dir.path().join("password.txt");
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(b"correct horse\n")
+            .unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+
+        assert_eq!(
+            read_password_from_fd(file.as_raw_fd()).unwrap(),
+            "correct horse"
+        );
+        // the descriptor is still owned by `file` and can be read again
+        assert_eq!(read_password_from_fd(file.as_raw_fd()).unwrap(), "");
+    }
 }
@@ -0,0 +1,558 @@
+//! Checks that the runtime of the connected parachain matches the pallet definitions
+//! in this crate, such that calls are encoded and events are decoded correctly.
+
+use crate::pallets::*;
+use crate::{Error, PolkaBtcRuntime};
+use frame_metadata::{
+    DecodeDifferent, RuntimeMetadata, RuntimeMetadataPrefixed, RuntimeMetadataV12,
+    StorageEntryType, META_RESERVED,
+};
+use jsonrpsee::{common::Params, Client as RpcClient};
+use log::warn;
+use parity_scale_codec::{Decode, Encode};
+use serde::Deserialize;
+use sp_core::Bytes;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use substrate_subxt::{Call, Event, Store};
+
+/// Spec versions of the parachain runtime this client was verified against. Other versions
+/// are accepted if the items in `pallets` match their metadata, but logged as a warning, since
+/// an upgrade may change the semantics of an item while keeping its signature.
+pub const SUPPORTED_SPEC_VERSIONS: RangeInclusive<u32> =
+    btc_parachain_runtime::VERSION.spec_version..=btc_parachain_runtime::VERSION.spec_version;
+
+/// The subset of the runtime version that determines compatibility.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RuntimeVersion {
+    pub spec_name: String,
+    pub spec_version: u32,
+    pub transaction_version: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+    Call,
+    Event,
+    Storage,
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemKind::Call => write!(f, "call"),
+            ItemKind::Event => write!(f, "event"),
+            ItemKind::Storage => write!(f, "storage"),
+        }
+    }
+}
+
+/// A runtime item which this client relies on. Its signature is taken from the metadata
+/// of the runtime this client was built against, so it can not drift from the runtime types.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Item {
+    kind: ItemKind,
+    module: &'static str,
+    name: &'static str,
+}
+
+fn call<C: Call<PolkaBtcRuntime>>() -> Item {
+    Item {
+        kind: ItemKind::Call,
+        module: C::MODULE,
+        name: C::FUNCTION,
+    }
+}
+
+fn event<E: Event<PolkaBtcRuntime>>() -> Item {
+    Item {
+        kind: ItemKind::Event,
+        module: E::MODULE,
+        name: E::EVENT,
+    }
+}
+
+fn store<S: Store<PolkaBtcRuntime>>() -> Item {
+    Item {
+        kind: ItemKind::Storage,
+        module: S::MODULE,
+        name: S::FIELD,
+    }
+}
+
+/// All calls, events and storage items defined in `pallets`.
+fn expected_items() -> Vec<Item> {
+    vec![
+        call::<balances_dot::TransferCall<'static, PolkaBtcRuntime>>(),
+        call::<balances_polka_btc::TransferCall<'static, PolkaBtcRuntime>>(),
+        call::<btc_relay::InitializeCall<PolkaBtcRuntime>>(),
+        call::<btc_relay::StoreBlockHeaderCall<PolkaBtcRuntime>>(),
+        call::<btc_relay::StoreBlockHeadersCall<PolkaBtcRuntime>>(),
+        call::<exchange_rate_oracle::SetExchangeRateCall<PolkaBtcRuntime>>(),
+        call::<exchange_rate_oracle::SetBtcTxFeesPerByteCall<PolkaBtcRuntime>>(),
+        call::<issue::RequestIssueCall<PolkaBtcRuntime>>(),
+        call::<issue::ExecuteIssueCall<PolkaBtcRuntime>>(),
+        call::<issue::CancelIssueCall<PolkaBtcRuntime>>(),
+        call::<issue::SetIssuePeriodCall<PolkaBtcRuntime>>(),
+        call::<redeem::RequestRedeemCall<PolkaBtcRuntime>>(),
+        call::<redeem::ExecuteRedeemCall<PolkaBtcRuntime>>(),
+        call::<redeem::CancelRedeemCall<PolkaBtcRuntime>>(),
+        call::<redeem::SetRedeemPeriodCall<PolkaBtcRuntime>>(),
+        call::<refund::ExecuteRefundCall<PolkaBtcRuntime>>(),
+        call::<replace::RequestReplaceCall<PolkaBtcRuntime>>(),
+        call::<replace::WithdrawReplaceCall<PolkaBtcRuntime>>(),
+        call::<replace::AcceptReplaceCall<PolkaBtcRuntime>>(),
+        call::<replace::AuctionReplaceCall<PolkaBtcRuntime>>(),
+        call::<replace::ExecuteReplaceCall<PolkaBtcRuntime>>(),
+        call::<replace::CancelReplaceCall<PolkaBtcRuntime>>(),
+        call::<replace::SetReplacePeriodCall<PolkaBtcRuntime>>(),
+        call::<staked_relayers::RegisterStakedRelayerCall<PolkaBtcRuntime>>(),
+        call::<staked_relayers::DeregisterStakedRelayerCall<PolkaBtcRuntime>>(),
+        call::<staked_relayers::SuggestStatusUpdateCall<PolkaBtcRuntime>>(),
+        call::<staked_relayers::VoteOnStatusUpdateCall<PolkaBtcRuntime>>(),
+        call::<staked_relayers::ReportOracleOffline<PolkaBtcRuntime>>(),
+        call::<staked_relayers::ReportVaultTheftCall<PolkaBtcRuntime>>(),
+        call::<staked_relayers::SetMaturityPeriodCall<PolkaBtcRuntime>>(),
+        call::<utility::BatchAllCall<PolkaBtcRuntime>>(),
+        call::<vault_registry::RegisterVaultCall<PolkaBtcRuntime>>(),
+        call::<vault_registry::LockAdditionalCollateralCall<PolkaBtcRuntime>>(),
+        call::<vault_registry::WithdrawCollateralCall<PolkaBtcRuntime>>(),
+        call::<vault_registry::UpdatePublicKeyCall<PolkaBtcRuntime>>(),
+        call::<vault_registry::RegisterAddressCall<PolkaBtcRuntime>>(),
+        event::<balances_dot::ReservedEvent<PolkaBtcRuntime>>(),
+        event::<balances_dot::TransferEvent<PolkaBtcRuntime>>(),
+        event::<balances_polka_btc::TransferEvent<PolkaBtcRuntime>>(),
+        event::<btc_relay::InitializedEvent<PolkaBtcRuntime>>(),
+        event::<btc_relay::StoreMainChainHeaderEvent<PolkaBtcRuntime>>(),
+        event::<collateral::LockCollateralEvent<PolkaBtcRuntime>>(),
+        event::<collateral::ReleaseCollateralEvent<PolkaBtcRuntime>>(),
+        event::<exchange_rate_oracle::SetExchangeRateEvent<PolkaBtcRuntime>>(),
+        event::<exchange_rate_oracle::SetBtcTxFeesPerByteEvent<PolkaBtcRuntime>>(),
+        event::<issue::RequestIssueEvent<PolkaBtcRuntime>>(),
+        event::<issue::ExecuteIssueEvent<PolkaBtcRuntime>>(),
+        event::<issue::CancelIssueEvent<PolkaBtcRuntime>>(),
+        event::<redeem::RequestRedeemEvent<PolkaBtcRuntime>>(),
+        event::<redeem::ExecuteRedeemEvent<PolkaBtcRuntime>>(),
+        event::<redeem::CancelRedeemEvent<PolkaBtcRuntime>>(),
+        event::<refund::RequestRefundEvent<PolkaBtcRuntime>>(),
+        event::<refund::ExecuteRefundEvent<PolkaBtcRuntime>>(),
+        event::<replace::RequestReplaceEvent<PolkaBtcRuntime>>(),
+        event::<replace::WithdrawReplaceEvent<PolkaBtcRuntime>>(),
+        event::<replace::AcceptReplaceEvent<PolkaBtcRuntime>>(),
+        event::<replace::ExecuteReplaceEvent<PolkaBtcRuntime>>(),
+        event::<replace::AuctionReplaceEvent<PolkaBtcRuntime>>(),
+        event::<replace::CancelReplaceEvent<PolkaBtcRuntime>>(),
+        event::<security::RecoverFromErrorsEvent<PolkaBtcRuntime>>(),
+        event::<sla::UpdateVaultSLAEvent<PolkaBtcRuntime>>(),
+        event::<sla::UpdateRelayerSLAEvent<PolkaBtcRuntime>>(),
+        event::<staked_relayers::RegisterStakedRelayerEvent<PolkaBtcRuntime>>(),
+        event::<staked_relayers::DeregisterStakedRelayerEvent<PolkaBtcRuntime>>(),
+        event::<staked_relayers::StatusUpdateSuggestedEvent<PolkaBtcRuntime>>(),
+        event::<staked_relayers::ExecuteStatusUpdateEvent<PolkaBtcRuntime>>(),
+        event::<treasury::MintEvent<PolkaBtcRuntime>>(),
+        event::<treasury::LockEvent<PolkaBtcRuntime>>(),
+        event::<treasury::BurnEvent<PolkaBtcRuntime>>(),
+        event::<vault_registry::RegisterVaultEvent<PolkaBtcRuntime>>(),
+        event::<vault_registry::LockAdditionalCollateralEvent<PolkaBtcRuntime>>(),
+        event::<vault_registry::WithdrawCollateralEvent<PolkaBtcRuntime>>(),
+        event::<vault_registry::IncreaseToBeIssuedTokensEvent<PolkaBtcRuntime>>(),
+        event::<vault_registry::UpdatePublicKeyEvent<PolkaBtcRuntime>>(),
+        event::<vault_registry::RegisterAddressEvent<PolkaBtcRuntime>>(),
+        store::<balances_dot::AccountStore<PolkaBtcRuntime>>(),
+        store::<balances_polka_btc::AccountStore<PolkaBtcRuntime>>(),
+        store::<balances_polka_btc::TotalIssuanceStore<PolkaBtcRuntime>>(),
+        store::<btc_relay::BestBlockStore<PolkaBtcRuntime>>(),
+        store::<btc_relay::BestBlockHeightStore<PolkaBtcRuntime>>(),
+        store::<btc_relay::ChainsHashesStore<PolkaBtcRuntime>>(),
+        store::<btc_relay::BlockHeadersStore<PolkaBtcRuntime>>(),
+        store::<btc_relay::StableBitcoinConfirmationsStore<PolkaBtcRuntime>>(),
+        store::<exchange_rate_oracle::ExchangeRateStore<PolkaBtcRuntime>>(),
+        store::<exchange_rate_oracle::LastExchangeRateTimeStore<PolkaBtcRuntime>>(),
+        store::<exchange_rate_oracle::MaxDelayStore<PolkaBtcRuntime>>(),
+        store::<exchange_rate_oracle::SatoshiPerBytesStore<PolkaBtcRuntime>>(),
+        store::<fee::IssueGriefingCollateralStore<PolkaBtcRuntime>>(),
+        store::<fee::IssueFeeStore<PolkaBtcRuntime>>(),
+        store::<fee::ReplaceGriefingCollateralStore<PolkaBtcRuntime>>(),
+        store::<frame_system::EventsStore<PolkaBtcRuntime>>(),
+        store::<frame_system::AccountStore<PolkaBtcRuntime>>(),
+        store::<issue::IssueRequestsStore<PolkaBtcRuntime>>(),
+        store::<issue::IssuePeriodStore<PolkaBtcRuntime>>(),
+        store::<redeem::RedeemRequestsStore<PolkaBtcRuntime>>(),
+        store::<refund::RefundRequestsStore<PolkaBtcRuntime>>(),
+        store::<replace::ReplacePeriodStore<PolkaBtcRuntime>>(),
+        store::<replace::ReplaceRequestsStore<PolkaBtcRuntime>>(),
+        store::<security::NonceStore<PolkaBtcRuntime>>(),
+        store::<security::ParachainStatusStore<PolkaBtcRuntime>>(),
+        store::<security::ErrorsStore<PolkaBtcRuntime>>(),
+        store::<sla::VaultSlaStore<PolkaBtcRuntime>>(),
+        store::<sla::RelayerSlaStore<PolkaBtcRuntime>>(),
+        store::<sla::VaultTargetSlaStore<PolkaBtcRuntime>>(),
+        store::<sla::RelayerTargetSlaStore<PolkaBtcRuntime>>(),
+        store::<staked_relayers::ActiveStakedRelayersStore<'static, PolkaBtcRuntime>>(),
+        store::<staked_relayers::ActiveStakedRelayersCountStore<PolkaBtcRuntime>>(),
+        store::<staked_relayers::ActiveStatusUpdatesStore<PolkaBtcRuntime>>(),
+        store::<timestamp::NowStore<PolkaBtcRuntime>>(),
+        store::<vault_registry::VaultsStore<PolkaBtcRuntime>>(),
+        store::<vault_registry::LiquidationCollateralThresholdStore<PolkaBtcRuntime>>(),
+    ]
+}
+
+/// The types of the arguments (or storage keys) of an item.
+type Signature = Vec<String>;
+
+/// The calls, events and storage items of a module as reported by the metadata,
+/// mapped to their signature.
+type ModuleItems = HashMap<(ItemKind, String), Signature>;
+
+/// A difference between the runtime this client was built for and the
+/// runtime of the connected chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    SpecName {
+        expected: String,
+        found: String,
+    },
+    TransactionVersion {
+        expected: u32,
+        found: u32,
+    },
+    SpecVersion {
+        supported: RangeInclusive<u32>,
+        found: u32,
+    },
+    MissingModule(&'static str),
+    MissingItem(ItemKind, &'static str, &'static str),
+    Arguments {
+        kind: ItemKind,
+        module: &'static str,
+        name: &'static str,
+        expected: Signature,
+        found: Signature,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::SpecName { expected, found } => {
+                write!(f, "spec name: expected {}, found {}", expected, found)
+            }
+            Mismatch::TransactionVersion { expected, found } => write!(
+                f,
+                "transaction version: expected {}, found {}",
+                expected, found
+            ),
+            Mismatch::SpecVersion { supported, found } => write!(
+                f,
+                "spec version: expected {} to {}, found {}",
+                supported.start(),
+                supported.end(),
+                found
+            ),
+            Mismatch::MissingModule(module) => write!(f, "module {}: not found", module),
+            Mismatch::MissingItem(kind, module, name) => {
+                write!(f, "{} {}::{}: not found", kind, module, name)
+            }
+            Mismatch::Arguments {
+                kind,
+                module,
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} {}::{}: expected arguments ({}), found ({})",
+                kind,
+                module,
+                name,
+                expected.join(", "),
+                found.join(", ")
+            ),
+        }
+    }
+}
+
+/// Newline separated list of mismatches, used in `Error::IncompatibleRuntime`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatches(pub Vec<Mismatch>);
+
+impl fmt::Display for Mismatches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for mismatch in self.0.iter() {
+            write!(f, "\n  {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn convert<B: 'static, O: 'static>(value: DecodeDifferent<B, O>) -> Result<O, Error> {
+    match value {
+        DecodeDifferent::Decoded(x) => Ok(x),
+        DecodeDifferent::Encode(_) => Err(Error::InvalidMetadata),
+    }
+}
+
+/// Parse the metadata as returned by `state_getMetadata`.
+pub(crate) fn parse_metadata(bytes: &[u8]) -> Result<RuntimeMetadataV12, Error> {
+    let prefixed = RuntimeMetadataPrefixed::decode(&mut &bytes[..])?;
+    if prefixed.0 != META_RESERVED {
+        return Err(Error::InvalidMetadata);
+    }
+    match prefixed.1 {
+        RuntimeMetadata::V12(metadata) => Ok(metadata),
+        _ => Err(Error::InvalidMetadata),
+    }
+}
+
+/// Collect the items of all modules in the metadata.
+fn decode_metadata(bytes: &[u8]) -> Result<HashMap<String, ModuleItems>, Error> {
+    let metadata = parse_metadata(bytes)?;
+
+    let mut modules = HashMap::new();
+    for module in convert(metadata.modules)?.into_iter() {
+        let mut items = ModuleItems::new();
+        for call in convert(module.calls.unwrap_or(DecodeDifferent::Decoded(vec![])))? {
+            let arguments = convert(call.arguments)?
+                .into_iter()
+                .map(|argument| convert(argument.ty))
+                .collect::<Result<_, _>>()?;
+            items.insert((ItemKind::Call, convert(call.name)?), arguments);
+        }
+        for event in convert(module.event.unwrap_or(DecodeDifferent::Decoded(vec![])))? {
+            let arguments = convert(event.arguments)?;
+            items.insert((ItemKind::Event, convert(event.name)?), arguments);
+        }
+        if let Some(storage) = module.storage {
+            for entry in convert(convert(storage)?.entries)? {
+                let keys = match entry.ty {
+                    StorageEntryType::Plain(_) => vec![],
+                    StorageEntryType::Map { key, .. } => vec![convert(key)?],
+                    StorageEntryType::DoubleMap { key1, key2, .. } => {
+                        vec![convert(key1)?, convert(key2)?]
+                    }
+                };
+                items.insert((ItemKind::Storage, convert(entry.name)?), keys);
+            }
+        }
+        modules.insert(convert(module.name)?, items);
+    }
+    Ok(modules)
+}
+
+/// Collect the items of the runtime this client was built against.
+fn built_runtime_modules() -> Result<HashMap<String, ModuleItems>, Error> {
+    // the native metadata is only decodable after a round trip through its encoding
+    decode_metadata(&btc_parachain_runtime::Runtime::metadata().encode())
+}
+
+fn lookup<'a>(
+    modules: &'a HashMap<String, ModuleItems>,
+    item: &Item,
+) -> Result<&'a Signature, Mismatch> {
+    modules
+        .get(item.module)
+        .ok_or(Mismatch::MissingModule(item.module))?
+        .get(&(item.kind, item.name.to_string()))
+        .ok_or(Mismatch::MissingItem(item.kind, item.module, item.name))
+}
+
+/// Compare the signatures of the expected items in the built runtime against the modules
+/// reported by the chain. Items missing from the built runtime are reported as well, since
+/// the definitions in `pallets` are then out of date.
+fn diff(
+    expected: Vec<Item>,
+    built: &HashMap<String, ModuleItems>,
+    chain: &HashMap<String, ModuleItems>,
+) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    for item in expected.into_iter() {
+        let result = lookup(built, &item).and_then(|expected| {
+            let found = lookup(chain, &item)?;
+            if expected == found {
+                Ok(())
+            } else {
+                Err(Mismatch::Arguments {
+                    kind: item.kind,
+                    module: item.module,
+                    name: item.name,
+                    expected: expected.clone(),
+                    found: found.clone(),
+                })
+            }
+        });
+        if let Err(mismatch) = result {
+            if !mismatches.contains(&mismatch) {
+                mismatches.push(mismatch);
+            }
+        }
+    }
+    mismatches
+}
+
+/// Checks that the runtime version and metadata of the connected chain match the
+/// runtime this client was built for. Returns the encoded metadata.
+///
+/// # Errors
+/// * `IncompatibleRuntime` - with a list of all mismatched items
+pub(crate) async fn check_compatibility(rpc_client: &RpcClient) -> Result<Bytes, Error> {
+    let version: RuntimeVersion = rpc_client
+        .request("state_getRuntimeVersion", Params::None)
+        .await?;
+    let metadata: Bytes = rpc_client
+        .request("state_getMetadata", Params::None)
+        .await?;
+    check_runtime(version, &metadata)?;
+    Ok(metadata)
+}
+
+/// Compares the runtime version and encoded metadata of the connected chain against the
+/// runtime this client was built for. A spec version outside of `SUPPORTED_SPEC_VERSIONS`
+/// is only a warning, the items this client relies on are checked in the metadata instead.
+fn check_runtime(version: RuntimeVersion, metadata: &[u8]) -> Result<(), Error> {
+    let mut mismatches = Vec::new();
+    let expected_version = &btc_parachain_runtime::VERSION;
+    let expected_spec_name = expected_version.spec_name.to_string();
+    if version.spec_name != expected_spec_name {
+        mismatches.push(Mismatch::SpecName {
+            expected: expected_spec_name,
+            found: version.spec_name,
+        });
+    }
+    if version.transaction_version != expected_version.transaction_version {
+        mismatches.push(Mismatch::TransactionVersion {
+            expected: expected_version.transaction_version,
+            found: version.transaction_version,
+        });
+    }
+    if !SUPPORTED_SPEC_VERSIONS.contains(&version.spec_version) {
+        warn!(
+            "Runtime spec version {} has not been verified with this client, which supports {} to {}",
+            version.spec_version,
+            SUPPORTED_SPEC_VERSIONS.start(),
+            SUPPORTED_SPEC_VERSIONS.end()
+        );
+    }
+    mismatches.extend(diff(
+        expected_items(),
+        &built_runtime_modules()?,
+        &decode_metadata(metadata)?,
+    ));
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::IncompatibleRuntime {
+            spec_version: version.spec_version,
+            mismatches: Mismatches(mismatches),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(types: &[&str]) -> Signature {
+        types.iter().map(|ty| ty.to_string()).collect()
+    }
+
+    fn modules(register_vault_event: &[&str]) -> HashMap<String, ModuleItems> {
+        let mut items = ModuleItems::new();
+        items.insert(
+            (ItemKind::Call, "register_vault".to_string()),
+            signature(&["DOT<T>", "BtcPublicKey"]),
+        );
+        items.insert(
+            (ItemKind::Event, "RegisterVault".to_string()),
+            signature(register_vault_event),
+        );
+        let mut modules = HashMap::new();
+        modules.insert("VaultRegistry".to_string(), items);
+        modules
+    }
+
+    #[test]
+    fn test_diff_matching_items() {
+        let built = modules(&["AccountId", "DOT"]);
+        let expected = vec![
+            call::<vault_registry::RegisterVaultCall<PolkaBtcRuntime>>(),
+            event::<vault_registry::RegisterVaultEvent<PolkaBtcRuntime>>(),
+        ];
+        assert_eq!(diff(expected, &built, &built), vec![]);
+    }
+
+    #[test]
+    fn test_diff_reports_mismatches() {
+        let built = modules(&["AccountId", "DOT"]);
+        let chain = modules(&["AccountId", "DOT", "BtcPublicKey"]);
+        let expected = vec![
+            event::<vault_registry::RegisterVaultEvent<PolkaBtcRuntime>>(),
+            store::<vault_registry::VaultsStore<PolkaBtcRuntime>>(),
+            call::<issue::CancelIssueCall<PolkaBtcRuntime>>(),
+            event::<issue::CancelIssueEvent<PolkaBtcRuntime>>(),
+        ];
+        assert_eq!(
+            diff(expected, &built, &chain),
+            vec![
+                Mismatch::Arguments {
+                    kind: ItemKind::Event,
+                    module: "VaultRegistry",
+                    name: "RegisterVault",
+                    expected: signature(&["AccountId", "DOT"]),
+                    found: signature(&["AccountId", "DOT", "BtcPublicKey"]),
+                },
+                Mismatch::MissingItem(ItemKind::Storage, "VaultRegistry", "Vaults"),
+                Mismatch::MissingModule("Issue"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pallets_match_built_runtime() {
+        // every item defined in `pallets` has to exist in the runtime this client is built
+        // against, otherwise the definitions are out of date
+        let built = built_runtime_modules().unwrap();
+        assert_eq!(diff(expected_items(), &built, &built), vec![]);
+    }
+
+    fn built_version() -> RuntimeVersion {
+        let version = &btc_parachain_runtime::VERSION;
+        RuntimeVersion {
+            spec_name: version.spec_name.to_string(),
+            spec_version: version.spec_version,
+            transaction_version: version.transaction_version,
+        }
+    }
+
+    /// The encoded metadata of the built runtime, without the given module.
+    fn built_metadata_without(module: &str) -> Vec<u8> {
+        let mut metadata =
+            parse_metadata(&btc_parachain_runtime::Runtime::metadata().encode()).unwrap();
+        let modules = convert(metadata.modules)
+            .unwrap()
+            .into_iter()
+            .filter(|m| !matches!(&m.name, DecodeDifferent::Decoded(name) if name == module))
+            .collect();
+        metadata.modules = DecodeDifferent::Decoded(modules);
+        RuntimeMetadataPrefixed(META_RESERVED, RuntimeMetadata::V12(metadata)).encode()
+    }
+
+    #[test]
+    fn test_check_runtime_accepts_unknown_spec_version() {
+        let mut version = built_version();
+        version.spec_version = SUPPORTED_SPEC_VERSIONS.end() + 1;
+        assert!(check_runtime(version, &built_metadata_without("")).is_ok());
+    }
+
+    #[test]
+    fn test_check_runtime_rejects_mismatching_metadata() {
+        match check_runtime(built_version(), &built_metadata_without("Issue")) {
+            Err(Error::IncompatibleRuntime { mismatches, .. }) => {
+                assert_eq!(mismatches.0, vec![Mismatch::MissingModule("Issue")])
+            }
+            result => panic!("expected incompatible runtime, got {:?}", result),
+        }
+    }
+}
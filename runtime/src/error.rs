@@ -1,7 +1,12 @@
+use crate::auth::Role;
+use crate::compatibility::Mismatches;
+use crate::AccountId;
+use base64::DecodeError as Base64DecodeError;
 use jsonrpsee::{client::RequestError as JsonRPSeeError, transport::ws::WsNewDnsError};
 use parity_scale_codec::Error as CodecError;
 use serde_json::Error as SerdeJsonError;
 use sp_core::crypto::SecretStringError;
+use sp_runtime::transaction_validity::TransactionValidityError;
 use std::array::TryFromSliceError;
 use std::io::Error as IoError;
 use std::num::TryFromIntError;
@@ -22,6 +27,41 @@ pub enum Error {
     BlockNotFound,
     #[error("Could not get vault")]
     VaultNotFound,
+    #[error("Pallet {0} does not emit any events")]
+    UnknownPallet(String),
+    #[error("Transaction is invalid: {0:?}")]
+    InvalidTransaction(TransactionValidityError),
+    #[error("Runtime (spec version {spec_version}) is incompatible with this client:{mismatches}")]
+    IncompatibleRuntime {
+        spec_version: u32,
+        mismatches: Mismatches,
+    },
+    #[error("Could not decode runtime metadata")]
+    InvalidMetadata,
+    #[error("None of the parachain endpoints is healthy")]
+    NoHealthyEndpoint,
+    #[error("Parachain endpoint did not respond in time")]
+    EndpointTimeout,
+    #[error("Remote signer refused to sign: {0}")]
+    SigningRefused(String),
+    #[error("Invalid response from remote signer")]
+    InvalidSignerResponse,
+    #[error("Remote signer did not respond in time")]
+    SignerTimeout,
+    #[error("Extrinsic has to be signed by {0}")]
+    UnexpectedSigner(AccountId),
+    #[error("Extrinsic was not included in a block: {0}")]
+    ExtrinsicNotIncluded(String),
+    #[error("Call {index} ({call}) of the batch failed: {source}")]
+    BatchCallFailed {
+        index: usize,
+        call: String,
+        source: Box<Error>,
+    },
+    #[error("Missing or invalid bearer token")]
+    Unauthorized,
+    #[error("Method requires the {0:?} role")]
+    Forbidden(Role),
     #[error("Callback error: {0}")]
     CallbackError(Box<dyn std::error::Error + Send + Sync>),
 
@@ -39,6 +79,8 @@ pub enum Error {
     CodecError(#[from] CodecError),
     #[error("Error encoding json data: {0}")]
     SerdeJsonError(#[from] SerdeJsonError),
+    #[error("Io error: {0}")]
+    IoError(#[from] IoError),
     #[error("Error getting json-rpsee data: {0}")]
     JsonRPSeeError(#[from] JsonRPSeeError),
     /// Occurs during websocket handshake
@@ -56,4 +98,18 @@ pub enum KeyLoadingError {
     IoError(#[from] IoError),
     #[error("Invalid secret string: {0:?}")]
     SecretStringError(SecretStringError),
+    #[error("Unsupported keystore encoding: {0}")]
+    UnsupportedEncoding(String),
+    #[error("Invalid keystore")]
+    InvalidKeystore,
+    #[error("Failed to decrypt keystore, the password is probably wrong")]
+    DecryptionFailure,
+    #[error("Base64 decoding error: {0}")]
+    Base64Error(#[from] Base64DecodeError),
+    #[error("Password environment variable {0} is not set")]
+    PasswordNotSet(String),
+    #[error("A key pair is required, the remote signer can not be used for this")]
+    RemoteSignerUnsupported,
+    #[error("No key configured, set either keyring, keyfile or keystore")]
+    NoKey,
 }
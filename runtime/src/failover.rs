@@ -0,0 +1,323 @@
+use crate::{Error, PolkaBtcHeader};
+use jsonrpsee::{client::RequestError, common::Params, Client as RpcClient};
+use log::warn;
+use serde::Deserialize;
+use std::future::Future;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use substrate_subxt::Error as XtError;
+use tokio::sync::{broadcast, Mutex, MutexGuard};
+use tokio::time::timeout;
+
+/// Interval at which the health of the current parachain endpoint is checked.
+pub const ENDPOINT_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Maximum time the best block of an endpoint may stay the same before it is considered stale.
+pub const MAX_BEST_BLOCK_AGE: Duration = Duration::from_secs(60);
+
+/// Maximum time to wait for an endpoint to connect or to answer a health probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Endpoints whose best block is more than this many blocks behind the most
+/// up to date endpoint are not selected.
+const MAX_BLOCK_LAG: u32 = 2;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SystemHealth {
+    peers: u64,
+    is_syncing: bool,
+    should_have_peers: bool,
+}
+
+/// Result of probing a single parachain endpoint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EndpointHealth {
+    pub peers: u64,
+    pub is_syncing: bool,
+    pub should_have_peers: bool,
+    pub best_block: u32,
+}
+
+impl EndpointHealth {
+    /// An endpoint is healthy if it is not syncing and, unless it runs a dev
+    /// chain, connected to at least one peer.
+    pub fn is_healthy(&self) -> bool {
+        !self.is_syncing && (self.peers > 0 || !self.should_have_peers)
+    }
+}
+
+/// Opens a client to the given url, which can be over WebSockets or HTTP.
+pub(crate) async fn connect(url: &str) -> Result<RpcClient, Error> {
+    if url.starts_with("ws://") || url.starts_with("wss://") {
+        Ok(jsonrpsee::ws_client(url).await?)
+    } else {
+        Ok(jsonrpsee::http_client(url))
+    }
+}
+
+/// Queries the peer count, sync status and best block of an endpoint.
+pub(crate) async fn probe(rpc_client: &RpcClient) -> Result<EndpointHealth, Error> {
+    let result = timeout(PROBE_TIMEOUT, async {
+        let health: SystemHealth = rpc_client.request("system_health", Params::None).await?;
+        let header: PolkaBtcHeader = rpc_client.request("chain_getHeader", Params::None).await?;
+        Result::<_, Error>::Ok(EndpointHealth {
+            peers: health.peers,
+            is_syncing: health.is_syncing,
+            should_have_peers: health.should_have_peers,
+            best_block: header.number,
+        })
+    })
+    .await;
+
+    result.map_err(|_| Error::EndpointTimeout)?
+}
+
+/// Connects to all given endpoints and returns the first one (in the given order) that is
+/// healthy and not lagging behind the other healthy endpoints.
+pub(crate) async fn connect_healthiest(urls: &[String]) -> Result<(String, RpcClient), Error> {
+    choose_endpoint(urls, |url| async move {
+        let rpc_client = timeout(PROBE_TIMEOUT, connect(&url))
+            .await
+            .map_err(|_| Error::EndpointTimeout)??;
+        let health = probe(&rpc_client).await?;
+        Ok((rpc_client, health))
+    })
+    .await
+}
+
+/// Probes all endpoints concurrently with `connect_and_probe`, see `connect_healthiest`.
+async fn choose_endpoint<C, F, R>(
+    urls: &[String],
+    connect_and_probe: F,
+) -> Result<(String, C), Error>
+where
+    F: Fn(String) -> R,
+    R: Future<Output = Result<(C, EndpointHealth), Error>>,
+{
+    let results =
+        futures::future::join_all(urls.iter().map(|url| connect_and_probe(url.clone()))).await;
+
+    let mut healthy = Vec::new();
+    for (url, result) in urls.iter().zip(results) {
+        match result {
+            Ok((client, health)) if health.is_healthy() => healthy.push((url, client, health)),
+            Ok((_, health)) => warn!("Parachain endpoint {} is unhealthy: {:?}", url, health),
+            Err(err) => warn!("Parachain endpoint {} is unavailable: {}", url, err),
+        }
+    }
+
+    let best_block = healthy
+        .iter()
+        .map(|(_, _, health)| health.best_block)
+        .max()
+        .ok_or(Error::NoHealthyEndpoint)?;
+
+    healthy
+        .into_iter()
+        .find(|(_, _, health)| health.best_block.saturating_add(MAX_BLOCK_LAG) >= best_block)
+        .map(|(url, client, _)| (url.clone(), client))
+        .ok_or(Error::NoHealthyEndpoint)
+}
+
+/// Returns true if the error was caused by the connection to the endpoint rather than by the
+/// request itself, such that the request can be retried on another endpoint.
+pub(crate) fn is_connection_error(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::EndpointTimeout
+            | Error::JsonRPSeeError(RequestError::TransportError(_))
+            | Error::XtError(XtError::Rpc(RequestError::TransportError(_)))
+    )
+}
+
+/// The heights of the blocks between the last seen block and the next block of a
+/// subscription, which were missed while switching endpoints.
+pub(crate) fn missed_blocks(last_seen: u32, next: u32) -> Range<u32> {
+    last_seen.saturating_add(1)..next.max(last_seen.saturating_add(1))
+}
+
+/// Shared state used to coordinate switching between parachain endpoints.
+pub(crate) struct Failover {
+    urls: Vec<String>,
+    /// Incremented every time the provider switches to a new connection.
+    generation: AtomicU64,
+    /// Held while switching, such that concurrent failures result in a single switch.
+    switching: Mutex<()>,
+    /// Notifies subscriptions that they have to be re-established on the new connection.
+    reconnected: broadcast::Sender<()>,
+}
+
+impl Failover {
+    pub(crate) fn new(urls: Vec<String>) -> Self {
+        let (reconnected, _) = broadcast::channel(1);
+        Self {
+            urls,
+            generation: AtomicU64::new(0),
+            switching: Mutex::new(()),
+            reconnected,
+        }
+    }
+
+    pub(crate) fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Returns false if there is no url to reconnect to.
+    pub(crate) fn can_switch(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub(crate) async fn lock(&self) -> MutexGuard<'_, ()> {
+        self.switching.lock().await
+    }
+
+    /// Must be called while holding the lock, after the new connection has been installed.
+    pub(crate) fn notify_reconnected(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        // sending only fails if there are no active subscriptions
+        let _ = self.reconnected.send(());
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<()> {
+        self.reconnected.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(peers: u64, is_syncing: bool, best_block: u32) -> EndpointHealth {
+        EndpointHealth {
+            peers,
+            is_syncing,
+            should_have_peers: true,
+            best_block,
+        }
+    }
+
+    fn urls(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    /// Choose between endpoints whose probes return the given health, None if unavailable.
+    async fn choose(endpoints: &[(&str, Option<EndpointHealth>)]) -> Result<String, Error> {
+        let urls = endpoints
+            .iter()
+            .map(|(url, _)| url.to_string())
+            .collect::<Vec<_>>();
+        choose_endpoint(&urls, |url| async move {
+            let (_, health) = endpoints.iter().find(|(x, _)| *x == url).unwrap();
+            health
+                .clone()
+                .map(|health| ((), health))
+                .ok_or(Error::EndpointTimeout)
+        })
+        .await
+        .map(|(url, _)| url)
+    }
+
+    #[test]
+    fn test_is_healthy() {
+        assert!(health(3, false, 10).is_healthy());
+        assert!(!health(3, true, 10).is_healthy());
+        assert!(!health(0, false, 10).is_healthy());
+        // dev chains run without peers
+        assert!(EndpointHealth {
+            should_have_peers: false,
+            ..health(0, false, 10)
+        }
+        .is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_choose_endpoint_prefers_given_order() {
+        let chosen = choose(&[
+            ("ws://a", Some(health(1, false, 100))),
+            ("ws://b", Some(health(1, false, 100))),
+        ])
+        .await;
+        assert_eq!(chosen.unwrap(), "ws://a");
+    }
+
+    #[tokio::test]
+    async fn test_choose_endpoint_skips_unhealthy_and_unavailable() {
+        let chosen = choose(&[
+            ("ws://syncing", Some(health(1, true, 100))),
+            ("ws://unavailable", None),
+            ("ws://healthy", Some(health(1, false, 100))),
+        ])
+        .await;
+        assert_eq!(chosen.unwrap(), "ws://healthy");
+    }
+
+    #[tokio::test]
+    async fn test_choose_endpoint_skips_lagging() {
+        let chosen = choose(&[
+            (
+                "ws://lagging",
+                Some(health(1, false, 100 - MAX_BLOCK_LAG - 1)),
+            ),
+            ("ws://behind", Some(health(1, false, 100 - MAX_BLOCK_LAG))),
+            ("ws://best", Some(health(1, false, 100))),
+        ])
+        .await;
+        // within the allowed lag, the order takes precedence over the height
+        assert_eq!(chosen.unwrap(), "ws://behind");
+    }
+
+    #[tokio::test]
+    async fn test_choose_endpoint_without_healthy_endpoint() {
+        let chosen = choose(&[
+            ("ws://syncing", Some(health(1, true, 100))),
+            ("ws://unavailable", None),
+        ])
+        .await;
+        assert!(matches!(chosen, Err(Error::NoHealthyEndpoint)));
+        assert!(matches!(choose(&[]).await, Err(Error::NoHealthyEndpoint)));
+    }
+
+    #[tokio::test]
+    async fn test_switch_notifies_subscriptions() {
+        let failover = Failover::new(urls(&["ws://a", "ws://b"]));
+        assert!(failover.can_switch());
+        assert!(!Failover::new(vec![]).can_switch());
+
+        let mut first = failover.subscribe();
+        let mut second = failover.subscribe();
+        let generation = failover.generation();
+        {
+            let _guard = failover.lock().await;
+            failover.notify_reconnected();
+        }
+        assert_eq!(failover.generation(), generation + 1);
+        assert!(first.recv().await.is_ok());
+        assert!(second.recv().await.is_ok());
+    }
+
+    #[test]
+    fn test_missed_blocks() {
+        assert_eq!(missed_blocks(10, 11), 11..11);
+        assert_eq!(missed_blocks(10, 14), 11..14);
+        // the new endpoint may report a block that was already seen
+        assert!(missed_blocks(10, 10).is_empty());
+        assert!(missed_blocks(10, 8).is_empty());
+    }
+
+    #[test]
+    fn test_is_connection_error() {
+        assert!(is_connection_error(&Error::EndpointTimeout));
+        assert!(is_connection_error(&Error::JsonRPSeeError(
+            RequestError::TransportError(Box::new(std::io::Error::from(
+                std::io::ErrorKind::ConnectionReset
+            )))
+        )));
+        assert!(!is_connection_error(&Error::VaultNotFound));
+    }
+}
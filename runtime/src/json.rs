@@ -0,0 +1,484 @@
+//! Human-readable JSON representation of parachain types, used by the JSON methods of the
+//! http apis. Amounts and fixed point numbers are serialized as decimal strings, such that
+//! they are not rounded by JSON parsers, and accounts are SS58 encoded. Errors of the apis
+//! are described by `ApiErrorData`.
+
+use crate::{AccountId, Error, FixedI128, FixedPointNumber, FixedU128};
+use jsonrpc_core::{Error as JsonRpcError, ErrorCode, Params, Value};
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
+use serde::{
+    de::{DeserializeOwned, Error as _},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use sp_core::crypto::Ss58Codec;
+use std::{convert::TryFrom, fmt::Display};
+use substrate_subxt::{Error as XtError, ModuleError, RuntimeError};
+
+/// Description of a JSON method of an http api. The `json_` methods take named parameters
+/// and return human-readable JSON instead of SCALE encoded hex.
+#[derive(Clone, Debug, Serialize)]
+pub struct MethodSchema {
+    pub name: String,
+    pub description: String,
+    pub params: RootSchema,
+    pub result: RootSchema,
+}
+
+impl MethodSchema {
+    /// Describe the method `name` that takes parameters of type `P` and returns `R`.
+    pub fn new<P: JsonSchema, R: JsonSchema>(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            params: schema_for!(P),
+            result: schema_for!(R),
+        }
+    }
+}
+
+/// Category of an error returned by the http apis, which determines the code of the
+/// JSON-RPC error object. The codes are stable, clients may match on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiErrorKind {
+    /// The parameters of the request could not be parsed.
+    InvalidParams,
+    /// The parachain rejected the extrinsic, see `ApiErrorData::module` and `error`.
+    Dispatch,
+    /// The parachain could not be reached or did not respond.
+    ParachainUnavailable,
+    /// Bitcoin Core failed or could not be reached.
+    Bitcoin,
+    /// The account or wallet has insufficient funds.
+    InsufficientFunds,
+    /// The requested vault, request or block does not exist.
+    NotFound,
+    /// The request is valid but refused, e.g. while shutting down or rate limited.
+    Rejected,
+    /// The bearer token is missing or unknown.
+    Unauthorized,
+    /// The role of the bearer token does not allow calling the method.
+    Forbidden,
+    /// The client does not match the runtime of the parachain, e.g. it failed to encode a
+    /// call or to decode the metadata, storage or events.
+    Incompatible,
+    Internal,
+}
+
+impl ApiErrorKind {
+    pub fn code(self) -> i64 {
+        match self {
+            ApiErrorKind::InvalidParams => -32602,
+            ApiErrorKind::Internal => -32603,
+            ApiErrorKind::Dispatch => -32010,
+            ApiErrorKind::ParachainUnavailable => -32011,
+            ApiErrorKind::Bitcoin => -32012,
+            ApiErrorKind::InsufficientFunds => -32013,
+            ApiErrorKind::NotFound => -32014,
+            ApiErrorKind::Rejected => -32015,
+            ApiErrorKind::Unauthorized => -32016,
+            ApiErrorKind::Forbidden => -32017,
+            ApiErrorKind::Incompatible => -32018,
+        }
+    }
+}
+
+/// The `data` of a JSON-RPC error object returned by the http apis.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ApiErrorData {
+    pub kind: ApiErrorKind,
+    /// Pallet that rejected the extrinsic, only set for dispatch errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    /// Name of the dispatch error, e.g. "ExceedingVaultLimit".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ApiErrorData {
+    pub fn new(kind: ApiErrorKind) -> Self {
+        Self {
+            kind,
+            module: None,
+            error: None,
+        }
+    }
+}
+
+/// Returns true if the dispatch error of the collateral or a balances pallet is caused by
+/// a lack of funds.
+fn is_insufficient_funds(module: &str, error: &str) -> bool {
+    matches!(
+        module,
+        "Collateral" | "Treasury" | "Balances" | "DOT" | "PolkaBTC"
+    ) && matches!(error, "InsufficientFunds" | "InsufficientBalance")
+}
+
+impl From<&XtError> for ApiErrorData {
+    fn from(err: &XtError) -> Self {
+        match err {
+            XtError::Runtime(RuntimeError::Module(ModuleError { module, error })) => Self {
+                kind: if is_insufficient_funds(module, error) {
+                    ApiErrorKind::InsufficientFunds
+                } else {
+                    ApiErrorKind::Dispatch
+                },
+                module: Some(module.clone()),
+                error: Some(error.clone()),
+            },
+            XtError::Runtime(err) => Self {
+                error: Some(err.to_string()),
+                ..Self::new(ApiErrorKind::Dispatch)
+            },
+            XtError::Io(_) | XtError::Rpc(_) => Self::new(ApiErrorKind::ParachainUnavailable),
+            XtError::Codec(_) | XtError::Metadata(_) | XtError::TypeSizeUnavailable(_) => {
+                Self::new(ApiErrorKind::Incompatible)
+            }
+            _ => Self::new(ApiErrorKind::Internal),
+        }
+    }
+}
+
+impl From<&Error> for ApiErrorData {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::XtError(err) => err.into(),
+            Error::BatchCallFailed { source, .. } => source.as_ref().into(),
+            Error::NoHealthyEndpoint
+            | Error::EndpointTimeout
+            | Error::ExtrinsicNotIncluded(_)
+            | Error::JsonRPSeeError(_)
+            | Error::WsHandshake(_) => Self::new(ApiErrorKind::ParachainUnavailable),
+            Error::VaultNotFound
+            | Error::BlockNotFound
+            | Error::RequestIssueIDNotFound
+            | Error::RequestRedeemIDNotFound
+            | Error::RequestReplaceIDNotFound => Self::new(ApiErrorKind::NotFound),
+            Error::IncompatibleRuntime { .. }
+            | Error::InvalidMetadata
+            | Error::UnknownPallet(_)
+            | Error::CodecError(_) => Self::new(ApiErrorKind::Incompatible),
+            Error::Unauthorized => Self::new(ApiErrorKind::Unauthorized),
+            Error::Forbidden(_) => Self::new(ApiErrorKind::Forbidden),
+            Error::InvalidTransaction(_)
+            | Error::SigningRefused(_)
+            | Error::UnexpectedSigner(_) => Self::new(ApiErrorKind::Rejected),
+            _ => Self::new(ApiErrorKind::Internal),
+        }
+    }
+}
+
+/// Describe the error with its stable code and data, see `ApiErrorKind`.
+pub fn json_rpc_error<E: Display>(err: E) -> JsonRpcError
+where
+    for<'a> ApiErrorData: From<&'a E>,
+{
+    let data = ApiErrorData::from(&err);
+    JsonRpcError {
+        code: ErrorCode::from(data.kind.code()),
+        message: err.to_string(),
+        data: serde_json::to_value(data).ok(),
+    }
+}
+
+/// Parse the named parameters of a `json_` method.
+pub fn parse_json_params<T: DeserializeOwned, E: From<JsonRpcError>>(
+    params: Params,
+) -> Result<T, E> {
+    Ok(params.parse()?)
+}
+
+/// Serialize the result of a `json_` method, or describe its error.
+pub fn handle_json_resp<T: Serialize, E: Display>(resp: Result<T, E>) -> Result<Value, JsonRpcError>
+where
+    for<'a> ApiErrorData: From<&'a E>,
+{
+    match resp {
+        Ok(data) => serde_json::to_value(data).map_err(|_| JsonRpcError::internal_error()),
+        Err(err) => Err(json_rpc_error(err)),
+    }
+}
+
+/// Number of decimals of a fixed point number with the given accuracy.
+fn decimals(accuracy: u128) -> usize {
+    accuracy.to_string().len() - 1
+}
+
+fn format_fixed(negative: bool, inner: u128, accuracy: u128) -> String {
+    let fraction = format!("{:0width$}", inner % accuracy, width = decimals(accuracy));
+    let fraction = fraction.trim_end_matches('0');
+    format!(
+        "{}{}{}{}",
+        if negative { "-" } else { "" },
+        inner / accuracy,
+        if fraction.is_empty() { "" } else { "." },
+        fraction
+    )
+}
+
+/// Parse a decimal string into its sign and the absolute inner value of a fixed point
+/// number with the given accuracy. Returns None if the string has more decimals than the
+/// accuracy supports or the value overflows.
+fn parse_fixed(value: &str, accuracy: u128) -> Option<(bool, u128)> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value),
+    };
+    let (integer, fraction) = match value.find('.') {
+        Some(index) => (&value[..index], &value[index + 1..]),
+        None => (value, ""),
+    };
+    if (integer.is_empty() && fraction.is_empty())
+        || fraction.len() > decimals(accuracy)
+        || !integer
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let integer: u128 = if integer.is_empty() {
+        0
+    } else {
+        integer.parse().ok()?
+    };
+    let fraction: u128 = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<width$}", fraction, width = decimals(accuracy))
+            .parse()
+            .ok()?
+    };
+    Some((
+        negative,
+        integer.checked_mul(accuracy)?.checked_add(fraction)?,
+    ))
+}
+
+/// (De)serialize a balance as a decimal string.
+pub mod amount {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// (De)serialize a `FixedU128` as a decimal string, e.g. "1.5".
+pub mod fixed_u128 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &FixedU128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_fixed(
+            false,
+            value.into_inner(),
+            FixedU128::accuracy(),
+        ))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<FixedU128, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        match parse_fixed(&value, FixedU128::accuracy()) {
+            Some((false, inner)) => Ok(FixedU128::from_inner(inner)),
+            _ => Err(D::Error::custom(format!(
+                "invalid fixed point number {}",
+                value
+            ))),
+        }
+    }
+}
+
+/// (De)serialize a `FixedI128` as a decimal string, e.g. "-1.5".
+pub mod fixed_i128 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &FixedI128, serializer: S) -> Result<S::Ok, S::Error> {
+        let inner = value.into_inner();
+        serializer.serialize_str(&format_fixed(
+            inner < 0,
+            inner.wrapping_abs() as u128,
+            FixedI128::accuracy() as u128,
+        ))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<FixedI128, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse_fixed(&value, FixedI128::accuracy() as u128)
+            .and_then(|(negative, inner)| {
+                let inner = i128::try_from(inner).ok()?;
+                Some(FixedI128::from_inner(if negative { -inner } else { inner }))
+            })
+            .ok_or_else(|| D::Error::custom(format!("invalid fixed point number {}", value)))
+    }
+}
+
+/// (De)serialize an account as its SS58 address.
+pub mod account_id {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &AccountId, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_ss58check())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<AccountId, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        AccountId::from_ss58check(&value)
+            .map_err(|err| D::Error::custom(format!("invalid account {}: {:?}", value, err)))
+    }
+}
+
+/// (De)serialize a SCALE encodable value, such as a public key, as 0x-prefixed hex.
+pub mod hex_encoded {
+    use super::*;
+    use parity_scale_codec::{Decode, Encode};
+
+    pub fn serialize<T: Encode, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hex::encode(value.encode())))
+    }
+
+    pub fn deserialize<'de, T: Decode, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        let bytes = hex::decode(value.trim_start_matches("0x")).map_err(D::Error::custom)?;
+        T::decode(&mut &bytes[..]).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Values {
+        #[serde(with = "amount")]
+        amount: u128,
+        #[serde(with = "fixed_u128")]
+        rate: FixedU128,
+        #[serde(with = "fixed_i128")]
+        sla: FixedI128,
+    }
+
+    #[test]
+    fn test_values_roundtrip() {
+        let values = Values {
+            amount: u128::MAX,
+            rate: FixedU128::checked_from_rational(3, 2).unwrap(),
+            sla: FixedI128::checked_from_rational(-1, 8).unwrap(),
+        };
+        let json = serde_json::to_value(&values).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "amount": u128::MAX.to_string(),
+                "rate": "1.5",
+                "sla": "-0.125",
+            })
+        );
+        assert_eq!(serde_json::from_value::<Values>(json).unwrap(), values);
+    }
+
+    #[test]
+    fn test_dispatch_error_data() {
+        let err = Error::BatchCallFailed {
+            index: 1,
+            call: "WithdrawCollateral".to_string(),
+            source: Box::new(Error::XtError(XtError::Runtime(RuntimeError::Module(
+                ModuleError {
+                    module: "VaultRegistry".to_string(),
+                    error: "InsufficientCollateralAvailable".to_string(),
+                },
+            )))),
+        };
+        assert_eq!(
+            serde_json::to_value(ApiErrorData::from(&err)).unwrap(),
+            serde_json::json!({
+                "kind": "dispatch",
+                "module": "VaultRegistry",
+                "error": "InsufficientCollateralAvailable",
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(ApiErrorData::from(&Error::NoHealthyEndpoint)).unwrap(),
+            serde_json::json!({ "kind": "parachain_unavailable" })
+        );
+    }
+
+    #[test]
+    fn test_insufficient_funds_error_data() {
+        let err = Error::XtError(XtError::Runtime(RuntimeError::Module(ModuleError {
+            module: "Collateral".to_string(),
+            error: "InsufficientFunds".to_string(),
+        })));
+        assert_eq!(
+            serde_json::to_value(ApiErrorData::from(&err)).unwrap(),
+            serde_json::json!({
+                "kind": "insufficient_funds",
+                "module": "Collateral",
+                "error": "InsufficientFunds",
+            })
+        );
+        let err = XtError::Runtime(RuntimeError::Module(ModuleError {
+            module: "DOT".to_string(),
+            error: "InsufficientBalance".to_string(),
+        }));
+        assert_eq!(
+            ApiErrorData::from(&err).kind,
+            ApiErrorKind::InsufficientFunds
+        );
+    }
+
+    #[test]
+    fn test_client_error_data() {
+        let codec_error = || XtError::Codec("missing field".into());
+        assert_eq!(
+            ApiErrorData::from(&codec_error()).kind,
+            ApiErrorKind::Incompatible
+        );
+        assert_eq!(
+            ApiErrorData::from(&Error::XtError(codec_error())).kind,
+            ApiErrorKind::Incompatible
+        );
+        assert_eq!(
+            ApiErrorData::from(&Error::InvalidMetadata).kind,
+            ApiErrorKind::Incompatible
+        );
+        let io_error = XtError::Io(std::io::ErrorKind::ConnectionRefused.into());
+        assert_eq!(
+            ApiErrorData::from(&io_error).kind,
+            ApiErrorKind::ParachainUnavailable
+        );
+        assert_eq!(
+            ApiErrorData::from(&XtError::Other("unknown".to_string())).kind,
+            ApiErrorKind::Internal
+        );
+    }
+
+    #[test]
+    fn test_parse_fixed() {
+        let accuracy = FixedU128::accuracy();
+        assert_eq!(parse_fixed("2", accuracy), Some((false, 2 * accuracy)));
+        assert_eq!(parse_fixed(".5", accuracy), Some((false, accuracy / 2)));
+        assert_eq!(parse_fixed("-0.5", accuracy), Some((true, accuracy / 2)));
+        assert_eq!(parse_fixed("", accuracy), None);
+        assert_eq!(parse_fixed("1.-5", accuracy), None);
+        assert_eq!(parse_fixed("0.0000000000000000001", accuracy), None);
+    }
+
+    #[test]
+    fn test_format_fixed() {
+        let accuracy = FixedU128::accuracy();
+        assert_eq!(format_fixed(false, 0, accuracy), "0");
+        assert_eq!(format_fixed(false, 10 * accuracy, accuracy), "10");
+        assert_eq!(format_fixed(true, accuracy / 4, accuracy), "-0.25");
+    }
+}
@@ -0,0 +1,203 @@
+use crate::error::KeyLoadingError;
+use serde::Deserialize;
+use sp_core::sr25519::Pair;
+use sp_core::Pair as _;
+use xsalsa20poly1305::aead::{generic_array::GenericArray, Aead, NewAead};
+use xsalsa20poly1305::XSalsa20Poly1305;
+
+const SALT_LENGTH: usize = 32;
+/// Salt followed by the scrypt parameters N, p and r, each a little endian u32.
+const SCRYPT_LENGTH: usize = SALT_LENGTH + 3 * 4;
+const NONCE_LENGTH: usize = 24;
+const SECRET_KEY_LENGTH: usize = 64;
+const PUBLIC_KEY_LENGTH: usize = 32;
+
+const PKCS8_HEADER: [u8; 16] = [48, 83, 2, 1, 1, 48, 5, 6, 3, 43, 101, 112, 4, 34, 4, 32];
+const PKCS8_DIVIDER: [u8; 5] = [161, 35, 3, 33, 0];
+
+/// Account exported by polkadot-js (e.g. from the browser extension), with the secret key
+/// encrypted using a key derived from a password.
+#[derive(Deserialize, Debug, Clone)]
+pub struct EncryptedAccount {
+    encoded: String,
+    encoding: Encoding,
+    pub address: String,
+    #[serde(default)]
+    meta: Meta,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Encoding {
+    content: Vec<String>,
+    #[serde(rename = "type")]
+    kind: Vec<String>,
+    version: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+struct Meta {
+    name: Option<String>,
+}
+
+impl EncryptedAccount {
+    /// Loads an exported account from a json file.
+    pub fn from_file(file_path: &str) -> Result<Self, KeyLoadingError> {
+        let file = std::fs::File::open(file_path)?;
+        let reader = std::io::BufReader::new(file);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// The name given to the account in polkadot-js, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.meta.name.as_deref()
+    }
+
+    /// Decrypts the secret key, only sr25519 keys encrypted with scrypt and xsalsa20-poly1305
+    /// (version 3 of the polkadot-js format) are supported.
+    pub fn decrypt(&self, password: &str) -> Result<Pair, KeyLoadingError> {
+        let encoding = &self.encoding;
+        if encoding.version != "3"
+            || encoding.kind != ["scrypt", "xsalsa20-poly1305"]
+            || encoding.content.first().map(String::as_str) != Some("pkcs8")
+            || !encoding.content.iter().any(|content| content == "sr25519")
+        {
+            return Err(KeyLoadingError::UnsupportedEncoding(format!(
+                "version {}, type {:?}, content {:?}",
+                encoding.version, encoding.kind, encoding.content
+            )));
+        }
+
+        let encoded = base64::decode(&self.encoded)?;
+        if encoded.len() < SCRYPT_LENGTH + NONCE_LENGTH {
+            return Err(KeyLoadingError::InvalidKeystore);
+        }
+        let (scrypt, encrypted) = encoded.split_at(SCRYPT_LENGTH);
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+
+        let key = derive_key(password, scrypt)?;
+        let plaintext = XSalsa20Poly1305::new(GenericArray::from_slice(&key))
+            .decrypt(GenericArray::from_slice(nonce), ciphertext)
+            .map_err(|_| KeyLoadingError::DecryptionFailure)?;
+
+        decode_pkcs8(&plaintext)
+    }
+}
+
+/// Derives the encryption key from the password, using the salt and parameters that are
+/// prepended to the encrypted data.
+fn derive_key(password: &str, scrypt: &[u8]) -> Result<[u8; 32], KeyLoadingError> {
+    let (salt, params) = scrypt.split_at(SALT_LENGTH);
+    let read_u32 = |index: usize| {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&params[index * 4..(index + 1) * 4]);
+        u32::from_le_bytes(bytes)
+    };
+    let (n, p, r) = (read_u32(0), read_u32(1), read_u32(2));
+    if !n.is_power_of_two() {
+        return Err(KeyLoadingError::InvalidKeystore);
+    }
+
+    let params = scrypt::ScryptParams::new(n.trailing_zeros() as u8, r, p)
+        .map_err(|_| KeyLoadingError::InvalidKeystore)?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+        .map_err(|_| KeyLoadingError::InvalidKeystore)?;
+    Ok(key)
+}
+
+/// Decodes the pkcs8 structure used by polkadot-js to store the key pair. The secret key is
+/// stored in the ed25519 compatible format of schnorrkel.
+fn decode_pkcs8(data: &[u8]) -> Result<Pair, KeyLoadingError> {
+    let secret_start = PKCS8_HEADER.len();
+    let divider_start = secret_start + SECRET_KEY_LENGTH;
+    let public_start = divider_start + PKCS8_DIVIDER.len();
+    if data.len() != public_start + PUBLIC_KEY_LENGTH
+        || data[..secret_start] != PKCS8_HEADER
+        || data[divider_start..public_start] != PKCS8_DIVIDER
+    {
+        return Err(KeyLoadingError::InvalidKeystore);
+    }
+
+    let secret = schnorrkel::SecretKey::from_ed25519_bytes(&data[secret_start..divider_start])
+        .map_err(|_| KeyLoadingError::InvalidKeystore)?;
+    let pair = Pair::from(secret);
+    if pair.public().0[..] != data[public_start..] {
+        return Err(KeyLoadingError::InvalidKeystore);
+    }
+    Ok(pair)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use sp_keyring::AccountKeyring;
+
+    fn export(pair: &Pair, password: &str) -> EncryptedAccount {
+        serde_json::from_value(export_json(pair, password)).unwrap()
+    }
+
+    /// Encrypts the pair the way polkadot-js does, with a low scrypt cost to keep the test fast.
+    pub(crate) fn export_json(pair: &Pair, password: &str) -> serde_json::Value {
+        let keypair: &schnorrkel::Keypair = pair.as_ref();
+        let mut plaintext = PKCS8_HEADER.to_vec();
+        plaintext.extend_from_slice(&keypair.secret.to_ed25519_bytes());
+        plaintext.extend_from_slice(&PKCS8_DIVIDER);
+        plaintext.extend_from_slice(&pair.public().0);
+
+        let mut encoded = vec![7u8; SALT_LENGTH];
+        for param in &[1024u32, 1, 8] {
+            encoded.extend_from_slice(&param.to_le_bytes());
+        }
+        let key = derive_key(password, &encoded).unwrap();
+        let nonce = [3u8; NONCE_LENGTH];
+        let ciphertext = XSalsa20Poly1305::new(GenericArray::from_slice(&key))
+            .encrypt(GenericArray::from_slice(&nonce), &plaintext[..])
+            .unwrap();
+        encoded.extend_from_slice(&nonce);
+        encoded.extend_from_slice(&ciphertext);
+
+        serde_json::json!({
+            "encoded": base64::encode(&encoded),
+            "encoding": {
+                "content": ["pkcs8", "sr25519"],
+                "type": ["scrypt", "xsalsa20-poly1305"],
+                "version": "3"
+            },
+            "address": pair.public().to_string(),
+            "meta": { "name": "vault" }
+        })
+    }
+
+    #[test]
+    fn test_decrypt_succeeds() {
+        let pair = AccountKeyring::Alice.pair();
+        let account = export(&pair, "correct horse");
+
+        assert_eq!(account.name(), Some("vault"));
+        assert_eq!(
+            account.decrypt("correct horse").unwrap().public(),
+            pair.public()
+        );
+    }
+
+    #[test]
+    fn test_decrypt_fails_with_wrong_password() {
+        let account = export(&AccountKeyring::Alice.pair(), "correct horse");
+
+        assert!(matches!(
+            account.decrypt("battery staple"),
+            Err(KeyLoadingError::DecryptionFailure)
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_rejects_unsupported_encoding() {
+        let mut account = export(&AccountKeyring::Alice.pair(), "correct horse");
+        account.encoding.content = vec!["pkcs8".to_string(), "ed25519".to_string()];
+
+        assert!(matches!(
+            account.decrypt("correct horse"),
+            Err(KeyLoadingError::UnsupportedEncoding(_))
+        ));
+    }
+}
@@ -1,26 +1,49 @@
+pub mod auth;
+mod batch;
+mod cache;
 pub mod cli;
+mod compatibility;
 mod error;
+mod failover;
+pub mod json;
+mod keystore;
+mod offline;
 pub mod pallets;
 mod rpc;
+mod signer;
+mod sla_history;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 #[cfg(test)]
 mod tests;
 
+pub use batch::Batch;
+pub use cache::{CacheStats, StorageCache};
 pub use btc_relay::{
     BitcoinBlockHeight, BlockBuilder, BtcAddress, BtcPublicKey, Formattable, H256Le,
     RawBlockHeader, RichBlockHeader,
 };
+pub use compatibility::{ItemKind, Mismatch, Mismatches};
 pub use error::{Error, XtError};
+pub use failover::{EndpointHealth, ENDPOINT_PROBE_INTERVAL, MAX_BEST_BLOCK_AGE};
+pub use keystore::EncryptedAccount;
+pub use offline::{UnsignedExtrinsic, UnsignedExtrinsicOptions};
 use pallets::*;
 pub use rpc::{
     historic_event_types, AccountId, BtcRelayPallet, BtcTxFeesPerByte, DotBalancesPallet,
-    ExchangeRateOraclePallet, FeePallet, IssuePallet, PolkaBtcHeader, PolkaBtcIssueRequest,
-    PolkaBtcProvider, PolkaBtcRedeemRequest, PolkaBtcRefundRequest, PolkaBtcReplaceRequest,
+    ExchangeRateOraclePallet, ExtrinsicFuncs, FeePallet, IssuePallet, PastBlockEvents,
+    PastEventsOptions, PolkaBtcHeader, PolkaBtcIssueRequest, PolkaBtcProvider,
+    PolkaBtcRedeemRequest, PolkaBtcRefundRequest, PolkaBtcReplaceRequest,
     PolkaBtcRequestIssueEvent, PolkaBtcStatusUpdate, PolkaBtcVault, RedeemPallet, RefundPallet,
-    ReplacePallet, SecurityPallet, StakedRelayerPallet, TimestampPallet, UtilFuncs,
-    VaultRegistryPallet,
+    ReplacePallet, SecurityPallet, SlaPallet, SlaParameters, StakedRelayerPallet, TimestampPallet,
+    TreasuryPallet, UtilFuncs, VaultRegistryPallet, DEFAULT_PAST_EVENTS_CONCURRENCY,
 };
 pub use security::{ErrorCode, StatusCode};
+pub use signer::{CallDecoder, DecodedArgument, DecodedCall, PolkaBtcSigner, RemoteSigner};
+pub use sla_history::{
+    SlaChange, SlaHistory, SlaJsonRpcResponse, SlaSubject, DEFAULT_SLA_HISTORY_LENGTH,
+};
 pub use sp_arithmetic::{traits as FixedPointTraits, FixedI128, FixedPointNumber, FixedU128};
 use sp_core::{H160, H256};
 pub use sp_runtime;
@@ -102,6 +125,10 @@ impl balances_dot::DOT for PolkaBtcRuntime {
     type Balance = Balance;
 }
 
+impl balances_polka_btc::PolkaBTC for PolkaBtcRuntime {
+    type Balance = Balance;
+}
+
 impl issue::Issue for PolkaBtcRuntime {}
 
 impl frame_system::System for PolkaBtcRuntime {}
@@ -119,3 +146,5 @@ impl fee::Fee for PolkaBtcRuntime {}
 impl sla::Sla for PolkaBtcRuntime {}
 
 impl treasury::Treasury for PolkaBtcRuntime {}
+
+impl utility::Utility for PolkaBtcRuntime {}
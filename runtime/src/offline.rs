@@ -0,0 +1,217 @@
+//! Extrinsics that are created on a host connected to the parachain, signed on a host that
+//! is not (e.g. an air-gapped machine holding the key), and then submitted by the former.
+
+use crate::compatibility::{Mismatch, Mismatches, SUPPORTED_SPEC_VERSIONS};
+use crate::signer::{signing_payload, CallDecoder, DecodedCall};
+use crate::{AccountId, Error};
+use parity_scale_codec::{Compact, Encode};
+use serde::{Deserialize, Serialize};
+use sp_core::sr25519::Pair as KeyPair;
+use sp_core::{Bytes, Pair as _, H256};
+use sp_runtime::generic::Era;
+use sp_runtime::MultiSignature;
+
+/// Version of the extrinsic format, with the bit set that marks it as signed.
+const SIGNED_EXTRINSIC_VERSION: u8 = 0b1000_0000 | 4;
+
+/// Everything needed to sign an extrinsic without access to the parachain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsignedExtrinsic {
+    /// The account that has to sign the extrinsic.
+    pub signer: AccountId,
+    /// The encoded call, which is decoded for review by `decode_call` before signing.
+    pub call: Bytes,
+    pub nonce: u32,
+    pub era: Era,
+    pub tip: u128,
+    pub spec_version: u32,
+    pub transaction_version: u32,
+    pub genesis_hash: H256,
+    /// Hash of the block in which a mortal era starts, the genesis hash if immortal.
+    pub era_hash: H256,
+}
+
+impl UnsignedExtrinsic {
+    /// The signed extensions that are included in the extrinsic, in the order of
+    /// subxt's `DefaultExtra`.
+    fn extra(&self) -> Vec<u8> {
+        let mut extra = self.era.encode();
+        Compact(self.nonce).encode_to(&mut extra);
+        Compact(self.tip).encode_to(&mut extra);
+        extra
+    }
+
+    /// The signed extensions that are signed, but not included in the extrinsic.
+    fn additional_signed(&self) -> Vec<u8> {
+        (
+            self.spec_version,
+            self.transaction_version,
+            self.genesis_hash,
+            self.era_hash,
+        )
+            .encode()
+    }
+
+    /// The data that has to be signed.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        signing_payload(
+            &self.call,
+            &[self.extra(), self.additional_signed()].concat(),
+        )
+    }
+
+    /// Decode the call using the metadata of the runtime this client is built against, rather
+    /// than trusting a decoding made by the host that created the extrinsic. Fails if the
+    /// extrinsic is for an unsupported runtime version, returns None if the call is unknown.
+    pub fn decode_call(&self) -> Result<Option<DecodedCall>, Error> {
+        if !SUPPORTED_SPEC_VERSIONS.contains(&self.spec_version) {
+            return Err(Error::IncompatibleRuntime {
+                spec_version: self.spec_version,
+                mismatches: Mismatches(vec![Mismatch::SpecVersion {
+                    supported: SUPPORTED_SPEC_VERSIONS,
+                    found: self.spec_version,
+                }]),
+            });
+        }
+        Ok(CallDecoder::from_built_runtime()?.decode(&self.call))
+    }
+
+    /// Sign the extrinsic with the given key, which has to belong to the `signer` account.
+    /// Returns the encoded extrinsic, ready for submission.
+    pub fn sign(&self, pair: &KeyPair) -> Result<Bytes, Error> {
+        let account_id = AccountId::from(pair.public());
+        if account_id != self.signer {
+            return Err(Error::UnexpectedSigner(self.signer.clone()));
+        }
+        let signature = MultiSignature::from(pair.sign(&self.signing_payload()));
+
+        let mut extrinsic = vec![SIGNED_EXTRINSIC_VERSION];
+        account_id.encode_to(&mut extrinsic);
+        signature.encode_to(&mut extrinsic);
+        extrinsic.extend(self.extra());
+        extrinsic.extend(self.call.iter());
+        Ok(Bytes(extrinsic.encode()))
+    }
+}
+
+/// Options for `PolkaBtcProvider::create_unsigned_extrinsic`.
+#[derive(Clone, Debug, Default)]
+pub struct UnsignedExtrinsicOptions {
+    /// The nonce to use, by default the next nonce of the signer (including pending extrinsics).
+    pub nonce: Option<u32>,
+    /// Number of blocks after the current finalized block for which the extrinsic is valid,
+    /// rounded up to a power of two. If None, the extrinsic is valid forever.
+    pub mortality: Option<u64>,
+    /// Tip for the block author.
+    pub tip: u128,
+}
+
+/// Status updates of `author_submitAndWatchExtrinsic`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum TransactionStatus {
+    Future,
+    Ready,
+    Broadcast(Vec<String>),
+    InBlock(H256),
+    Retracted(H256),
+    FinalityTimeout(H256),
+    Finalized(H256),
+    Usurped(H256),
+    Dropped,
+    Invalid,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_scale_codec::Decode;
+    use sp_keyring::AccountKeyring;
+
+    fn unsigned(call: Vec<u8>) -> UnsignedExtrinsic {
+        UnsignedExtrinsic {
+            signer: AccountKeyring::Alice.to_account_id(),
+            call: Bytes(call),
+            nonce: 5,
+            era: Era::mortal(64, 1000),
+            tip: 0,
+            spec_version: *SUPPORTED_SPEC_VERSIONS.start(),
+            transaction_version: 1,
+            genesis_hash: H256::repeat_byte(1),
+            era_hash: H256::repeat_byte(2),
+        }
+    }
+
+    #[test]
+    fn test_sign_encodes_extrinsic() {
+        let unsigned = unsigned(vec![9, 1, 42]);
+        let extrinsic =
+            Vec::<u8>::decode(&mut &unsigned.sign(&AccountKeyring::Alice.pair()).unwrap()[..])
+                .unwrap();
+
+        assert_eq!(extrinsic[0], SIGNED_EXTRINSIC_VERSION);
+        assert_eq!(
+            &extrinsic[1..33],
+            AsRef::<[u8]>::as_ref(&AccountKeyring::Alice.to_account_id())
+        );
+        // sr25519 variant of the MultiSignature
+        assert_eq!(extrinsic[33], 1);
+        let signature = sp_core::sr25519::Signature::decode(&mut &extrinsic[34..98]).unwrap();
+        assert!(KeyPair::verify(
+            &signature,
+            unsigned.signing_payload(),
+            &AccountKeyring::Alice.public()
+        ));
+        assert_eq!(
+            &extrinsic[98..],
+            &[unsigned.extra(), vec![9, 1, 42]].concat()[..]
+        );
+    }
+
+    #[test]
+    fn test_sign_hashes_long_payload() {
+        let unsigned = unsigned(vec![0; 300]);
+        assert_eq!(unsigned.signing_payload().len(), 32);
+    }
+
+    #[test]
+    fn test_sign_with_wrong_key_fails() {
+        let result = unsigned(vec![9, 1, 42]).sign(&AccountKeyring::Bob.pair());
+        assert!(matches!(result, Err(Error::UnexpectedSigner(_))));
+    }
+
+    #[test]
+    fn test_decode_call_ignores_decoding_in_json() {
+        // a call the built runtime does not know, with a decoding claiming otherwise
+        let mut json = serde_json::to_value(unsigned(vec![255, 255])).unwrap();
+        json["decodedCall"] = serde_json::json!({
+            "module": "VaultRegistry",
+            "function": "register_vault",
+            "arguments": [],
+            "undecoded": null
+        });
+        let unsigned: UnsignedExtrinsic = serde_json::from_value(json).unwrap();
+        assert_eq!(unsigned.decode_call().unwrap(), None);
+    }
+
+    #[test]
+    fn test_decode_call_rejects_unsupported_spec_version() {
+        let mut unsigned = unsigned(vec![9, 1, 42]);
+        unsigned.spec_version = SUPPORTED_SPEC_VERSIONS.end() + 1;
+        assert!(matches!(
+            unsigned.decode_call(),
+            Err(Error::IncompatibleRuntime { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unsigned_extrinsic_json_roundtrip() {
+        let unsigned = unsigned(vec![9, 1, 42]);
+        let json = serde_json::to_string(&unsigned).unwrap();
+        assert_eq!(
+            serde_json::from_str::<UnsignedExtrinsic>(&json).unwrap(),
+            unsigned
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use core::marker::PhantomData;
+use frame_support::Parameter;
+use parity_scale_codec::{Codec, Decode, Encode};
+use sp_runtime::traits::{AtLeast32Bit, MaybeSerialize, Member};
+use std::fmt::Debug;
+use substrate_subxt::balances::AccountData;
+use substrate_subxt::system::{System, SystemEventsDecoder};
+use substrate_subxt_proc_macro::{module, Call, Event, Store};
+
+#[module]
+pub trait PolkaBTC: System {
+    type Balance: Parameter
+        + Member
+        + AtLeast32Bit
+        + Codec
+        + Default
+        + Copy
+        + MaybeSerialize
+        + Debug
+        + From<<Self as System>::BlockNumber>;
+}
+
+/// The balance of an account.
+#[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
+pub struct AccountStore<T: PolkaBTC> {
+    #[store(returns = AccountData<T::Balance>)]
+    pub _runtime: PhantomData<T>,
+    pub account_id: T::AccountId,
+}
+
+/// The total amount of PolkaBTC in existence.
+#[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
+pub struct TotalIssuanceStore<T: PolkaBTC> {
+    #[store(returns = T::Balance)]
+    pub _runtime: PhantomData<T>,
+}
+
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct TransferCall<'a, T: PolkaBTC> {
+    pub to: &'a <T as System>::Address,
+    #[codec(compact)]
+    pub amount: T::Balance,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+pub struct TransferEvent<T: PolkaBTC> {
+    pub from: <T as System>::AccountId,
+    pub to: <T as System>::AccountId,
+    pub amount: T::Balance,
+}
@@ -3,6 +3,7 @@ use crate::timestamp::{Timestamp, TimestampEventsDecoder};
 use core::marker::PhantomData;
 use module_exchange_rate_oracle::BtcTxFeesPerByte;
 use parity_scale_codec::{Decode, Encode};
+use serde::Serialize;
 use std::fmt::Debug;
 use substrate_subxt_proc_macro::{module, Call, Event, Store};
 
@@ -36,7 +37,7 @@ pub struct SetExchangeRateCall<T: ExchangeRateOracle> {
     pub _runtime: PhantomData<T>,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct SetExchangeRateEvent<T: ExchangeRateOracle> {
     pub sender: T::AccountId,
     pub rate: T::UnsignedFixedPoint,
@@ -50,7 +51,7 @@ pub struct SetBtcTxFeesPerByteCall<T: ExchangeRateOracle> {
     pub _runtime: PhantomData<T>,
 }
 
-#[derive(Clone, Debug, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, PartialEq, Event, Decode, Serialize)]
 pub struct SetBtcTxFeesPerByteEvent<T: ExchangeRateOracle> {
     pub sender: T::AccountId,
     pub fast: u32,
@@ -1,4 +1,5 @@
 pub mod balances_dot;
+pub mod balances_polka_btc;
 pub mod btc_relay;
 pub mod collateral;
 pub mod exchange_rate_oracle;
@@ -13,6 +14,7 @@ pub mod sla;
 pub mod staked_relayers;
 pub mod timestamp;
 pub mod treasury;
+pub mod utility;
 pub mod vault_registry;
 
 pub use btc_relay::{
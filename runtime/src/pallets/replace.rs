@@ -3,6 +3,7 @@ use core::marker::PhantomData;
 pub use module_bitcoin::types::H256Le;
 pub use module_replace::ReplaceRequest;
 use parity_scale_codec::{Decode, Encode};
+use serde::Serialize;
 pub use sp_core::{H160, H256};
 use std::fmt::Debug;
 use substrate_subxt_proc_macro::{module, Call, Event, Store};
@@ -49,20 +50,20 @@ pub struct CancelReplaceCall<T: Replace> {
     pub replace_id: T::H256,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct RequestReplaceEvent<T: Replace> {
     pub old_vault_id: T::AccountId,
     pub amount: T::PolkaBTC,
     pub replace_id: T::H256,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct WithdrawReplaceEvent<T: Replace> {
     pub vault_id: T::AccountId,
     pub request_id: T::H256,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct AcceptReplaceEvent<T: Replace> {
     pub old_vault_id: T::AccountId,
     pub new_vault_id: T::AccountId,
@@ -72,14 +73,14 @@ pub struct AcceptReplaceEvent<T: Replace> {
     pub btc_address: T::BtcAddress,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct ExecuteReplaceEvent<T: Replace> {
     pub old_vault_id: T::AccountId,
     pub new_vault_id: T::AccountId,
     pub replace_id: T::H256,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct AuctionReplaceEvent<T: Replace> {
     pub old_vault_id: T::AccountId,
     pub new_vault_id: T::AccountId,
@@ -90,7 +91,7 @@ pub struct AuctionReplaceEvent<T: Replace> {
     pub btc_address: T::BtcAddress,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct CancelReplaceEvent<T: Replace> {
     pub new_vault_id: T::AccountId,
     pub old_vault_id: T::AccountId,
@@ -2,6 +2,7 @@ use super::{Core, CoreEventsDecoder};
 use core::marker::PhantomData;
 pub use module_security::{ErrorCode, StatusCode};
 use parity_scale_codec::{Decode, Encode};
+use serde::Serialize;
 use sp_core::U256;
 use std::fmt::Debug;
 use substrate_subxt_proc_macro::{module, Event, Store};
@@ -27,7 +28,7 @@ pub struct ErrorsStore<T: Security> {
     pub _runtime: PhantomData<T>,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct RecoverFromErrorsEvent<T: Security> {
     pub status_code: T::StatusCode,
     pub error_codes: Vec<T::ErrorCode>,
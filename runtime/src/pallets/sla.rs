@@ -1,9 +1,10 @@
 use super::{Core, CoreEventsDecoder};
+use core::marker::PhantomData;
 pub use module_refund::RefundRequest;
-use parity_scale_codec::Decode;
+use parity_scale_codec::{Decode, Encode};
 use serde::Serialize;
 use std::fmt::Debug;
-use substrate_subxt_proc_macro::{module, Event};
+use substrate_subxt_proc_macro::{module, Event, Store};
 #[module]
 pub trait Sla: Core {}
 
@@ -18,3 +19,33 @@ pub struct UpdateRelayerSLAEvent<T: Sla> {
     pub relayer_id: T::AccountId,
     pub new_sla: T::SignedFixedPoint,
 }
+
+/// The current SLA score of a vault.
+#[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
+pub struct VaultSlaStore<T: Sla> {
+    #[store(returns = T::SignedFixedPoint)]
+    pub _runtime: PhantomData<T>,
+    pub account_id: T::AccountId,
+}
+
+/// The current SLA score of a staked relayer.
+#[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
+pub struct RelayerSlaStore<T: Sla> {
+    #[store(returns = T::SignedFixedPoint)]
+    pub _runtime: PhantomData<T>,
+    pub account_id: T::AccountId,
+}
+
+/// The SLA score at which a vault receives the full reward.
+#[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
+pub struct VaultTargetSlaStore<T: Sla> {
+    #[store(returns = T::SignedFixedPoint)]
+    pub _runtime: PhantomData<T>,
+}
+
+/// The SLA score at which a staked relayer receives the full reward.
+#[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
+pub struct RelayerTargetSlaStore<T: Sla> {
+    #[store(returns = T::SignedFixedPoint)]
+    pub _runtime: PhantomData<T>,
+}
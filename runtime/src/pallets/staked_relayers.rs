@@ -3,6 +3,7 @@ use core::marker::PhantomData;
 pub use module_staked_relayers::types::StatusUpdate;
 pub use module_staked_relayers::Error as StakedRelayersError;
 use parity_scale_codec::{Decode, Encode};
+use serde::Serialize;
 use std::fmt::Debug;
 use substrate_subxt::balances::{Balances, BalancesEventsDecoder};
 use substrate_subxt_proc_macro::{module, Call, Event, Store};
@@ -55,19 +56,19 @@ pub struct SetMaturityPeriodCall<T: StakedRelayers> {
     pub period: T::BlockNumber,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct RegisterStakedRelayerEvent<T: StakedRelayers> {
     pub account_id: T::AccountId,
     pub maturity: T::BlockNumber,
     pub collateral: T::DOT,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct DeregisterStakedRelayerEvent<T: StakedRelayers> {
     pub account_id: T::AccountId,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct StatusUpdateSuggestedEvent<T: StakedRelayers> {
     pub status_update_id: u64,
     pub account_id: T::AccountId,
@@ -77,7 +78,7 @@ pub struct StatusUpdateSuggestedEvent<T: StakedRelayers> {
     pub block_hash: Option<T::H256Le>,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct ExecuteStatusUpdateEvent<T: StakedRelayers> {
     pub status_code: T::StatusCode,
     pub add_error: Option<T::ErrorCode>,
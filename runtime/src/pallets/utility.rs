@@ -0,0 +1,15 @@
+use core::marker::PhantomData;
+use parity_scale_codec::Encode;
+use substrate_subxt::system::{System, SystemEventsDecoder};
+use substrate_subxt::Encoded;
+use substrate_subxt_proc_macro::{module, Call};
+
+#[module]
+pub trait Utility: System {}
+
+/// Dispatch the calls in order, reverting all of them if any of them fails.
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct BatchAllCall<T: Utility> {
+    pub calls: Vec<Encoded>,
+    pub _runtime: PhantomData<T>,
+}
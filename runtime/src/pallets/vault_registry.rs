@@ -2,6 +2,7 @@ use super::{Core, CoreEventsDecoder};
 use core::marker::PhantomData;
 pub use module_vault_registry::{Vault, VaultStatus};
 use parity_scale_codec::{Decode, Encode};
+use serde::Serialize;
 use std::fmt::Debug;
 use substrate_subxt_proc_macro::{module, Call, Event, Store};
 
@@ -14,7 +15,7 @@ pub struct RegisterVaultCall<T: VaultRegistry> {
     pub public_key: T::BtcPublicKey,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct RegisterVaultEvent<T: VaultRegistry> {
     pub account_id: T::AccountId,
     pub collateral: T::DOT,
@@ -25,7 +26,7 @@ pub struct LockAdditionalCollateralCall<T: VaultRegistry> {
     pub amount: T::DOT,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct LockAdditionalCollateralEvent<T: VaultRegistry> {
     pub vault_id: T::AccountId,
     pub new_collateral: T::DOT,
@@ -38,7 +39,7 @@ pub struct WithdrawCollateralCall<T: VaultRegistry> {
     pub amount: T::DOT,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct WithdrawCollateralEvent<T: VaultRegistry> {
     pub vault_id: T::AccountId,
     pub withdrawn_collateral: T::DOT,
@@ -58,7 +59,7 @@ pub struct LiquidationCollateralThresholdStore<T: VaultRegistry> {
     pub _runtime: PhantomData<T>,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct IncreaseToBeIssuedTokensEvent<T: VaultRegistry> {
     pub vault_id: T::AccountId,
     pub tokens: T::BTCBalance,
@@ -69,7 +70,7 @@ pub struct UpdatePublicKeyCall<T: VaultRegistry> {
     pub public_key: T::BtcPublicKey,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct UpdatePublicKeyEvent<T: VaultRegistry> {
     pub vault_id: T::AccountId,
     pub public_key: T::BtcPublicKey,
@@ -80,7 +81,7 @@ pub struct RegisterAddressCall<T: VaultRegistry> {
     pub btc_address: T::BtcAddress,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
 pub struct RegisterAddressEvent<T: VaultRegistry> {
     pub vault_id: T::AccountId,
     pub btc_address: T::BtcAddress,
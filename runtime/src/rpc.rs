@@ -2,43 +2,61 @@ pub use module_exchange_rate_oracle::BtcTxFeesPerByte;
 
 use async_trait::async_trait;
 use core::marker::PhantomData;
+use frame_system::{Phase, RawEvent as SystemEvent};
 use jsonrpsee::{
     common::{to_value as to_json_value, Params},
     Client as RpcClient,
 };
+use log::{error, info, warn};
 use module_exchange_rate_oracle_rpc_runtime_api::BalanceWrapper;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
 use sp_arithmetic::FixedU128;
-use sp_core::sr25519::Pair as KeyPair;
-use sp_core::H256;
-use std::collections::BTreeSet;
+use sp_core::{storage::StorageChangeSet, Bytes, H256};
+use sp_runtime::generic::Era;
+use sp_runtime::traits::Header as _;
+use sp_runtime::ApplyExtrinsicResult;
+use std::collections::{BTreeSet, VecDeque};
+use std::convert::TryInto;
 use std::future::Future;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use substrate_subxt::Error as XtError;
 use substrate_subxt::{
-    sudo::*, system::System, Call, Client, ClientBuilder, Event, EventSubscription, EventsDecoder,
-    PairSigner, Signer,
+    balances::AccountData, sudo::*, system::System, Call, Client, ClientBuilder, Encoded, Event,
+    RuntimeError, Signer, Store,
 };
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tokio::time::delay_for;
 
 use crate::balances_dot::*;
+use crate::balances_polka_btc;
+use crate::batch::Batch;
 use crate::btc_relay::*;
+use crate::cache::{CacheStats, StorageCache};
+use crate::compatibility::{check_compatibility, RuntimeVersion};
 use crate::exchange_rate_oracle::*;
+use crate::failover::{
+    connect, connect_healthiest, is_connection_error, missed_blocks, probe, Failover,
+    ENDPOINT_PROBE_INTERVAL, MAX_BEST_BLOCK_AGE,
+};
 use crate::fee::*;
 use crate::frame_system::*;
 use crate::issue::*;
+use crate::offline::{TransactionStatus, UnsignedExtrinsic, UnsignedExtrinsicOptions};
 use crate::pallets::Core;
 use crate::redeem::*;
 use crate::refund::*;
 use crate::replace::*;
 use crate::security::*;
+use crate::signer::PolkaBtcSigner;
+use crate::sla::*;
 use crate::staked_relayers::*;
 use crate::timestamp::*;
 use crate::vault_registry::*;
 use crate::Error;
 use crate::PolkaBtcRuntime;
-use futures::{stream::StreamExt, SinkExt};
+use futures::{stream::StreamExt, SinkExt, Stream};
+use parity_scale_codec::{Decode, Encode};
 
 pub type PolkaBtcHeader = <PolkaBtcRuntime as System>::Header;
 
@@ -85,82 +103,664 @@ pub mod historic_event_types {
         btc_parachain_runtime::RawReplaceEvent<super::AccountId, u128, u128, u32>;
 }
 
-#[derive(Clone)]
-pub struct PolkaBtcProvider {
+/// Default number of blocks that are fetched concurrently when scanning historic events.
+pub const DEFAULT_PAST_EVENTS_CONCURRENCY: usize = 16;
+
+/// Number of recent blocks whose events are kept for the listeners, enough for the listeners
+/// of finalized blocks to catch up with those of best blocks.
+const RECENT_EVENTS_CAPACITY: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct PastEventsOptions {
+    /// The height to start iterating at. If None, it starts from the genesis.
+    pub start: Option<u32>,
+    /// The height to stop iterating at, exclusive. If None, it ends after the current chain height.
+    pub end: Option<u32>,
+    /// The maximum number of blocks to fetch concurrently.
+    pub concurrency: usize,
+    /// Only return the events of these pallets. If None, all events are returned.
+    pub pallets: Option<Vec<String>>,
+}
+
+impl Default for PastEventsOptions {
+    fn default() -> Self {
+        Self {
+            start: None,
+            end: None,
+            concurrency: DEFAULT_PAST_EVENTS_CONCURRENCY,
+            pallets: None,
+        }
+    }
+}
+
+/// The (filtered) events of a single historic block, see `PolkaBtcProvider::past_events`.
+#[derive(Clone, Debug)]
+pub struct PastBlockEvents {
+    /// Height of the block in which the events were emitted.
+    pub height: u32,
+    /// Number of blocks left in the scanned range after this one.
+    pub remaining: u32,
+    pub events: Vec<btc_parachain_runtime::Event>,
+}
+
+struct Connection {
     rpc_client: RpcClient,
     ext_client: Client<PolkaBtcRuntime>,
-    signer: Arc<RwLock<PairSigner<PolkaBtcRuntime, KeyPair>>>,
+}
+
+#[derive(Clone)]
+pub struct PolkaBtcProvider {
+    connection: Arc<std::sync::RwLock<Connection>>,
+    failover: Arc<Failover>,
+    signer: Arc<RwLock<PolkaBtcSigner>>,
     account_id: AccountId,
+    /// The block whose state is queried, the best block if None.
+    at: Option<H256>,
+    /// Caches frequently read storage items if set, see `with_cache`.
+    cache: Option<Arc<StorageCache>>,
+    /// The events of recent blocks, shared by all clones, see `get_events`.
+    recent_events: Arc<Mutex<VecDeque<(H256, Vec<btc_parachain_runtime::Event>)>>>,
 }
 
 impl PolkaBtcProvider {
-    pub async fn new<P: Into<jsonrpsee::Client>>(
+    /// Create a provider for the given client. Since there is no url to reconnect to,
+    /// the provider is not able to fail over to another endpoint.
+    pub async fn new<P: Into<jsonrpsee::Client>, S: Into<PolkaBtcSigner>>(
         rpc_client: P,
-        signer: PairSigner<PolkaBtcRuntime, KeyPair>,
+        signer: S,
+    ) -> Result<Self, Error> {
+        Self::with_failover(rpc_client.into(), Failover::new(vec![]), signer.into()).await
+    }
+
+    pub async fn from_url<S: Into<PolkaBtcSigner>>(url: String, signer: S) -> Result<Self, Error> {
+        let rpc_client = connect(&url).await?;
+        Self::with_failover(rpc_client, Failover::new(vec![url]), signer.into()).await
+    }
+
+    /// Connect to the healthiest of the given parachain endpoints, which can be over WebSockets
+    /// or HTTP. If the connection fails, `switch_endpoint` reconnects to the healthiest endpoint
+    /// at that time, see also `monitor_endpoints`.
+    pub async fn from_urls<S: Into<PolkaBtcSigner>>(
+        urls: Vec<String>,
+        signer: S,
+    ) -> Result<Self, Error> {
+        let (url, rpc_client) = connect_healthiest(&urls).await?;
+        info!("Connected to parachain endpoint {}", url);
+        Self::with_failover(rpc_client, Failover::new(urls), signer.into()).await
+    }
+
+    async fn with_failover(
+        rpc_client: RpcClient,
+        failover: Failover,
+        mut signer: PolkaBtcSigner,
     ) -> Result<Self, Error> {
         let account_id = signer.account_id().clone();
-        let rpc_client = rpc_client.into();
-        let ext_client = ClientBuilder::<PolkaBtcRuntime>::new()
-            .set_client(rpc_client.clone())
-            .build()
-            .await?;
+        let (ext_client, metadata) = Self::build_ext_client(rpc_client.clone()).await?;
+        signer.set_metadata(&metadata)?;
 
         // there is a race condition on signing
         // since we run the relayer in the background
         Ok(Self {
-            rpc_client,
-            ext_client,
+            connection: Arc::new(std::sync::RwLock::new(Connection {
+                rpc_client,
+                ext_client,
+            })),
+            failover: Arc::new(failover),
             signer: Arc::new(RwLock::new(signer)),
             account_id,
+            at: None,
+            cache: None,
+            recent_events: Arc::new(Mutex::new(VecDeque::new())),
         })
     }
 
-    pub async fn from_url(
-        url: String,
-        signer: PairSigner<PolkaBtcRuntime, KeyPair>,
-    ) -> Result<Self, Error> {
-        let rpc_client = if url.starts_with("ws://") || url.starts_with("wss://") {
-            jsonrpsee::ws_client(&url).await?
-        } else {
-            jsonrpsee::http_client(&url)
+    /// A view of the provider that queries the state as of the given parachain block,
+    /// e.g. `provider.at(hash).get_vault(vault_id)`. Extrinsics, subscriptions and past
+    /// events are not affected.
+    pub fn at(&self, block_hash: H256) -> Self {
+        Self {
+            at: Some(block_hash),
+            ..self.clone()
+        }
+    }
+
+    /// A provider that caches frequently read storage items, such as the vaults, the exchange
+    /// rate, the request periods and the fees. Cached items are read as of the latest finalized
+    /// block rather than the best block, so they can lag behind by a few blocks. The cache is
+    /// only filled while `maintain_cache` is running, and is shared by all clones.
+    pub fn with_cache(&self) -> Self {
+        Self {
+            cache: Some(Arc::new(StorageCache::new())),
+            ..self.clone()
+        }
+    }
+
+    /// A view of the provider that bypasses the cache, for reads that must reflect the best
+    /// block, e.g. `provider.uncached().get_vault(vault_id)` right after registering.
+    pub fn uncached(&self) -> Self {
+        Self {
+            cache: None,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the hit and miss counters of the cache, None if caching is disabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
+    }
+
+    /// Invalidate the cache whenever a new block is finalized. Should run for as long as the
+    /// provider is used, returns immediately if caching is disabled.
+    pub async fn maintain_cache(&self) -> Result<(), Error> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return Ok(()),
+        };
+        self.on_block(|header| async move {
+            cache.set_finalized_block(header.number, header.hash());
+            Ok(())
+        })
+        .await
+    }
+
+    /// Fetch the storage item from the cache if enabled, otherwise or on a miss from the
+    /// parachain. Queries of historic state are never cached.
+    async fn fetch_cached<S>(&self, store: S) -> Result<S::Returns, Error>
+    where
+        S: Store<PolkaBtcRuntime>,
+        S::Returns: Encode,
+    {
+        self.with_retry(|| self.fetch_cached_once(&store)).await
+    }
+
+    async fn fetch_cached_once<S>(&self, store: &S) -> Result<S::Returns, Error>
+    where
+        S: Store<PolkaBtcRuntime>,
+        S::Returns: Encode,
+    {
+        let ext_client = self.ext_client();
+        let (cache, block_hash) = match (&self.cache, self.at) {
+            (Some(cache), None) => match cache.block_hash() {
+                Some(block_hash) => (cache, block_hash),
+                None => return Ok(ext_client.fetch_or_default(store, None).await?),
+            },
+            _ => return Ok(ext_client.fetch_or_default(store, self.at).await?),
+        };
+
+        let key = store.key(ext_client.metadata()).map_err(XtError::from)?.0;
+        if let Some(data) = cache.get(block_hash, &key) {
+            return Ok(S::Returns::decode(&mut &data[..])?);
+        }
+        let value = ext_client.fetch_or_default(store, Some(block_hash)).await?;
+        cache.insert(block_hash, key, value.encode());
+        Ok(value)
+    }
+
+    /// Build a client after checking the compatibility of the runtime, also returns the
+    /// encoded runtime metadata.
+    async fn build_ext_client(
+        rpc_client: RpcClient,
+    ) -> Result<(Client<PolkaBtcRuntime>, Bytes), Error> {
+        let metadata = check_compatibility(&rpc_client).await?;
+
+        let ext_client = ClientBuilder::<PolkaBtcRuntime>::new()
+            .set_client(rpc_client)
+            .build()
+            .await?;
+        Ok((ext_client, metadata))
+    }
+
+    fn rpc_client(&self) -> RpcClient {
+        self.connection.read().unwrap().rpc_client.clone()
+    }
+
+    fn ext_client(&self) -> Client<PolkaBtcRuntime> {
+        self.connection.read().unwrap().ext_client.clone()
+    }
+
+    /// Run a query, and if the connection fails while it is in flight, switch to another
+    /// endpoint and run it again. Only used for reads, extrinsics are never submitted twice.
+    async fn with_retry<T, F, R>(&self, query: F) -> Result<T, Error>
+    where
+        F: Fn() -> R,
+        R: Future<Output = Result<T, Error>>,
+    {
+        match query().await {
+            Err(err) if is_connection_error(&err) && self.failover.can_switch() => {
+                warn!(
+                    "Parachain query failed, retrying on another endpoint: {}",
+                    err
+                );
+                self.switch_endpoint().await?;
+                query().await
+            }
+            result => result,
+        }
+    }
+
+    /// Send an rpc request, which is retried on another endpoint if the connection fails.
+    async fn request<T: DeserializeOwned>(&self, method: &str, params: Params) -> Result<T, Error> {
+        self.with_retry(move || {
+            let params = params.clone();
+            async move { Ok(self.rpc_client().request(method, params).await?) }
+        })
+        .await
+    }
+
+    /// Reconnect to the healthiest of the configured parachain endpoints. All clones of this
+    /// provider use the new connection, and running subscriptions are re-established on it.
+    /// Concurrent calls result in a single reconnect.
+    pub async fn switch_endpoint(&self) -> Result<(), Error> {
+        let generation = self.failover.generation();
+        let _guard = self.failover.lock().await;
+        if self.failover.generation() != generation {
+            // another task reconnected while we were waiting for the lock
+            return Ok(());
+        }
+
+        let (url, rpc_client) = connect_healthiest(self.failover.urls()).await?;
+        let (ext_client, metadata) = Self::build_ext_client(rpc_client.clone()).await?;
+        self.signer.write().await.set_metadata(&metadata)?;
+        *self.connection.write().unwrap() = Connection {
+            rpc_client,
+            ext_client,
         };
+        self.failover.notify_reconnected();
+
+        info!("Switched to parachain endpoint {}", url);
+        Ok(())
+    }
 
-        Self::new(rpc_client, signer).await
+    /// Periodically probe the current parachain endpoint, and switch to another endpoint if it
+    /// is unreachable, unhealthy (syncing or without peers) or if its best block has not
+    /// advanced for `MAX_BEST_BLOCK_AGE`. Never returns.
+    pub async fn monitor_endpoints(&self) {
+        let mut best_block = 0;
+        let mut last_progress = Instant::now();
+        loop {
+            delay_for(ENDPOINT_PROBE_INTERVAL).await;
+
+            match probe(&self.rpc_client()).await {
+                Ok(health) if health.is_healthy() => {
+                    if health.best_block > best_block {
+                        best_block = health.best_block;
+                        last_progress = Instant::now();
+                        continue;
+                    } else if last_progress.elapsed() < MAX_BEST_BLOCK_AGE {
+                        continue;
+                    }
+                    warn!(
+                        "Best block of parachain endpoint is stuck at {}",
+                        health.best_block
+                    );
+                }
+                Ok(health) => warn!("Parachain endpoint is unhealthy: {:?}", health),
+                Err(err) => warn!("Parachain endpoint is unavailable: {}", err),
+            }
+
+            match self.switch_endpoint().await {
+                Ok(_) => {
+                    best_block = 0;
+                    last_progress = Instant::now();
+                }
+                Err(err) => error!("Failed to switch parachain endpoint: {}", err),
+            }
+        }
     }
 
     /// Fetch all active vaults.
     pub async fn get_all_vaults(&self) -> Result<Vec<PolkaBtcVault>, Error> {
-        let mut vaults = Vec::new();
-        let mut iter = self.ext_client.vaults_iter(None).await?;
-        while let Some((_, account)) = iter.next().await? {
-            vaults.push(account);
-        }
-        Ok(vaults)
+        self.with_retry(|| async {
+            let mut vaults = Vec::new();
+            let ext_client = self.ext_client();
+            let mut iter = ext_client.vaults_iter(self.at).await?;
+            while let Some((_, account)) = iter.next().await? {
+                vaults.push(account);
+            }
+            Ok(vaults)
+        })
+        .await
     }
 
-    /// Subscribe to new parachain blocks.
+    /// Subscribe to new finalized parachain blocks. The subscription is re-established when
+    /// the provider switches to another endpoint, and the blocks finalized in between are
+    /// reported before the next new block.
     pub async fn on_block<F, R>(&self, on_block: F) -> Result<(), Error>
     where
         F: Fn(PolkaBtcHeader) -> R,
         R: Future<Output = Result<(), Error>>,
     {
-        let mut sub = self.ext_client.subscribe_finalized_blocks().await?;
+        self.follow_blocks(true, on_block).await
+    }
+
+    /// Calls `on_block` with every new best block, or every new finalized block if `finalized`
+    /// is set. After switching to another endpoint, the blocks that were missed are replayed
+    /// from the last block seen on the previous endpoint.
+    async fn follow_blocks<F, R>(&self, finalized: bool, on_block: F) -> Result<(), Error>
+    where
+        F: Fn(PolkaBtcHeader) -> R,
+        R: Future<Output = Result<(), Error>>,
+    {
+        let mut last_seen: Option<(u32, H256)> = None;
         loop {
-            on_block(sub.next().await).await?;
+            let mut reconnected = self.failover.subscribe();
+            let ext_client = self.ext_client();
+            let mut sub = if finalized {
+                ext_client.subscribe_finalized_blocks().await?
+            } else {
+                ext_client.subscribe_blocks().await?
+            };
+            loop {
+                let header = tokio::select! {
+                    header = sub.next() => header,
+                    _ = reconnected.recv() => break,
+                };
+                if let Some((number, hash)) = last_seen {
+                    if hash == header.hash() {
+                        // a new subscription starts with the block that was already seen
+                        continue;
+                    }
+                    for height in missed_blocks(number, header.number) {
+                        on_block(self.get_header_at(height).await?).await?;
+                    }
+                }
+                last_seen = Some((header.number, header.hash()));
+                on_block(header).await?;
+            }
+        }
+    }
+
+    /// Fetch the header of the block at the given height of the best chain.
+    async fn get_header_at(&self, height: u32) -> Result<PolkaBtcHeader, Error> {
+        self.with_retry(|| async {
+            let ext_client = self.ext_client();
+            let hash = ext_client.block_hash(Some(height.into())).await?;
+            ext_client.header(hash).await?.ok_or(Error::BlockNotFound)
+        })
+        .await
+    }
+
+    /// Fetch the events emitted in the block with the given hash.
+    async fn fetch_events(&self, hash: H256) -> Result<Vec<btc_parachain_runtime::Event>, Error> {
+        self.with_retry(|| async {
+            let records = self.ext_client().events(Some(hash)).await?;
+            Ok(records.into_iter().map(|record| record.event).collect())
+        })
+        .await
+    }
+
+    /// Like `fetch_events`, but the events of recent blocks are kept, such that all listeners
+    /// following the same blocks fetch the events of each block once.
+    async fn get_events(&self, hash: H256) -> Result<Vec<btc_parachain_runtime::Event>, Error> {
+        // the lock is held while fetching, so that concurrent listeners wait for the first one
+        let mut recent_events = self.recent_events.lock().await;
+        if let Some((_, events)) = recent_events.iter().find(|(block, _)| *block == hash) {
+            return Ok(events.clone());
+        }
+        let events = self.fetch_events(hash).await?;
+        if recent_events.len() >= RECENT_EVENTS_CAPACITY {
+            recent_events.pop_front();
         }
+        recent_events.push_back((hash, events.clone()));
+        Ok(events)
+    }
+
+    /// Like `get_events`, but events that can not be decoded are reported to `on_error`
+    /// rather than returned as an error, since retrying would not help.
+    async fn get_events_or_report<E: Fn(XtError)>(
+        &self,
+        hash: H256,
+        on_error: &E,
+    ) -> Result<Vec<btc_parachain_runtime::Event>, Error> {
+        match self.get_events(hash).await {
+            Err(Error::XtError(err @ XtError::Codec(_))) => {
+                on_error(err);
+                Ok(vec![])
+            }
+            result => result,
+        }
+    }
+
+    /// Subscription service that should listen forever, only returns if the subscription
+    /// cannot be established or the callback fails. Unlike `on_event`, the callback is called
+    /// with the header of the finalized block that emitted the event of type `T`.
+    ///
+    /// # Arguments
+    /// * `on_event` - callback for events
+    /// * `on_error` - callback for decoding errors, is not allowed to take too long
+    pub async fn on_block_event<T, F, R, E>(&self, on_event: F, on_error: E) -> Result<(), Error>
+    where
+        T: Event<PolkaBtcRuntime>,
+        F: Fn(T, PolkaBtcHeader) -> R,
+        R: Future<Output = Result<(), Error>>,
+        E: Fn(XtError),
+    {
+        let on_event = &on_event;
+        let on_error = &on_error;
+        self.on_block(|header| async move {
+            for event in self.get_events_or_report(header.hash(), on_error).await? {
+                match self.try_decode_event::<T>(&event) {
+                    Some(Ok(event)) => on_event(event, header.clone()).await?,
+                    Some(Err(err)) => on_error(err),
+                    None => {}
+                }
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Decode the event as `T`, returns None if it is an event of another type. Unlike the
+    /// `historic_event_types`, this relies on the metadata rather than on the runtime's enums.
+    pub fn decode_event<T: Event<PolkaBtcRuntime>>(
+        &self,
+        event: &btc_parachain_runtime::Event,
+    ) -> Option<T> {
+        self.try_decode_event(event)?.ok()
+    }
+
+    /// Like `decode_event`, but returns the error if the event is of type `T` and fails
+    /// to decode.
+    fn try_decode_event<T: Event<PolkaBtcRuntime>>(
+        &self,
+        event: &btc_parachain_runtime::Event,
+    ) -> Option<Result<T, XtError>> {
+        // the encoding starts with the index of the module and of the event within it
+        let encoded = event.encode();
+        let ext_client = self.ext_client();
+        let module = ext_client.metadata().module_with_events(encoded[0]).ok()?;
+        let metadata = module.event(encoded[1]).ok()?;
+        if module.name() != T::MODULE || metadata.name != T::EVENT {
+            return None;
+        }
+        Some(T::decode(&mut &encoded[2..]).map_err(Into::into))
+    }
+
+    /// Returns the index of the module and of the event within it, which prefix the encoding of
+    /// events of type `T`. Returns None if the event does not exist in the metadata.
+    pub fn event_indices<T: Event<PolkaBtcRuntime>>(&self) -> Option<(u8, u8)> {
+        let ext_client = self.ext_client();
+        let metadata = ext_client.metadata();
+        (0..=u8::MAX).find_map(|module_index| {
+            let module = metadata.module_with_events(module_index).ok()?;
+            if module.name() != T::MODULE {
+                return None;
+            }
+            (0..=u8::MAX).find_map(|event_index| {
+                let event = module.event(event_index).ok()?;
+                if event.name == T::EVENT {
+                    Some((module_index, event_index))
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
+    /// Returns a stream of the values of the storage item, starting with its current value
+    /// followed by the new value whenever it changes. Changes are reported for new best blocks,
+    /// together with the hash of the block. The stream ends if the connection is lost, see
+    /// `on_storage_change` to keep following the item across endpoint switches.
+    pub async fn storage_changes<S: Store<PolkaBtcRuntime>>(
+        &self,
+        store: S,
+    ) -> Result<impl Stream<Item = Result<(H256, S::Returns), Error>>, Error> {
+        let metadata = self.ext_client().metadata().clone();
+        let key = store.key(&metadata).map_err(XtError::from)?;
+        let sub = self
+            .rpc_client()
+            .subscribe::<StorageChangeSet<H256>>(
+                "state_subscribeStorage",
+                Params::Array(vec![to_json_value(vec![&key])?]),
+                "state_unsubscribeStorage",
+            )
+            .await?;
+
+        Ok(
+            futures::stream::unfold(sub, |mut sub| async move { Some((sub.next().await, sub)) })
+                .map(move |change_set| {
+                    // we only subscribed to a single key, so it is the only one in the set
+                    let data = change_set
+                        .changes
+                        .into_iter()
+                        .find(|(changed_key, _)| changed_key == &key)
+                        .and_then(|(_, data)| data);
+                    let value = match data {
+                        Some(data) => S::Returns::decode(&mut &data.0[..])?,
+                        // the item was removed, so it has its default value again
+                        None => store.default(&metadata).map_err(XtError::from)?,
+                    };
+                    Ok((change_set.block, value))
+                }),
+        )
+    }
+
+    /// Subscription service that should listen forever, only returns if the subscription
+    /// cannot be established or the callback fails. Calls `on_change` with the current value
+    /// of the storage item and then with every new value, see `storage_changes`. The
+    /// subscription is re-established when the provider switches to another endpoint, starting
+    /// again with the value at that time.
+    pub async fn on_storage_change<S, F, R>(&self, store: S, on_change: F) -> Result<(), Error>
+    where
+        S: Store<PolkaBtcRuntime> + Clone,
+        F: Fn(S::Returns) -> R,
+        R: Future<Output = Result<(), Error>>,
+    {
+        loop {
+            let mut reconnected = self.failover.subscribe();
+            let changes = self.storage_changes(store.clone()).await?;
+            futures::pin_mut!(changes);
+            loop {
+                tokio::select! {
+                    change = changes.next() => match change {
+                        Some(change) => on_change(change?.1).await?,
+                        None => {
+                            // end of stream, the endpoint is most likely gone
+                            self.switch_endpoint().await?;
+                            break;
+                        }
+                    },
+                    _ = reconnected.recv() => break,
+                }
+            }
+        }
+    }
+
+    /// Calls `on_change` with the vault of the given account whenever it changes, see
+    /// `on_storage_change`.
+    pub async fn on_vault_change<F, R>(
+        &self,
+        vault_id: AccountId,
+        on_change: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn(PolkaBtcVault) -> R,
+        R: Future<Output = Result<(), Error>>,
+    {
+        let store = VaultsStore {
+            _runtime: PhantomData,
+            account_id: vault_id,
+        };
+        self.on_storage_change(store, on_change).await
+    }
+
+    /// Calls `on_change` with the BTC/DOT exchange rate whenever it changes, see
+    /// `on_storage_change`.
+    pub async fn on_exchange_rate_change<F, R>(&self, on_change: F) -> Result<(), Error>
+    where
+        F: Fn(FixedU128) -> R,
+        R: Future<Output = Result<(), Error>>,
+    {
+        let store = ExchangeRateStore {
+            _runtime: PhantomData,
+        };
+        self.on_storage_change(store, on_change).await
+    }
+
+    /// Calls `on_change` with the status of the parachain whenever it changes, see
+    /// `on_storage_change`.
+    pub async fn on_parachain_status_change<F, R>(&self, on_change: F) -> Result<(), Error>
+    where
+        F: Fn(StatusCode) -> R,
+        R: Future<Output = Result<(), Error>>,
+    {
+        let store = ParachainStatusStore {
+            _runtime: PhantomData,
+        };
+        self.on_storage_change(store, on_change).await
+    }
+
+    /// Returns a stream of the events stored in the given range of parachain blocks. Blocks are
+    /// fetched concurrently, but always delivered in ascending order of height, so consumers can
+    /// persist the height of the last delivered block and later resume from the next one.
+    ///
+    /// # Arguments
+    /// * `options` - the range to scan, the number of concurrent block requests and an optional
+    ///               filter on the pallets whose events should be returned
+    pub async fn past_events(
+        &self,
+        options: PastEventsOptions,
+    ) -> Result<impl Stream<Item = Result<PastBlockEvents, Error>> + '_, Error> {
+        let start = options.start.unwrap_or(1);
+        let chain_height = self.get_current_chain_height().await?;
+        let end = match options.end {
+            Some(x) => u32::min(x, chain_height + 1),
+            None => chain_height + 1,
+        };
+        let event_filter = match options.pallets {
+            Some(ref pallets) => Some(self.get_pallet_event_indices(pallets)?),
+            None => None,
+        };
+
+        Ok(futures::stream::iter(start..end)
+            .map(move |height| self.get_block_events(height))
+            .buffered(usize::max(options.concurrency, 1))
+            .map(move |result| {
+                let (height, events) = result?;
+                let events = match event_filter {
+                    Some(ref indices) => events
+                        .into_iter()
+                        .filter(|event| indices.contains(&event.using_encoded(|bytes| bytes[0])))
+                        .collect(),
+                    None => events,
+                };
+                Ok(PastBlockEvents {
+                    height,
+                    remaining: end - height - 1,
+                    events,
+                })
+            }))
     }
 
     /// Calls `callback` with each of the past events stored in the chain
     ///
     /// # Arguments
-    /// * `start` - the height to start iterating at. If None, it starts from the genesis.
-    /// * `end` - the height to stop iterating at. If None, it ends at the current chain height.
+    /// * `options` - the range of blocks to scan, see `past_events`
     /// * `callback` - the callback to be called with the event. The callback will be called with
     ///                the event and the numbering of remaining blocks to process
     pub async fn on_past_events<T>(
         &self,
-        start: Option<u32>,
-        end: Option<u32>,
+        options: PastEventsOptions,
         mut callback: T,
     ) -> Result<(), Error>
     where
@@ -169,16 +769,12 @@ impl PolkaBtcProvider {
             u32,
         ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
     {
-        let start = start.unwrap_or(1);
-        let end = match end {
-            Some(x) => u32::min(x, self.get_current_chain_height().await?),
-            None => self.get_current_chain_height().await?,
-        };
-        for i in start..end {
-            let hash = self.ext_client.block_hash(Some(i.into())).await?;
-            let events = self.ext_client.events(hash).await?;
-            for event in events.into_iter() {
-                if let Err(e) = callback(event.event, end - i) {
+        let stream = self.past_events(options).await?;
+        futures::pin_mut!(stream);
+        while let Some(block) = stream.next().await {
+            let block = block?;
+            for event in block.events.into_iter() {
+                if let Err(e) = callback(event, block.remaining) {
                     return Err(Error::CallbackError(e));
                 }
             }
@@ -186,36 +782,81 @@ impl PolkaBtcProvider {
         Ok(())
     }
 
-    /// Subscription service that should listen forever, only returns if the initial subscription
-    /// cannot be established. Calls `on_error` when an error event has been received, or when an
-    /// event has been received that failed to be decoded into a raw event.
+    /// Fetch all events emitted in the parachain block at the given height.
+    async fn get_block_events(
+        &self,
+        height: u32,
+    ) -> Result<(u32, Vec<btc_parachain_runtime::Event>), Error> {
+        let hash = self.get_block_hash(height).await?;
+        Ok((height, self.fetch_events(hash).await?))
+    }
+
+    /// Resolve pallet names (e.g. `issue` or `VaultRegistry`) to the indices used to encode
+    /// their events.
+    fn get_pallet_event_indices(&self, pallets: &[String]) -> Result<BTreeSet<u8>, Error> {
+        let normalize = |name: &str| {
+            name.replace(|c: char| c == '_' || c == '-', "")
+                .to_lowercase()
+        };
+        let ext_client = self.ext_client();
+        let metadata = ext_client.metadata();
+        let modules = (0..=u8::MAX)
+            .filter_map(|index| {
+                let module = metadata.module_with_events(index).ok()?;
+                Some((normalize(module.name()), index))
+            })
+            .collect::<Vec<_>>();
+
+        pallets
+            .iter()
+            .map(|pallet| {
+                let name = normalize(pallet);
+                modules
+                    .iter()
+                    .find(|(module, _)| *module == name)
+                    .map(|(_, index)| *index)
+                    .ok_or_else(|| Error::UnknownPallet(pallet.clone()))
+            })
+            .collect()
+    }
+
+    /// Subscription service that should listen forever, only returns if the subscription
+    /// cannot be established. Calls `on_error` when an extrinsic failed to dispatch, or when the
+    /// events of a block failed to decode. Events of new best blocks are reported; when the
+    /// provider switches to another endpoint, the blocks produced in between are replayed.
     ///
     /// # Arguments
     /// * `on_error` - callback for decoding errors, is not allowed to take too long
     pub async fn on_event_error<E: Fn(XtError)>(&self, on_error: E) -> Result<(), Error> {
-        let sub = self.ext_client.subscribe_events().await?;
-        let mut decoder = EventsDecoder::<PolkaBtcRuntime>::new(self.ext_client.metadata().clone());
-        // We would need with_core to be able to decode all types, but since
-        // it does not exist, instead use a random module that includes it:
-        decoder.with_vault_registry();
-
-        let mut sub = EventSubscription::<PolkaBtcRuntime>::new(sub, decoder);
-        loop {
-            match sub.next().await {
-                Some(Err(err)) => on_error(err), // report error
-                Some(Ok(_)) => {}                // do nothing
-                None => break Ok(()),            // end of stream
+        let on_error = &on_error;
+        self.follow_blocks(false, |header| async move {
+            for event in self.get_events_or_report(header.hash(), on_error).await? {
+                if let btc_parachain_runtime::Event::frame_system(SystemEvent::ExtrinsicFailed(
+                    dispatch_error,
+                    _,
+                )) = event
+                {
+                    match RuntimeError::from_dispatch(self.ext_client().metadata(), dispatch_error)
+                    {
+                        Ok(err) => on_error(XtError::Runtime(err)),
+                        Err(err) => on_error(err),
+                    }
+                }
             }
-        }
+            Ok(())
+        })
+        .await
     }
 
-    /// Subscription service that should listen forever, only returns if the initial subscription
+    /// Subscription service that should listen forever, only returns if the subscription
     /// cannot be established. This function uses two concurrent tasks: one for the event listener,
     /// and one that calls the given callback. This allows the callback to take a long time to
     /// complete without breaking the rpc communication, which could otherwise happen. Still, since
     /// the queue of callbacks is processed sequentially, some care should be taken that the queue
-    /// does not overflow. `on_error` is called when the event has successfully been decoded into a
-    /// raw_event, but failed to decode into an event of type `T`
+    /// does not overflow. `on_error` is called when the events of a block fail to decode, or when
+    /// an event failed to decode into an event of type `T`. Events of new best blocks are
+    /// reported; when the provider switches to another endpoint, the blocks produced in between
+    /// are replayed, so no event is dropped.
     ///
     /// # Arguments
     /// * `on_event` - callback for events, is allowed to sometimes take a longer time
@@ -227,38 +868,29 @@ impl PolkaBtcProvider {
         R: Future<Output = ()>,
         E: Fn(XtError),
     {
-        let sub = self.ext_client.subscribe_events().await?;
-        let mut decoder = EventsDecoder::<PolkaBtcRuntime>::new(self.ext_client.metadata().clone());
-        // We would need with_core to be able to decode all types, but since
-        // it does not exist, instead use a random module that includes it:
-        decoder.with_vault_registry();
-
-        let mut sub = EventSubscription::<PolkaBtcRuntime>::new(sub, decoder);
-        sub.filter_event::<T>();
-
         let (tx, mut rx) = futures::channel::mpsc::channel::<T>(32);
 
         // two tasks: one for event listening and one for callback calling
         futures::future::try_join(
             async move {
                 let tx = &tx;
-                while let Some(result) = sub.next().await {
-                    if let Ok(raw_event) = result {
-                        let decoded = T::decode(&mut &raw_event.data[..]);
-                        match decoded {
-                            Ok(event) => {
+                let on_error = &on_error;
+                self.follow_blocks(false, |header| async move {
+                    for event in self.get_events_or_report(header.hash(), on_error).await? {
+                        match self.try_decode_event::<T>(&event) {
+                            Some(Ok(event)) => {
                                 // send the event to the other task
                                 if tx.clone().send(event).await.is_err() {
-                                    break;
+                                    return Err(Error::ChannelClosed);
                                 }
                             }
-                            Err(err) => {
-                                on_error(err.into());
-                            }
-                        };
+                            Some(Err(err)) => on_error(err),
+                            None => {}
+                        }
                     }
-                }
-                Result::<(), _>::Err(Error::ChannelClosed)
+                    Ok(())
+                })
+                .await
             },
             async move {
                 loop {
@@ -279,13 +911,182 @@ impl PolkaBtcProvider {
         Ok(())
     }
 
+    /// Sign the call with the configured signer and encode the resulting extrinsic,
+    /// without submitting it.
+    async fn create_signed_extrinsic<C: Call<PolkaBtcRuntime> + Send + Sync>(
+        &self,
+        call: C,
+    ) -> Result<Bytes, Error> {
+        let extrinsic = self
+            .ext_client()
+            .create_signed(call, &*self.signer.read().await)
+            .await?;
+        Ok(Bytes(extrinsic.encode()))
+    }
+
     async fn sudo<C: Call<PolkaBtcRuntime>>(&self, call: C) -> Result<(), Error> {
-        let encoded = self.ext_client.encode(call)?;
-        self.ext_client
+        let encoded = self.ext_client().encode(call)?;
+        self.ext_client()
             .sudo_and_watch(&*self.signer.write().await, &encoded)
             .await?;
         Ok(())
     }
+
+    /// Submit the call and wait until it is included in a block.
+    pub(crate) async fn watch<C: Call<PolkaBtcRuntime> + Send + Sync>(
+        &self,
+        call: C,
+    ) -> Result<(), Error> {
+        self.ext_client()
+            .watch(call, &*self.signer.write().await)
+            .await?;
+        Ok(())
+    }
+
+    /// Start a batch of calls that are submitted in a single extrinsic, e.g.
+    /// `provider.batch().add(call1).add(call2).submit().await`.
+    pub fn batch(&self) -> Batch<'_> {
+        Batch::new(self)
+    }
+
+    /// Encode the call, e.g. for `create_unsigned_extrinsic`.
+    pub fn encode_call<C: Call<PolkaBtcRuntime>>(&self, call: C) -> Result<Bytes, Error> {
+        Ok(Bytes(self.ext_client().encode(call)?.0))
+    }
+
+    /// Wrap the encoded call in `sudo`, such that it is dispatched as root.
+    pub fn encode_sudo_call(&self, call: Bytes) -> Result<Bytes, Error> {
+        let encoded = self.ext_client().encode(SudoCall {
+            _runtime: PhantomData,
+            call: &Encoded(call.0),
+        })?;
+        Ok(Bytes(encoded.0))
+    }
+
+    /// Collect everything needed to sign the encoded call offline, see
+    /// `UnsignedExtrinsic::sign`. The result can be submitted with `submit_signed_extrinsic`.
+    ///
+    /// # Arguments
+    /// * `call` - the encoded call, see `encode_call` and `encode_sudo_call`
+    /// * `signer` - the account that will sign the extrinsic
+    /// * `options` - nonce, mortality and tip of the extrinsic
+    pub async fn create_unsigned_extrinsic(
+        &self,
+        call: Bytes,
+        signer: AccountId,
+        options: UnsignedExtrinsicOptions,
+    ) -> Result<UnsignedExtrinsic, Error> {
+        let version: RuntimeVersion = self
+            .request("state_getRuntimeVersion", Params::None)
+            .await?;
+        let genesis_hash = self.get_block_hash(0).await?;
+
+        let nonce = match options.nonce {
+            Some(nonce) => nonce,
+            None => {
+                self.request(
+                    "system_accountNextIndex",
+                    Params::Array(vec![to_json_value(&signer)?]),
+                )
+                .await?
+            }
+        };
+
+        let (era, era_hash) = match options.mortality {
+            Some(period) => {
+                let finalized_head: H256 =
+                    self.request("chain_getFinalizedHead", Params::None).await?;
+                let header: PolkaBtcHeader = self
+                    .request(
+                        "chain_getHeader",
+                        Params::Array(vec![to_json_value(finalized_head)?]),
+                    )
+                    .await?;
+                let era = Era::mortal(period, header.number.into());
+                let birth = era.birth(header.number.into()).try_into()?;
+                (era, self.get_block_hash(birth).await?)
+            }
+            None => (Era::Immortal, genesis_hash),
+        };
+
+        Ok(UnsignedExtrinsic {
+            signer,
+            call,
+            nonce,
+            era,
+            tip: options.tip,
+            spec_version: version.spec_version,
+            transaction_version: version.transaction_version,
+            genesis_hash,
+            era_hash,
+        })
+    }
+
+    async fn get_block_hash(&self, height: u32) -> Result<H256, Error> {
+        let hash: Option<H256> = self
+            .request(
+                "chain_getBlockHash",
+                Params::Array(vec![to_json_value(height)?]),
+            )
+            .await?;
+        hash.ok_or(Error::BlockNotFound)
+    }
+
+    /// Submit an extrinsic that was signed offline, and wait until it is included in a block.
+    /// Returns the hash of that block.
+    ///
+    /// # Errors
+    /// * `ExtrinsicNotIncluded` - if the extrinsic is invalid, dropped or usurped
+    /// * `XtError` - if the extrinsic was included, but failed to dispatch
+    pub async fn submit_signed_extrinsic(&self, extrinsic: Bytes) -> Result<H256, Error> {
+        let mut sub = self
+            .rpc_client()
+            .subscribe::<TransactionStatus>(
+                "author_submitAndWatchExtrinsic",
+                Params::Array(vec![to_json_value(&extrinsic)?]),
+                "author_unwatchExtrinsic",
+            )
+            .await?;
+        let block_hash = loop {
+            match sub.next().await {
+                TransactionStatus::InBlock(hash) => break hash,
+                TransactionStatus::Future
+                | TransactionStatus::Ready
+                | TransactionStatus::Broadcast(_) => continue,
+                status => return Err(Error::ExtrinsicNotIncluded(format!("{:?}", status))),
+            }
+        };
+
+        // check that the extrinsic was dispatched successfully
+        let ext_client = self.ext_client();
+        let block = ext_client
+            .block(Some(block_hash))
+            .await?
+            .ok_or(Error::BlockNotFound)?;
+        let index = block
+            .block
+            .extrinsics
+            .iter()
+            .position(|included| included.encode() == extrinsic.0)
+            .ok_or(Error::BlockNotFound)?;
+        for record in ext_client.events(Some(block_hash)).await? {
+            if record.phase != Phase::ApplyExtrinsic(index as u32) {
+                continue;
+            }
+            if let btc_parachain_runtime::Event::frame_system(SystemEvent::ExtrinsicFailed(
+                dispatch_error,
+                _,
+            )) = record.event
+            {
+                return Err(XtError::Runtime(RuntimeError::from_dispatch(
+                    ext_client.metadata(),
+                    dispatch_error,
+                )?)
+                .into());
+            }
+        }
+        Ok(block_hash)
+    }
 }
 
 #[async_trait]
@@ -302,7 +1103,9 @@ pub trait UtilFuncs {
 #[async_trait]
 impl UtilFuncs for PolkaBtcProvider {
     async fn get_current_chain_height(&self) -> Result<u32, Error> {
-        let query_result = self.ext_client.block(Option::<H256>::None).await?;
+        let query_result = self
+            .with_retry(|| async { Ok(self.ext_client().block(self.at).await?) })
+            .await?;
         match query_result {
             Some(x) => Ok(x.block.header.number),
             None => Err(Error::BlockNotFound),
@@ -310,11 +1113,12 @@ impl UtilFuncs for PolkaBtcProvider {
     }
 
     async fn get_blockchain_height_at(&self, parachain_height: u32) -> Result<u32, Error> {
-        let hash = self
-            .ext_client
-            .block_hash(Some(parachain_height.into()))
-            .await?;
-        Ok(self.ext_client.best_block_height(hash).await?)
+        self.with_retry(|| async {
+            let ext_client = self.ext_client();
+            let hash = ext_client.block_hash(Some(parachain_height.into())).await?;
+            Ok(ext_client.best_block_height(hash).await?)
+        })
+        .await
     }
 
     fn get_account_id(&self) -> &AccountId {
@@ -322,6 +1126,91 @@ impl UtilFuncs for PolkaBtcProvider {
     }
 }
 
+/// Fee information of an extrinsic, as returned by `payment_queryInfo`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuntimeDispatchInfo {
+    #[serde(deserialize_with = "deserialize_balance")]
+    partial_fee: u128,
+}
+
+/// Balances may be serialized as strings since they can exceed the range of json numbers.
+fn deserialize_balance<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(u64),
+        String(String),
+    }
+
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(x) => Ok(x.into()),
+        NumberOrString::String(x) => x.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+#[async_trait]
+pub trait ExtrinsicFuncs {
+    /// Estimate the fee that would be charged for including the given call,
+    /// signed by the configured signer.
+    async fn estimate_fee<C: Call<PolkaBtcRuntime> + Send + Sync + 'static>(
+        &self,
+        call: C,
+    ) -> Result<u128, Error>;
+
+    /// Execute the given call, signed by the configured signer, against the
+    /// current best block without submitting it.
+    async fn dry_run<C: Call<PolkaBtcRuntime> + Send + Sync + 'static>(
+        &self,
+        call: C,
+    ) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl ExtrinsicFuncs for PolkaBtcProvider {
+    async fn estimate_fee<C: Call<PolkaBtcRuntime> + Send + Sync + 'static>(
+        &self,
+        call: C,
+    ) -> Result<u128, Error> {
+        let extrinsic = self.create_signed_extrinsic(call).await?;
+        let result: RuntimeDispatchInfo = self
+            .request(
+                "payment_queryInfo",
+                Params::Array(vec![to_json_value(extrinsic)?]),
+            )
+            .await?;
+
+        Ok(result.partial_fee)
+    }
+
+    /// # Errors
+    /// * `XtError` - if the call would fail with a dispatch error
+    /// * `InvalidTransaction` - if the extrinsic would not be included at all,
+    ///                          e.g. because the account cannot pay the fee
+    async fn dry_run<C: Call<PolkaBtcRuntime> + Send + Sync + 'static>(
+        &self,
+        call: C,
+    ) -> Result<(), Error> {
+        let extrinsic = self.create_signed_extrinsic(call).await?;
+        let result: Bytes = self
+            .request(
+                "system_dryRun",
+                Params::Array(vec![to_json_value(extrinsic)?]),
+            )
+            .await?;
+
+        match ApplyExtrinsicResult::decode(&mut &result[..])? {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(dispatch_error)) => Err(XtError::Runtime(RuntimeError::from_dispatch(
+                self.ext_client().metadata(),
+                dispatch_error,
+            )?)
+            .into()),
+            Err(validity_error) => Err(Error::InvalidTransaction(validity_error)),
+        }
+    }
+}
+
 #[async_trait]
 pub trait DotBalancesPallet {
     async fn get_free_dot_balance(&self) -> Result<<PolkaBtcRuntime as Core>::Balance, Error>;
@@ -346,25 +1235,204 @@ impl DotBalancesPallet for PolkaBtcProvider {
         &self,
         id: AccountId,
     ) -> Result<<PolkaBtcRuntime as Core>::Balance, Error> {
-        Ok(self.ext_client.account(id.clone(), None).await?.free)
+        self.with_retry(|| async { Ok(self.ext_client().account(id.clone(), self.at).await?.free) })
+            .await
     }
 
     async fn get_reserved_dot_balance(&self) -> Result<<PolkaBtcRuntime as Core>::Balance, Error> {
-        Ok(self
-            .ext_client
-            .account(self.account_id.clone(), None)
-            .await?
-            .reserved)
+        self.with_retry(|| async {
+            Ok(self
+                .ext_client()
+                .account(self.account_id.clone(), self.at)
+                .await?
+                .reserved)
+        })
+        .await
     }
 
     async fn transfer_to(&self, destination: AccountId, amount: u128) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .transfer_and_watch(&*self.signer.write().await, &destination, amount)
             .await?;
         Ok(())
     }
 }
 
+/// Parameters of the SLA pallet, see `SlaPallet::get_sla_parameters`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct SlaParameters {
+    /// The SLA score at which a vault receives the full reward.
+    pub vault_target_sla: <PolkaBtcRuntime as Core>::SignedFixedPoint,
+    /// The SLA score at which a staked relayer receives the full reward.
+    pub relayer_target_sla: <PolkaBtcRuntime as Core>::SignedFixedPoint,
+}
+
+#[async_trait]
+pub trait SlaPallet {
+    async fn get_vault_sla(
+        &self,
+        vault_id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::SignedFixedPoint, Error>;
+
+    async fn get_relayer_sla(
+        &self,
+        relayer_id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::SignedFixedPoint, Error>;
+
+    async fn get_sla_parameters(&self) -> Result<SlaParameters, Error>;
+}
+
+#[async_trait]
+impl SlaPallet for PolkaBtcProvider {
+    async fn get_vault_sla(
+        &self,
+        vault_id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::SignedFixedPoint, Error> {
+        self.with_retry(|| async {
+            Ok(self
+                .ext_client()
+                .vault_sla(vault_id.clone(), self.at)
+                .await?)
+        })
+        .await
+    }
+
+    async fn get_relayer_sla(
+        &self,
+        relayer_id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::SignedFixedPoint, Error> {
+        self.with_retry(|| async {
+            Ok(self
+                .ext_client()
+                .relayer_sla(relayer_id.clone(), self.at)
+                .await?)
+        })
+        .await
+    }
+
+    async fn get_sla_parameters(&self) -> Result<SlaParameters, Error> {
+        self.with_retry(|| async {
+            let ext_client = self.ext_client();
+            Ok(SlaParameters {
+                vault_target_sla: ext_client.vault_target_sla(self.at).await?,
+                relayer_target_sla: ext_client.relayer_target_sla(self.at).await?,
+            })
+        })
+        .await
+    }
+}
+
+#[async_trait]
+pub trait TreasuryPallet {
+    async fn get_free_polka_btc_balance(
+        &self,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error>;
+
+    async fn get_free_polka_btc_balance_for_id(
+        &self,
+        id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error>;
+
+    /// PolkaBTC that is locked, e.g. by pending redeem requests.
+    async fn get_locked_polka_btc_balance(
+        &self,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error>;
+
+    async fn get_locked_polka_btc_balance_for_id(
+        &self,
+        id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error>;
+
+    /// The total amount of PolkaBTC in existence.
+    async fn get_polka_btc_total_supply(
+        &self,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error>;
+
+    async fn transfer_polka_btc(&self, destination: AccountId, amount: u128) -> Result<(), Error>;
+}
+
+impl PolkaBtcProvider {
+    async fn get_polka_btc_account(
+        &self,
+        id: AccountId,
+    ) -> Result<AccountData<<PolkaBtcRuntime as Core>::PolkaBTC>, Error> {
+        self.with_retry(|| async {
+            Ok(self
+                .ext_client()
+                .fetch_or_default(
+                    &balances_polka_btc::AccountStore {
+                        _runtime: PhantomData,
+                        account_id: id.clone(),
+                    },
+                    self.at,
+                )
+                .await?)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl TreasuryPallet for PolkaBtcProvider {
+    async fn get_free_polka_btc_balance(
+        &self,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error> {
+        self.get_free_polka_btc_balance_for_id(self.account_id.clone())
+            .await
+    }
+
+    async fn get_free_polka_btc_balance_for_id(
+        &self,
+        id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error> {
+        Ok(self.get_polka_btc_account(id).await?.free)
+    }
+
+    async fn get_locked_polka_btc_balance(
+        &self,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error> {
+        self.get_locked_polka_btc_balance_for_id(self.account_id.clone())
+            .await
+    }
+
+    async fn get_locked_polka_btc_balance_for_id(
+        &self,
+        id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error> {
+        Ok(self.get_polka_btc_account(id).await?.reserved)
+    }
+
+    async fn get_polka_btc_total_supply(
+        &self,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error> {
+        self.with_retry(|| async {
+            Ok(self
+                .ext_client()
+                .fetch_or_default(
+                    &balances_polka_btc::TotalIssuanceStore {
+                        _runtime: PhantomData,
+                    },
+                    self.at,
+                )
+                .await?)
+        })
+        .await
+    }
+
+    /// Transfer PolkaBTC to the destination account.
+    ///
+    /// # Arguments
+    /// * `destination` - the account that receives the PolkaBTC
+    /// * `amount` - the amount of PolkaBTC to transfer
+    async fn transfer_polka_btc(&self, destination: AccountId, amount: u128) -> Result<(), Error> {
+        self.watch(balances_polka_btc::TransferCall {
+            to: &destination,
+            amount,
+        })
+        .await
+    }
+}
+
 #[async_trait]
 pub trait ReplacePallet {
     /// Request the replacement of a new vault ownership
@@ -434,6 +1502,17 @@ pub trait ReplacePallet {
         raw_tx: Vec<u8>,
     ) -> Result<(), Error>;
 
+    /// Register the return-to-self address of the payment and execute the replace request
+    /// in a single batch, such that the address is only registered if the execution succeeds.
+    async fn register_address_and_execute_replace(
+        &self,
+        btc_address: BtcAddress,
+        replace_id: H256,
+        tx_id: H256Le,
+        merkle_proof: Vec<u8>,
+        raw_tx: Vec<u8>,
+    ) -> Result<(), Error>;
+
     /// Cancel vault replacement
     ///
     /// # Arguments
@@ -474,7 +1553,7 @@ impl ReplacePallet for PolkaBtcProvider {
         griefing_collateral: u128,
     ) -> Result<H256, Error> {
         let result = self
-            .ext_client
+            .ext_client()
             .request_replace_and_watch(&*self.signer.write().await, amount, griefing_collateral)
             .await?;
 
@@ -486,7 +1565,7 @@ impl ReplacePallet for PolkaBtcProvider {
     }
 
     async fn withdraw_replace(&self, replace_id: H256) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .withdraw_replace_and_watch(&*self.signer.write().await, replace_id)
             .await?;
         Ok(())
@@ -498,7 +1577,7 @@ impl ReplacePallet for PolkaBtcProvider {
         collateral: u128,
         btc_address: BtcAddress,
     ) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .accept_replace_and_watch(
                 &*self.signer.write().await,
                 replace_id,
@@ -516,7 +1595,7 @@ impl ReplacePallet for PolkaBtcProvider {
         collateral: u128,
         btc_address: BtcAddress,
     ) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .auction_replace_and_watch(
                 &*self.signer.write().await,
                 old_vault,
@@ -535,7 +1614,7 @@ impl ReplacePallet for PolkaBtcProvider {
         merkle_proof: Vec<u8>,
         raw_tx: Vec<u8>,
     ) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .execute_replace_and_watch(
                 &*self.signer.write().await,
                 replace_id,
@@ -547,8 +1626,28 @@ impl ReplacePallet for PolkaBtcProvider {
         Ok(())
     }
 
+    async fn register_address_and_execute_replace(
+        &self,
+        btc_address: BtcAddress,
+        replace_id: H256,
+        tx_id: H256Le,
+        merkle_proof: Vec<u8>,
+        raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.batch()
+            .add(RegisterAddressCall { btc_address })
+            .add(ExecuteReplaceCall {
+                replace_id,
+                tx_id,
+                merkle_proof,
+                raw_tx,
+            })
+            .submit()
+            .await
+    }
+
     async fn cancel_replace(&self, replace_id: H256) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .cancel_replace_and_watch(&*self.signer.write().await, replace_id)
             .await?;
         Ok(())
@@ -560,10 +1659,9 @@ impl ReplacePallet for PolkaBtcProvider {
         account_id: AccountId,
     ) -> Result<Vec<(H256, PolkaBtcReplaceRequest)>, Error> {
         let result: Vec<(H256, PolkaBtcReplaceRequest)> = self
-            .rpc_client
             .request(
                 "replace_getNewVaultReplaceRequests",
-                Params::Array(vec![to_json_value(account_id)?]),
+                Params::Array(vec![to_json_value(account_id)?, to_json_value(self.at)?]),
             )
             .await?;
 
@@ -576,10 +1674,9 @@ impl ReplacePallet for PolkaBtcProvider {
         account_id: AccountId,
     ) -> Result<Vec<(H256, PolkaBtcReplaceRequest)>, Error> {
         let result: Vec<(H256, PolkaBtcReplaceRequest)> = self
-            .rpc_client
             .request(
                 "replace_getOldVaultReplaceRequests",
-                Params::Array(vec![to_json_value(account_id)?]),
+                Params::Array(vec![to_json_value(account_id)?, to_json_value(self.at)?]),
             )
             .await?;
 
@@ -587,7 +1684,10 @@ impl ReplacePallet for PolkaBtcProvider {
     }
 
     async fn get_replace_period(&self) -> Result<u32, Error> {
-        Ok(self.ext_client.replace_period(None).await?)
+        self.fetch_cached(ReplacePeriodStore {
+            _runtime: PhantomData,
+        })
+        .await
     }
 
     async fn set_replace_period(&self, period: u32) -> Result<(), Error> {
@@ -600,7 +1700,13 @@ impl ReplacePallet for PolkaBtcProvider {
     }
 
     async fn get_replace_request(&self, replace_id: H256) -> Result<PolkaBtcReplaceRequest, Error> {
-        Ok(self.ext_client.replace_requests(replace_id, None).await?)
+        self.with_retry(|| async {
+            Ok(self
+                .ext_client()
+                .replace_requests(replace_id, self.at)
+                .await?)
+        })
+        .await
     }
 }
 
@@ -613,7 +1719,8 @@ pub trait TimestampPallet {
 impl TimestampPallet for PolkaBtcProvider {
     /// Get the current time as defined by the `timestamp` pallet.
     async fn get_time_now(&self) -> Result<u64, Error> {
-        Ok(self.ext_client.now(None).await?)
+        self.with_retry(|| async { Ok(self.ext_client().now(self.at).await?) })
+            .await
     }
 }
 
@@ -637,9 +1744,15 @@ impl ExchangeRateOraclePallet for PolkaBtcProvider {
     /// Returns the last exchange rate in planck per satoshis, the time at which it was set
     /// and the configured max delay.
     async fn get_exchange_rate_info(&self) -> Result<(FixedU128, u64, u64), Error> {
-        let get_rate = self.ext_client.exchange_rate(None);
-        let get_time = self.ext_client.last_exchange_rate_time(None);
-        let get_delay = self.ext_client.max_delay(None);
+        let get_rate = self.fetch_cached(ExchangeRateStore {
+            _runtime: PhantomData,
+        });
+        let get_time = self.fetch_cached(LastExchangeRateTimeStore {
+            _runtime: PhantomData,
+        });
+        let get_delay = self.fetch_cached(MaxDelayStore {
+            _runtime: PhantomData,
+        });
 
         match tokio::try_join!(get_rate, get_time, get_delay) {
             Ok((rate, time, delay)) => Ok((rate, time, delay)),
@@ -652,7 +1765,7 @@ impl ExchangeRateOraclePallet for PolkaBtcProvider {
     /// # Arguments
     /// * `dot_per_btc` - the current dot per btc exchange rate
     async fn set_exchange_rate_info(&self, dot_per_btc: FixedU128) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .set_exchange_rate_and_watch(&*self.signer.write().await, dot_per_btc)
             .await?;
         Ok(())
@@ -666,7 +1779,7 @@ impl ExchangeRateOraclePallet for PolkaBtcProvider {
     /// * `half` - The estimated Satoshis per bytes to get included in the next 3 blocks (~half hour)
     /// * `hour` - The estimated Satoshis per bytes to get included in the next 6 blocks (~hour)
     async fn set_btc_tx_fees_per_byte(&self, fast: u32, half: u32, hour: u32) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .set_btc_tx_fees_per_byte_and_watch(&*self.signer.write().await, fast, half, hour)
             .await?;
         Ok(())
@@ -675,16 +1788,19 @@ impl ExchangeRateOraclePallet for PolkaBtcProvider {
     /// Gets the estimated Satoshis per bytes required to get a Bitcoin transaction included in
     /// in the next x blocks
     async fn get_btc_tx_fees_per_byte(&self) -> Result<BtcTxFeesPerByte, Error> {
-        Ok(self.ext_client.satoshi_per_bytes(None).await?)
+        self.with_retry(|| async { Ok(self.ext_client().satoshi_per_bytes(self.at).await?) })
+            .await
     }
 
     /// Converts the amount in btc to dot, based on the current set exchange rate.
     async fn btc_to_dots(&self, amount_btc: u128) -> Result<u128, Error> {
         let result: BalanceWrapper<_> = self
-            .rpc_client
             .request(
                 "exchangeRateOracle_btcToDots",
-                Params::Array(vec![to_json_value(BalanceWrapper { amount: amount_btc })?]),
+                Params::Array(vec![
+                    to_json_value(BalanceWrapper { amount: amount_btc })?,
+                    to_json_value(self.at)?,
+                ]),
             )
             .await?;
 
@@ -694,10 +1810,12 @@ impl ExchangeRateOraclePallet for PolkaBtcProvider {
     /// Converts the amount in dot to btc, based on the current set exchange rate.
     async fn dots_to_btc(&self, amount_dot: u128) -> Result<u128, Error> {
         let result: BalanceWrapper<_> = self
-            .rpc_client
             .request(
                 "exchangeRateOracle_dotsToBtc",
-                Params::Array(vec![to_json_value(BalanceWrapper { amount: amount_dot })?]),
+                Params::Array(vec![
+                    to_json_value(BalanceWrapper { amount: amount_dot })?,
+                    to_json_value(self.at)?,
+                ]),
             )
             .await?;
 
@@ -754,10 +1872,13 @@ pub trait StakedRelayerPallet {
 impl StakedRelayerPallet for PolkaBtcProvider {
     /// Get the stake registered for this staked relayer.
     async fn get_stake(&self) -> Result<u64, Error> {
-        Ok(self
-            .ext_client
-            .active_staked_relayers(self.signer.read().await.account_id(), None)
-            .await?)
+        self.with_retry(|| async {
+            Ok(self
+                .ext_client()
+                .active_staked_relayers(&self.account_id, self.at)
+                .await?)
+        })
+        .await
     }
 
     /// Submit extrinsic to register the staked relayer.
@@ -765,7 +1886,7 @@ impl StakedRelayerPallet for PolkaBtcProvider {
     /// # Arguments
     /// * `stake` - deposit
     async fn register_staked_relayer(&self, stake: u128) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .register_staked_relayer_and_watch(&*self.signer.write().await, stake)
             .await?;
         Ok(())
@@ -773,7 +1894,7 @@ impl StakedRelayerPallet for PolkaBtcProvider {
 
     /// Submit extrinsic to deregister the staked relayer.
     async fn deregister_staked_relayer(&self) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .deregister_staked_relayer_and_watch(&*self.signer.write().await)
             .await?;
         Ok(())
@@ -804,7 +1925,7 @@ impl StakedRelayerPallet for PolkaBtcProvider {
         block_hash: Option<H256Le>,
         message: String,
     ) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .suggest_status_update_and_watch(
                 &*self.signer.write().await,
                 deposit,
@@ -828,7 +1949,7 @@ impl StakedRelayerPallet for PolkaBtcProvider {
         status_update_id: u64,
         approve: bool,
     ) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .vote_on_status_update_and_watch(&*self.signer.write().await, status_update_id, approve)
             .await?;
         Ok(())
@@ -842,15 +1963,18 @@ impl StakedRelayerPallet for PolkaBtcProvider {
         &self,
         status_update_id: u64,
     ) -> Result<PolkaBtcStatusUpdate, Error> {
-        Ok(self
-            .ext_client
-            .active_status_updates(status_update_id, None)
-            .await?)
+        self.with_retry(|| async {
+            Ok(self
+                .ext_client()
+                .active_status_updates(status_update_id, self.at)
+                .await?)
+        })
+        .await
     }
 
     /// Submit extrinsic to report that the oracle is offline.
     async fn report_oracle_offline(&self) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .report_oracle_offline_and_watch(&*self.signer.write().await)
             .await?;
         Ok(())
@@ -873,7 +1997,7 @@ impl StakedRelayerPallet for PolkaBtcProvider {
         merkle_proof: Vec<u8>,
         raw_tx: Vec<u8>,
     ) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .report_vault_theft_and_watch(
                 &*self.signer.write().await,
                 vault_id,
@@ -902,10 +2026,13 @@ impl StakedRelayerPallet for PolkaBtcProvider {
     ) -> Result<bool, Error> {
         Ok(
             match self
-                .rpc_client
                 .request(
                     "stakedRelayers_isTransactionInvalid",
-                    Params::Array(vec![to_json_value(vault_id)?, to_json_value(raw_tx)?]),
+                    Params::Array(vec![
+                        to_json_value(vault_id)?,
+                        to_json_value(raw_tx)?,
+                        to_json_value(self.at)?,
+                    ]),
                 )
                 .await
             {
@@ -937,11 +2064,13 @@ impl SecurityPallet for PolkaBtcProvider {
     /// Get the current security status of the parachain.
     /// Should be one of; `Running`, `Error` or `Shutdown`.
     async fn get_parachain_status(&self) -> Result<StatusCode, Error> {
-        Ok(self.ext_client.parachain_status(None).await?)
+        self.with_retry(|| async { Ok(self.ext_client().parachain_status(self.at).await?) })
+            .await
     }
     /// Return any `ErrorCode`s set in the security module.
     async fn get_error_codes(&self) -> Result<BTreeSet<ErrorCode>, Error> {
-        Ok(self.ext_client.errors(None).await?)
+        self.with_retry(|| async { Ok(self.ext_client().errors(self.at).await?) })
+            .await
     }
 }
 
@@ -988,7 +2117,7 @@ impl IssuePallet for PolkaBtcProvider {
         griefing_collateral: u128,
     ) -> Result<PolkaBtcRequestIssueEvent, Error> {
         let result = self
-            .ext_client
+            .ext_client()
             .request_issue_and_watch(
                 &*self.signer.write().await,
                 amount,
@@ -1007,7 +2136,7 @@ impl IssuePallet for PolkaBtcProvider {
         merkle_proof: Vec<u8>,
         raw_tx: Vec<u8>,
     ) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .execute_issue_and_watch(
                 &*self.signer.write().await,
                 issue_id,
@@ -1020,14 +2149,15 @@ impl IssuePallet for PolkaBtcProvider {
     }
 
     async fn cancel_issue(&self, issue_id: H256) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .cancel_issue_and_watch(&*self.signer.write().await, issue_id)
             .await?;
         Ok(())
     }
 
     async fn get_issue_request(&self, issue_id: H256) -> Result<PolkaBtcIssueRequest, Error> {
-        Ok(self.ext_client.issue_requests(issue_id, None).await?)
+        self.with_retry(|| async { Ok(self.ext_client().issue_requests(issue_id, self.at).await?) })
+            .await
     }
 
     async fn get_vault_issue_requests(
@@ -1035,10 +2165,9 @@ impl IssuePallet for PolkaBtcProvider {
         account_id: AccountId,
     ) -> Result<Vec<(H256, PolkaBtcIssueRequest)>, Error> {
         let result: Vec<(H256, PolkaBtcIssueRequest)> = self
-            .rpc_client
             .request(
                 "issue_getVaultIssueRequests",
-                Params::Array(vec![to_json_value(account_id)?]),
+                Params::Array(vec![to_json_value(account_id)?, to_json_value(self.at)?]),
             )
             .await?;
 
@@ -1046,7 +2175,10 @@ impl IssuePallet for PolkaBtcProvider {
     }
 
     async fn get_issue_period(&self) -> Result<u32, Error> {
-        Ok(self.ext_client.issue_period(None).await?)
+        self.fetch_cached(IssuePeriodStore {
+            _runtime: PhantomData,
+        })
+        .await
     }
 
     async fn set_issue_period(&self, period: u32) -> Result<(), Error> {
@@ -1078,6 +2210,17 @@ pub trait RedeemPallet {
         raw_tx: Vec<u8>,
     ) -> Result<(), Error>;
 
+    /// Register the return-to-self address of the payment and execute the redeem request
+    /// in a single batch, such that the address is only registered if the execution succeeds.
+    async fn register_address_and_execute_redeem(
+        &self,
+        btc_address: BtcAddress,
+        redeem_id: H256,
+        tx_id: H256Le,
+        merkle_proof: Vec<u8>,
+        raw_tx: Vec<u8>,
+    ) -> Result<(), Error>;
+
     /// Cancel an ongoing redeem request
     async fn cancel_redeem(&self, redeem_id: H256, reimburse: bool) -> Result<(), Error>;
 
@@ -1101,7 +2244,7 @@ impl RedeemPallet for PolkaBtcProvider {
         vault_id: AccountId,
     ) -> Result<H256, Error> {
         let result = self
-            .ext_client
+            .ext_client()
             .request_redeem_and_watch(
                 &*self.signer.write().await,
                 amount_polka_btc,
@@ -1124,7 +2267,7 @@ impl RedeemPallet for PolkaBtcProvider {
         merkle_proof: Vec<u8>,
         raw_tx: Vec<u8>,
     ) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .execute_redeem_and_watch(
                 &*self.signer.write().await,
                 redeem_id,
@@ -1136,15 +2279,42 @@ impl RedeemPallet for PolkaBtcProvider {
         Ok(())
     }
 
+    async fn register_address_and_execute_redeem(
+        &self,
+        btc_address: BtcAddress,
+        redeem_id: H256,
+        tx_id: H256Le,
+        merkle_proof: Vec<u8>,
+        raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.batch()
+            .add(RegisterAddressCall { btc_address })
+            .add(ExecuteRedeemCall {
+                redeem_id,
+                tx_id,
+                merkle_proof,
+                raw_tx,
+                _runtime: PhantomData,
+            })
+            .submit()
+            .await
+    }
+
     async fn cancel_redeem(&self, redeem_id: H256, reimburse: bool) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .cancel_redeem_and_watch(&*self.signer.write().await, redeem_id, reimburse)
             .await?;
         Ok(())
     }
 
     async fn get_redeem_request(&self, redeem_id: H256) -> Result<PolkaBtcRedeemRequest, Error> {
-        Ok(self.ext_client.redeem_requests(redeem_id, None).await?)
+        self.with_retry(|| async {
+            Ok(self
+                .ext_client()
+                .redeem_requests(redeem_id, self.at)
+                .await?)
+        })
+        .await
     }
 
     async fn get_vault_redeem_requests(
@@ -1152,10 +2322,9 @@ impl RedeemPallet for PolkaBtcProvider {
         account_id: AccountId,
     ) -> Result<Vec<(H256, PolkaBtcRedeemRequest)>, Error> {
         let result: Vec<(H256, PolkaBtcRedeemRequest)> = self
-            .rpc_client
             .request(
                 "redeem_getVaultRedeemRequests",
-                Params::Array(vec![to_json_value(account_id)?]),
+                Params::Array(vec![to_json_value(account_id)?, to_json_value(self.at)?]),
             )
             .await?;
 
@@ -1183,6 +2352,17 @@ pub trait RefundPallet {
         raw_tx: Vec<u8>,
     ) -> Result<(), Error>;
 
+    /// Register the return-to-self address of the payment and execute the refund request
+    /// in a single batch, such that the address is only registered if the execution succeeds.
+    async fn register_address_and_execute_refund(
+        &self,
+        btc_address: BtcAddress,
+        refund_id: H256,
+        tx_id: H256Le,
+        merkle_proof: Vec<u8>,
+        raw_tx: Vec<u8>,
+    ) -> Result<(), Error>;
+
     /// Get all open refund requests requested of the given vault
     async fn get_vault_refund_requests(
         &self,
@@ -1199,7 +2379,7 @@ impl RefundPallet for PolkaBtcProvider {
         merkle_proof: Vec<u8>,
         raw_tx: Vec<u8>,
     ) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .execute_refund_and_watch(
                 &*self.signer.write().await,
                 refund_id,
@@ -1211,15 +2391,35 @@ impl RefundPallet for PolkaBtcProvider {
         Ok(())
     }
 
+    async fn register_address_and_execute_refund(
+        &self,
+        btc_address: BtcAddress,
+        refund_id: H256,
+        tx_id: H256Le,
+        merkle_proof: Vec<u8>,
+        raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.batch()
+            .add(RegisterAddressCall { btc_address })
+            .add(ExecuteRefundCall {
+                refund_id,
+                tx_id,
+                merkle_proof,
+                raw_tx,
+                _runtime: PhantomData,
+            })
+            .submit()
+            .await
+    }
+
     async fn get_vault_refund_requests(
         &self,
         account_id: AccountId,
     ) -> Result<Vec<(H256, PolkaBtcRefundRequest)>, Error> {
         let result: Vec<(H256, PolkaBtcRefundRequest)> = self
-            .rpc_client
             .request(
                 "refund_getVaultRefundRequests",
-                Params::Array(vec![to_json_value(account_id)?]),
+                Params::Array(vec![to_json_value(account_id)?, to_json_value(self.at)?]),
             )
             .await?;
 
@@ -1262,12 +2462,14 @@ pub trait BtcRelayPallet {
 impl BtcRelayPallet for PolkaBtcProvider {
     /// Get the hash of the current best tip.
     async fn get_best_block(&self) -> Result<H256Le, Error> {
-        Ok(self.ext_client.best_block(None).await?)
+        self.with_retry(|| async { Ok(self.ext_client().best_block(self.at).await?) })
+            .await
     }
 
     /// Get the current best known height.
     async fn get_best_block_height(&self) -> Result<u32, Error> {
-        Ok(self.ext_client.best_block_height(None).await?)
+        self.with_retry(|| async { Ok(self.ext_client().best_block_height(self.at).await?) })
+            .await
     }
 
     /// Get the block hash for the main chain at the specified height.
@@ -1275,7 +2477,8 @@ impl BtcRelayPallet for PolkaBtcProvider {
     /// # Arguments
     /// * `height` - chain height
     async fn get_block_hash(&self, height: u32) -> Result<H256Le, Error> {
-        Ok(self.ext_client.chains_hashes(0, height, None).await?)
+        self.with_retry(|| async { Ok(self.ext_client().chains_hashes(0, height, self.at).await?) })
+            .await
     }
 
     /// Get the corresponding block header for the given hash.
@@ -1283,7 +2486,8 @@ impl BtcRelayPallet for PolkaBtcProvider {
     /// # Arguments
     /// * `hash` - little endian block hash
     async fn get_block_header(&self, hash: H256Le) -> Result<RichBlockHeader, Error> {
-        Ok(self.ext_client.block_headers(hash, None).await?)
+        self.with_retry(|| async { Ok(self.ext_client().block_headers(hash, self.at).await?) })
+            .await
     }
 
     /// Initializes the relay with the provided block header and height,
@@ -1300,7 +2504,7 @@ impl BtcRelayPallet for PolkaBtcProvider {
     ) -> Result<(), Error> {
         // TODO: can we initialize the relay through the chain-spec?
         // we would also need to consider re-initialization per governance
-        self.ext_client
+        self.ext_client()
             .initialize_and_watch(&*self.signer.write().await, header, height)
             .await?;
         Ok(())
@@ -1311,7 +2515,7 @@ impl BtcRelayPallet for PolkaBtcProvider {
     /// # Arguments
     /// * `header` - raw block header
     async fn store_block_header(&self, header: RawBlockHeader) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .store_block_header_and_watch(&*self.signer.write().await, header)
             .await?;
         Ok(())
@@ -1322,7 +2526,7 @@ impl BtcRelayPallet for PolkaBtcProvider {
     /// # Arguments
     /// * `headers` - raw block headers
     async fn store_block_headers(&self, headers: Vec<RawBlockHeader>) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .store_block_headers_and_watch(&*self.signer.write().await, headers)
             .await?;
         Ok(())
@@ -1330,7 +2534,10 @@ impl BtcRelayPallet for PolkaBtcProvider {
 
     /// Get the global security parameter k for stable Bitcoin transactions
     async fn get_bitcoin_confirmations(&self) -> Result<u32, Error> {
-        Ok(self.ext_client.stable_bitcoin_confirmations(None).await?)
+        self.fetch_cached(StableBitcoinConfirmationsStore {
+            _runtime: PhantomData,
+        })
+        .await
     }
 
     /// Wait until Bitcoin block is submitted to the relay
@@ -1363,6 +2570,9 @@ impl BtcRelayPallet for PolkaBtcProvider {
 pub trait VaultRegistryPallet {
     async fn get_vault(&self, vault_id: AccountId) -> Result<PolkaBtcVault, Error>;
 
+    /// Like `get_vault`, but never served from the storage cache.
+    async fn get_vault_uncached(&self, vault_id: AccountId) -> Result<PolkaBtcVault, Error>;
+
     async fn get_all_vaults(&self) -> Result<Vec<PolkaBtcVault>, Error>;
 
     async fn register_vault(&self, collateral: u128, public_key: BtcPublicKey)
@@ -1393,7 +2603,12 @@ impl VaultRegistryPallet for PolkaBtcProvider {
     /// # Errors
     /// * `VaultNotFound` - if the rpc returned a default value rather than the vault we want
     async fn get_vault(&self, vault_id: AccountId) -> Result<PolkaBtcVault, Error> {
-        let vault: PolkaBtcVault = self.ext_client.vaults(vault_id.clone(), None).await?;
+        let vault: PolkaBtcVault = self
+            .fetch_cached(VaultsStore {
+                _runtime: PhantomData,
+                account_id: vault_id.clone(),
+            })
+            .await?;
         if vault.id == vault_id {
             Ok(vault)
         } else {
@@ -1401,14 +2616,23 @@ impl VaultRegistryPallet for PolkaBtcProvider {
         }
     }
 
+    /// Fetch a specific vault by ID as of the best block, bypassing the cache, see `uncached`.
+    async fn get_vault_uncached(&self, vault_id: AccountId) -> Result<PolkaBtcVault, Error> {
+        self.uncached().get_vault(vault_id).await
+    }
+
     /// Fetch all active vaults.
     async fn get_all_vaults(&self) -> Result<Vec<PolkaBtcVault>, Error> {
-        let mut vaults = Vec::new();
-        let mut iter = self.ext_client.vaults_iter(None).await?;
-        while let Some((_, account)) = iter.next().await? {
-            vaults.push(account);
-        }
-        Ok(vaults)
+        self.with_retry(|| async {
+            let mut vaults = Vec::new();
+            let ext_client = self.ext_client();
+            let mut iter = ext_client.vaults_iter(self.at).await?;
+            while let Some((_, account)) = iter.next().await? {
+                vaults.push(account);
+            }
+            Ok(vaults)
+        })
+        .await
     }
 
     /// Submit extrinsic to register a vault.
@@ -1421,7 +2645,7 @@ impl VaultRegistryPallet for PolkaBtcProvider {
         collateral: u128,
         public_key: BtcPublicKey,
     ) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .register_vault_and_watch(&*self.signer.write().await, collateral, public_key)
             .await?;
         Ok(())
@@ -1433,7 +2657,7 @@ impl VaultRegistryPallet for PolkaBtcProvider {
     /// # Arguments
     /// * `amount` - the amount of extra collateral to lock
     async fn lock_additional_collateral(&self, amount: u128) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .lock_additional_collateral_and_watch(&*self.signer.write().await, amount)
             .await?;
         Ok(())
@@ -1450,7 +2674,7 @@ impl VaultRegistryPallet for PolkaBtcProvider {
     /// # Arguments
     /// * `amount` - the amount of collateral to withdraw
     async fn withdraw_collateral(&self, amount: u128) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .withdraw_collateral_and_watch(&*self.signer.write().await, amount)
             .await?;
         Ok(())
@@ -1461,7 +2685,7 @@ impl VaultRegistryPallet for PolkaBtcProvider {
     /// # Arguments
     /// * `public_key` - the new public key of the vault
     async fn update_public_key(&self, public_key: BtcPublicKey) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .update_public_key_and_watch(&*self.signer.write().await, public_key)
             .await?;
         Ok(())
@@ -1472,7 +2696,7 @@ impl VaultRegistryPallet for PolkaBtcProvider {
     /// # Arguments
     /// * `btc_address` - the new btc address of the vault
     async fn register_address(&self, btc_address: BtcAddress) -> Result<(), Error> {
-        self.ext_client
+        self.ext_client()
             .register_address_and_watch(&*self.signer.write().await, btc_address)
             .await?;
         Ok(())
@@ -1484,10 +2708,12 @@ impl VaultRegistryPallet for PolkaBtcProvider {
     /// * `amount_btc` - amount of btc to cover
     async fn get_required_collateral_for_polkabtc(&self, amount_btc: u128) -> Result<u128, Error> {
         let result: BalanceWrapper<_> = self
-            .rpc_client
             .request(
                 "vaultRegistry_getRequiredCollateralForPolkabtc",
-                Params::Array(vec![to_json_value(BalanceWrapper { amount: amount_btc })?]),
+                Params::Array(vec![
+                    to_json_value(BalanceWrapper { amount: amount_btc })?,
+                    to_json_value(self.at)?,
+                ]),
             )
             .await?;
 
@@ -1498,10 +2724,9 @@ impl VaultRegistryPallet for PolkaBtcProvider {
     /// current SecureCollateralThreshold with the current exchange rate
     async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<u128, Error> {
         let result: BalanceWrapper<_> = self
-            .rpc_client
             .request(
                 "vaultRegistry_getRequiredCollateralForVault",
-                Params::Array(vec![to_json_value(vault_id)?]),
+                Params::Array(vec![to_json_value(vault_id)?, to_json_value(self.at)?]),
             )
             .await?;
 
@@ -1514,10 +2739,9 @@ impl VaultRegistryPallet for PolkaBtcProvider {
     /// * `vault_id` - vault account to check
     async fn is_vault_below_auction_threshold(&self, vault_id: AccountId) -> Result<bool, Error> {
         Ok(self
-            .rpc_client
             .request(
                 "vaultRegistry_isVaultBelowAuctionThreshold",
-                Params::Array(vec![to_json_value(vault_id)?]),
+                Params::Array(vec![to_json_value(vault_id)?, to_json_value(self.at)?]),
             )
             .await?)
     }
@@ -1533,14 +2757,23 @@ pub trait FeePallet {
 #[async_trait]
 impl FeePallet for PolkaBtcProvider {
     async fn get_issue_griefing_collateral(&self) -> Result<FixedU128, Error> {
-        Ok(self.ext_client.issue_griefing_collateral(None).await?)
+        self.fetch_cached(IssueGriefingCollateralStore {
+            _runtime: PhantomData,
+        })
+        .await
     }
 
     async fn get_issue_fee(&self) -> Result<FixedU128, Error> {
-        Ok(self.ext_client.issue_fee(None).await?)
+        self.fetch_cached(IssueFeeStore {
+            _runtime: PhantomData,
+        })
+        .await
     }
 
     async fn get_replace_griefing_collateral(&self) -> Result<FixedU128, Error> {
-        Ok(self.ext_client.replace_griefing_collateral(None).await?)
+        self.fetch_cached(ReplaceGriefingCollateralStore {
+            _runtime: PhantomData,
+        })
+        .await
     }
 }
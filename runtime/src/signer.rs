@@ -0,0 +1,655 @@
+//! Signers for parachain extrinsics. Besides a key pair held in memory, extrinsics can be
+//! signed by an external process that is reached over a unix socket, such that the key does
+//! not have to be accessible by the client.
+//!
+//! The remote signer is sent one json request per connection, terminated by a newline, and
+//! has to respond within `SIGNER_TIMEOUT`:
+//! * `{"method": "account_id"}` - should respond with `{"result": "<ss58 address>"}`.
+//! * `{"method": "sign", "params": {"call": <decoded call or null>, "call_data": "0x..",
+//!   "extra": "0x..", "payload": "0x.."}}` - should respond with
+//!   `{"result": "0x<sr25519 signature>"}`, or `{"error": "<reason>"}` to refuse signing.
+//!
+//! The `payload` is the data to be signed: the `call_data` followed by the `extra` (the
+//! encoded signed extensions and the data they add to the signature), or the blake2-256 hash
+//! of these if they are longer than 256 bytes. The signer must not rely on the `call`, which
+//! is only given for logging: it has to decode the `call_data` itself, using the runtime
+//! metadata it trusts, and refuse to sign unless the `payload` equals the `call_data` followed
+//! by the `extra`, respectively its hash. Otherwise a compromised client could have any
+//! payload signed by presenting an innocuous call.
+//!
+//! The decoded call contains the module, function and arguments of the call, e.g.
+//! `{"module": "VaultRegistry", "function": "withdraw_collateral", "arguments":
+//! [{"name": "amount", "type": "DOT<T>", "value": "1000"}], "undecoded": null}`.
+//! Balances and fixed point numbers (as their inner representation) are given as strings,
+//! accounts as ss58 addresses and binary data as hex. Arguments of a type that cannot be
+//! decoded are given as hex in `undecoded`, together with all arguments following them.
+
+use crate::compatibility::{convert, parse_metadata};
+use crate::{AccountId, Error, PolkaBtcRuntime};
+use parity_scale_codec::{Compact, Decode, Encode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sp_core::crypto::Ss58Codec;
+use sp_core::sr25519::{Pair as KeyPair, Public, Signature};
+use sp_core::{hashing::blake2_256, Bytes, Pair as _};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use substrate_subxt::extrinsic::{SignedPayload, UncheckedExtrinsic};
+use substrate_subxt::{PairSigner, Signer};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::time::timeout;
+
+/// Time the remote signer has to respond to a request. Extrinsics are signed while holding
+/// the signer of the provider, so an unresponsive signer must not block it indefinitely.
+pub const SIGNER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Signed data longer than this is hashed before signing.
+const MAX_UNHASHED_PAYLOAD_LENGTH: usize = 256;
+
+/// A call argument, decoded according to the type given in the runtime metadata.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DecodedArgument {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub value: Value,
+}
+
+/// Human-readable representation of an encoded call.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DecodedCall {
+    pub module: String,
+    pub function: String,
+    pub arguments: Vec<DecodedArgument>,
+    /// The encoded arguments that could not be decoded, if any.
+    pub undecoded: Option<Bytes>,
+}
+
+#[derive(Debug, Clone)]
+struct CallMetadata {
+    name: String,
+    /// Names and types of the arguments.
+    arguments: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+struct ModuleMetadata {
+    name: String,
+    calls: Vec<CallMetadata>,
+}
+
+/// Decodes calls using the runtime metadata of the parachain.
+#[derive(Debug, Clone, Default)]
+pub struct CallDecoder {
+    modules: HashMap<u8, ModuleMetadata>,
+}
+
+impl CallDecoder {
+    /// Create a decoder from the metadata as returned by `state_getMetadata`.
+    pub fn from_metadata(bytes: &[u8]) -> Result<Self, Error> {
+        let mut modules = HashMap::new();
+        for module in convert(parse_metadata(bytes)?.modules)?.into_iter() {
+            let calls = match module.calls {
+                Some(calls) => convert(calls)?,
+                None => continue,
+            };
+            let calls = calls
+                .into_iter()
+                .map(|call| {
+                    let arguments = convert(call.arguments)?
+                        .into_iter()
+                        .map(|argument| Ok((convert(argument.name)?, convert(argument.ty)?)))
+                        .collect::<Result<_, Error>>()?;
+                    Ok(CallMetadata {
+                        name: convert(call.name)?,
+                        arguments,
+                    })
+                })
+                .collect::<Result<_, Error>>()?;
+            modules.insert(
+                module.index,
+                ModuleMetadata {
+                    name: convert(module.name)?,
+                    calls,
+                },
+            );
+        }
+        Ok(Self { modules })
+    }
+
+    /// Create a decoder from the metadata of the runtime this client is built against,
+    /// e.g. to decode calls without access to the parachain.
+    pub fn from_built_runtime() -> Result<Self, Error> {
+        Self::from_metadata(&btc_parachain_runtime::Runtime::metadata().encode())
+    }
+
+    /// Decode the encoded call, returns None if the module or function is unknown.
+    pub fn decode(&self, call: &[u8]) -> Option<DecodedCall> {
+        let (module_index, call_index, mut input) = match call {
+            [module_index, call_index, input @ ..] => (*module_index, *call_index, input),
+            _ => return None,
+        };
+        let module = self.modules.get(&module_index)?;
+        let metadata = module.calls.get(call_index as usize)?;
+
+        let mut arguments = Vec::new();
+        for (name, ty) in metadata.arguments.iter() {
+            let remaining = input;
+            match decode_value(ty, &mut input) {
+                Some(value) => arguments.push(DecodedArgument {
+                    name: name.clone(),
+                    ty: ty.clone(),
+                    value,
+                }),
+                None => {
+                    input = remaining;
+                    break;
+                }
+            }
+        }
+
+        Some(DecodedCall {
+            module: module.name.clone(),
+            function: metadata.name.clone(),
+            arguments,
+            undecoded: if input.is_empty() {
+                None
+            } else {
+                Some(Bytes(input.to_vec()))
+            },
+        })
+    }
+}
+
+/// Returns the generic argument if `ty` is of the form `outer<argument>`.
+fn generic_argument<'a>(ty: &'a str, outer: &str) -> Option<&'a str> {
+    ty.strip_prefix(outer)?
+        .strip_prefix('<')?
+        .strip_suffix('>')
+        .map(str::trim)
+}
+
+fn decode_string<T: Decode + ToString>(input: &mut &[u8]) -> Option<Value> {
+    Some(Value::String(T::decode(input).ok()?.to_string()))
+}
+
+fn decode_fixed_bytes(len: usize, input: &mut &[u8]) -> Option<Value> {
+    if input.len() < len {
+        return None;
+    }
+    let (bytes, remaining) = input.split_at(len);
+    *input = remaining;
+    serde_json::to_value(Bytes(bytes.to_vec())).ok()
+}
+
+/// Decode a value of the type with the given name, as used in the metadata. Only supports
+/// the types used in the calls of the parachain, returns None for all other types.
+fn decode_value(ty: &str, input: &mut &[u8]) -> Option<Value> {
+    let ty = ty.trim();
+    if let Some(inner) = generic_argument(ty, "Compact") {
+        return match inner.trim_start_matches("T::") {
+            "u8" | "u16" | "u32" | "BlockNumber" => {
+                Some(Value::from(Compact::<u32>::decode(input).ok()?.0))
+            }
+            "u64" | "Moment" => Some(Value::from(Compact::<u64>::decode(input).ok()?.0)),
+            _ => Some(Value::String(
+                Compact::<u128>::decode(input).ok()?.0.to_string(),
+            )),
+        };
+    }
+    if let Some(inner) = generic_argument(ty, "Option") {
+        return match u8::decode(input).ok()? {
+            0 => Some(Value::Null),
+            1 => decode_value(inner, input),
+            _ => None,
+        };
+    }
+    if ty == "Vec<u8>" {
+        return serde_json::to_value(Bytes(Vec::<u8>::decode(input).ok()?)).ok();
+    }
+    if let Some(inner) = generic_argument(ty, "Vec") {
+        let len = Compact::<u32>::decode(input).ok()?.0;
+        return (0..len)
+            .map(|_| decode_value(inner, input))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array);
+    }
+
+    // strip the `T::` prefix and the `<T>` suffix, e.g. of `T::AccountId` or `DOT<T>`
+    let name = ty.trim_start_matches("T::").split('<').next()?;
+    match name {
+        "bool" => Some(Value::from(bool::decode(input).ok()?)),
+        "u8" => Some(Value::from(u8::decode(input).ok()?)),
+        "u16" => Some(Value::from(u16::decode(input).ok()?)),
+        "u32" | "BlockNumber" => Some(Value::from(u32::decode(input).ok()?)),
+        "u64" | "Moment" => Some(Value::from(u64::decode(input).ok()?)),
+        "u128" | "Balance" | "BalanceOf" | "DOT" | "PolkaBTC" | "UnsignedFixedPoint" => {
+            decode_string::<u128>(input)
+        }
+        "i128" | "SignedFixedPoint" => decode_string::<i128>(input),
+        "AccountId" => Some(Value::String(AccountId::decode(input).ok()?.to_ss58check())),
+        "H256" | "Hash" | "H256Le" => decode_fixed_bytes(32, input),
+        "H160" => decode_fixed_bytes(20, input),
+        "BtcPublicKey" => decode_fixed_bytes(33, input),
+        _ => None,
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+enum Request<'a> {
+    AccountId,
+    Sign {
+        call: Option<&'a DecodedCall>,
+        call_data: Bytes,
+        extra: Bytes,
+        payload: Bytes,
+    },
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    result: Option<T>,
+    error: Option<String>,
+}
+
+/// Signer that forwards the payloads to an external process, see the module documentation
+/// for the protocol.
+#[derive(Clone)]
+pub struct RemoteSigner {
+    socket_path: PathBuf,
+    account_id: AccountId,
+    decoder: Arc<CallDecoder>,
+}
+
+impl RemoteSigner {
+    /// Connect to the signer listening on the given unix socket, and request its account.
+    pub async fn connect<P: Into<PathBuf>>(socket_path: P) -> Result<Self, Error> {
+        let socket_path = socket_path.into();
+        let address: String = request(&socket_path, &Request::AccountId).await?;
+        let account_id =
+            AccountId::from_ss58check(&address).map_err(|_| Error::InvalidSignerResponse)?;
+
+        Ok(Self {
+            socket_path,
+            account_id,
+            decoder: Default::default(),
+        })
+    }
+
+    /// Request a signature for the call and extra data, and verify that it was made by our
+    /// account.
+    async fn request_signature(
+        socket_path: PathBuf,
+        account_id: AccountId,
+        call: Option<DecodedCall>,
+        call_data: Vec<u8>,
+        extra: Vec<u8>,
+    ) -> Result<Signature, Error> {
+        let payload = signing_payload(&call_data, &extra);
+        let signature: Bytes = request(
+            &socket_path,
+            &Request::Sign {
+                call: call.as_ref(),
+                call_data: Bytes(call_data),
+                extra: Bytes(extra),
+                payload: Bytes(payload.clone()),
+            },
+        )
+        .await?;
+
+        if signature.len() != 64 {
+            return Err(Error::InvalidSignerResponse);
+        }
+        let mut raw = [0u8; 64];
+        raw.copy_from_slice(&signature);
+        let signature = Signature::from_raw(raw);
+
+        let public = Public::from_raw(account_id.into());
+        if !KeyPair::verify(&signature, payload, &public) {
+            return Err(Error::InvalidSignerResponse);
+        }
+        Ok(signature)
+    }
+}
+
+/// The data that is signed for an extrinsic, the same way as `SignedPayload` encodes it.
+pub(crate) fn signing_payload(call_data: &[u8], extra: &[u8]) -> Vec<u8> {
+    let data = [call_data, extra].concat();
+    if data.len() > MAX_UNHASHED_PAYLOAD_LENGTH {
+        blake2_256(&data).to_vec()
+    } else {
+        data
+    }
+}
+
+/// Send a single request to the signer, and wait at most `SIGNER_TIMEOUT` for the response.
+async fn request<T: serde::de::DeserializeOwned>(
+    socket_path: &Path,
+    request: &Request<'_>,
+) -> Result<T, Error> {
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+
+    let line = timeout(SIGNER_TIMEOUT, async {
+        let mut stream = BufReader::new(UnixStream::connect(socket_path).await?);
+        stream.get_mut().write_all(line.as_bytes()).await?;
+
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        Result::<_, Error>::Ok(line)
+    })
+    .await
+    .map_err(|_| Error::SignerTimeout)??;
+    let response: Response<T> = serde_json::from_str(&line)?;
+    match (response.result, response.error) {
+        (_, Some(reason)) => Err(Error::SigningRefused(reason)),
+        (Some(result), None) => Ok(result),
+        (None, None) => Err(Error::InvalidSignerResponse),
+    }
+}
+
+impl Signer<PolkaBtcRuntime> for RemoteSigner {
+    fn account_id(&self) -> &AccountId {
+        &self.account_id
+    }
+
+    fn nonce(&self) -> Option<u32> {
+        None
+    }
+
+    fn sign(
+        &self,
+        extrinsic: SignedPayload<PolkaBtcRuntime>,
+    ) -> Pin<
+        Box<dyn Future<Output = Result<UncheckedExtrinsic<PolkaBtcRuntime>, String>> + Send + Sync>,
+    > {
+        let (call, extra, additional_signed) = extrinsic.deconstruct();
+        let decoded = self.decoder.decode(&call.0);
+        let call_data = call.0.clone();
+        let extra_data = (&extra, additional_signed).encode();
+        let socket_path = self.socket_path.clone();
+        let account_id = self.account_id.clone();
+
+        Box::pin(async move {
+            let signature = Self::request_signature(
+                socket_path,
+                account_id.clone(),
+                decoded,
+                call_data,
+                extra_data,
+            )
+            .await
+            .map_err(|err| err.to_string())?;
+
+            Ok(UncheckedExtrinsic::<PolkaBtcRuntime>::new_signed(
+                call,
+                account_id,
+                signature.into(),
+                extra,
+            ))
+        })
+    }
+}
+
+/// The signer used by `PolkaBtcProvider`.
+#[derive(Clone)]
+pub enum PolkaBtcSigner {
+    /// Signs with a key pair held in memory.
+    Local(PairSigner<PolkaBtcRuntime, KeyPair>),
+    /// Forwards the payloads to an external signer.
+    Remote(RemoteSigner),
+}
+
+impl PolkaBtcSigner {
+    /// Update the metadata used to decode calls for the remote signer.
+    pub(crate) fn set_metadata(&mut self, metadata: &[u8]) -> Result<(), Error> {
+        if let PolkaBtcSigner::Remote(signer) = self {
+            signer.decoder = Arc::new(CallDecoder::from_metadata(metadata)?);
+        }
+        Ok(())
+    }
+}
+
+impl From<PairSigner<PolkaBtcRuntime, KeyPair>> for PolkaBtcSigner {
+    fn from(signer: PairSigner<PolkaBtcRuntime, KeyPair>) -> Self {
+        PolkaBtcSigner::Local(signer)
+    }
+}
+
+impl From<RemoteSigner> for PolkaBtcSigner {
+    fn from(signer: RemoteSigner) -> Self {
+        PolkaBtcSigner::Remote(signer)
+    }
+}
+
+impl Signer<PolkaBtcRuntime> for PolkaBtcSigner {
+    fn account_id(&self) -> &AccountId {
+        match self {
+            PolkaBtcSigner::Local(signer) => signer.account_id(),
+            PolkaBtcSigner::Remote(signer) => signer.account_id(),
+        }
+    }
+
+    fn nonce(&self) -> Option<u32> {
+        match self {
+            PolkaBtcSigner::Local(signer) => signer.nonce(),
+            PolkaBtcSigner::Remote(signer) => signer.nonce(),
+        }
+    }
+
+    fn sign(
+        &self,
+        extrinsic: SignedPayload<PolkaBtcRuntime>,
+    ) -> Pin<
+        Box<dyn Future<Output = Result<UncheckedExtrinsic<PolkaBtcRuntime>, String>> + Send + Sync>,
+    > {
+        match self {
+            PolkaBtcSigner::Local(signer) => signer.sign(extrinsic),
+            PolkaBtcSigner::Remote(signer) => signer.sign(extrinsic),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_keyring::AccountKeyring;
+    use tempdir::TempDir;
+    use tokio::net::UnixListener;
+
+    fn decoder() -> CallDecoder {
+        let call = |name: &str, arguments: &[(&str, &str)]| CallMetadata {
+            name: name.to_string(),
+            arguments: arguments
+                .iter()
+                .map(|(name, ty)| (name.to_string(), ty.to_string()))
+                .collect(),
+        };
+        let mut modules = HashMap::new();
+        modules.insert(
+            9,
+            ModuleMetadata {
+                name: "VaultRegistry".to_string(),
+                calls: vec![
+                    call(
+                        "register_vault",
+                        &[("collateral", "DOT<T>"), ("public_key", "BtcPublicKey")],
+                    ),
+                    call("withdraw_collateral", &[("amount", "DOT<T>")]),
+                    call("register_address", &[("btc_address", "BtcAddress")]),
+                ],
+            },
+        );
+        CallDecoder { modules }
+    }
+
+    #[test]
+    fn test_decode_call() {
+        let mut call = vec![9, 0];
+        call.extend(1000u128.encode());
+        call.extend(vec![2u8; 33]);
+
+        let decoded = decoder().decode(&call).unwrap();
+        assert_eq!(decoded.module, "VaultRegistry");
+        assert_eq!(decoded.function, "register_vault");
+        assert_eq!(decoded.arguments[0].value, Value::from("1000"));
+        assert_eq!(
+            decoded.arguments[1].value,
+            Value::from(format!("0x{}", "02".repeat(33)))
+        );
+        assert_eq!(decoded.undecoded, None);
+    }
+
+    #[test]
+    fn test_decode_call_with_unsupported_type() {
+        let call = vec![9, 2, 0, 1, 2, 3];
+
+        let decoded = decoder().decode(&call).unwrap();
+        assert_eq!(decoded.function, "register_address");
+        assert!(decoded.arguments.is_empty());
+        assert_eq!(decoded.undecoded, Some(Bytes(vec![0, 1, 2, 3])));
+    }
+
+    #[test]
+    fn test_decode_unknown_call() {
+        assert_eq!(decoder().decode(&[9, 3]), None);
+        assert_eq!(decoder().decode(&[10, 0]), None);
+    }
+
+    #[test]
+    fn test_decode_value() {
+        let account_id = AccountKeyring::Alice.to_account_id();
+        assert_eq!(
+            decode_value("T::AccountId", &mut &account_id.encode()[..]),
+            Some(Value::from(account_id.to_ss58check()))
+        );
+        assert_eq!(
+            decode_value("Compact<T::Balance>", &mut &Compact(5u128).encode()[..]),
+            Some(Value::from("5"))
+        );
+        assert_eq!(
+            decode_value("Option<u32>", &mut &Some(7u32).encode()[..]),
+            Some(Value::from(7))
+        );
+        assert_eq!(
+            decode_value("Vec<u32>", &mut &vec![1u32, 2].encode()[..]),
+            Some(Value::from(vec![1, 2]))
+        );
+        assert_eq!(decode_value("RawBlockHeader", &mut &[0u8; 80][..]), None);
+    }
+
+    /// Serve requests like a signing daemon would: decode the call from the call data rather
+    /// than trusting the given call, check that it is what is signed, and refuse to sign
+    /// collateral withdrawals.
+    async fn serve(listener: &mut UnixListener, pair: KeyPair) {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            let request: Value = serde_json::from_str(&line).unwrap();
+
+            let bytes = |name: &str| -> Bytes {
+                serde_json::from_value(request["params"][name].clone()).unwrap()
+            };
+
+            let response = match request["method"].as_str().unwrap() {
+                "account_id" => serde_json::json!({ "result": pair.public().to_ss58check() }),
+                _ => {
+                    let (call_data, extra, payload) =
+                        (bytes("call_data"), bytes("extra"), bytes("payload"));
+                    let signed = [&call_data[..], &extra[..]].concat();
+                    let call = decoder().decode(&call_data);
+                    if payload.0 != signed && payload.0 != blake2_256(&signed) {
+                        serde_json::json!({ "error": "payload does not match the call" })
+                    } else if call.map(|call| call.function).as_deref() != Some("register_vault") {
+                        serde_json::json!({ "error": "only registration is allowed" })
+                    } else {
+                        let signature = pair.sign(&payload);
+                        serde_json::json!({ "result": Bytes(signature.0.to_vec()) })
+                    }
+                }
+            };
+            let response = format!("{}\n", response);
+            stream
+                .get_mut()
+                .write_all(response.as_bytes())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer() {
+        let dir = TempDir::new("remote-signer").unwrap();
+        let socket_path = dir.path().join("signer.sock");
+        let mut listener = UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(async move { serve(&mut listener, AccountKeyring::Bob.pair()).await });
+
+        let signer = RemoteSigner::connect(socket_path.clone()).await.unwrap();
+        assert_eq!(signer.account_id, AccountKeyring::Bob.to_account_id());
+
+        let register = decoder().decode(&[9, 0]);
+        let signature = RemoteSigner::request_signature(
+            socket_path.clone(),
+            signer.account_id.clone(),
+            register.clone(),
+            vec![9, 0],
+            vec![1, 2, 3],
+        )
+        .await
+        .unwrap();
+        assert!(KeyPair::verify(
+            &signature,
+            &[9u8, 0, 1, 2, 3][..],
+            &AccountKeyring::Bob.public()
+        ));
+
+        // long payloads are signed as their hash
+        let extra = vec![1u8; 300];
+        let signature = RemoteSigner::request_signature(
+            socket_path.clone(),
+            signer.account_id.clone(),
+            register.clone(),
+            vec![9, 0],
+            extra.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(KeyPair::verify(
+            &signature,
+            &blake2_256(&[&[9u8, 0][..], &extra[..]].concat())[..],
+            &AccountKeyring::Bob.public()
+        ));
+
+        let mut call = vec![9, 1];
+        call.extend(1000u128.encode());
+        let withdraw = decoder().decode(&call);
+        let result = RemoteSigner::request_signature(
+            socket_path.clone(),
+            signer.account_id.clone(),
+            withdraw,
+            call.clone(),
+            vec![1, 2, 3],
+        )
+        .await;
+        assert!(matches!(result, Err(Error::SigningRefused(_))));
+
+        // presenting an innocuous call does not get the withdrawal signed
+        let result = RemoteSigner::request_signature(
+            socket_path,
+            signer.account_id.clone(),
+            register,
+            call,
+            vec![1, 2, 3],
+        )
+        .await;
+        assert!(matches!(result, Err(Error::SigningRefused(_))));
+    }
+}
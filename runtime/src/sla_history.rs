@@ -0,0 +1,164 @@
+use crate::{json::MethodSchema, Error, FixedI128, PolkaBtcHeader, SlaPallet, UtilFuncs};
+use parity_scale_codec::{Decode, Encode};
+use schemars::JsonSchema;
+use serde::Serialize;
+use sp_core::H256;
+use sp_runtime::traits::Header as _;
+use std::collections::VecDeque;
+use std::sync::RwLock;
+
+/// Default number of SLA changes kept by `SlaHistory`.
+pub const DEFAULT_SLA_HISTORY_LENGTH: usize = 100;
+
+/// A change of the SLA score of a vault or staked relayer.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, JsonSchema)]
+pub struct SlaChange {
+    /// Height of the parachain block that emitted the change.
+    pub block_number: u32,
+    #[schemars(with = "String")]
+    pub block_hash: H256,
+    /// The score after the change.
+    #[serde(with = "crate::json::fixed_i128")]
+    #[schemars(with = "String")]
+    pub new_sla: FixedI128,
+}
+
+/// The account whose SLA score is reported, see `SlaHistory::report`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlaSubject {
+    Vault,
+    Relayer,
+}
+
+/// Response of the `sla` api methods of the vault and the staked relayer.
+#[derive(Encode, Decode, Serialize, JsonSchema, Debug)]
+pub struct SlaJsonRpcResponse {
+    #[serde(with = "crate::json::fixed_i128")]
+    #[schemars(with = "String")]
+    pub current: FixedI128,
+    #[serde(with = "crate::json::fixed_i128")]
+    #[schemars(with = "String")]
+    pub target: FixedI128,
+    /// The changes observed since the client started, oldest first. The history is only kept
+    /// in memory, so it is empty after a restart.
+    pub history: Vec<SlaChange>,
+}
+
+impl SlaJsonRpcResponse {
+    /// Schema of the `json_sla` method of the vault and the staked relayer.
+    pub fn method_schema() -> MethodSchema {
+        MethodSchema::new::<(), Self>(
+            "json_sla",
+            "SLA score, target and the changes since startup, which are kept in memory only.",
+        )
+    }
+}
+
+/// The most recent SLA changes of a single vault or staked relayer, oldest first. The changes
+/// are only kept in memory.
+pub struct SlaHistory {
+    changes: RwLock<VecDeque<SlaChange>>,
+    length: usize,
+}
+
+impl Default for SlaHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_SLA_HISTORY_LENGTH)
+    }
+}
+
+impl SlaHistory {
+    /// Keep at most `length` changes, dropping the oldest ones. Nothing is kept if `length` is 0.
+    pub fn new(length: usize) -> Self {
+        Self {
+            changes: RwLock::new(VecDeque::with_capacity(length)),
+            length,
+        }
+    }
+
+    /// Record that the SLA score changed to `new_sla` in the block with the given header.
+    pub fn record(&self, header: &PolkaBtcHeader, new_sla: FixedI128) {
+        if self.length == 0 {
+            return;
+        }
+        let mut changes = self.changes.write().unwrap();
+        if changes.len() >= self.length {
+            changes.pop_front();
+        }
+        changes.push_back(SlaChange {
+            block_number: header.number,
+            block_hash: header.hash(),
+            new_sla,
+        });
+    }
+
+    pub fn changes(&self) -> Vec<SlaChange> {
+        self.changes.read().unwrap().iter().cloned().collect()
+    }
+
+    /// Query the current and target SLA score of the account of the provider, and add the
+    /// recorded changes.
+    pub async fn report<P: SlaPallet + UtilFuncs>(
+        &self,
+        provider: &P,
+        subject: SlaSubject,
+    ) -> Result<SlaJsonRpcResponse, Error> {
+        let account_id = provider.get_account_id().clone();
+        let parameters = provider.get_sla_parameters().await?;
+        let (current, target) = match subject {
+            SlaSubject::Vault => (
+                provider.get_vault_sla(account_id).await?,
+                parameters.vault_target_sla,
+            ),
+            SlaSubject::Relayer => (
+                provider.get_relayer_sla(account_id).await?,
+                parameters.relayer_target_sla,
+            ),
+        };
+        Ok(SlaJsonRpcResponse {
+            current,
+            target,
+            history: self.changes(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedPointNumber;
+    use sp_runtime::traits::Header as _;
+
+    fn header(number: u32) -> PolkaBtcHeader {
+        PolkaBtcHeader::new(
+            number,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn test_record_drops_oldest_changes() {
+        let history = SlaHistory::new(2);
+        for number in 1..=3 {
+            history.record(&header(number), FixedI128::from_inner(number.into()));
+        }
+
+        let changes = history.changes();
+        assert_eq!(
+            changes.iter().map(|x| x.block_number).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(changes[1].new_sla, FixedI128::from_inner(3));
+        assert_eq!(changes[1].block_hash, header(3).hash());
+    }
+
+    #[test]
+    fn test_record_without_length() {
+        let history = SlaHistory::new(0);
+        history.record(&header(1), FixedI128::from_inner(1));
+        assert_eq!(history.changes(), vec![]);
+    }
+}
@@ -0,0 +1,1852 @@
+//! An in-memory parachain for unit tests, see `MockParachain`, and a provider connected to
+//! an in-process development parachain, see `test_client_with`.
+
+use crate::pallets::{
+    balances_dot, balances_polka_btc,
+    btc_relay::{InitializedEvent, StoreMainChainHeaderEvent},
+    exchange_rate_oracle::{SetBtcTxFeesPerByteEvent, SetExchangeRateEvent},
+    issue::{CancelIssueEvent, ExecuteIssueEvent, RequestIssueEvent},
+    redeem::{CancelRedeemEvent, ExecuteRedeemEvent, RequestRedeemEvent},
+    refund::ExecuteRefundEvent,
+    replace::{
+        AcceptReplaceEvent, AuctionReplaceEvent, CancelReplaceEvent, ExecuteReplaceEvent,
+        RequestReplaceEvent, WithdrawReplaceEvent,
+    },
+    staked_relayers::{
+        DeregisterStakedRelayerEvent, ExecuteStatusUpdateEvent, RegisterStakedRelayerEvent,
+        StatusUpdateSuggestedEvent,
+    },
+    vault_registry::{
+        LockAdditionalCollateralEvent, RegisterAddressEvent, RegisterVaultEvent,
+        UpdatePublicKeyEvent, VaultStatus, WithdrawCollateralEvent,
+    },
+    Core,
+};
+use crate::{
+    AccountId, BitcoinBlockHeight, BtcAddress, BtcPublicKey, BtcRelayPallet, BtcTxFeesPerByte,
+    DotBalancesPallet, Error, ErrorCode, ExchangeRateOraclePallet, ExtrinsicFuncs, FeePallet,
+    FixedI128, FixedPointNumber, FixedU128, H256Le, IssuePallet, PolkaBtcIssueRequest,
+    PolkaBtcProvider, PolkaBtcRedeemRequest, PolkaBtcRefundRequest, PolkaBtcReplaceRequest,
+    PolkaBtcRequestIssueEvent, PolkaBtcRuntime, PolkaBtcStatusUpdate, PolkaBtcVault,
+    RawBlockHeader, RedeemPallet, RefundPallet, ReplacePallet, RichBlockHeader, SecurityPallet,
+    SlaPallet, SlaParameters, StakedRelayerPallet, StatusCode, TimestampPallet, TreasuryPallet,
+    UtilFuncs, VaultRegistryPallet, XtError, MINIMUM_STAKE,
+};
+use async_trait::async_trait;
+use core::marker::PhantomData;
+use sp_core::{H160, H256};
+use sp_keyring::AccountKeyring;
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use substrate_subxt::{Call, ModuleError, PairSigner, RuntimeError};
+use substrate_subxt_client::{
+    DatabaseConfig, KeystoreConfig, Role, SubxtClient, SubxtClientConfig,
+};
+use tempdir::TempDir;
+use tokio::time::delay_for;
+
+/// Number of blocks after which issue, redeem and replace requests expire.
+pub const DEFAULT_REQUEST_PERIOD: u32 = 10;
+
+/// Milliseconds that the timestamp advances with every parachain block.
+pub const BLOCK_TIME_MS: u64 = 6000;
+
+/// Milliseconds after which the exchange rate can be reported as outdated.
+pub const MAX_EXCHANGE_RATE_DELAY_MS: u64 = 3_600_000;
+
+/// Interval at which `wait_for_block_in_relay` checks the relay.
+const RELAY_POLLING_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Collateral of a vault as a percentage of the DOT value of its tokens, below which it
+/// cannot issue any more tokens.
+fn secure_collateral_threshold() -> FixedU128 {
+    FixedU128::saturating_from_rational(150, 100)
+}
+
+/// Collateral of a vault as a percentage of the DOT value of its tokens, below which it
+/// can be replaced through an auction.
+fn auction_collateral_threshold() -> FixedU128 {
+    FixedU128::saturating_from_rational(120, 100)
+}
+
+/// The error that the parachain would return if the call failed with `error` in `module`.
+fn dispatch_error(module: &str, error: &str) -> Error {
+    XtError::Runtime(RuntimeError::Module(ModuleError {
+        module: module.to_string(),
+        error: error.to_string(),
+    }))
+    .into()
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct AccountBalance {
+    free: u128,
+    reserved: u128,
+}
+
+struct State {
+    height: u32,
+    time_now: u64,
+    nonce: u64,
+    events: Vec<Box<dyn Any + Send>>,
+    dot: BTreeMap<AccountId, AccountBalance>,
+    /// The reserved balance is the PolkaBTC locked by redeem requests.
+    polka_btc: BTreeMap<AccountId, AccountBalance>,
+    vaults: BTreeMap<AccountId, PolkaBtcVault>,
+    relayers: BTreeMap<AccountId, u128>,
+    issue_requests: BTreeMap<H256, PolkaBtcIssueRequest>,
+    redeem_requests: BTreeMap<H256, PolkaBtcRedeemRequest>,
+    replace_requests: BTreeMap<H256, PolkaBtcReplaceRequest>,
+    refund_requests: BTreeMap<H256, PolkaBtcRefundRequest>,
+    status_updates: BTreeMap<u64, PolkaBtcStatusUpdate>,
+    next_status_update_id: u64,
+    status: StatusCode,
+    error_codes: BTreeSet<ErrorCode>,
+    exchange_rate: FixedU128,
+    exchange_rate_time: u64,
+    btc_tx_fees: BtcTxFeesPerByte,
+    /// Hashes of the bitcoin main chain, starting at `relay_start`.
+    relay: Vec<H256Le>,
+    relay_start: u32,
+    bitcoin_confirmations: u32,
+    issue_period: u32,
+    redeem_period: u32,
+    replace_period: u32,
+    maturity_period: u32,
+    vault_sla: BTreeMap<AccountId, FixedI128>,
+    relayer_sla: BTreeMap<AccountId, FixedI128>,
+    /// Raw transactions that `is_transaction_invalid` reports as invalid.
+    invalid_transactions: BTreeSet<Vec<u8>>,
+    extrinsic_fee: u128,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            height: 1,
+            time_now: BLOCK_TIME_MS,
+            nonce: 0,
+            events: Vec::new(),
+            dot: BTreeMap::new(),
+            polka_btc: BTreeMap::new(),
+            vaults: BTreeMap::new(),
+            relayers: BTreeMap::new(),
+            issue_requests: BTreeMap::new(),
+            redeem_requests: BTreeMap::new(),
+            replace_requests: BTreeMap::new(),
+            refund_requests: BTreeMap::new(),
+            status_updates: BTreeMap::new(),
+            next_status_update_id: 1,
+            status: StatusCode::Running,
+            error_codes: BTreeSet::new(),
+            exchange_rate: FixedU128::one(),
+            exchange_rate_time: BLOCK_TIME_MS,
+            btc_tx_fees: BtcTxFeesPerByte {
+                fast: 0,
+                half: 0,
+                hour: 0,
+            },
+            relay: Vec::new(),
+            relay_start: 0,
+            bitcoin_confirmations: 0,
+            issue_period: DEFAULT_REQUEST_PERIOD,
+            redeem_period: DEFAULT_REQUEST_PERIOD,
+            replace_period: DEFAULT_REQUEST_PERIOD,
+            maturity_period: 0,
+            vault_sla: BTreeMap::new(),
+            relayer_sla: BTreeMap::new(),
+            invalid_transactions: BTreeSet::new(),
+            extrinsic_fee: 0,
+        }
+    }
+}
+
+impl State {
+    fn emit<T: Send + 'static>(&mut self, event: T) {
+        self.events.push(Box::new(event));
+    }
+
+    /// Returns a new unique id for a request.
+    fn next_id(&mut self) -> H256 {
+        self.nonce += 1;
+        H256::from_low_u64_be(self.nonce)
+    }
+
+    fn ensure_running(&self) -> Result<(), Error> {
+        if self.status == StatusCode::Running {
+            Ok(())
+        } else {
+            Err(dispatch_error("Security", "ParachainNotRunning"))
+        }
+    }
+
+    fn ensure_relayer(&self, account_id: &AccountId) -> Result<(), Error> {
+        if self.relayers.contains_key(account_id) {
+            Ok(())
+        } else {
+            Err(dispatch_error("StakedRelayers", "NotStakedRelayer"))
+        }
+    }
+
+    fn has_expired(&self, opentime: u32, period: u32) -> bool {
+        self.height > opentime + period
+    }
+
+    fn vault(&self, vault_id: &AccountId) -> Result<&PolkaBtcVault, Error> {
+        self.vaults
+            .get(vault_id)
+            .ok_or_else(|| dispatch_error("VaultRegistry", "VaultNotFound"))
+    }
+
+    fn vault_mut(&mut self, vault_id: &AccountId) -> Result<&mut PolkaBtcVault, Error> {
+        self.vaults
+            .get_mut(vault_id)
+            .ok_or_else(|| dispatch_error("VaultRegistry", "VaultNotFound"))
+    }
+
+    fn btc_to_dots(&self, amount: u128) -> u128 {
+        self.exchange_rate.saturating_mul_int(amount)
+    }
+
+    fn dots_to_btc(&self, amount: u128) -> Result<u128, Error> {
+        let rate = self
+            .exchange_rate
+            .reciprocal()
+            .ok_or_else(|| dispatch_error("ExchangeRateOracle", "MissingExchangeRate"))?;
+        Ok(rate.saturating_mul_int(amount))
+    }
+
+    fn required_collateral(&self, amount_btc: u128) -> u128 {
+        secure_collateral_threshold().saturating_mul_int(self.btc_to_dots(amount_btc))
+    }
+
+    fn collateral(&self, vault_id: &AccountId) -> u128 {
+        self.dot.get(vault_id).copied().unwrap_or_default().reserved
+    }
+
+    /// Collateral that does not back any issued or to-be-issued tokens.
+    fn free_collateral(&self, vault_id: &AccountId) -> Result<u128, Error> {
+        let vault = self.vault(vault_id)?;
+        let used = self.required_collateral(vault.issued_tokens + vault.to_be_issued_tokens);
+        Ok(self.collateral(vault_id).saturating_sub(used))
+    }
+
+    fn reserve_dot(&mut self, account_id: &AccountId, amount: u128) -> Result<(), Error> {
+        let balance = self.dot.entry(account_id.clone()).or_default();
+        if balance.free < amount {
+            return Err(dispatch_error("Collateral", "InsufficientFunds"));
+        }
+        balance.free -= amount;
+        balance.reserved += amount;
+        Ok(())
+    }
+
+    fn unreserve_dot(&mut self, account_id: &AccountId, amount: u128) {
+        let balance = self.dot.entry(account_id.clone()).or_default();
+        let amount = u128::min(amount, balance.reserved);
+        balance.reserved -= amount;
+        balance.free += amount;
+    }
+
+    /// Move reserved DOT of `from` to the free balance of `to`.
+    fn slash_dot(&mut self, from: &AccountId, to: &AccountId, amount: u128) {
+        let balance = self.dot.entry(from.clone()).or_default();
+        let amount = u128::min(amount, balance.reserved);
+        balance.reserved -= amount;
+        self.dot.entry(to.clone()).or_default().free += amount;
+    }
+
+    fn transfer(
+        balances: &mut BTreeMap<AccountId, AccountBalance>,
+        module: &str,
+        from: &AccountId,
+        to: &AccountId,
+        amount: u128,
+    ) -> Result<(), Error> {
+        let balance = balances.entry(from.clone()).or_default();
+        if balance.free < amount {
+            return Err(dispatch_error(module, "InsufficientBalance"));
+        }
+        balance.free -= amount;
+        balances.entry(to.clone()).or_default().free += amount;
+        Ok(())
+    }
+}
+
+/// A parachain that keeps its state in memory and implements all pallet traits, such that
+/// clients can be tested without running a node. Calls apply the same state transitions as
+/// the parachain and record the emitted events, see `events`. Clones share the state.
+///
+/// Some simplifications apply:
+/// * bitcoin transactions and inclusion proofs are not verified
+/// * the collateral of a vault is its reserved DOT balance
+/// * the relay only stores the main chain, forks are not supported
+/// * a single approving vote executes a status update
+/// * fees are estimated, see `set_extrinsic_fee`, but not charged, and dry runs always succeed
+#[derive(Clone)]
+pub struct MockParachain {
+    state: Arc<Mutex<State>>,
+    account_id: AccountId,
+}
+
+impl MockParachain {
+    /// Create an empty parachain, with calls signed by the given account.
+    pub fn new(account_id: AccountId) -> Self {
+        Self {
+            state: Default::default(),
+            account_id,
+        }
+    }
+
+    /// Returns a handle to the same parachain with calls signed by another account.
+    pub fn with_account(&self, account_id: AccountId) -> Self {
+        Self {
+            state: self.state.clone(),
+            account_id,
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Register the address and execute a request like a batch of both calls would: if the
+    /// execution fails, the registration is reverted.
+    async fn register_address_and<F, R>(
+        &self,
+        btc_address: BtcAddress,
+        execute: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce() -> R,
+        R: Future<Output = Result<(), Error>>,
+    {
+        self.register_address(btc_address).await?;
+        let result = execute().await;
+        if result.is_err() {
+            let mut state = self.state();
+            state
+                .vault_mut(&self.account_id)?
+                .wallet
+                .addresses
+                .remove(&btc_address);
+            // failed executions do not emit events, so this is the registration
+            state.events.pop();
+        }
+        result
+    }
+
+    /// Set the free DOT balance of the account, e.g. to endow it before registering a vault.
+    pub fn set_free_dot_balance(&self, account_id: AccountId, amount: u128) {
+        self.state().dot.entry(account_id).or_default().free = amount;
+    }
+
+    /// Produce the given number of parachain blocks, such that requests can expire.
+    pub fn advance_chain(&self, blocks: u32) {
+        let mut state = self.state();
+        state.height += blocks;
+        state.time_now += BLOCK_TIME_MS * blocks as u64;
+    }
+
+    /// Add an issue request as is, e.g. with an open time that is not consistent with the chain.
+    pub fn insert_issue_request(&self, issue_id: H256, request: PolkaBtcIssueRequest) {
+        self.state().issue_requests.insert(issue_id, request);
+    }
+
+    /// Make `is_transaction_invalid` report the raw transaction as invalid.
+    pub fn insert_invalid_transaction(&self, raw_tx: Vec<u8>) {
+        self.state().invalid_transactions.insert(raw_tx);
+    }
+
+    /// Set the fee that is estimated for every extrinsic.
+    pub fn set_extrinsic_fee(&self, fee: u128) {
+        self.state().extrinsic_fee = fee;
+    }
+
+    /// Add a refund request, as the parachain does when an issue request is overpaid.
+    pub fn insert_refund_request(&self, refund_id: H256, request: PolkaBtcRefundRequest) {
+        self.state().refund_requests.insert(refund_id, request);
+    }
+
+    /// Returns all events of type `T` emitted so far, oldest first.
+    pub fn events<T: Clone + 'static>(&self) -> Vec<T> {
+        self.state()
+            .events
+            .iter()
+            .filter_map(|event| event.downcast_ref::<T>().cloned())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl UtilFuncs for MockParachain {
+    async fn get_current_chain_height(&self) -> Result<u32, Error> {
+        Ok(self.state().height)
+    }
+
+    /// The relay does not keep its history, so this is the current best height.
+    async fn get_blockchain_height_at(&self, _parachain_height: u32) -> Result<u32, Error> {
+        self.get_best_block_height().await
+    }
+
+    fn get_account_id(&self) -> &AccountId {
+        &self.account_id
+    }
+}
+
+#[async_trait]
+impl ExtrinsicFuncs for MockParachain {
+    async fn estimate_fee<C: Call<PolkaBtcRuntime> + Send + Sync + 'static>(
+        &self,
+        _call: C,
+    ) -> Result<u128, Error> {
+        Ok(self.state().extrinsic_fee)
+    }
+
+    async fn dry_run<C: Call<PolkaBtcRuntime> + Send + Sync + 'static>(
+        &self,
+        _call: C,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DotBalancesPallet for MockParachain {
+    async fn get_free_dot_balance(&self) -> Result<<PolkaBtcRuntime as Core>::Balance, Error> {
+        self.get_free_dot_balance_for_id(self.account_id.clone())
+            .await
+    }
+
+    async fn get_free_dot_balance_for_id(
+        &self,
+        id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::Balance, Error> {
+        Ok(self.state().dot.get(&id).copied().unwrap_or_default().free)
+    }
+
+    async fn get_reserved_dot_balance(&self) -> Result<<PolkaBtcRuntime as Core>::Balance, Error> {
+        Ok(self.state().collateral(&self.account_id))
+    }
+
+    async fn transfer_to(&self, destination: AccountId, amount: u128) -> Result<(), Error> {
+        let mut state = self.state();
+        State::transfer(
+            &mut state.dot,
+            "Balances",
+            &self.account_id,
+            &destination,
+            amount,
+        )?;
+        state.emit(balances_dot::TransferEvent::<PolkaBtcRuntime> {
+            from: self.account_id.clone(),
+            to: destination,
+            amount,
+        });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TreasuryPallet for MockParachain {
+    async fn get_free_polka_btc_balance(
+        &self,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error> {
+        self.get_free_polka_btc_balance_for_id(self.account_id.clone())
+            .await
+    }
+
+    async fn get_free_polka_btc_balance_for_id(
+        &self,
+        id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error> {
+        Ok(self
+            .state()
+            .polka_btc
+            .get(&id)
+            .copied()
+            .unwrap_or_default()
+            .free)
+    }
+
+    async fn get_locked_polka_btc_balance(
+        &self,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error> {
+        self.get_locked_polka_btc_balance_for_id(self.account_id.clone())
+            .await
+    }
+
+    async fn get_locked_polka_btc_balance_for_id(
+        &self,
+        id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error> {
+        Ok(self
+            .state()
+            .polka_btc
+            .get(&id)
+            .copied()
+            .unwrap_or_default()
+            .reserved)
+    }
+
+    async fn get_polka_btc_total_supply(
+        &self,
+    ) -> Result<<PolkaBtcRuntime as Core>::PolkaBTC, Error> {
+        Ok(self
+            .state()
+            .polka_btc
+            .values()
+            .map(|balance| balance.free + balance.reserved)
+            .sum())
+    }
+
+    async fn transfer_polka_btc(&self, destination: AccountId, amount: u128) -> Result<(), Error> {
+        let mut state = self.state();
+        State::transfer(
+            &mut state.polka_btc,
+            "PolkaBTC",
+            &self.account_id,
+            &destination,
+            amount,
+        )?;
+        state.emit(balances_polka_btc::TransferEvent::<PolkaBtcRuntime> {
+            from: self.account_id.clone(),
+            to: destination,
+            amount,
+        });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SlaPallet for MockParachain {
+    async fn get_vault_sla(
+        &self,
+        vault_id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::SignedFixedPoint, Error> {
+        Ok(self
+            .state()
+            .vault_sla
+            .get(&vault_id)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    async fn get_relayer_sla(
+        &self,
+        relayer_id: AccountId,
+    ) -> Result<<PolkaBtcRuntime as Core>::SignedFixedPoint, Error> {
+        Ok(self
+            .state()
+            .relayer_sla
+            .get(&relayer_id)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    async fn get_sla_parameters(&self) -> Result<SlaParameters, Error> {
+        Ok(SlaParameters {
+            vault_target_sla: FixedI128::saturating_from_integer(100),
+            relayer_target_sla: FixedI128::saturating_from_integer(100),
+        })
+    }
+}
+
+#[async_trait]
+impl ReplacePallet for MockParachain {
+    async fn request_replace(
+        &self,
+        amount: u128,
+        griefing_collateral: u128,
+    ) -> Result<H256, Error> {
+        let mut state = self.state();
+        state.ensure_running()?;
+        if state.vault(&self.account_id)?.issued_tokens < amount {
+            return Err(dispatch_error("Replace", "InsufficientTokensCommitted"));
+        }
+        state.reserve_dot(&self.account_id, griefing_collateral)?;
+
+        let replace_id = state.next_id();
+        let request = PolkaBtcReplaceRequest {
+            old_vault: self.account_id.clone(),
+            open_time: state.height,
+            amount,
+            griefing_collateral,
+            ..Default::default()
+        };
+        state.replace_requests.insert(replace_id, request);
+        state.emit(RequestReplaceEvent::<PolkaBtcRuntime> {
+            old_vault_id: self.account_id.clone(),
+            amount,
+            replace_id,
+        });
+        Ok(replace_id)
+    }
+
+    async fn withdraw_replace(&self, replace_id: H256) -> Result<(), Error> {
+        let mut state = self.state();
+        let request = state.get_replace_request(replace_id)?;
+        if request.old_vault != self.account_id {
+            return Err(dispatch_error("Replace", "UnauthorizedVault"));
+        }
+        if request.new_vault.is_some() {
+            return Err(dispatch_error("Replace", "CancelAcceptedRequest"));
+        }
+        state.unreserve_dot(&self.account_id, request.griefing_collateral);
+        state.replace_requests.remove(&replace_id);
+        state.emit(WithdrawReplaceEvent::<PolkaBtcRuntime> {
+            vault_id: self.account_id.clone(),
+            request_id: replace_id,
+        });
+        Ok(())
+    }
+
+    async fn accept_replace(
+        &self,
+        replace_id: H256,
+        collateral: u128,
+        btc_address: BtcAddress,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_running()?;
+        state.vault(&self.account_id)?;
+        let request = state.get_replace_request(replace_id)?;
+        if request.new_vault.is_some() {
+            return Err(dispatch_error("Replace", "ReplaceAlreadyAccepted"));
+        }
+        if collateral < state.required_collateral(request.amount) {
+            return Err(dispatch_error("Replace", "InsufficientCollateral"));
+        }
+        state.reserve_dot(&self.account_id, collateral)?;
+
+        let request = state.replace_requests.get_mut(&replace_id).unwrap();
+        request.new_vault = Some(self.account_id.clone());
+        request.collateral = collateral;
+        request.btc_address = Some(btc_address);
+        let event = AcceptReplaceEvent::<PolkaBtcRuntime> {
+            old_vault_id: request.old_vault.clone(),
+            new_vault_id: self.account_id.clone(),
+            replace_id,
+            collateral,
+            btc_amount: request.amount,
+            btc_address,
+        };
+        state.emit(event);
+        Ok(())
+    }
+
+    async fn auction_replace(
+        &self,
+        old_vault: AccountId,
+        btc_amount: u128,
+        collateral: u128,
+        btc_address: BtcAddress,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_running()?;
+        state.vault(&self.account_id)?;
+        if !state.is_vault_below_auction_threshold(&old_vault)? {
+            return Err(dispatch_error("Replace", "VaultOverAuctionThreshold"));
+        }
+        if collateral < state.required_collateral(btc_amount) {
+            return Err(dispatch_error("Replace", "InsufficientCollateral"));
+        }
+        state.reserve_dot(&self.account_id, collateral)?;
+
+        let replace_id = state.next_id();
+        let request = PolkaBtcReplaceRequest {
+            old_vault: old_vault.clone(),
+            open_time: state.height,
+            amount: btc_amount,
+            new_vault: Some(self.account_id.clone()),
+            collateral,
+            btc_address: Some(btc_address),
+            ..Default::default()
+        };
+        state.replace_requests.insert(replace_id, request);
+        let current_height = state.height;
+        state.emit(AuctionReplaceEvent::<PolkaBtcRuntime> {
+            old_vault_id: old_vault,
+            new_vault_id: self.account_id.clone(),
+            replace_id,
+            btc_amount,
+            collateral,
+            current_height,
+            btc_address,
+        });
+        Ok(())
+    }
+
+    async fn execute_replace(
+        &self,
+        replace_id: H256,
+        _tx_id: H256Le,
+        _merkle_proof: Vec<u8>,
+        _raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_running()?;
+        let request = state.get_replace_request(replace_id)?;
+        let new_vault = request
+            .new_vault
+            .clone()
+            .ok_or_else(|| dispatch_error("Replace", "ReplaceNotAccepted"))?;
+        if state.has_expired(request.open_time, state.replace_period) {
+            return Err(dispatch_error("Replace", "ReplacePeriodExpired"));
+        }
+
+        state.vault_mut(&request.old_vault)?.issued_tokens -= request.amount;
+        state.vault_mut(&new_vault)?.issued_tokens += request.amount;
+        state.unreserve_dot(&request.old_vault, request.griefing_collateral);
+        state
+            .replace_requests
+            .get_mut(&replace_id)
+            .unwrap()
+            .completed = true;
+        state.emit(ExecuteReplaceEvent::<PolkaBtcRuntime> {
+            old_vault_id: request.old_vault,
+            new_vault_id: new_vault,
+            replace_id,
+        });
+        Ok(())
+    }
+
+    async fn register_address_and_execute_replace(
+        &self,
+        btc_address: BtcAddress,
+        replace_id: H256,
+        tx_id: H256Le,
+        merkle_proof: Vec<u8>,
+        raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.register_address_and(btc_address, || {
+            self.execute_replace(replace_id, tx_id, merkle_proof, raw_tx)
+        })
+        .await
+    }
+
+    async fn cancel_replace(&self, replace_id: H256) -> Result<(), Error> {
+        let mut state = self.state();
+        let request = state.get_replace_request(replace_id)?;
+        if request.new_vault.as_ref() != Some(&self.account_id) {
+            return Err(dispatch_error("Replace", "UnauthorizedVault"));
+        }
+        if !state.has_expired(request.open_time, state.replace_period) {
+            return Err(dispatch_error("Replace", "ReplacePeriodNotExpired"));
+        }
+
+        // the new vault is compensated with the griefing collateral of the old vault
+        state.slash_dot(
+            &request.old_vault,
+            &self.account_id,
+            request.griefing_collateral,
+        );
+        state.unreserve_dot(&self.account_id, request.collateral);
+        state
+            .replace_requests
+            .get_mut(&replace_id)
+            .unwrap()
+            .cancelled = true;
+        state.emit(CancelReplaceEvent::<PolkaBtcRuntime> {
+            new_vault_id: self.account_id.clone(),
+            old_vault_id: request.old_vault,
+            replace_id,
+        });
+        Ok(())
+    }
+
+    async fn get_new_vault_replace_requests(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<(H256, PolkaBtcReplaceRequest)>, Error> {
+        Ok(self
+            .state()
+            .replace_requests
+            .iter()
+            .filter(|(_, request)| request.new_vault.as_ref() == Some(&account_id))
+            .map(|(id, request)| (*id, request.clone()))
+            .collect())
+    }
+
+    async fn get_old_vault_replace_requests(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<(H256, PolkaBtcReplaceRequest)>, Error> {
+        Ok(self
+            .state()
+            .replace_requests
+            .iter()
+            .filter(|(_, request)| request.old_vault == account_id)
+            .map(|(id, request)| (*id, request.clone()))
+            .collect())
+    }
+
+    async fn get_replace_period(&self) -> Result<u32, Error> {
+        Ok(self.state().replace_period)
+    }
+
+    async fn set_replace_period(&self, period: u32) -> Result<(), Error> {
+        self.state().replace_period = period;
+        Ok(())
+    }
+
+    async fn get_replace_request(&self, replace_id: H256) -> Result<PolkaBtcReplaceRequest, Error> {
+        // like the storage of the parachain, return the default for unknown requests
+        Ok(self
+            .state()
+            .replace_requests
+            .get(&replace_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+impl State {
+    /// Returns the open replace request with the given id.
+    fn get_replace_request(&self, replace_id: H256) -> Result<PolkaBtcReplaceRequest, Error> {
+        match self.replace_requests.get(&replace_id) {
+            Some(request) if request.completed => {
+                Err(dispatch_error("Replace", "ReplaceCompleted"))
+            }
+            Some(request) if request.cancelled => {
+                Err(dispatch_error("Replace", "ReplaceCancelled"))
+            }
+            Some(request) => Ok(request.clone()),
+            None => Err(dispatch_error("Replace", "ReplaceIdNotFound")),
+        }
+    }
+
+    fn is_vault_below_auction_threshold(&self, vault_id: &AccountId) -> Result<bool, Error> {
+        let vault = self.vault(vault_id)?;
+        let threshold = auction_collateral_threshold()
+            .saturating_mul_int(self.btc_to_dots(vault.issued_tokens));
+        Ok(self.collateral(vault_id) < threshold)
+    }
+}
+
+#[async_trait]
+impl TimestampPallet for MockParachain {
+    async fn get_time_now(&self) -> Result<u64, Error> {
+        Ok(self.state().time_now)
+    }
+}
+
+#[async_trait]
+impl ExchangeRateOraclePallet for MockParachain {
+    async fn get_exchange_rate_info(&self) -> Result<(FixedU128, u64, u64), Error> {
+        let state = self.state();
+        Ok((
+            state.exchange_rate,
+            state.exchange_rate_time,
+            MAX_EXCHANGE_RATE_DELAY_MS,
+        ))
+    }
+
+    async fn set_exchange_rate_info(&self, dot_per_btc: FixedU128) -> Result<(), Error> {
+        let mut state = self.state();
+        state.exchange_rate = dot_per_btc;
+        state.exchange_rate_time = state.time_now;
+        // a new exchange rate recovers the parachain from an offline oracle
+        if state.error_codes.remove(&ErrorCode::OracleOffline) && state.error_codes.is_empty() {
+            state.status = StatusCode::Running;
+        }
+        state.emit(SetExchangeRateEvent::<PolkaBtcRuntime> {
+            sender: self.account_id.clone(),
+            rate: dot_per_btc,
+        });
+        Ok(())
+    }
+
+    async fn set_btc_tx_fees_per_byte(&self, fast: u32, half: u32, hour: u32) -> Result<(), Error> {
+        let mut state = self.state();
+        state.btc_tx_fees = BtcTxFeesPerByte { fast, half, hour };
+        state.emit(SetBtcTxFeesPerByteEvent::<PolkaBtcRuntime> {
+            sender: self.account_id.clone(),
+            fast,
+            half,
+            hour,
+        });
+        Ok(())
+    }
+
+    async fn get_btc_tx_fees_per_byte(&self) -> Result<BtcTxFeesPerByte, Error> {
+        Ok(self.state().btc_tx_fees.clone())
+    }
+
+    async fn btc_to_dots(&self, amount: u128) -> Result<u128, Error> {
+        Ok(self.state().btc_to_dots(amount))
+    }
+
+    async fn dots_to_btc(&self, amount: u128) -> Result<u128, Error> {
+        self.state().dots_to_btc(amount)
+    }
+}
+
+#[async_trait]
+impl StakedRelayerPallet for MockParachain {
+    async fn get_stake(&self) -> Result<u64, Error> {
+        let stake = self
+            .state()
+            .relayers
+            .get(&self.account_id)
+            .copied()
+            .unwrap_or_default();
+        Ok(stake as u64)
+    }
+
+    async fn register_staked_relayer(&self, stake: u128) -> Result<(), Error> {
+        let mut state = self.state();
+        if state.relayers.contains_key(&self.account_id) {
+            return Err(dispatch_error("StakedRelayers", "AlreadyRegistered"));
+        }
+        if stake < MINIMUM_STAKE as u128 {
+            return Err(dispatch_error("StakedRelayers", "InsufficientStake"));
+        }
+        state.reserve_dot(&self.account_id, stake)?;
+        state.relayers.insert(self.account_id.clone(), stake);
+        let maturity = state.height + state.maturity_period;
+        state.emit(RegisterStakedRelayerEvent::<PolkaBtcRuntime> {
+            account_id: self.account_id.clone(),
+            maturity,
+            collateral: stake,
+        });
+        Ok(())
+    }
+
+    async fn deregister_staked_relayer(&self) -> Result<(), Error> {
+        let mut state = self.state();
+        let stake = state
+            .relayers
+            .remove(&self.account_id)
+            .ok_or_else(|| dispatch_error("StakedRelayers", "NotStakedRelayer"))?;
+        state.unreserve_dot(&self.account_id, stake);
+        state.emit(DeregisterStakedRelayerEvent::<PolkaBtcRuntime> {
+            account_id: self.account_id.clone(),
+        });
+        Ok(())
+    }
+
+    async fn suggest_status_update(
+        &self,
+        deposit: u128,
+        status_code: StatusCode,
+        add_error: Option<ErrorCode>,
+        remove_error: Option<ErrorCode>,
+        block_hash: Option<H256Le>,
+        message: String,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_relayer(&self.account_id)?;
+        state.reserve_dot(&self.account_id, deposit)?;
+
+        let status_update_id = state.next_status_update_id;
+        state.next_status_update_id += 1;
+        let status_update = PolkaBtcStatusUpdate {
+            new_status_code: status_code.clone(),
+            old_status_code: state.status.clone(),
+            add_error: add_error.clone(),
+            remove_error: remove_error.clone(),
+            btc_block_hash: block_hash,
+            proposer: self.account_id.clone(),
+            deposit,
+            message: message.into_bytes(),
+            ..Default::default()
+        };
+        state.status_updates.insert(status_update_id, status_update);
+        state.emit(StatusUpdateSuggestedEvent::<PolkaBtcRuntime> {
+            status_update_id,
+            account_id: self.account_id.clone(),
+            status_code,
+            add_error,
+            remove_error,
+            block_hash,
+        });
+        Ok(())
+    }
+
+    async fn vote_on_status_update(
+        &self,
+        status_update_id: u64,
+        approve: bool,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_relayer(&self.account_id)?;
+        let status_update = state
+            .status_updates
+            .remove(&status_update_id)
+            .ok_or_else(|| dispatch_error("StakedRelayers", "StatusUpdateNotFound"))?;
+
+        if !approve {
+            // the deposit of a rejected proposal is forfeited
+            let proposer = status_update.proposer.clone();
+            state.slash_dot(&proposer, &self.account_id, status_update.deposit);
+            return Ok(());
+        }
+
+        state.status = status_update.new_status_code.clone();
+        if let Some(ref error) = status_update.add_error {
+            state.error_codes.insert(error.clone());
+        }
+        if let Some(ref error) = status_update.remove_error {
+            state.error_codes.remove(error);
+        }
+        state.unreserve_dot(&status_update.proposer, status_update.deposit);
+        state.emit(ExecuteStatusUpdateEvent::<PolkaBtcRuntime> {
+            status_code: status_update.new_status_code,
+            add_error: status_update.add_error,
+            remove_error: status_update.remove_error,
+            block_hash: status_update.btc_block_hash,
+        });
+        Ok(())
+    }
+
+    async fn get_status_update(&self, id: u64) -> Result<PolkaBtcStatusUpdate, Error> {
+        Ok(self
+            .state()
+            .status_updates
+            .get(&id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn report_oracle_offline(&self) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_relayer(&self.account_id)?;
+        if state.time_now <= state.exchange_rate_time + MAX_EXCHANGE_RATE_DELAY_MS {
+            return Err(dispatch_error("StakedRelayers", "OracleOnline"));
+        }
+        if state.error_codes.contains(&ErrorCode::OracleOffline) {
+            return Err(dispatch_error("StakedRelayers", "OracleAlreadyReported"));
+        }
+        state.error_codes.insert(ErrorCode::OracleOffline);
+        state.status = StatusCode::Error;
+        Ok(())
+    }
+
+    async fn report_vault_theft(
+        &self,
+        vault_id: AccountId,
+        _tx_id: H256Le,
+        _merkle_proof: Vec<u8>,
+        _raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_relayer(&self.account_id)?;
+        let vault = state.vault_mut(&vault_id)?;
+        if vault.status == VaultStatus::CommittedTheft {
+            return Err(dispatch_error("StakedRelayers", "VaultAlreadyReported"));
+        }
+        vault.status = VaultStatus::CommittedTheft;
+        Ok(())
+    }
+
+    /// Transactions are not parsed, so they are only invalid if inserted with
+    /// `insert_invalid_transaction`.
+    async fn is_transaction_invalid(
+        &self,
+        vault_id: AccountId,
+        raw_tx: Vec<u8>,
+    ) -> Result<bool, Error> {
+        let state = self.state();
+        state.vault(&vault_id)?;
+        Ok(state.invalid_transactions.contains(&raw_tx))
+    }
+
+    async fn set_maturity_period(&self, period: u32) -> Result<(), Error> {
+        self.state().maturity_period = period;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SecurityPallet for MockParachain {
+    async fn get_parachain_status(&self) -> Result<StatusCode, Error> {
+        Ok(self.state().status.clone())
+    }
+
+    async fn get_error_codes(&self) -> Result<BTreeSet<ErrorCode>, Error> {
+        Ok(self.state().error_codes.clone())
+    }
+}
+
+#[async_trait]
+impl IssuePallet for MockParachain {
+    async fn request_issue(
+        &self,
+        amount: u128,
+        vault_id: AccountId,
+        griefing_collateral: u128,
+    ) -> Result<PolkaBtcRequestIssueEvent, Error> {
+        let mut state = self.state();
+        state.ensure_running()?;
+        if state.free_collateral(&vault_id)? < state.required_collateral(amount) {
+            return Err(dispatch_error("VaultRegistry", "ExceedingVaultLimit"));
+        }
+        state.reserve_dot(&self.account_id, griefing_collateral)?;
+
+        let issue_id = state.next_id();
+        let btc_address = BtcAddress::P2PKH(H160::from_low_u64_be(state.nonce));
+        let vault = state.vault_mut(&vault_id)?;
+        vault.to_be_issued_tokens += amount;
+        let public_key = vault.wallet.public_key.clone();
+
+        let request = PolkaBtcIssueRequest {
+            vault: vault_id.clone(),
+            opentime: state.height,
+            griefing_collateral,
+            amount,
+            requester: self.account_id.clone(),
+            btc_address,
+            ..Default::default()
+        };
+        state.issue_requests.insert(issue_id, request);
+        let event = RequestIssueEvent::<PolkaBtcRuntime> {
+            issue_id,
+            requester: self.account_id.clone(),
+            amount,
+            vault_id,
+            btc_address,
+            public_key,
+        };
+        state.emit(event.clone());
+        Ok(event)
+    }
+
+    async fn execute_issue(
+        &self,
+        issue_id: H256,
+        _tx_id: H256Le,
+        _merkle_proof: Vec<u8>,
+        _raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_running()?;
+        let request = state.get_issue_request(issue_id)?;
+        if state.has_expired(request.opentime, state.issue_period) {
+            return Err(dispatch_error("Issue", "CommitPeriodExpired"));
+        }
+
+        let vault = state.vault_mut(&request.vault)?;
+        vault.to_be_issued_tokens -= request.amount;
+        vault.issued_tokens += request.amount;
+        state
+            .polka_btc
+            .entry(request.requester.clone())
+            .or_default()
+            .free += request.amount;
+        state.unreserve_dot(&request.requester, request.griefing_collateral);
+        state.issue_requests.get_mut(&issue_id).unwrap().completed = true;
+        state.emit(ExecuteIssueEvent::<PolkaBtcRuntime> {
+            issue_id,
+            requester: request.requester,
+            vault_id: request.vault,
+        });
+        Ok(())
+    }
+
+    async fn cancel_issue(&self, issue_id: H256) -> Result<(), Error> {
+        let mut state = self.state();
+        let request = state.get_issue_request(issue_id)?;
+        if !state.has_expired(request.opentime, state.issue_period) {
+            return Err(dispatch_error("Issue", "TimeNotExpired"));
+        }
+
+        state.vault_mut(&request.vault)?.to_be_issued_tokens -= request.amount;
+        // the vault is compensated with the griefing collateral of the requester
+        state.slash_dot(
+            &request.requester,
+            &request.vault,
+            request.griefing_collateral,
+        );
+        state.issue_requests.get_mut(&issue_id).unwrap().cancelled = true;
+        state.emit(CancelIssueEvent::<PolkaBtcRuntime> {
+            issue_id,
+            requester: request.requester,
+        });
+        Ok(())
+    }
+
+    async fn get_issue_request(&self, issue_id: H256) -> Result<PolkaBtcIssueRequest, Error> {
+        Ok(self
+            .state()
+            .issue_requests
+            .get(&issue_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn get_vault_issue_requests(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<(H256, PolkaBtcIssueRequest)>, Error> {
+        Ok(self
+            .state()
+            .issue_requests
+            .iter()
+            .filter(|(_, request)| request.vault == account_id)
+            .map(|(id, request)| (*id, request.clone()))
+            .collect())
+    }
+
+    async fn get_issue_period(&self) -> Result<u32, Error> {
+        Ok(self.state().issue_period)
+    }
+
+    async fn set_issue_period(&self, period: u32) -> Result<(), Error> {
+        self.state().issue_period = period;
+        Ok(())
+    }
+}
+
+impl State {
+    /// Returns the open issue request with the given id.
+    fn get_issue_request(&self, issue_id: H256) -> Result<PolkaBtcIssueRequest, Error> {
+        match self.issue_requests.get(&issue_id) {
+            Some(request) if request.completed => Err(dispatch_error("Issue", "IssueCompleted")),
+            Some(request) if request.cancelled => Err(dispatch_error("Issue", "IssueCancelled")),
+            Some(request) => Ok(request.clone()),
+            None => Err(dispatch_error("Issue", "IssueIdNotFound")),
+        }
+    }
+
+    /// Returns the open redeem request with the given id.
+    fn get_redeem_request(&self, redeem_id: H256) -> Result<PolkaBtcRedeemRequest, Error> {
+        match self.redeem_requests.get(&redeem_id) {
+            Some(request) if request.completed => Err(dispatch_error("Redeem", "RedeemCompleted")),
+            Some(request) if request.cancelled => Err(dispatch_error("Redeem", "RedeemCancelled")),
+            Some(request) => Ok(request.clone()),
+            None => Err(dispatch_error("Redeem", "RedeemIdNotFound")),
+        }
+    }
+}
+
+#[async_trait]
+impl RedeemPallet for MockParachain {
+    async fn request_redeem(
+        &self,
+        amount_polka_btc: u128,
+        btc_address: BtcAddress,
+        vault_id: AccountId,
+    ) -> Result<H256, Error> {
+        let mut state = self.state();
+        state.ensure_running()?;
+        let vault = state.vault(&vault_id)?;
+        if vault.issued_tokens - vault.to_be_redeemed_tokens < amount_polka_btc {
+            return Err(dispatch_error(
+                "VaultRegistry",
+                "InsufficientTokensCommitted",
+            ));
+        }
+        let balance = state.polka_btc.entry(self.account_id.clone()).or_default();
+        if balance.free < amount_polka_btc {
+            return Err(dispatch_error("Redeem", "AmountExceedsUserBalance"));
+        }
+        balance.free -= amount_polka_btc;
+        balance.reserved += amount_polka_btc;
+        state.vault_mut(&vault_id)?.to_be_redeemed_tokens += amount_polka_btc;
+
+        let redeem_id = state.next_id();
+        let request = PolkaBtcRedeemRequest {
+            vault: vault_id.clone(),
+            opentime: state.height,
+            amount_polka_btc,
+            amount_btc: amount_polka_btc,
+            redeemer: self.account_id.clone(),
+            btc_address,
+            ..Default::default()
+        };
+        state.redeem_requests.insert(redeem_id, request);
+        state.emit(RequestRedeemEvent::<PolkaBtcRuntime> {
+            redeem_id,
+            redeemer: self.account_id.clone(),
+            amount_polka_btc,
+            vault_id,
+            btc_address,
+        });
+        Ok(redeem_id)
+    }
+
+    async fn execute_redeem(
+        &self,
+        redeem_id: H256,
+        _tx_id: H256Le,
+        _merkle_proof: Vec<u8>,
+        _raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_running()?;
+        let request = state.get_redeem_request(redeem_id)?;
+        if request.vault != self.account_id {
+            return Err(dispatch_error("Redeem", "UnauthorizedVault"));
+        }
+        if state.has_expired(request.opentime, state.redeem_period) {
+            return Err(dispatch_error("Redeem", "CommitPeriodExpired"));
+        }
+
+        // burn the locked tokens
+        state
+            .polka_btc
+            .entry(request.redeemer.clone())
+            .or_default()
+            .reserved -= request.amount_polka_btc;
+        let vault = state.vault_mut(&request.vault)?;
+        vault.issued_tokens -= request.amount_polka_btc;
+        vault.to_be_redeemed_tokens -= request.amount_polka_btc;
+        state.redeem_requests.get_mut(&redeem_id).unwrap().completed = true;
+        state.emit(ExecuteRedeemEvent::<PolkaBtcRuntime> {
+            redeem_id,
+            redeemer: request.redeemer,
+            vault_id: request.vault,
+        });
+        Ok(())
+    }
+
+    async fn register_address_and_execute_redeem(
+        &self,
+        btc_address: BtcAddress,
+        redeem_id: H256,
+        tx_id: H256Le,
+        merkle_proof: Vec<u8>,
+        raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.register_address_and(btc_address, || {
+            self.execute_redeem(redeem_id, tx_id, merkle_proof, raw_tx)
+        })
+        .await
+    }
+
+    async fn cancel_redeem(&self, redeem_id: H256, reimburse: bool) -> Result<(), Error> {
+        let mut state = self.state();
+        let request = state.get_redeem_request(redeem_id)?;
+        if request.redeemer != self.account_id {
+            return Err(dispatch_error("Redeem", "UnauthorizedUser"));
+        }
+        if !state.has_expired(request.opentime, state.redeem_period) {
+            return Err(dispatch_error("Redeem", "TimeNotExpired"));
+        }
+
+        let amount = request.amount_polka_btc;
+        let balance = state.polka_btc.entry(self.account_id.clone()).or_default();
+        balance.reserved -= amount;
+        if reimburse {
+            // the tokens are burned and the redeemer receives the vault's collateral instead
+            let collateral = state.btc_to_dots(amount);
+            state.slash_dot(&request.vault, &self.account_id, collateral);
+            state.vault_mut(&request.vault)?.issued_tokens -= amount;
+        } else {
+            state
+                .polka_btc
+                .entry(self.account_id.clone())
+                .or_default()
+                .free += amount;
+        }
+        state.vault_mut(&request.vault)?.to_be_redeemed_tokens -= amount;
+        state.redeem_requests.get_mut(&redeem_id).unwrap().cancelled = true;
+        state.emit(CancelRedeemEvent::<PolkaBtcRuntime> {
+            redeem_id,
+            redeemer: self.account_id.clone(),
+        });
+        Ok(())
+    }
+
+    async fn get_redeem_request(&self, redeem_id: H256) -> Result<PolkaBtcRedeemRequest, Error> {
+        Ok(self
+            .state()
+            .redeem_requests
+            .get(&redeem_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn get_vault_redeem_requests(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<(H256, PolkaBtcRedeemRequest)>, Error> {
+        Ok(self
+            .state()
+            .redeem_requests
+            .iter()
+            .filter(|(_, request)| request.vault == account_id)
+            .map(|(id, request)| (*id, request.clone()))
+            .collect())
+    }
+
+    async fn set_redeem_period(&self, period: u32) -> Result<(), Error> {
+        self.state().redeem_period = period;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RefundPallet for MockParachain {
+    async fn execute_refund(
+        &self,
+        refund_id: H256,
+        _tx_id: H256Le,
+        _merkle_proof: Vec<u8>,
+        _raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_running()?;
+        let request = match state.refund_requests.get_mut(&refund_id) {
+            Some(request) if request.completed => {
+                return Err(dispatch_error("Refund", "RefundCompleted"))
+            }
+            Some(request) => request,
+            None => return Err(dispatch_error("Refund", "RefundIdNotFound")),
+        };
+        if request.vault != self.account_id {
+            return Err(dispatch_error("Refund", "UnauthorizedVault"));
+        }
+        request.completed = true;
+        let event = ExecuteRefundEvent::<PolkaBtcRuntime> {
+            refund_id,
+            refundee: request.issuer.clone(),
+            vault_id: request.vault.clone(),
+            amount: request.amount_polka_btc,
+        };
+        state.emit(event);
+        Ok(())
+    }
+
+    async fn register_address_and_execute_refund(
+        &self,
+        btc_address: BtcAddress,
+        refund_id: H256,
+        tx_id: H256Le,
+        merkle_proof: Vec<u8>,
+        raw_tx: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.register_address_and(btc_address, || {
+            self.execute_refund(refund_id, tx_id, merkle_proof, raw_tx)
+        })
+        .await
+    }
+
+    async fn get_vault_refund_requests(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<(H256, PolkaBtcRefundRequest)>, Error> {
+        Ok(self
+            .state()
+            .refund_requests
+            .iter()
+            .filter(|(_, request)| request.vault == account_id)
+            .map(|(id, request)| (*id, request.clone()))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl BtcRelayPallet for MockParachain {
+    async fn get_best_block(&self) -> Result<H256Le, Error> {
+        Ok(self.state().relay.last().copied().unwrap_or_default())
+    }
+
+    async fn get_best_block_height(&self) -> Result<u32, Error> {
+        let state = self.state();
+        Ok(match state.relay.len() {
+            0 => 0,
+            len => state.relay_start + len as u32 - 1,
+        })
+    }
+
+    async fn get_block_hash(&self, height: u32) -> Result<H256Le, Error> {
+        let state = self.state();
+        Ok(height
+            .checked_sub(state.relay_start)
+            .and_then(|index| state.relay.get(index as usize))
+            .copied()
+            .unwrap_or_default())
+    }
+
+    /// Only the height of the returned header is set, unknown blocks have height zero.
+    async fn get_block_header(&self, hash: H256Le) -> Result<RichBlockHeader, Error> {
+        let state = self.state();
+        let block_height = state
+            .relay
+            .iter()
+            .position(|x| x == &hash)
+            .map(|index| state.relay_start + index as u32)
+            .unwrap_or_default();
+        Ok(RichBlockHeader {
+            block_height,
+            ..Default::default()
+        })
+    }
+
+    async fn initialize_btc_relay(
+        &self,
+        header: RawBlockHeader,
+        height: BitcoinBlockHeight,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        if !state.relay.is_empty() {
+            return Err(dispatch_error("BTCRelay", "AlreadyInitialized"));
+        }
+        let block_header_hash = header.hash();
+        state.relay.push(block_header_hash);
+        state.relay_start = height;
+        state.emit(InitializedEvent::<PolkaBtcRuntime> {
+            _runtime: PhantomData,
+            block_height: height,
+            block_header_hash,
+        });
+        Ok(())
+    }
+
+    /// Appends the header to the main chain, forks are not supported.
+    async fn store_block_header(&self, header: RawBlockHeader) -> Result<(), Error> {
+        let mut state = self.state();
+        if state.relay.is_empty() {
+            return Err(dispatch_error("BTCRelay", "NotInitialized"));
+        }
+        let block_header_hash = header.hash();
+        if state.relay.contains(&block_header_hash) {
+            return Err(dispatch_error("BTCRelay", "DuplicateBlock"));
+        }
+        state.relay.push(block_header_hash);
+        let block_height = state.relay_start + state.relay.len() as u32 - 1;
+        state.emit(StoreMainChainHeaderEvent::<PolkaBtcRuntime> {
+            _runtime: PhantomData,
+            block_height,
+            block_header_hash,
+        });
+        Ok(())
+    }
+
+    async fn store_block_headers(&self, headers: Vec<RawBlockHeader>) -> Result<(), Error> {
+        for header in headers {
+            self.store_block_header(header).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_bitcoin_confirmations(&self) -> Result<u32, Error> {
+        Ok(self.state().bitcoin_confirmations)
+    }
+
+    async fn wait_for_block_in_relay(
+        &self,
+        block_hash: H256Le,
+        num_confirmations: u32,
+    ) -> Result<(), Error> {
+        loop {
+            let block_height = self.get_block_header(block_hash).await?.block_height;
+            if block_height > 0
+                && block_height + num_confirmations <= self.get_best_block_height().await?
+            {
+                return Ok(());
+            }
+            delay_for(RELAY_POLLING_INTERVAL).await;
+        }
+    }
+}
+
+#[async_trait]
+impl VaultRegistryPallet for MockParachain {
+    async fn get_vault(&self, vault_id: AccountId) -> Result<PolkaBtcVault, Error> {
+        self.state()
+            .vaults
+            .get(&vault_id)
+            .cloned()
+            .ok_or(Error::VaultNotFound)
+    }
+
+    async fn get_vault_uncached(&self, vault_id: AccountId) -> Result<PolkaBtcVault, Error> {
+        self.get_vault(vault_id).await
+    }
+
+    async fn get_all_vaults(&self) -> Result<Vec<PolkaBtcVault>, Error> {
+        Ok(self.state().vaults.values().cloned().collect())
+    }
+
+    async fn register_vault(
+        &self,
+        collateral: u128,
+        public_key: BtcPublicKey,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.ensure_running()?;
+        if state.vaults.contains_key(&self.account_id) {
+            return Err(dispatch_error("VaultRegistry", "VaultAlreadyRegistered"));
+        }
+        state.reserve_dot(&self.account_id, collateral)?;
+
+        let mut vault = PolkaBtcVault {
+            id: self.account_id.clone(),
+            status: VaultStatus::Active,
+            ..Default::default()
+        };
+        vault.wallet.public_key = public_key;
+        state.vaults.insert(self.account_id.clone(), vault);
+        state.emit(RegisterVaultEvent::<PolkaBtcRuntime> {
+            account_id: self.account_id.clone(),
+            collateral,
+        });
+        Ok(())
+    }
+
+    async fn lock_additional_collateral(&self, amount: u128) -> Result<(), Error> {
+        let mut state = self.state();
+        state.vault(&self.account_id)?;
+        state.reserve_dot(&self.account_id, amount)?;
+        let total_collateral = state.collateral(&self.account_id);
+        let free_collateral = state.free_collateral(&self.account_id)?;
+        state.emit(LockAdditionalCollateralEvent::<PolkaBtcRuntime> {
+            vault_id: self.account_id.clone(),
+            new_collateral: amount,
+            total_collateral,
+            free_collateral,
+        });
+        Ok(())
+    }
+
+    async fn withdraw_collateral(&self, amount: u128) -> Result<(), Error> {
+        let mut state = self.state();
+        if state.free_collateral(&self.account_id)? < amount {
+            return Err(dispatch_error(
+                "VaultRegistry",
+                "InsufficientCollateralAvailable",
+            ));
+        }
+        state.unreserve_dot(&self.account_id, amount);
+        let total_collateral = state.collateral(&self.account_id);
+        state.emit(WithdrawCollateralEvent::<PolkaBtcRuntime> {
+            vault_id: self.account_id.clone(),
+            withdrawn_collateral: amount,
+            total_collateral,
+        });
+        Ok(())
+    }
+
+    async fn update_public_key(&self, public_key: BtcPublicKey) -> Result<(), Error> {
+        let mut state = self.state();
+        state.vault_mut(&self.account_id)?.wallet.public_key = public_key.clone();
+        state.emit(UpdatePublicKeyEvent::<PolkaBtcRuntime> {
+            vault_id: self.account_id.clone(),
+            public_key,
+        });
+        Ok(())
+    }
+
+    async fn register_address(&self, btc_address: BtcAddress) -> Result<(), Error> {
+        let mut state = self.state();
+        let vault = state.vault_mut(&self.account_id)?;
+        if !vault.wallet.addresses.insert(btc_address) {
+            return Err(dispatch_error("VaultRegistry", "ReservedDepositAddress"));
+        }
+        state.emit(RegisterAddressEvent::<PolkaBtcRuntime> {
+            vault_id: self.account_id.clone(),
+            btc_address,
+        });
+        Ok(())
+    }
+
+    async fn get_required_collateral_for_polkabtc(&self, amount_btc: u128) -> Result<u128, Error> {
+        Ok(self.state().required_collateral(amount_btc))
+    }
+
+    async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<u128, Error> {
+        let state = self.state();
+        let issued_tokens = state.vault(&vault_id)?.issued_tokens;
+        Ok(state.required_collateral(issued_tokens))
+    }
+
+    async fn is_vault_below_auction_threshold(&self, vault_id: AccountId) -> Result<bool, Error> {
+        self.state().is_vault_below_auction_threshold(&vault_id)
+    }
+}
+
+#[async_trait]
+impl FeePallet for MockParachain {
+    async fn get_issue_griefing_collateral(&self) -> Result<FixedU128, Error> {
+        Ok(FixedU128::saturating_from_rational(5, 100_000))
+    }
+
+    async fn get_issue_fee(&self) -> Result<FixedU128, Error> {
+        Ok(FixedU128::saturating_from_rational(5, 1000))
+    }
+
+    async fn get_replace_griefing_collateral(&self) -> Result<FixedU128, Error> {
+        Ok(FixedU128::saturating_from_rational(1, 10))
+    }
+}
+
+/// A provider connected to an in-process development parachain, signing with `key`.
+pub async fn test_client_with(key: AccountKeyring) -> PolkaBtcProvider {
+    let tmp = TempDir::new("btc-parachain-").expect("failed to create tempdir");
+    let config = SubxtClientConfig {
+        impl_name: "btc-parachain-full-client",
+        impl_version: "0.0.1",
+        author: "Interlay Ltd",
+        copyright_start_year: 2020,
+        db: DatabaseConfig::ParityDb {
+            path: tmp.path().join("db"),
+        },
+        keystore: KeystoreConfig::Path {
+            path: tmp.path().join("keystore"),
+            password: None,
+        },
+        chain_spec: btc_parachain::chain_spec::development_config(true).unwrap(),
+        role: Role::Authority(key.clone()),
+        telemetry: None,
+    };
+
+    let signer = PairSigner::<PolkaBtcRuntime, _>::new(key.pair());
+
+    PolkaBtcProvider::new(
+        SubxtClient::from_config(config, btc_parachain::service::new_full)
+            .expect("Error creating subxt client"),
+        signer,
+    )
+    .await
+    .expect("Error creating client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_keyring::AccountKeyring;
+
+    fn parachain_with_vault() -> (MockParachain, MockParachain) {
+        let vault = MockParachain::new(AccountKeyring::Bob.to_account_id());
+        vault.set_free_dot_balance(AccountKeyring::Bob.to_account_id(), 1000);
+        let user = vault.with_account(AccountKeyring::Alice.to_account_id());
+        user.set_free_dot_balance(AccountKeyring::Alice.to_account_id(), 1000);
+        (vault, user)
+    }
+
+    #[tokio::test]
+    async fn test_issue_and_redeem() {
+        let (vault, user) = parachain_with_vault();
+        vault.register_vault(300, Default::default()).await.unwrap();
+
+        let issue = user
+            .request_issue(100, vault.get_account_id().clone(), 10)
+            .await
+            .unwrap();
+        assert_eq!(user.get_free_dot_balance().await.unwrap(), 990);
+        user.execute_issue(issue.issue_id, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
+        assert_eq!(user.get_free_polka_btc_balance().await.unwrap(), 100);
+        assert_eq!(user.get_free_dot_balance().await.unwrap(), 1000);
+
+        let redeem_id = user
+            .request_redeem(40, Default::default(), vault.get_account_id().clone())
+            .await
+            .unwrap();
+        assert_eq!(user.get_locked_polka_btc_balance().await.unwrap(), 40);
+        vault
+            .execute_redeem(redeem_id, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
+
+        assert_eq!(user.get_polka_btc_total_supply().await.unwrap(), 60);
+        let vault_id = vault.get_account_id().clone();
+        assert_eq!(vault.get_vault(vault_id).await.unwrap().issued_tokens, 60);
+        assert_eq!(
+            vault.events::<ExecuteIssueEvent<PolkaBtcRuntime>>().len(),
+            1
+        );
+        assert_eq!(
+            vault.events::<ExecuteRedeemEvent<PolkaBtcRuntime>>(),
+            vec![ExecuteRedeemEvent {
+                redeem_id,
+                redeemer: AccountKeyring::Alice.to_account_id(),
+                vault_id: AccountKeyring::Bob.to_account_id(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_address_and_execute_redeem() {
+        let (vault, user) = parachain_with_vault();
+        vault.register_vault(300, Default::default()).await.unwrap();
+        let vault_id = vault.get_account_id().clone();
+        let issue = user.request_issue(100, vault_id.clone(), 10).await.unwrap();
+        user.execute_issue(issue.issue_id, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
+        let redeem_id = user
+            .request_redeem(40, Default::default(), vault_id.clone())
+            .await
+            .unwrap();
+        let address = BtcAddress::P2PKH(H160::repeat_byte(1));
+
+        // the registration is reverted if the execution fails
+        let result = vault
+            .register_address_and_execute_redeem(
+                address,
+                H256::repeat_byte(9),
+                Default::default(),
+                vec![],
+                vec![],
+            )
+            .await;
+        assert!(result.is_err());
+
+        vault
+            .register_address_and_execute_redeem(
+                address,
+                redeem_id,
+                Default::default(),
+                vec![],
+                vec![],
+            )
+            .await
+            .unwrap();
+        let vault_state = vault.get_vault(vault_id).await.unwrap();
+        assert!(vault_state.wallet.has_btc_address(&address));
+        assert_eq!(vault_state.issued_tokens, 60);
+        assert_eq!(
+            vault
+                .events::<RegisterAddressEvent<PolkaBtcRuntime>>()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_issue_exceeding_collateral_fails() {
+        let (vault, user) = parachain_with_vault();
+        vault.register_vault(100, Default::default()).await.unwrap();
+
+        // 100 collateral only covers 66 tokens at the secure threshold of 150%
+        let result = user
+            .request_issue(100, vault.get_account_id().clone(), 10)
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::XtError(XtError::Runtime(RuntimeError::Module(ModuleError { error, .. }))))
+                if error == "ExceedingVaultLimit"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_issue_slashes_griefing_collateral() {
+        let (vault, user) = parachain_with_vault();
+        vault.register_vault(300, Default::default()).await.unwrap();
+        let issue = user
+            .request_issue(100, vault.get_account_id().clone(), 10)
+            .await
+            .unwrap();
+
+        assert!(user.cancel_issue(issue.issue_id).await.is_err());
+        user.advance_chain(DEFAULT_REQUEST_PERIOD + 1);
+        user.cancel_issue(issue.issue_id).await.unwrap();
+
+        assert_eq!(user.get_free_dot_balance().await.unwrap(), 990);
+        assert_eq!(vault.get_free_dot_balance().await.unwrap(), 710);
+        assert!(
+            user.get_issue_request(issue.issue_id)
+                .await
+                .unwrap()
+                .cancelled
+        );
+    }
+}
@@ -1,18 +1,21 @@
+use super::pallets::vault_registry::{
+    LockAdditionalCollateralCall, RegisterAddressCall, RegisterVaultCall, VaultsStore,
+    WithdrawCollateralCall,
+};
+use super::testing::test_client_with;
 use super::{
-    BtcAddress, BtcPublicKey, BtcRelayPallet, DotBalancesPallet, PolkaBtcProvider, PolkaBtcRuntime,
-    SecurityPallet, StatusCode, VaultRegistryPallet,
+    BtcAddress, BtcPublicKey, BtcRelayPallet, DotBalancesPallet, Error, FixedI128,
+    FixedPointNumber, PastEventsOptions, SecurityPallet, SlaPallet, StatusCode, TreasuryPallet,
+    VaultRegistryPallet,
 };
+use futures::StreamExt;
 use module_bitcoin::{
     formatter::Formattable,
     types::{BlockBuilder, RawBlockHeader},
 };
 use sp_core::{H160, U256};
 use sp_keyring::AccountKeyring;
-use substrate_subxt::PairSigner;
-use substrate_subxt_client::{
-    DatabaseConfig, KeystoreConfig, Role, SubxtClient, SubxtClientConfig,
-};
-use tempdir::TempDir;
+use std::time::Duration;
 
 fn dummy_public_key() -> BtcPublicKey {
     BtcPublicKey([
@@ -21,42 +24,35 @@ fn dummy_public_key() -> BtcPublicKey {
     ])
 }
 
-async fn test_client_with(key: AccountKeyring) -> PolkaBtcProvider {
-    let tmp = TempDir::new("btc-parachain-").expect("failed to create tempdir");
-    let config = SubxtClientConfig {
-        impl_name: "btc-parachain-full-client",
-        impl_version: "0.0.1",
-        author: "Interlay Ltd",
-        copyright_start_year: 2020,
-        db: DatabaseConfig::ParityDb {
-            path: tmp.path().join("db")
-        },
-        keystore: KeystoreConfig::Path {
-            path: tmp.path().join("keystore"),
-            password: None,
-        },
-        chain_spec: btc_parachain::chain_spec::development_config(true).unwrap(),
-        role: Role::Authority(key.clone()),
-        telemetry: None,
-    };
+#[tokio::test]
+async fn test_get_free_dot_balance() {
+    let provider = test_client_with(AccountKeyring::Alice).await;
 
-    let signer = PairSigner::<PolkaBtcRuntime, _>::new(key.pair());
+    let balance = provider.get_free_dot_balance().await.unwrap();
+    assert_eq!(balance, 1 << 60);
+}
 
-    PolkaBtcProvider::new(
-        SubxtClient::from_config(config, btc_parachain::service::new_full)
-            .expect("Error creating subxt client"),
-        signer,
-    )
-    .await
-    .expect("Error creating client")
+#[tokio::test]
+async fn test_polka_btc_balance() {
+    let provider = test_client_with(AccountKeyring::Alice).await;
+
+    assert_eq!(provider.get_free_polka_btc_balance().await.unwrap(), 0);
+    assert_eq!(provider.get_locked_polka_btc_balance().await.unwrap(), 0);
+    assert_eq!(provider.get_polka_btc_total_supply().await.unwrap(), 0);
 }
 
 #[tokio::test]
-async fn test_get_free_dot_balance() {
+async fn test_vault_sla() {
     let provider = test_client_with(AccountKeyring::Alice).await;
 
-    let balance = provider.get_free_dot_balance().await.unwrap();
-    assert_eq!(balance, 1 << 60);
+    let sla = provider
+        .get_vault_sla(AccountKeyring::Alice.to_account_id())
+        .await
+        .unwrap();
+    assert_eq!(sla, FixedI128::zero());
+
+    let parameters = provider.get_sla_parameters().await.unwrap();
+    assert!(parameters.vault_target_sla > FixedI128::zero());
 }
 
 #[tokio::test]
@@ -81,6 +77,215 @@ async fn test_register_vault() {
     assert_eq!(vault.wallet.public_key, dummy_public_key());
 }
 
+#[tokio::test]
+async fn test_storage_changes() {
+    let provider = test_client_with(AccountKeyring::Alice).await;
+    let changes = provider
+        .storage_changes(VaultsStore {
+            _runtime: Default::default(),
+            account_id: AccountKeyring::Alice.to_account_id(),
+        })
+        .await
+        .unwrap();
+    futures::pin_mut!(changes);
+
+    // the vault is not registered yet, so the first value is the default
+    let (_, vault) = changes.next().await.unwrap().unwrap();
+    assert_eq!(vault.wallet.public_key, Default::default());
+
+    provider
+        .register_vault(100, dummy_public_key())
+        .await
+        .unwrap();
+    let (_, vault) = changes.next().await.unwrap().unwrap();
+    assert_eq!(vault.wallet.public_key, dummy_public_key());
+}
+
+#[tokio::test]
+async fn test_get_vault_at_block() {
+    let provider = test_client_with(AccountKeyring::Alice).await;
+    let vault_id = AccountKeyring::Alice.to_account_id();
+    let changes = provider
+        .storage_changes(VaultsStore {
+            _runtime: Default::default(),
+            account_id: vault_id.clone(),
+        })
+        .await
+        .unwrap();
+    futures::pin_mut!(changes);
+    let (before, _) = changes.next().await.unwrap().unwrap();
+
+    provider
+        .register_vault(100, dummy_public_key())
+        .await
+        .unwrap();
+    let (registered, _) = changes.next().await.unwrap().unwrap();
+
+    // queries at a block before the registration do not see the vault
+    assert!(matches!(
+        provider.at(before).get_vault(vault_id.clone()).await,
+        Err(Error::VaultNotFound)
+    ));
+    let vault = provider
+        .at(registered)
+        .get_vault(vault_id.clone())
+        .await
+        .unwrap();
+    assert_eq!(vault.wallet.public_key, dummy_public_key());
+    let vault = provider.get_vault(vault_id).await.unwrap();
+    assert_eq!(vault.wallet.public_key, dummy_public_key());
+}
+
+#[tokio::test]
+async fn test_cached_get_vault() {
+    let provider = test_client_with(AccountKeyring::Alice).await;
+    let vault_id = AccountKeyring::Alice.to_account_id();
+    provider
+        .register_vault(100, dummy_public_key())
+        .await
+        .unwrap();
+
+    let cached = provider.with_cache();
+    let cache_maintainer = cached.clone();
+    tokio::spawn(async move { cache_maintainer.maintain_cache().await });
+
+    // reads only go through the cache once a block has been finalized
+    let has_cached_block = || cached.cache_stats().unwrap().misses > 0;
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while !has_cached_block() {
+            cached.get_vault(vault_id.clone()).await.unwrap();
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let vault = cached.get_vault(vault_id.clone()).await.unwrap();
+    assert_eq!(vault.wallet.public_key, dummy_public_key());
+    assert!(cached.cache_stats().unwrap().hits > 0);
+
+    let mut public_key = dummy_public_key();
+    public_key.0[0] = 3;
+    provider
+        .update_public_key(public_key.clone())
+        .await
+        .unwrap();
+
+    // uncached reads see the change right away
+    let vault = cached.get_vault_uncached(vault_id.clone()).await.unwrap();
+    assert_eq!(vault.wallet.public_key, public_key);
+
+    // cached reads see it once the block that changed it is finalized
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while cached
+            .get_vault(vault_id.clone())
+            .await
+            .unwrap()
+            .wallet
+            .public_key
+            != public_key
+        {
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_batch() {
+    let provider = test_client_with(AccountKeyring::Alice).await;
+    let reserved_before = provider.get_reserved_dot_balance().await.unwrap();
+    let address = BtcAddress::P2PKH(H160::repeat_byte(1));
+    provider
+        .batch()
+        .add(RegisterVaultCall {
+            collateral: 100,
+            public_key: dummy_public_key(),
+        })
+        .add(LockAdditionalCollateralCall { amount: 50 })
+        .add(RegisterAddressCall {
+            btc_address: address,
+        })
+        .submit()
+        .await
+        .unwrap();
+
+    // every call of the batch took effect
+    let vault = provider
+        .get_vault(AccountKeyring::Alice.to_account_id())
+        .await
+        .unwrap();
+    assert_eq!(vault.wallet.public_key, dummy_public_key());
+    assert!(vault.wallet.has_btc_address(&address));
+    assert_eq!(
+        provider.get_reserved_dot_balance().await.unwrap(),
+        reserved_before + 150
+    );
+}
+
+#[tokio::test]
+async fn test_batch_reports_failed_call() {
+    let provider = test_client_with(AccountKeyring::Alice).await;
+    let result = provider
+        .batch()
+        .add(RegisterVaultCall {
+            collateral: 100,
+            public_key: dummy_public_key(),
+        })
+        .add(WithdrawCollateralCall { amount: 1 << 62 })
+        .submit()
+        .await;
+    assert!(matches!(
+        result,
+        Err(Error::BatchCallFailed { index: 1, .. })
+    ));
+    // the whole batch is reverted
+    assert!(matches!(
+        provider
+            .get_vault(AccountKeyring::Alice.to_account_id())
+            .await,
+        Err(Error::VaultNotFound)
+    ));
+}
+
+#[tokio::test]
+async fn test_past_events() {
+    let provider = test_client_with(AccountKeyring::Alice).await;
+    provider
+        .register_vault(100, dummy_public_key())
+        .await
+        .unwrap();
+
+    let options = PastEventsOptions {
+        concurrency: 2,
+        pallets: Some(vec!["vault_registry".to_string()]),
+        ..Default::default()
+    };
+    let blocks = provider
+        .past_events(options)
+        .await
+        .unwrap()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(blocks.first().unwrap().height, 1);
+    assert!(blocks.windows(2).all(|x| x[0].height + 1 == x[1].height));
+    assert_eq!(blocks.last().unwrap().remaining, 0);
+    assert!(blocks.iter().any(|block| !block.events.is_empty()));
+
+    let options = PastEventsOptions {
+        pallets: Some(vec!["unknown".to_string()]),
+        ..Default::default()
+    };
+    assert!(matches!(
+        provider.past_events(options).await,
+        Err(Error::UnknownPallet(_))
+    ));
+}
+
 #[tokio::test]
 async fn test_btc_relay() {
     let provider = test_client_with(AccountKeyring::Alice).await;
@@ -5,6 +5,7 @@ use bitcoin::Error as BitcoinError;
 use jsonrpc_http_server::jsonrpc_core::Error as JsonRpcError;
 use parity_scale_codec::Error as CodecError;
 use relayer_core::Error as CoreError;
+use runtime::json::{ApiErrorData, ApiErrorKind};
 use runtime::substrate_subxt::Error as XtError;
 use runtime::Error as RuntimeError;
 use std::net::AddrParseError;
@@ -40,6 +41,22 @@ pub enum Error {
     JsonRpcError(#[from] JsonRpcError),
 }
 
+impl From<&Error> for ApiErrorData {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::RuntimeError(err) => err.into(),
+            Error::SubXtError(err) => err.into(),
+            Error::JsonRpcError(_) | Error::CodecError(_) => {
+                ApiErrorData::new(ApiErrorKind::InvalidParams)
+            }
+            Error::BitcoinError(_) | Error::BitcoinCoreError(_) => {
+                ApiErrorData::new(ApiErrorKind::Bitcoin)
+            }
+            _ => ApiErrorData::new(ApiErrorKind::Internal),
+        }
+    }
+}
+
 /// Gets the default retrying policy
 pub fn get_retry_policy() -> ExponentialBackoff {
     ExponentialBackoff {
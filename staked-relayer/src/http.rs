@@ -3,13 +3,20 @@ use futures::executor::block_on;
 use hex::FromHex;
 use jsonrpc_http_server::jsonrpc_core::serde_json::Value;
 use jsonrpc_http_server::jsonrpc_core::Error as JsonRpcError;
-use jsonrpc_http_server::jsonrpc_core::{IoHandler, Params};
-use jsonrpc_http_server::{DomainsValidation, ServerBuilder};
+use jsonrpc_http_server::jsonrpc_core::{MetaIoHandler, Params};
+use jsonrpc_http_server::{hyper, DomainsValidation, ServerBuilder};
+use log::warn;
 use parity_scale_codec::{Decode, Encode};
 use runtime::ErrorCode as PolkaBtcErrorCode;
 use runtime::StatusCode as PolkaBtcStatusCode;
-use runtime::{H256Le, PolkaBtcProvider, SecurityPallet, StakedRelayerPallet, UtilFuncs};
-use serde::{Deserialize, Deserializer};
+use runtime::{
+    auth::{ApiAuth, AuditLog, AuthMiddleware, Meta, Role},
+    json::{self, MethodSchema},
+    H256Le, PolkaBtcProvider, SecurityPallet, SlaHistory, SlaJsonRpcResponse, SlaSubject,
+    StakedRelayerPallet, UtilFuncs,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
 use sp_core::crypto::Ss58Codec;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -23,7 +30,7 @@ fn parse_params<T: Decode>(params: Params) -> Result<T, Error> {
 fn handle_resp<T: Encode>(resp: Result<T, Error>) -> Result<Value, JsonRpcError> {
     match resp {
         Ok(data) => Ok(format!("0x{}", hex::encode(data.encode())).into()),
-        Err(_) => Err(JsonRpcError::internal_error()),
+        Err(err) => Err(json::json_rpc_error(err)),
     }
 }
 
@@ -40,12 +47,32 @@ where
     })
 }
 
+/// Parse an optional bitcoin block hash in the big-endian hex notation of block explorers.
+fn deserialize_block_hash<'de, D>(deserializer: D) -> Result<Option<H256Le>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    match Option::<String>::deserialize(deserializer)? {
+        Some(hash) => {
+            let mut bytes = Vec::from_hex(hash.trim_start_matches("0x"))
+                .map_err(|err| Error::custom(err.to_string()))?;
+            if bytes.len() != 32 {
+                return Err(Error::custom("block hash must be 32 bytes"));
+            }
+            bytes.reverse();
+            Ok(Some(H256Le::from_bytes_le(&bytes)))
+        }
+        None => Ok(None),
+    }
+}
+
 fn _system_health(api: &Arc<PolkaBtcProvider>) -> Result<(), Error> {
     block_on(api.get_parachain_status())?;
     Ok(())
 }
 
-#[derive(Encode, Decode, Debug)]
+#[derive(Encode, Decode, Serialize, JsonSchema, Debug)]
 struct AccountIdJsonRpcResponse {
     account_id: String,
 }
@@ -56,13 +83,17 @@ fn _account_id(api: &Arc<PolkaBtcProvider>) -> Result<AccountIdJsonRpcResponse,
     })
 }
 
-#[derive(Encode, Decode, Debug)]
+#[derive(Encode, Decode, Deserialize, JsonSchema, Debug)]
 struct RegisterStakedRelayerJsonRpcRequest {
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
     stake: u128,
 }
 
-fn _register_staked_relayer(api: &Arc<PolkaBtcProvider>, params: Params) -> Result<(), Error> {
-    let req: RegisterStakedRelayerJsonRpcRequest = parse_params(params)?;
+fn _register_staked_relayer(
+    api: &Arc<PolkaBtcProvider>,
+    req: RegisterStakedRelayerJsonRpcRequest,
+) -> Result<(), Error> {
     Ok(block_on(api.register_staked_relayer(req.stake))?)
 }
 
@@ -70,18 +101,29 @@ fn _deregister_staked_relayer(api: &Arc<PolkaBtcProvider>) -> Result<(), Error>
     Ok(block_on(api.deregister_staked_relayer())?)
 }
 
-#[derive(Encode, Decode, Debug)]
+#[derive(Encode, Decode, Deserialize, JsonSchema, Debug)]
 struct SuggestStatusUpdateJsonRpcRequest {
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
     deposit: u128,
+    #[schemars(with = "String")]
     status_code: PolkaBtcStatusCode,
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
     add_error: Option<PolkaBtcErrorCode>,
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
     remove_error: Option<PolkaBtcErrorCode>,
+    #[serde(default, deserialize_with = "deserialize_block_hash")]
+    #[schemars(with = "Option<String>")]
     block_hash: Option<H256Le>,
     message: String,
 }
 
-fn _suggest_status_update(api: &Arc<PolkaBtcProvider>, params: Params) -> Result<(), Error> {
-    let req: SuggestStatusUpdateJsonRpcRequest = parse_params(params)?;
+fn _suggest_status_update(
+    api: &Arc<PolkaBtcProvider>,
+    req: SuggestStatusUpdateJsonRpcRequest,
+) -> Result<(), Error> {
     Ok(block_on(api.suggest_status_update(
         req.deposit,
         req.status_code,
@@ -92,58 +134,189 @@ fn _suggest_status_update(api: &Arc<PolkaBtcProvider>, params: Params) -> Result
     ))?)
 }
 
-#[derive(Encode, Decode, Debug)]
+#[derive(Encode, Decode, Deserialize, JsonSchema, Debug)]
 struct VoteOnStatusUpdateJsonRpcRequest {
     pub status_update_id: u64,
     pub approve: bool,
 }
 
-fn _vote_on_status_update(api: &Arc<PolkaBtcProvider>, params: Params) -> Result<(), Error> {
-    let req: VoteOnStatusUpdateJsonRpcRequest = parse_params(params)?;
+fn _vote_on_status_update(
+    api: &Arc<PolkaBtcProvider>,
+    req: VoteOnStatusUpdateJsonRpcRequest,
+) -> Result<(), Error> {
     Ok(block_on(
         api.vote_on_status_update(req.status_update_id, req.approve),
     )?)
 }
 
-pub async fn start(api: Arc<PolkaBtcProvider>, addr: SocketAddr, origin: String) {
-    let mut io = IoHandler::default();
+fn _sla(api: &Arc<PolkaBtcProvider>, history: &SlaHistory) -> Result<SlaJsonRpcResponse, Error> {
+    Ok(block_on(history.report(&**api, SlaSubject::Relayer))?)
+}
+
+fn _json_schema() -> Result<Vec<MethodSchema>, Error> {
+    Ok(vec![
+        MethodSchema::new::<(), ()>(
+            "json_system_health",
+            "Fails if the parachain is unreachable.",
+        ),
+        MethodSchema::new::<(), AccountIdJsonRpcResponse>(
+            "json_account_id",
+            "SS58 address of the staked relayer account.",
+        ),
+        MethodSchema::new::<RegisterStakedRelayerJsonRpcRequest, ()>(
+            "json_register_staked_relayer",
+            "Register the staked relayer with the given stake in planck.",
+        ),
+        MethodSchema::new::<(), ()>(
+            "json_deregister_staked_relayer",
+            "Deregister the staked relayer.",
+        ),
+        MethodSchema::new::<SuggestStatusUpdateJsonRpcRequest, ()>(
+            "json_suggest_status_update",
+            "Suggest a status update of the parachain, the block hash is big-endian hex.",
+        ),
+        MethodSchema::new::<VoteOnStatusUpdateJsonRpcRequest, ()>(
+            "json_vote_on_status_update",
+            "Vote on a suggested status update.",
+        ),
+        SlaJsonRpcResponse::method_schema(),
+    ])
+}
+
+/// Role required to call the method, None if it is public. Methods that are not listed
+/// change the state of the relayer and require the admin role.
+fn required_role(method: &str) -> Option<Role> {
+    match method.trim_start_matches("json_") {
+        // used as health check, which can not be authenticated
+        "system_health" => None,
+        "account_id" | "sla" | "schema" => Some(Role::Read),
+        _ => Some(Role::Admin),
+    }
+}
+
+pub async fn start(
+    api: Arc<PolkaBtcProvider>,
+    sla_history: Arc<SlaHistory>,
+    addr: SocketAddr,
+    origins: Option<String>,
+    auth: ApiAuth,
+    audit: AuditLog,
+) {
+    if !auth.is_enabled() && !addr.ip().is_loopback() {
+        warn!(
+            "The api listens on {} without authentication, anyone who can reach it can suggest status updates",
+            addr
+        );
+    }
+    let mut io = MetaIoHandler::with_middleware(AuthMiddleware::new(required_role, audit));
+    {
+        let api = api.clone();
+        io.add_sync_method("system_health", move |_| handle_resp(_system_health(&api)));
+    }
+    {
+        let api = api.clone();
+        io.add_sync_method("json_system_health", move |_| {
+            json::handle_json_resp(_system_health(&api))
+        });
+    }
+    {
+        let api = api.clone();
+        io.add_sync_method("account_id", move |_| handle_resp(_account_id(&api)));
+    }
     {
         let api = api.clone();
-        io.add_method("system_health", move |_| handle_resp(_system_health(&api)));
+        io.add_sync_method("json_account_id", move |_| {
+            json::handle_json_resp(_account_id(&api))
+        });
     }
     {
         let api = api.clone();
-        io.add_method("account_id", move |_| handle_resp(_account_id(&api)));
+        io.add_sync_method("register_staked_relayer", move |params| {
+            handle_resp(parse_params(params).and_then(|req| _register_staked_relayer(&api, req)))
+        });
     }
     {
         let api = api.clone();
-        io.add_method("register_staked_relayer", move |params| {
-            handle_resp(_register_staked_relayer(&api, params))
+        io.add_sync_method("json_register_staked_relayer", move |params| {
+            json::handle_json_resp(
+                json::parse_json_params(params).and_then(|req| _register_staked_relayer(&api, req)),
+            )
         });
     }
     {
         let api = api.clone();
-        io.add_method("deregister_staked_relayer", move |_| {
+        io.add_sync_method("deregister_staked_relayer", move |_| {
             handle_resp(_deregister_staked_relayer(&api))
         });
     }
     {
         let api = api.clone();
-        io.add_method("suggest_status_update", move |params| {
-            handle_resp(_suggest_status_update(&api, params))
+        io.add_sync_method("json_deregister_staked_relayer", move |_| {
+            json::handle_json_resp(_deregister_staked_relayer(&api))
         });
     }
     {
         let api = api.clone();
-        io.add_method("vote_on_status_update", move |params| {
-            handle_resp(_vote_on_status_update(&api, params))
+        io.add_sync_method("suggest_status_update", move |params| {
+            handle_resp(parse_params(params).and_then(|req| _suggest_status_update(&api, req)))
         });
     }
+    {
+        let api = api.clone();
+        io.add_sync_method("json_suggest_status_update", move |params| {
+            json::handle_json_resp(
+                json::parse_json_params(params).and_then(|req| _suggest_status_update(&api, req)),
+            )
+        });
+    }
+    {
+        let api = api.clone();
+        io.add_sync_method("vote_on_status_update", move |params| {
+            handle_resp(parse_params(params).and_then(|req| _vote_on_status_update(&api, req)))
+        });
+    }
+    {
+        let api = api.clone();
+        io.add_sync_method("json_vote_on_status_update", move |params| {
+            json::handle_json_resp(
+                json::parse_json_params(params).and_then(|req| _vote_on_status_update(&api, req)),
+            )
+        });
+    }
+    {
+        let api = api.clone();
+        let sla_history = sla_history.clone();
+        io.add_sync_method("sla", move |_| handle_resp(_sla(&api, &sla_history)));
+    }
+    {
+        let api = api.clone();
+        io.add_sync_method("json_sla", move |_| {
+            json::handle_json_resp(_sla(&api, &sla_history))
+        });
+    }
+    io.add_sync_method("json_schema", move |_| {
+        json::handle_json_resp(_json_schema())
+    });
 
-    let server = ServerBuilder::new(io)
+    let server =
+        ServerBuilder::with_meta_extractor(io, move |req: &hyper::Request<hyper::Body>| {
+            Meta(
+                auth.authenticate(
+                    req.headers()
+                        .get(hyper::header::AUTHORIZATION)
+                        .and_then(|value| value.to_str().ok()),
+                ),
+            )
+        })
         .health_api(("/health", "system_health"))
-        .rest_api(jsonrpc_http_server::RestApi::Unsecure)
-        .cors(DomainsValidation::AllowOnly(vec![origin.into()]))
+        .rest_api(jsonrpc_http_server::RestApi::Secure)
+        .cors(DomainsValidation::AllowOnly(
+            origins
+                .iter()
+                .flat_map(|origins| origins.split(','))
+                .map(|origin| origin.trim().into())
+                .collect(),
+        ))
         .start_http(&addr)
         .expect("Unable to start RPC server");
 
@@ -153,3 +326,23 @@ pub async fn start(api: Arc<PolkaBtcProvider>, addr: SocketAddr, origin: String)
     .await
     .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(required_role("system_health"), None);
+        assert_eq!(required_role("json_system_health"), None);
+        assert_eq!(required_role("account_id"), Some(Role::Read));
+        assert_eq!(required_role("json_sla"), Some(Role::Read));
+        assert_eq!(required_role("json_schema"), Some(Role::Read));
+        assert_eq!(required_role("suggest_status_update"), Some(Role::Admin));
+        assert_eq!(
+            required_role("json_register_staked_relayer"),
+            Some(Role::Admin)
+        );
+        assert_eq!(required_role("unknown"), Some(Role::Admin));
+    }
+}
@@ -9,13 +9,12 @@ mod vault;
 use bitcoin::{BitcoinCore, BitcoinCoreApi};
 use clap::Clap;
 use error::Error;
+use log::*;
 use oracle::OracleMonitor;
 use relay::{BitcoinClient, PolkaBtcClient};
-use log::*;
 use relayer_core::{Config, Runner};
 use runtime::pallets::sla::UpdateRelayerSLAEvent;
-use runtime::substrate_subxt::PairSigner;
-use runtime::{PolkaBtcProvider, PolkaBtcRuntime, UtilFuncs};
+use runtime::{PolkaBtcProvider, PolkaBtcRuntime, SlaHistory, UtilFuncs};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -27,12 +26,13 @@ use vault::*;
 #[derive(Clap)]
 #[clap(version = "0.1", author = "Interlay <contact@interlay.io>")]
 struct Opts {
-    /// Parachain URL, can be over WebSockets or HTTP.
-    #[clap(long, default_value = "ws://127.0.0.1:9944")]
-    polka_btc_url: String,
+    /// Comma separated list of parachain URLs, can be over WebSockets or HTTP.
+    /// Switches to another URL if the current one becomes unhealthy.
+    #[clap(long, default_value = "ws://127.0.0.1:9944", use_delimiter = true)]
+    polka_btc_url: Vec<String>,
 
     /// Address to listen on for JSON-RPC requests.
-    #[clap(long, default_value = "[::0]:3030")]
+    #[clap(long, default_value = "127.0.0.1:3030")]
     http_addr: String,
 
     /// Starting height for vault theft checks, if not defined
@@ -61,9 +61,14 @@ struct Opts {
     #[clap(long, default_value = "100")]
     status_update_deposit: u128,
 
-    /// Comma separated list of allowed origins.
-    #[clap(long, default_value = "*")]
-    rpc_cors_domain: String,
+    /// Comma separated list of origins that may call the api from a browser. If not set,
+    /// cross-origin requests are refused.
+    #[clap(long)]
+    rpc_cors_domain: Option<String>,
+
+    /// Authentication of the JSON-RPC api.
+    #[clap(flatten)]
+    api_auth: runtime::cli::ApiAuthOpts,
 
     /// keyring / keyfile options.
     #[clap(flatten)]
@@ -81,9 +86,8 @@ async fn main() -> Result<(), Error> {
     let http_addr = opts.http_addr.parse()?;
     let oracle_timeout_ms = opts.oracle_timeout_ms;
 
-    let (key_pair, _) = opts.account_info.get_key_pair()?;
-    let signer = PairSigner::<PolkaBtcRuntime, _>::new(key_pair);
-    let provider = Arc::new(PolkaBtcProvider::from_url(opts.polka_btc_url, signer).await?);
+    let (signer, _) = opts.account_info.get_signer().await?;
+    let provider = Arc::new(PolkaBtcProvider::from_urls(opts.polka_btc_url, signer).await?);
 
     let dummy_network = bitcoin::Network::Regtest; // we don't make any transaction so this is not used
     let btc_rpc = Arc::new(BitcoinCore::new(
@@ -139,22 +143,47 @@ async fn main() -> Result<(), Error> {
         opts.status_update_deposit,
     );
 
-    let api = http::start(provider.clone(), http_addr, opts.rpc_cors_domain);
+    // recent changes of our SLA score, served by the api
+    let sla_history = Arc::new(SlaHistory::default());
+
+    let (api_auth, audit_log) = opts.api_auth.load()?;
+    let api = http::start(
+        provider.clone(),
+        sla_history.clone(),
+        http_addr,
+        opts.rpc_cors_domain,
+        api_auth,
+        audit_log,
+    );
+
+    let endpoint_monitor = provider.clone();
 
     let result = tokio::try_join!(
+        // switches to another parachain endpoint if the current one fails
+        tokio::spawn(async move {
+            endpoint_monitor.monitor_endpoints().await;
+        }),
         tokio::spawn(async move {
             let relayer_id = provider.get_account_id();
-            provider
-                .on_event::<UpdateRelayerSLAEvent<PolkaBtcRuntime>, _, _, _>(
-                    |event| async move {
+            let sla_history = &sla_history;
+            let result = provider
+                .on_block_event::<UpdateRelayerSLAEvent<PolkaBtcRuntime>, _, _, _>(
+                    |event, header| async move {
                         if &event.relayer_id == relayer_id {
-                            info!("Received event: new total SLA score = {:?}", event.new_sla);
+                            info!(
+                                "Received event: new total SLA score = {:?} at block {}",
+                                event.new_sla, header.number
+                            );
+                            sla_history.record(&header, event.new_sla);
                         }
+                        Ok(())
                     },
-                    |err| error!("Error (UpdateRelayerSLAEvent): {}", err.to_string()),
+                    |error| error!("Failed to decode SLA event: {}", error),
                 )
-                .await
-                .unwrap();
+                .await;
+            if let Err(err) = result {
+                error!("SLA listener stopped: {}", err);
+            }
         }),
         // runs json-rpc server for incoming requests
         tokio::spawn(async move { api.await }),
@@ -53,88 +53,30 @@ impl<P: TimestampPallet + ExchangeRateOraclePallet + StakedRelayerPallet + Secur
 #[cfg(test)]
 mod tests {
     use super::*;
-    use async_trait::async_trait;
-    use runtime::PolkaBtcStatusUpdate;
-    use runtime::{
-        AccountId, BtcTxFeesPerByte, Error, ErrorCode, FixedPointNumber, FixedU128, H256Le,
-        StatusCode,
-    };
-    use std::collections::BTreeSet;
-    use std::iter::FromIterator;
-
-    mockall::mock! {
-        Provider {}
-
-        #[async_trait]
-        trait TimestampPallet {
-            async fn get_time_now(&self) -> Result<u64, Error>;
-        }
-
-        #[async_trait]
-        trait ExchangeRateOraclePallet {
-            async fn get_exchange_rate_info(&self) -> Result<(FixedU128, u64, u64), Error>;
-
-            async fn set_exchange_rate_info(&self, dot_per_btc: FixedU128) -> Result<(), Error>;
-
-            async fn set_btc_tx_fees_per_byte(&self, fast: u32, half: u32, hour: u32) -> Result<(), Error>;
-
-            async fn get_btc_tx_fees_per_byte(&self) -> Result<BtcTxFeesPerByte, Error>;
-
-            async fn btc_to_dots(&self, amount: u128) -> Result<u128, Error>;
-
-            async fn dots_to_btc(&self, amount: u128) -> Result<u128, Error>;
-        }
-
-        #[async_trait]
-        trait StakedRelayerPallet {
-            async fn get_stake(&self) -> Result<u64, Error>;
-            async fn register_staked_relayer(&self, stake: u128) -> Result<(), Error>;
-            async fn deregister_staked_relayer(&self) -> Result<(), Error>;
-            async fn suggest_status_update(
-                &self,
-                deposit: u128,
-                status_code: StatusCode,
-                add_error: Option<ErrorCode>,
-                remove_error: Option<ErrorCode>,
-                block_hash: Option<H256Le>,
-                message: String,
-            ) -> Result<(), Error>;
-            async fn vote_on_status_update(
-                &self,
-                status_update_id: u64,
-                approve: bool,
-            ) -> Result<(), Error>;
-            async fn get_status_update(&self, id: u64) -> Result<PolkaBtcStatusUpdate, Error>;
-            async fn report_oracle_offline(&self) -> Result<(), Error>;
-            async fn report_vault_theft(
-                &self,
-                vault_id: AccountId,
-                tx_id: H256Le,
-                merkle_proof: Vec<u8>,
-                raw_tx: Vec<u8>,
-            ) -> Result<(), Error>;
-            async fn is_transaction_invalid(
-                &self,
-                vault_id: AccountId,
-                raw_tx: Vec<u8>,
-            ) -> Result<bool, Error>;
-            async fn set_maturity_period(&self, period: u32) -> Result<(), Error>;
-        }
+    use runtime::testing::{MockParachain, BLOCK_TIME_MS, MAX_EXCHANGE_RATE_DELAY_MS};
+    use sp_keyring::AccountKeyring;
+
+    /// Returns a parachain on which Alice is a staked relayer.
+    async fn parachain_with_relayer() -> MockParachain {
+        let relayer_id = AccountKeyring::Alice.to_account_id();
+        let parachain = MockParachain::new(relayer_id.clone());
+        parachain.set_free_dot_balance(relayer_id, MINIMUM_STAKE as u128);
+        parachain
+            .register_staked_relayer(MINIMUM_STAKE as u128)
+            .await
+            .unwrap();
+        parachain
+    }
 
-        #[async_trait]
-        trait SecurityPallet {
-            async fn get_parachain_status(&self) -> Result<StatusCode, Error>;
-            async fn get_error_codes(&self) -> Result<BTreeSet<ErrorCode>, Error>;
-        }
+    /// Produce blocks until the exchange rate is outdated.
+    fn outdate_exchange_rate(parachain: &MockParachain) {
+        parachain.advance_chain((MAX_EXCHANGE_RATE_DELAY_MS / BLOCK_TIME_MS) as u32 + 1);
     }
 
     #[tokio::test]
     async fn test_is_oracle_offline_true() {
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_get_exchange_rate_info()
-            .returning(|| Ok((FixedU128::one(), 0, 0)));
-        parachain.expect_get_time_now().returning(|| Ok(1));
+        let parachain = parachain_with_relayer().await;
+        outdate_exchange_rate(&parachain);
 
         assert_eq!(
             OracleMonitor::new(Arc::new(parachain))
@@ -147,11 +89,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_is_oracle_offline_false() {
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_get_exchange_rate_info()
-            .returning(|| Ok((FixedU128::one(), 1, 3)));
-        parachain.expect_get_time_now().returning(|| Ok(2));
+        let parachain = parachain_with_relayer().await;
 
         assert_eq!(
             OracleMonitor::new(Arc::new(parachain))
@@ -164,58 +102,28 @@ mod tests {
 
     #[tokio::test]
     async fn test_report_oracle_offline_not_reported() {
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_get_stake()
-            .once()
-            .returning(|| Ok(MINIMUM_STAKE));
-
-        // is_offline should return true
-        parachain
-            .expect_get_exchange_rate_info()
-            .returning(|| Ok((FixedU128::one(), 0, 0)));
-        parachain.expect_get_time_now().returning(|| Ok(1));
+        let parachain = parachain_with_relayer().await;
+        outdate_exchange_rate(&parachain);
 
         // should report if error not known
-        parachain
-            .expect_get_error_codes()
-            .once()
-            .returning(|| Ok(BTreeSet::new()));
-        parachain
-            .expect_report_oracle_offline()
-            .once()
-            .returning(|| Ok(()));
-
-        OracleMonitor::new(Arc::new(parachain))
+        OracleMonitor::new(Arc::new(parachain.clone()))
             .report_offline()
             .await
             .unwrap();
+        assert!(parachain
+            .get_error_codes()
+            .await
+            .unwrap()
+            .contains(&ErrorCode::OracleOffline));
     }
 
     #[tokio::test]
     async fn test_report_oracle_offline_already_reported() {
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_get_stake()
-            .once()
-            .returning(|| Ok(MINIMUM_STAKE));
-
-        // is_offline should return true
-        parachain
-            .expect_get_exchange_rate_info()
-            .returning(|| Ok((FixedU128::one(), 0, 0)));
-        parachain.expect_get_time_now().returning(|| Ok(1));
-
-        // should not report if error already known
-        parachain
-            .expect_get_error_codes()
-            .once()
-            .returning(|| Ok(BTreeSet::from_iter(vec![ErrorCode::OracleOffline])));
-        parachain
-            .expect_report_oracle_offline()
-            .never()
-            .returning(|| Ok(()));
+        let parachain = parachain_with_relayer().await;
+        outdate_exchange_rate(&parachain);
+        parachain.report_oracle_offline().await.unwrap();
 
+        // should not report if error already known, which the parachain would reject
         OracleMonitor::new(Arc::new(parachain))
             .report_offline()
             .await
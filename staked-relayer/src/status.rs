@@ -189,8 +189,10 @@ mod tests {
         Block, GetBlockResult, LockedTransaction, PartialAddress, Transaction, TransactionMetadata,
         Txid, PUBLIC_KEY_SIZE,
     };
-    use runtime::PolkaBtcStatusUpdate;
-    use runtime::{AccountId, Error as RuntimeError, ErrorCode, H256Le, StatusCode, MINIMUM_STAKE};
+    use runtime::{
+        pallets::staked_relayers::ExecuteStatusUpdateEvent, testing::MockParachain,
+        DotBalancesPallet, SecurityPallet, MINIMUM_STAKE,
+    };
     use sp_core::H256;
     use sp_keyring::AccountKeyring;
     use std::time::Duration;
@@ -226,46 +228,6 @@ mod tests {
         assert_eq!(block_hash, BlockHash::from_slice(&[0; 32]).unwrap());
     }
 
-    mockall::mock! {
-        Provider {}
-
-        #[async_trait]
-        trait StakedRelayerPallet {
-            async fn get_stake(&self) -> Result<u64, RuntimeError>;
-            async fn register_staked_relayer(&self, stake: u128) -> Result<(), RuntimeError>;
-            async fn deregister_staked_relayer(&self) -> Result<(), RuntimeError>;
-            async fn suggest_status_update(
-                &self,
-                deposit: u128,
-                status_code: StatusCode,
-                add_error: Option<ErrorCode>,
-                remove_error: Option<ErrorCode>,
-                block_hash: Option<H256Le>,
-                message: String,
-            ) -> Result<(), RuntimeError>;
-            async fn vote_on_status_update(
-                &self,
-                status_update_id: u64,
-                approve: bool,
-            ) -> Result<(), RuntimeError>;
-            async fn get_status_update(&self, id: u64) -> Result<PolkaBtcStatusUpdate, RuntimeError>;
-            async fn report_oracle_offline(&self) -> Result<(), RuntimeError>;
-            async fn report_vault_theft(
-                &self,
-                vault_id: AccountId,
-                tx_id: H256Le,
-                merkle_proof: Vec<u8>,
-                raw_tx: Vec<u8>,
-            ) -> Result<(), RuntimeError>;
-            async fn is_transaction_invalid(
-                &self,
-                vault_id: AccountId,
-                raw_tx: Vec<u8>,
-            ) -> Result<bool, RuntimeError>;
-            async fn set_maturity_period(&self, period: u32) -> Result<(), RuntimeError>;
-        }
-    }
-
     mockall::mock! {
         Bitcoin {}
 
@@ -273,6 +235,7 @@ mod tests {
         trait BitcoinCoreApi {
             async fn wait_for_block(&self, height: u32, delay: Duration, num_confirmations: u32) -> Result<BlockHash, BitcoinError>;
             async fn get_block_count(&self) -> Result<u64, BitcoinError>;
+            async fn get_balance(&self) -> Result<u64, BitcoinError>;
             async fn get_raw_tx_for(&self, txid: &Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
             async fn get_proof_for(&self, txid: Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
            async  fn get_block_hash_for(&self, height: u32) -> Result<BlockHash, BitcoinError>;
@@ -283,7 +246,9 @@ mod tests {
                 &self,
                 public_key: P,
                 secret_key: Vec<u8>,
-            ) -> Result<(), BitcoinError>;
+                rescan: bool,
+            ) -> Result<bool, BitcoinError>;
+            async fn rescan_blockchain(&self, start_height: u32) -> Result<(), BitcoinError>;
             async fn get_best_block_hash(&self) -> Result<BlockHash, BitcoinError>;
             async fn get_block(&self, hash: &BlockHash) -> Result<Block, BitcoinError>;
             async fn get_block_info(&self, hash: &BlockHash) -> Result<GetBlockResult, BitcoinError>;
@@ -324,28 +289,53 @@ mod tests {
         }
     }
 
+    /// Returns the handle of Alice to a parachain on which Alice and Bob are staked relayers.
+    async fn parachain_with_relayers() -> MockParachain {
+        let parachain = MockParachain::new(AccountKeyring::Alice.to_account_id());
+        for account in &[AccountKeyring::Alice, AccountKeyring::Bob] {
+            let relayer = parachain.with_account(account.to_account_id());
+            relayer.set_free_dot_balance(account.to_account_id(), 1000);
+            relayer
+                .register_staked_relayer(MINIMUM_STAKE as u128)
+                .await
+                .unwrap();
+        }
+        parachain
+    }
+
+    /// Returns the event of Bob suggesting that the relay has no data for a block.
+    async fn suggest_no_data(parachain: &MockParachain) -> PolkaBtcStatusUpdateSuggestedEvent {
+        let relayer = parachain.with_account(AccountKeyring::Bob.to_account_id());
+        report_no_data_btc_relay(&Arc::new(relayer), 100, H256Le::zero())
+            .await
+            .unwrap();
+        parachain
+            .events::<PolkaBtcStatusUpdateSuggestedEvent>()
+            .pop()
+            .unwrap()
+    }
+
+    fn suggested_status_updates(parachain: &MockParachain) -> usize {
+        parachain
+            .events::<PolkaBtcStatusUpdateSuggestedEvent>()
+            .len()
+    }
+
     #[tokio::test]
     async fn test_on_store_block_exists() {
         let mut bitcoin = MockBitcoin::default();
         bitcoin
             .expect_get_block_hash_for()
             .returning(|_| Ok(BlockHash::from_slice(&[1; 32]).unwrap()));
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_suggest_status_update()
-            .never()
-            .returning(|_, _, _, _, _, _| Ok(()));
-        parachain
-            .expect_get_stake()
-            .once()
-            .returning(|| Ok(MINIMUM_STAKE));
+        let parachain = parachain_with_relayers().await;
 
-        let monitor = RelayMonitor::new(Arc::new(bitcoin), Arc::new(parachain), 100);
+        let monitor = RelayMonitor::new(Arc::new(bitcoin), Arc::new(parachain.clone()), 100);
         assert_ok!(
             monitor
                 .on_store_block(123, H256Le::from_bytes_le(&[1; 32]))
                 .await
         );
+        assert_eq!(suggested_status_updates(&parachain), 0);
     }
 
     #[tokio::test]
@@ -354,32 +344,21 @@ mod tests {
         bitcoin
             .expect_get_block_hash_for()
             .returning(|_| Err(BitcoinError::InvalidBitcoinHeight.into()));
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_suggest_status_update()
-            .once()
-            .returning(|_, _, _, _, _, _| Ok(()));
-        parachain
-            .expect_get_stake()
-            .once()
-            .returning(|| Ok(MINIMUM_STAKE));
+        let parachain = parachain_with_relayers().await;
 
-        let monitor = RelayMonitor::new(Arc::new(bitcoin), Arc::new(parachain), 100);
+        let monitor = RelayMonitor::new(Arc::new(bitcoin), Arc::new(parachain.clone()), 100);
         assert_ok!(
             monitor
                 .on_store_block(123, H256Le::from_bytes_le(&[1; 32]))
                 .await
         );
+        assert_eq!(suggested_status_updates(&parachain), 1);
     }
 
     #[tokio::test]
     async fn test_on_store_block_no_stake() {
         let bitcoin = MockBitcoin::default();
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_get_stake()
-            .once()
-            .returning(|| Ok(MINIMUM_STAKE - 1));
+        let parachain = MockParachain::new(AccountKeyring::Alice.to_account_id());
 
         let monitor = RelayMonitor::new(Arc::new(bitcoin), Arc::new(parachain), 100);
         assert_ok!(monitor.on_store_block(0, H256Le::zero()).await);
@@ -389,12 +368,8 @@ mod tests {
     async fn test_on_status_update_suggested_ignore() {
         let mut bitcoin = MockBitcoin::default();
         bitcoin.expect_is_block_known().never();
-        let mut parachain = MockProvider::default();
-        parachain.expect_vote_on_status_update().never();
-        parachain
-            .expect_get_stake()
-            .once()
-            .returning(|| Ok(MINIMUM_STAKE));
+        // voting on the unknown status update would fail
+        let parachain = parachain_with_relayers().await;
 
         let monitor = StatusUpdateMonitor::new(Arc::new(bitcoin), Arc::new(parachain));
         assert_ok!(
@@ -415,27 +390,23 @@ mod tests {
     async fn test_on_status_update_suggested_add_error_no_block_hash() {
         let mut bitcoin = MockBitcoin::default();
         bitcoin.expect_is_block_known().never();
-        let mut parachain = MockProvider::default();
-        parachain.expect_vote_on_status_update().never();
-        parachain
-            .expect_get_stake()
-            .once()
-            .returning(|| Ok(MINIMUM_STAKE));
+        let parachain = parachain_with_relayers().await;
+        let event = suggest_no_data(&parachain).await;
 
-        let monitor = StatusUpdateMonitor::new(Arc::new(bitcoin), Arc::new(parachain));
+        let monitor = StatusUpdateMonitor::new(Arc::new(bitcoin), Arc::new(parachain.clone()));
         assert_err!(
             monitor
                 .on_status_update_suggested(PolkaBtcStatusUpdateSuggestedEvent {
-                    status_update_id: 0,
-                    account_id: AccountKeyring::Bob.to_account_id(),
-                    status_code: StatusCode::Error,
-                    add_error: Some(ErrorCode::NoDataBTCRelay),
-                    remove_error: None,
                     block_hash: None,
+                    ..event
                 })
                 .await,
             Error::EventNoBlockHash
         );
+        assert_eq!(
+            parachain.get_parachain_status().await.unwrap(),
+            StatusCode::Running
+        );
     }
 
     #[tokio::test]
@@ -445,29 +416,21 @@ mod tests {
             .expect_is_block_known()
             .once()
             .returning(|_| Ok(false));
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_vote_on_status_update()
-            .withf(|_, approve| approve == &true)
-            .once()
-            .returning(|_, _| Ok(()));
-        parachain
-            .expect_get_stake()
-            .once()
-            .returning(|| Ok(MINIMUM_STAKE));
-
-        let monitor = StatusUpdateMonitor::new(Arc::new(bitcoin), Arc::new(parachain));
-        assert_ok!(
-            monitor
-                .on_status_update_suggested(PolkaBtcStatusUpdateSuggestedEvent {
-                    status_update_id: 0,
-                    account_id: AccountKeyring::Bob.to_account_id(),
-                    status_code: StatusCode::Error,
-                    add_error: Some(ErrorCode::NoDataBTCRelay),
-                    remove_error: None,
-                    block_hash: Some(H256Le::zero()),
-                })
-                .await
+        let parachain = parachain_with_relayers().await;
+        let event = suggest_no_data(&parachain).await;
+
+        let monitor = StatusUpdateMonitor::new(Arc::new(bitcoin), Arc::new(parachain.clone()));
+        assert_ok!(monitor.on_status_update_suggested(event).await);
+        // the approved status update is executed
+        assert_eq!(
+            parachain
+                .events::<ExecuteStatusUpdateEvent<PolkaBtcRuntime>>()
+                .len(),
+            1
+        );
+        assert_eq!(
+            parachain.get_parachain_status().await.unwrap(),
+            StatusCode::Error
         );
     }
 
@@ -478,40 +441,27 @@ mod tests {
             .expect_is_block_known()
             .once()
             .returning(|_| Ok(true));
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_vote_on_status_update()
-            .withf(|_, approve| approve == &false)
-            .once()
-            .returning(|_, _| Ok(()));
-        parachain
-            .expect_get_stake()
-            .once()
-            .returning(|| Ok(MINIMUM_STAKE));
-
-        let monitor = StatusUpdateMonitor::new(Arc::new(bitcoin), Arc::new(parachain));
-        assert_ok!(
-            monitor
-                .on_status_update_suggested(PolkaBtcStatusUpdateSuggestedEvent {
-                    status_update_id: 0,
-                    account_id: AccountKeyring::Bob.to_account_id(),
-                    status_code: StatusCode::Error,
-                    add_error: Some(ErrorCode::NoDataBTCRelay),
-                    remove_error: None,
-                    block_hash: Some(H256Le::zero()),
-                })
-                .await
+        let parachain = parachain_with_relayers().await;
+        let event = suggest_no_data(&parachain).await;
+        let free_balance = parachain.get_free_dot_balance().await.unwrap();
+
+        let monitor = StatusUpdateMonitor::new(Arc::new(bitcoin), Arc::new(parachain.clone()));
+        assert_ok!(monitor.on_status_update_suggested(event).await);
+        // the deposit of the rejected status update goes to the voter
+        assert_eq!(
+            parachain.get_free_dot_balance().await.unwrap(),
+            free_balance + 100
+        );
+        assert_eq!(
+            parachain.get_parachain_status().await.unwrap(),
+            StatusCode::Running
         );
     }
 
     #[tokio::test]
     async fn test_on_status_update_suggested_no_stake() {
         let bitcoin = MockBitcoin::default();
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_get_stake()
-            .once()
-            .returning(|| Ok(MINIMUM_STAKE - 1));
+        let parachain = MockParachain::new(AccountKeyring::Alice.to_account_id());
 
         let monitor = StatusUpdateMonitor::new(Arc::new(bitcoin), Arc::new(parachain));
         assert_ok!(
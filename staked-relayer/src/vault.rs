@@ -228,73 +228,10 @@ mod tests {
         Block, Error as BitcoinError, GetBlockResult, LockedTransaction, PartialAddress,
         Transaction, TransactionMetadata, PUBLIC_KEY_SIZE,
     };
-    use runtime::PolkaBtcStatusUpdate;
-    use runtime::{AccountId, Error as RuntimeError, ErrorCode, H256Le, StatusCode};
-    use runtime::{BitcoinBlockHeight, RawBlockHeader, RichBlockHeader};
+    use runtime::{pallets::vault_registry::VaultStatus, testing::MockParachain, MINIMUM_STAKE};
     use sp_core::{H160, H256};
     use sp_keyring::AccountKeyring;
 
-    mockall::mock! {
-        Provider {}
-
-        #[async_trait]
-        trait StakedRelayerPallet {
-            async fn get_stake(&self) -> Result<u64, RuntimeError>;
-            async fn register_staked_relayer(&self, stake: u128) -> Result<(), RuntimeError>;
-            async fn deregister_staked_relayer(&self) -> Result<(), RuntimeError>;
-            async fn suggest_status_update(
-                &self,
-                deposit: u128,
-                status_code: StatusCode,
-                add_error: Option<ErrorCode>,
-                remove_error: Option<ErrorCode>,
-                block_hash: Option<H256Le>,
-                message: String,
-            ) -> Result<(), RuntimeError>;
-            async fn vote_on_status_update(
-                &self,
-                status_update_id: u64,
-                approve: bool,
-            ) -> Result<(), RuntimeError>;
-            async fn get_status_update(&self, id: u64) -> Result<PolkaBtcStatusUpdate, RuntimeError>;
-            async fn report_oracle_offline(&self) -> Result<(), RuntimeError>;
-            async fn report_vault_theft(
-                &self,
-                vault_id: AccountId,
-                tx_id: H256Le,
-                merkle_proof: Vec<u8>,
-                raw_tx: Vec<u8>,
-            ) -> Result<(), RuntimeError>;
-            async fn is_transaction_invalid(
-                &self,
-                vault_id: AccountId,
-                raw_tx: Vec<u8>,
-            ) -> Result<bool, RuntimeError>;
-            async fn set_maturity_period(&self, period: u32) -> Result<(), RuntimeError>;
-        }
-
-        #[async_trait]
-        pub trait BtcRelayPallet {
-            async fn get_best_block(&self) -> Result<H256Le, RuntimeError>;
-            async fn get_best_block_height(&self) -> Result<u32, RuntimeError>;
-            async fn get_block_hash(&self, height: u32) -> Result<H256Le, RuntimeError>;
-            async fn get_block_header(&self, hash: H256Le) -> Result<RichBlockHeader, RuntimeError>;
-            async fn initialize_btc_relay(
-                &self,
-                header: RawBlockHeader,
-                height: BitcoinBlockHeight,
-            ) -> Result<(), RuntimeError>;
-            async fn store_block_header(&self, header: RawBlockHeader) -> Result<(), RuntimeError>;
-            async fn store_block_headers(&self, headers: Vec<RawBlockHeader>) -> Result<(), RuntimeError>;
-            async fn get_bitcoin_confirmations(&self) -> Result<u32, RuntimeError>;
-            async fn wait_for_block_in_relay(
-                &self,
-                block_hash: H256Le,
-                num_confirmations: u32,
-            ) -> Result<(), RuntimeError>;
-        }
-    }
-
     mockall::mock! {
         Bitcoin {}
 
@@ -302,6 +239,7 @@ mod tests {
         trait BitcoinCoreApi {
             async fn wait_for_block(&self, height: u32, delay: Duration, num_confirmations: u32) -> Result<BlockHash, BitcoinError>;
             async fn get_block_count(&self) -> Result<u64, BitcoinError>;
+            async fn get_balance(&self) -> Result<u64, BitcoinError>;
             async fn get_raw_tx_for(&self, txid: &Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
             async fn get_proof_for(&self, txid: Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
            async  fn get_block_hash_for(&self, height: u32) -> Result<BlockHash, BitcoinError>;
@@ -312,7 +250,9 @@ mod tests {
                 &self,
                 public_key: P,
                 secret_key: Vec<u8>,
-            ) -> Result<(), BitcoinError>;
+                rescan: bool,
+            ) -> Result<bool, BitcoinError>;
+            async fn rescan_blockchain(&self, start_height: u32) -> Result<(), BitcoinError>;
             async fn get_best_block_hash(&self) -> Result<BlockHash, BitcoinError>;
             async fn get_block(&self, hash: &BlockHash) -> Result<Block, BitcoinError>;
             async fn get_block_info(&self, hash: &BlockHash) -> Result<GetBlockResult, BitcoinError>;
@@ -377,63 +317,54 @@ mod tests {
         );
     }
 
-    #[tokio::test]
-    async fn test_report_valid_transaction() {
-        let mut parachain = MockProvider::default();
+    /// Returns the handle of Alice, a staked relayer, to a parachain on which Bob is a vault.
+    async fn parachain_with_vault() -> MockParachain {
+        let parachain = MockParachain::new(AccountKeyring::Alice.to_account_id());
+        parachain.set_free_dot_balance(AccountKeyring::Alice.to_account_id(), 1000);
         parachain
-            .expect_is_transaction_invalid()
-            .returning(|_, _| Ok(false));
+            .register_staked_relayer(MINIMUM_STAKE as u128)
+            .await
+            .unwrap();
+        let vault = parachain.with_account(AccountKeyring::Bob.to_account_id());
+        vault.register_vault(0, Default::default()).await.unwrap();
         parachain
-            .expect_report_vault_theft()
-            .never()
-            .returning(|_, _, _, _| Ok(()));
+    }
 
+    async fn report_invalid(parachain: &MockParachain, raw_tx: Vec<u8>) -> VaultStatus {
         let monitor = VaultTheftMonitor::new(
             0,
             Arc::new(MockBitcoin::default()),
             Arc::new(Vaults::default()),
-            Arc::new(parachain),
+            Arc::new(parachain.clone()),
             Duration::from_millis(100),
         );
 
+        let vault_id = AccountKeyring::Bob.to_account_id();
         monitor
-            .report_invalid(
-                AccountKeyring::Bob.to_account_id(),
-                &Txid::default(),
-                vec![],
-                vec![],
-            )
+            .report_invalid(vault_id.clone(), &Txid::default(), raw_tx, vec![])
             .await
             .unwrap();
+        parachain.get_vault(vault_id).await.unwrap().status
     }
 
     #[tokio::test]
-    async fn test_report_invalid_transaction() {
-        let mut parachain = MockProvider::default();
-        parachain
-            .expect_is_transaction_invalid()
-            .returning(|_, _| Ok(true));
-        parachain
-            .expect_report_vault_theft()
-            .once()
-            .returning(|_, _, _, _| Ok(()));
+    async fn test_report_valid_transaction() {
+        let parachain = parachain_with_vault().await;
 
-        let monitor = VaultTheftMonitor::new(
-            0,
-            Arc::new(MockBitcoin::default()),
-            Arc::new(Vaults::default()),
-            Arc::new(parachain),
-            Duration::from_millis(100),
+        assert_eq!(
+            report_invalid(&parachain, vec![1]).await,
+            VaultStatus::Active
         );
+    }
 
-        monitor
-            .report_invalid(
-                AccountKeyring::Bob.to_account_id(),
-                &Txid::default(),
-                vec![],
-                vec![],
-            )
-            .await
-            .unwrap();
+    #[tokio::test]
+    async fn test_report_invalid_transaction() {
+        let parachain = parachain_with_vault().await;
+        parachain.insert_invalid_transaction(vec![1]);
+
+        assert_eq!(
+            report_invalid(&parachain, vec![1]).await,
+            VaultStatus::CommittedTheft
+        );
     }
 }
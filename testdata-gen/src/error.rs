@@ -20,6 +20,8 @@ pub enum Error {
     IssueCompleted,
     #[error("Issue has been cancelled")]
     IssueCancelled,
+    #[error("Event {0}::{1} not found in the metadata")]
+    UnknownEvent(&'static str, &'static str),
 
     #[error("IoError: {0}")]
     IoError(#[from] IoError),
@@ -1,6 +1,7 @@
 mod api;
 mod error;
 mod issue;
+mod offline;
 mod redeem;
 mod replace;
 mod stats;
@@ -12,15 +13,18 @@ use clap::Clap;
 use error::Error;
 use log::*;
 use parity_scale_codec::{Decode, Encode};
+use runtime::pallets::exchange_rate_oracle::SetExchangeRateCall;
+use runtime::pallets::vault_registry::WithdrawCollateralCall;
 use runtime::{
-    substrate_subxt::PairSigner, BtcAddress, ErrorCode as PolkaBtcErrorCode,
-    ExchangeRateOraclePallet, FeePallet, FixedPointNumber, FixedPointTraits::*, FixedU128, H256Le,
-    PolkaBtcProvider, PolkaBtcRuntime, RedeemPallet, StakedRelayerPallet,
-    StatusCode as PolkaBtcStatusCode, TimestampPallet,
+    AccountId, BtcAddress, ErrorCode as PolkaBtcErrorCode, ExchangeRateOraclePallet, FeePallet,
+    FixedPointNumber, FixedPointTraits::*, FixedU128, H256Le, PastEventsOptions, PolkaBtcProvider,
+    RedeemPallet, StakedRelayerPallet, StatusCode as PolkaBtcStatusCode, TimestampPallet,
+    TreasuryPallet, UnsignedExtrinsicOptions, UtilFuncs,
 };
-use sp_core::H256;
+use sp_core::{Bytes, H256};
 use sp_keyring::AccountKeyring;
 use std::convert::TryInto;
+use std::marker::PhantomData;
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -56,6 +60,14 @@ impl std::str::FromStr for H256LeFromStr {
     }
 }
 
+fn parse_block_hash(hash: &str) -> Result<H256, String> {
+    let bytes = hex::decode(hash.trim_start_matches("0x")).map_err(|err| err.to_string())?;
+    if bytes.len() != 32 {
+        return Err("Block hash must be 32 bytes".to_string());
+    }
+    Ok(H256::from_slice(&bytes))
+}
+
 #[derive(Debug, Encode, Decode)]
 struct PolkaBtcErrorCodeFromStr(PolkaBtcErrorCode);
 impl std::str::FromStr for PolkaBtcErrorCodeFromStr {
@@ -80,6 +92,11 @@ struct Opts {
     #[clap(long, default_value = "ws://127.0.0.1:9944")]
     polka_btc_url: String,
 
+    /// Hash of the parachain block as of which the `get-*` subcommands query the state.
+    /// If left unspecified, the state of the best block is queried.
+    #[clap(long, parse(try_from_str = parse_block_hash))]
+    at_block: Option<H256>,
+
     /// keyring / keyfile options.
     #[clap(flatten)]
     account_info: runtime::cli::ProviderUserOpts,
@@ -104,6 +121,12 @@ enum SubCommand {
     GetBtcTxFees,
     /// Get the time as reported by the chain.
     GetCurrentTime,
+    /// Get the free and locked PolkaBTC of an account.
+    GetPolkaBtcBalance(GetPolkaBtcBalanceInfo),
+    /// Get the total amount of PolkaBTC in existence.
+    GetPolkaBtcTotalSupply,
+    /// Transfer PolkaBTC to another account.
+    TransferPolkaBtc(TransferPolkaBtcInfo),
     /// Register a new vault using the global keyring.
     RegisterVault(RegisterVaultInfo),
     /// Request issuance of PolkaBTC and transfer to vault.
@@ -134,6 +157,14 @@ enum SubCommand {
     SetReplacePeriod(SetReplacePeriodInfo),
     /// Set relayer maturity period.
     SetRelayerMaturityPeriod(SetRelayerMaturityPeriodInfo),
+    /// Create an extrinsic to be signed offline with `sign-extrinsic`. The configured
+    /// account is only used to connect, not for signing.
+    CreateUnsignedExtrinsic(CreateUnsignedExtrinsicInfo),
+    /// Sign an extrinsic created with `create-unsigned-extrinsic`, without connecting
+    /// to the parachain.
+    SignExtrinsic(SignExtrinsicInfo),
+    /// Submit an extrinsic signed with `sign-extrinsic`.
+    SubmitExtrinsic(SubmitExtrinsicInfo),
 }
 
 #[derive(Clap)]
@@ -219,7 +250,9 @@ impl FromStr for BitcoinNetwork {
 
 #[derive(Clap)]
 struct DumpOpts {
-    /// Print all raw events, rather than the JSON output of a select subset.
+    /// Print all raw events, rather than writing the events of the protocol pallets
+    /// (issue, redeem, replace, refund, vault_registry, staked_relayers, exchange_rate_oracle,
+    /// treasury and security) to one JSON file per event type.
     #[clap(long)]
     raw: bool,
 
@@ -227,10 +260,24 @@ struct DumpOpts {
     /// If any logs exist in this folder, they will be overwritten.
     #[clap(long, default_value = "event-logs", conflicts_with = "raw")]
     output_folder: String,
+
+    /// Only include the events of this pallet, e.g. `issue` or `vault_registry`.
+    /// Can be given multiple times. If left unspecified, all events are included.
+    #[clap(long = "pallet")]
+    pallets: Vec<String>,
+
+    #[clap(flatten)]
+    range: BlockRangeOpts,
 }
 
 #[derive(Clap)]
 struct ChainStatOpts {
+    #[clap(flatten)]
+    range: BlockRangeOpts,
+}
+
+#[derive(Clap)]
+struct BlockRangeOpts {
     /// The height of the chain to start from. If left unspecified, it starts from the genesis.
     #[clap(long)]
     start: Option<u32>,
@@ -238,6 +285,25 @@ struct ChainStatOpts {
     /// The height of the chain to end at. If left unspecified, it continues at the current chain height.
     #[clap(long)]
     end: Option<u32>,
+
+    /// The maximum number of blocks to fetch concurrently.
+    #[clap(long, default_value = "16")]
+    concurrency: usize,
+}
+
+impl BlockRangeOpts {
+    fn into_past_events_options(self, pallets: Vec<String>) -> PastEventsOptions {
+        PastEventsOptions {
+            start: self.start,
+            end: self.end,
+            concurrency: self.concurrency,
+            pallets: if pallets.is_empty() {
+                None
+            } else {
+                Some(pallets)
+            },
+        }
+    }
 }
 
 #[derive(Clap)]
@@ -262,6 +328,24 @@ struct SetBtcTxFeesInfo {
     hour: u32,
 }
 
+#[derive(Clap)]
+struct GetPolkaBtcBalanceInfo {
+    /// The account to query, in SS58 format. If left unspecified, the configured account is used.
+    #[clap(long)]
+    account_id: Option<AccountId>,
+}
+
+#[derive(Clap)]
+struct TransferPolkaBtcInfo {
+    /// The account that receives the PolkaBTC, in SS58 format.
+    #[clap(long)]
+    destination: AccountId,
+
+    /// Amount of PolkaBTC to transfer.
+    #[clap(long)]
+    amount: u128,
+}
+
 #[derive(Clap)]
 struct RegisterVaultInfo {
     /// Collateral to secure position.
@@ -405,6 +489,78 @@ struct ExecuteReplaceInfo {
     bitcoin_network: BitcoinNetwork,
 }
 
+#[derive(Clap)]
+struct CreateUnsignedExtrinsicInfo {
+    /// Account that will sign the extrinsic, in SS58 format.
+    #[clap(long)]
+    signer: AccountId,
+
+    /// Nonce of the extrinsic. If left unspecified, the next nonce of the signer is used.
+    #[clap(long)]
+    nonce: Option<u32>,
+
+    /// Number of blocks for which the extrinsic is valid. If left unspecified, it is valid forever.
+    #[clap(long)]
+    mortality: Option<u64>,
+
+    /// Tip for the block author.
+    #[clap(long, default_value = "0")]
+    tip: u128,
+
+    /// Path of the output file.
+    #[clap(long, default_value = "unsigned-extrinsic.json")]
+    output: String,
+
+    #[clap(subcommand)]
+    call: UnsignedCall,
+}
+
+#[derive(Clap)]
+enum UnsignedCall {
+    /// Set the DOT to BTC exchange rate.
+    SetExchangeRate(SetExchangeRateInfo),
+    /// Withdraw collateral of the signing vault.
+    WithdrawCollateral(WithdrawCollateralInfo),
+    /// Any other call, encoded as hex.
+    Raw(RawCallInfo),
+}
+
+#[derive(Clap)]
+struct WithdrawCollateralInfo {
+    /// Amount of DOT to withdraw.
+    #[clap(long)]
+    amount: u128,
+}
+
+#[derive(Clap)]
+struct RawCallInfo {
+    /// Hex encoded call.
+    #[clap(long)]
+    call: String,
+
+    /// Dispatch the call as root, through the sudo pallet.
+    #[clap(long)]
+    sudo: bool,
+}
+
+#[derive(Clap)]
+struct SignExtrinsicInfo {
+    /// Path of the unsigned extrinsic.
+    #[clap(long, default_value = "unsigned-extrinsic.json")]
+    input: String,
+
+    /// Path of the output file.
+    #[clap(long, default_value = "signed-extrinsic.hex")]
+    output: String,
+}
+
+#[derive(Clap)]
+struct SubmitExtrinsicInfo {
+    /// Path of the signed extrinsic.
+    #[clap(long, default_value = "signed-extrinsic.hex")]
+    input: String,
+}
+
 #[derive(Clap, Encode, Decode, Debug)]
 struct RequestReplaceJsonRpcRequest {
     /// Amount to replace.
@@ -516,17 +672,27 @@ async fn main() -> Result<(), Error> {
     env_logger::init();
     let opts: Opts = Opts::parse();
 
-    let (key_pair, wallet_name) = opts.account_info.get_key_pair()?;
-    let signer = PairSigner::<PolkaBtcRuntime, _>::new(key_pair);
+    // signing happens on a host without access to the parachain
+    if let SubCommand::SignExtrinsic(info) = &opts.subcmd {
+        let (pair, _) = opts.account_info.get_key_pair()?;
+        return offline::sign_extrinsic(&pair, &info.input, &info.output);
+    }
+
+    let (signer, wallet_name) = opts.account_info.get_signer().await?;
     let provider = PolkaBtcProvider::from_url(opts.polka_btc_url, signer).await?;
+    let getter = match opts.at_block {
+        Some(block_hash) => provider.at(block_hash),
+        None => provider.clone(),
+    };
 
     match opts.subcmd {
         SubCommand::SetExchangeRate(info) => {
-            let rate = FixedU128::checked_from_rational(info.exchange_rate, 100_000).unwrap();
+            let rate = FixedU128::checked_from_rational(info.exchange_rate, 100_000)
+                .ok_or(Error::MathError)?;
             provider.set_exchange_rate_info(rate).await?;
         }
         SubCommand::GetExchangeRate => {
-            let (rate, time, delay) = provider.get_exchange_rate_info().await?;
+            let (rate, time, delay) = getter.get_exchange_rate_info().await?;
             println!(
                 "Exchange Rate BTC/DOT: {:?}, Last Update: {}, Delay: {}",
                 rate, time, delay
@@ -538,14 +704,34 @@ async fn main() -> Result<(), Error> {
                 .await?;
         }
         SubCommand::GetBtcTxFees => {
-            let fees = provider.get_btc_tx_fees_per_byte().await?;
+            let fees = getter.get_btc_tx_fees_per_byte().await?;
             println!(
                 "Fees per byte: fast={} half={} hour={}",
                 fees.fast, fees.half, fees.hour
             );
         }
         SubCommand::GetCurrentTime => {
-            println!("{}", provider.get_time_now().await?);
+            println!("{}", getter.get_time_now().await?);
+        }
+        SubCommand::GetPolkaBtcBalance(info) => {
+            let account_id = info
+                .account_id
+                .unwrap_or_else(|| getter.get_account_id().clone());
+            let free = getter
+                .get_free_polka_btc_balance_for_id(account_id.clone())
+                .await?;
+            let locked = getter
+                .get_locked_polka_btc_balance_for_id(account_id)
+                .await?;
+            println!("Free: {}, Locked: {}", free, locked);
+        }
+        SubCommand::GetPolkaBtcTotalSupply => {
+            println!("{}", getter.get_polka_btc_total_supply().await?);
+        }
+        SubCommand::TransferPolkaBtc(info) => {
+            provider
+                .transfer_polka_btc(info.destination, info.amount)
+                .await?;
         }
         SubCommand::RegisterVault(info) => {
             let btc_rpc = get_btc_rpc(wallet_name, opts.bitcoin, info.bitcoin_network).await?;
@@ -681,13 +867,15 @@ async fn main() -> Result<(), Error> {
             replace::execute_replace(&provider, &btc_rpc, info.replace_id).await?;
         }
         SubCommand::GetChainStats(opts) => {
-            stats::report_chain_stats(&provider, opts.start, opts.end).await?;
+            let options = opts.range.into_past_events_options(vec![]);
+            stats::report_chain_stats(&provider, options).await?;
         }
         SubCommand::DumpEvents(opts) => {
+            let options = opts.range.into_past_events_options(opts.pallets);
             if opts.raw {
-                stats::dump_raw_events(&provider).await?;
+                stats::dump_raw_events(&provider, options).await?;
             } else {
-                stats::dump_json(&provider, &opts.output_folder).await?;
+                stats::dump_json(&provider, options, &opts.output_folder).await?;
             }
         }
         SubCommand::ApiCall(api_call) => match api_call.subcmd {
@@ -745,6 +933,38 @@ async fn main() -> Result<(), Error> {
         SubCommand::SetRelayerMaturityPeriod(info) => {
             provider.set_maturity_period(info.period).await?;
         }
+        SubCommand::CreateUnsignedExtrinsic(info) => {
+            let call = match info.call {
+                UnsignedCall::SetExchangeRate(call) => {
+                    provider.encode_call(SetExchangeRateCall {
+                        rate: FixedU128::checked_from_rational(call.exchange_rate, 100_000)
+                            .ok_or(Error::MathError)?,
+                        _runtime: PhantomData,
+                    })?
+                }
+                UnsignedCall::WithdrawCollateral(call) => {
+                    provider.encode_call(WithdrawCollateralCall {
+                        amount: call.amount,
+                    })?
+                }
+                UnsignedCall::Raw(call) if call.sudo => {
+                    provider.encode_sudo_call(Bytes(hex::decode(call.call)?))?
+                }
+                UnsignedCall::Raw(call) => Bytes(hex::decode(call.call)?),
+            };
+            let options = UnsignedExtrinsicOptions {
+                nonce: info.nonce,
+                mortality: info.mortality,
+                tip: info.tip,
+            };
+            offline::create_unsigned_extrinsic(&provider, call, info.signer, options, &info.output)
+                .await?;
+        }
+        SubCommand::SignExtrinsic(_) => unreachable!("handled before connecting"),
+        SubCommand::SubmitExtrinsic(info) => {
+            let block_hash = offline::submit_extrinsic(&provider, &info.input).await?;
+            println!("{:?}", block_hash);
+        }
     }
 
     Ok(())
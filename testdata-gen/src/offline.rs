@@ -0,0 +1,45 @@
+use crate::Error;
+use log::info;
+use runtime::{AccountId, PolkaBtcProvider, UnsignedExtrinsic, UnsignedExtrinsicOptions};
+use sp_core::sr25519::Pair;
+use sp_core::{Bytes, H256};
+use std::fs;
+
+/// Create an extrinsic for the encoded call and write it as JSON to the output file,
+/// such that it can be reviewed and signed on a host without access to the parachain.
+pub async fn create_unsigned_extrinsic(
+    provider: &PolkaBtcProvider,
+    call: Bytes,
+    signer: AccountId,
+    options: UnsignedExtrinsicOptions,
+    output: &str,
+) -> Result<(), Error> {
+    let unsigned = provider
+        .create_unsigned_extrinsic(call, signer, options)
+        .await?;
+    fs::write(output, serde_json::to_string_pretty(&unsigned)?)?;
+    info!("Wrote unsigned extrinsic to {}", output);
+    Ok(())
+}
+
+/// Sign the extrinsic in the input file and write the hex encoded result to the output file.
+/// Does not require a connection to the parachain: the call that is logged for review is
+/// decoded with the metadata of the runtime this binary is built against.
+pub fn sign_extrinsic(pair: &Pair, input: &str, output: &str) -> Result<(), Error> {
+    let unsigned: UnsignedExtrinsic = serde_json::from_str(&fs::read_to_string(input)?)?;
+    match &unsigned.decode_call()? {
+        Some(call) => info!("Signing {}", serde_json::to_string_pretty(call)?),
+        None => info!("Signing undecoded call {}", hex::encode(&unsigned.call.0)),
+    }
+    let extrinsic = unsigned.sign(pair)?;
+    fs::write(output, hex::encode(&extrinsic.0))?;
+    info!("Wrote signed extrinsic to {}", output);
+    Ok(())
+}
+
+/// Submit the hex encoded extrinsic in the input file, returns the hash of the block that
+/// includes it.
+pub async fn submit_extrinsic(provider: &PolkaBtcProvider, input: &str) -> Result<H256, Error> {
+    let extrinsic = hex::decode(fs::read_to_string(input)?.trim())?;
+    Ok(provider.submit_signed_extrinsic(Bytes(extrinsic)).await?)
+}
@@ -1,25 +1,62 @@
 use crate::Error;
+use futures::{pin_mut, StreamExt};
 use log::*;
+use parity_scale_codec::{Decode, Encode};
 use runtime::historic_event_types::*;
 use runtime::{
+    pallets::exchange_rate_oracle::{SetBtcTxFeesPerByteEvent, SetExchangeRateEvent},
     pallets::issue::{CancelIssueEvent, ExecuteIssueEvent, RequestIssueEvent},
     pallets::redeem::{CancelRedeemEvent, ExecuteRedeemEvent, RequestRedeemEvent},
-    PolkaBtcProvider, PolkaBtcRuntime,
+    pallets::refund::{ExecuteRefundEvent, RequestRefundEvent},
+    pallets::replace::{
+        AcceptReplaceEvent, AuctionReplaceEvent, CancelReplaceEvent, ExecuteReplaceEvent,
+        RequestReplaceEvent, WithdrawReplaceEvent,
+    },
+    pallets::security::RecoverFromErrorsEvent,
+    pallets::staked_relayers::{
+        DeregisterStakedRelayerEvent, ExecuteStatusUpdateEvent, RegisterStakedRelayerEvent,
+        StatusUpdateSuggestedEvent,
+    },
+    pallets::treasury::{BurnEvent, LockEvent, MintEvent},
+    pallets::vault_registry::{
+        IncreaseToBeIssuedTokensEvent, LockAdditionalCollateralEvent, RegisterAddressEvent,
+        RegisterVaultEvent, UpdatePublicKeyEvent, WithdrawCollateralEvent,
+    },
+    substrate_subxt::Event as RuntimeEvent,
+    PastBlockEvents, PastEventsOptions, PolkaBtcProvider, PolkaBtcRuntime,
 };
 use serde::Serialize;
 use std::{
+    collections::HashMap,
     fs::File,
     io::{BufWriter, Write},
+    marker::PhantomData,
     path::Path,
 };
 
-pub async fn dump_raw_events(provider: &PolkaBtcProvider) -> Result<(), Error> {
-    provider
-        .on_past_events(None, None, |event, _| {
-            println!("{:?}", event);
-            Ok(())
-        })
-        .await?;
+/// Log the scanning progress, including the height from which an interrupted scan can be resumed
+fn log_progress(block: &PastBlockEvents) {
+    info!(
+        "Processed block {}, {} blocks remaining (resume with --start {})",
+        block.height,
+        block.remaining,
+        block.height + 1
+    );
+}
+
+pub async fn dump_raw_events(
+    provider: &PolkaBtcProvider,
+    options: PastEventsOptions,
+) -> Result<(), Error> {
+    let blocks = provider.past_events(options).await?;
+    pin_mut!(blocks);
+    while let Some(block) = blocks.next().await {
+        let block = block?;
+        for event in block.events.iter() {
+            println!("{}: {:?}", block.height, event);
+        }
+        log_progress(&block);
+    }
     Ok(())
 }
 
@@ -59,64 +96,98 @@ impl Drop for LogWriter {
     }
 }
 
+/// Writes the historic events of a single type to a JSON file, see `dump_json`.
+trait EventLog {
+    /// Decode the event of the logged type from its encoded fields and write it.
+    fn write(&mut self, fields: &[u8]) -> Result<(), Error>;
+}
+
+struct TypedLogWriter<T> {
+    writer: LogWriter,
+    _marker: PhantomData<T>,
+}
+
+impl<T: RuntimeEvent<PolkaBtcRuntime> + Serialize> EventLog for TypedLogWriter<T> {
+    fn write(&mut self, fields: &[u8]) -> Result<(), Error> {
+        let event = T::decode(&mut &fields[..])?;
+        self.writer.write(event)
+    }
+}
+
+/// Create a log for the events of type `T`, written to the file with the given name. The log is
+/// returned together with the module and event indices that its events are encoded with.
+fn event_log<T: RuntimeEvent<PolkaBtcRuntime> + Serialize + 'static>(
+    provider: &PolkaBtcProvider,
+    folder: &Path,
+    name: &str,
+) -> Result<((u8, u8), Box<dyn EventLog>), Error> {
+    let indices = provider
+        .event_indices::<T>()
+        .ok_or(Error::UnknownEvent(T::MODULE, T::EVENT))?;
+    let log: Box<dyn EventLog> = Box::new(TypedLogWriter::<T> {
+        writer: LogWriter::new(folder, name)?,
+        _marker: PhantomData,
+    });
+    Ok((indices, log))
+}
+
+/// Write the events of the protocol pallets to the output folder, one JSON file per event type.
+/// The files are always created, such that an archive has the same layout regardless of the
+/// events emitted in the scanned range.
 #[rustfmt::skip]
-pub async fn dump_json(provider: &PolkaBtcProvider, output_folder_name: &str) -> Result<(), Error> {
+pub async fn dump_json(provider: &PolkaBtcProvider, options: PastEventsOptions, output_folder_name: &str) -> Result<(), Error> {
     let output_folder = Path::new(output_folder_name);
     if !output_folder.is_dir() {
         std::fs::create_dir_all(output_folder)?;
     }
-    
-    let mut issue_requests = LogWriter::new(output_folder, "issue-requests.json")?;
-    let mut issue_cancellations = LogWriter::new(output_folder, "issue-cancellations.json")?;
-    let mut issue_executions = LogWriter::new(output_folder, "issue-executions.json")?;
-    let mut redeem_requests = LogWriter::new(output_folder, "redeem-requests.json")?;
-    let mut redeem_cancellations = LogWriter::new(output_folder, "redeem-cancellations.json")?;
-    let mut redeem_executions = LogWriter::new(output_folder, "redeem-executions.json")?;
-
-    provider
-        .on_past_events(None, None, |event, num_blocks_remaining| {
-            info!("{} blocks remaining..", num_blocks_remaining);
-            match event {
-                Event::issue(x) => match x {
-                    IssueEvent::RequestIssue(issue_id, requester, amount, vault_id, btc_address, public_key) => {
-                        issue_requests.write(RequestIssueEvent::<PolkaBtcRuntime> {
-                            issue_id, requester, amount, vault_id, btc_address, public_key
-                        })?;
-                    }
-                    IssueEvent::CancelIssue(issue_id, requester) => {
-                        issue_cancellations.write(CancelIssueEvent::<PolkaBtcRuntime> {
-                            issue_id, requester,
-                        })?;
-                    }
-                    IssueEvent::ExecuteIssue(issue_id, requester, vault_id) => {
-                        issue_executions.write(ExecuteIssueEvent::<PolkaBtcRuntime> {
-                            issue_id, requester, vault_id,
-                        })?;
-                    }
-                },
-                Event::redeem(x) => match x {
-                    RedeemEvent::RequestRedeem(
-                        redeem_id, redeemer, amount_polka_btc, vault_id, btc_address,
-                    ) => {
-                        redeem_requests.write(RequestRedeemEvent::<PolkaBtcRuntime> {
-                            redeem_id, redeemer, amount_polka_btc, vault_id, btc_address,
-                        })?;
-                    }
-                    RedeemEvent::CancelRedeem(redeem_id, redeemer) => {
-                        redeem_cancellations.write(CancelRedeemEvent::<PolkaBtcRuntime> {
-                            redeem_id, redeemer,
-                        })?;
-                    }
-                    RedeemEvent::ExecuteRedeem(redeem_id, redeemer, vault_id) => {
-                        redeem_executions.write(ExecuteRedeemEvent::<PolkaBtcRuntime> {
-                            redeem_id, redeemer, vault_id,
-                        })?;
-                    }
-                },
-                _ => {}
+
+    let mut logs: HashMap<_, _> = vec![
+        event_log::<RequestIssueEvent<PolkaBtcRuntime>>(provider, output_folder, "issue-requests.json")?,
+        event_log::<CancelIssueEvent<PolkaBtcRuntime>>(provider, output_folder, "issue-cancellations.json")?,
+        event_log::<ExecuteIssueEvent<PolkaBtcRuntime>>(provider, output_folder, "issue-executions.json")?,
+        event_log::<RequestRedeemEvent<PolkaBtcRuntime>>(provider, output_folder, "redeem-requests.json")?,
+        event_log::<CancelRedeemEvent<PolkaBtcRuntime>>(provider, output_folder, "redeem-cancellations.json")?,
+        event_log::<ExecuteRedeemEvent<PolkaBtcRuntime>>(provider, output_folder, "redeem-executions.json")?,
+        event_log::<RequestReplaceEvent<PolkaBtcRuntime>>(provider, output_folder, "replace-requests.json")?,
+        event_log::<WithdrawReplaceEvent<PolkaBtcRuntime>>(provider, output_folder, "replace-withdrawals.json")?,
+        event_log::<AcceptReplaceEvent<PolkaBtcRuntime>>(provider, output_folder, "replace-acceptances.json")?,
+        event_log::<ExecuteReplaceEvent<PolkaBtcRuntime>>(provider, output_folder, "replace-executions.json")?,
+        event_log::<AuctionReplaceEvent<PolkaBtcRuntime>>(provider, output_folder, "replace-auctions.json")?,
+        event_log::<CancelReplaceEvent<PolkaBtcRuntime>>(provider, output_folder, "replace-cancellations.json")?,
+        event_log::<RequestRefundEvent<PolkaBtcRuntime>>(provider, output_folder, "refund-requests.json")?,
+        event_log::<ExecuteRefundEvent<PolkaBtcRuntime>>(provider, output_folder, "refund-executions.json")?,
+        event_log::<RegisterVaultEvent<PolkaBtcRuntime>>(provider, output_folder, "vault-registrations.json")?,
+        event_log::<LockAdditionalCollateralEvent<PolkaBtcRuntime>>(provider, output_folder, "vault-collateral-locks.json")?,
+        event_log::<WithdrawCollateralEvent<PolkaBtcRuntime>>(provider, output_folder, "vault-collateral-withdrawals.json")?,
+        event_log::<IncreaseToBeIssuedTokensEvent<PolkaBtcRuntime>>(provider, output_folder, "vault-to-be-issued-increases.json")?,
+        event_log::<UpdatePublicKeyEvent<PolkaBtcRuntime>>(provider, output_folder, "vault-public-key-updates.json")?,
+        event_log::<RegisterAddressEvent<PolkaBtcRuntime>>(provider, output_folder, "vault-address-registrations.json")?,
+        event_log::<RegisterStakedRelayerEvent<PolkaBtcRuntime>>(provider, output_folder, "relayer-registrations.json")?,
+        event_log::<DeregisterStakedRelayerEvent<PolkaBtcRuntime>>(provider, output_folder, "relayer-deregistrations.json")?,
+        event_log::<StatusUpdateSuggestedEvent<PolkaBtcRuntime>>(provider, output_folder, "status-update-suggestions.json")?,
+        event_log::<ExecuteStatusUpdateEvent<PolkaBtcRuntime>>(provider, output_folder, "status-update-executions.json")?,
+        event_log::<SetExchangeRateEvent<PolkaBtcRuntime>>(provider, output_folder, "exchange-rate-updates.json")?,
+        event_log::<SetBtcTxFeesPerByteEvent<PolkaBtcRuntime>>(provider, output_folder, "btc-tx-fee-updates.json")?,
+        event_log::<MintEvent<PolkaBtcRuntime>>(provider, output_folder, "treasury-mints.json")?,
+        event_log::<LockEvent<PolkaBtcRuntime>>(provider, output_folder, "treasury-locks.json")?,
+        event_log::<BurnEvent<PolkaBtcRuntime>>(provider, output_folder, "treasury-burns.json")?,
+        event_log::<RecoverFromErrorsEvent<PolkaBtcRuntime>>(provider, output_folder, "security-recoveries.json")?,
+    ].into_iter().collect();
+
+    let blocks = provider.past_events(options).await?;
+    pin_mut!(blocks);
+    while let Some(block) = blocks.next().await {
+        let block = block?;
+        for event in block.events.iter() {
+            // the encoding of the typed event starts with the index of the module and of the
+            // event within it, so each event is routed to its log and decoded only once
+            let encoded = event.encode();
+            if let Some(log) = logs.get_mut(&(encoded[0], encoded[1])) {
+                log.write(&encoded[2..])?;
             }
-            Ok(())
-        }).await?;
+        }
+        log_progress(&block);
+    }
 
     println!("Wrote json files to {}", output_folder_name);
 
@@ -127,17 +198,22 @@ pub async fn dump_json(provider: &PolkaBtcProvider, output_folder_name: &str) ->
 /// issues/redeems is very large
 pub async fn report_chain_stats(
     provider: &PolkaBtcProvider,
-    start: Option<u32>,
-    end: Option<u32>,
+    options: PastEventsOptions,
 ) -> Result<(), Error> {
     let mut issue_requests = vec![];
     let mut issue_executes = vec![];
     let mut redeem_requests = vec![];
     let mut redeem_executes = vec![];
 
-    provider
-        .on_past_events(start, end, |event, num_blocks_remaining| {
-            info!("{} blocks remaining..", num_blocks_remaining);
+    let options = PastEventsOptions {
+        pallets: Some(vec!["issue".to_string(), "redeem".to_string()]),
+        ..options
+    };
+    let blocks = provider.past_events(options).await?;
+    pin_mut!(blocks);
+    while let Some(block) = blocks.next().await {
+        let block = block?;
+        for event in block.events.iter().cloned() {
             match event {
                 Event::issue(IssueEvent::RequestIssue(id, _, amount, _, _, _)) => {
                     issue_requests.push((id, amount));
@@ -153,9 +229,9 @@ pub async fn report_chain_stats(
                 }
                 _ => {}
             }
-            Ok(())
-        })
-        .await?;
+        }
+        log_progress(&block);
+    }
 
     let issue_executes = issue_executes
         .into_iter()
@@ -180,3 +256,43 @@ pub async fn report_chain_stats(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use runtime::{testing::test_client_with, BtcPublicKey, VaultRegistryPallet};
+    use sp_keyring::AccountKeyring;
+    use tempdir::TempDir;
+
+    fn read_log(folder: &Path, name: &str) -> Vec<serde_json::Value> {
+        let file = File::open(folder.join(name)).unwrap();
+        serde_json::from_reader(file).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dump_json() {
+        let tmp = TempDir::new("testdata-gen-").unwrap();
+        let provider = test_client_with(AccountKeyring::Alice).await;
+        provider
+            .register_vault(100, BtcPublicKey([2; 33]))
+            .await
+            .unwrap();
+
+        let output_folder = tmp.path().join("json");
+        dump_json(
+            &provider,
+            PastEventsOptions::default(),
+            output_folder.to_str().unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let registrations = read_log(&output_folder, "vault-registrations.json");
+        assert_eq!(registrations.len(), 1);
+        assert_eq!(registrations[0]["collateral"], 100);
+
+        // logs of events that were not emitted are still created
+        assert!(read_log(&output_folder, "issue-requests.json").is_empty());
+        assert!(read_log(&output_folder, "security-recoveries.json").is_empty());
+    }
+}
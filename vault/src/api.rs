@@ -1,24 +1,51 @@
-use super::Error;
-use bitcoin::BitcoinCoreApi;
+use super::{
+    metrics, ActiveRequest, CancellationQueue, Error, HealthMonitor, IssueRequests, PaymentJournal,
+    PaymentStatus, ServiceHealth,
+};
+use bitcoin::{BitcoinCoreApi, Error as BitcoinError, Network, PartialAddress};
 use futures::executor::block_on;
 use hex::FromHex;
 use jsonrpc_http_server::{
-    jsonrpc_core::{serde_json::Value, Error as JsonRpcError, IoHandler, Params},
-    DomainsValidation, ServerBuilder,
+    hyper,
+    jsonrpc_core::{serde_json::Value, Error as JsonRpcError, MetaIoHandler, Params},
+    CloseHandle, DomainsValidation, RequestMiddlewareAction, ServerBuilder,
 };
-use log::info;
+use log::{info, warn};
 use parity_scale_codec::{Decode, Encode};
 use runtime::{
-    BtcPublicKey, ExchangeRateOraclePallet, FeePallet, FixedPointNumber,
+    auth::{authorize, ApiAuth, AuditLog, AuthMiddleware, Meta, Role},
+    json::{self, MethodSchema},
+    pallets::vault_registry::{
+        LockAdditionalCollateralCall, RegisterAddressCall, WithdrawCollateralCall,
+    },
+    AccountId, BtcAddress, BtcPublicKey, CacheStats, DotBalancesPallet, ExchangeRateOraclePallet,
+    FeePallet, FixedPointNumber,
     FixedPointTraits::{CheckedAdd, CheckedMul},
-    PolkaBtcProvider, ReplacePallet, UtilFuncs, VaultRegistryPallet,
+    IssuePallet, PolkaBtcIssueRequest, PolkaBtcProvider, PolkaBtcRedeemRequest,
+    PolkaBtcRefundRequest, PolkaBtcReplaceRequest, PolkaBtcVault, RedeemPallet, RefundPallet,
+    ReplacePallet, SlaHistory, SlaJsonRpcResponse, SlaSubject, UtilFuncs, VaultRegistryPallet,
 };
-use serde::{Deserialize, Deserializer};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
 use sp_arithmetic::FixedU128;
 use sp_core::crypto::Ss58Codec;
 use sp_core::H256;
 use std::{net::SocketAddr, sync::Arc};
 
+/// State of the running vault that is served by the api.
+#[derive(Clone)]
+pub struct VaultState {
+    pub sla_history: Arc<SlaHistory>,
+    pub health: HealthMonitor,
+    pub journal: Arc<PaymentJournal>,
+    pub issue_set: Arc<IssueRequests>,
+    pub issue_cancellations: CancellationQueue,
+    pub replace_cancellations: CancellationQueue,
+    pub num_confirmations: u32,
+    /// Network used to encode bitcoin addresses in the JSON responses.
+    pub network: Network,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 struct RawBytes(#[serde(deserialize_with = "hex_to_buffer")] Vec<u8>);
 
@@ -38,32 +65,49 @@ fn parse_params<T: Decode>(params: Params) -> Result<T, Error> {
     Ok(req)
 }
 
+/// Count the extrinsic rejected by the parachain, see `metrics::record_extrinsic_failure`.
+fn record_failure<T>(resp: Result<T, Error>) -> Result<T, Error> {
+    if let Err(err) = &resp {
+        metrics::record_extrinsic_failure(err);
+    }
+    resp
+}
+
 fn handle_resp<T: Encode>(resp: Result<T, Error>) -> Result<Value, JsonRpcError> {
-    match resp {
+    match record_failure(resp) {
         Ok(data) => Ok(format!("0x{}", hex::encode(data.encode())).into()),
-        Err(_) => Err(JsonRpcError::internal_error()),
+        Err(err) => Err(json::json_rpc_error(err)),
     }
 }
 
-#[derive(Encode, Decode, Debug)]
+fn encode_address(btc_address: &BtcAddress, network: Network) -> Result<String, Error> {
+    Ok(btc_address
+        .encode_str(network)
+        .map_err(BitcoinError::from)?)
+}
+
+#[derive(Encode, Decode, Serialize, JsonSchema, Debug)]
 struct AccountIdJsonRpcResponse {
     account_id: String,
 }
 
-fn _account_id(api: &Arc<PolkaBtcProvider>) -> Result<AccountIdJsonRpcResponse, Error> {
+fn _account_id<P: UtilFuncs>(api: &Arc<P>) -> Result<AccountIdJsonRpcResponse, Error> {
     Ok(AccountIdJsonRpcResponse {
         account_id: api.get_account_id().to_ss58check(),
     })
 }
 
-#[derive(Encode, Decode, Debug)]
+#[derive(Encode, Decode, Deserialize, JsonSchema, Debug)]
 struct ReplaceRequestJsonRpcRequest {
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
     amount: u128,
 }
 
-async fn _request_replace(api: &Arc<PolkaBtcProvider>, params: Params) -> Result<(), Error> {
-    let req = parse_params::<ReplaceRequestJsonRpcRequest>(params)?;
-
+async fn _request_replace<P: ExchangeRateOraclePallet + FeePallet + ReplacePallet>(
+    api: &Arc<P>,
+    req: ReplaceRequestJsonRpcRequest,
+) -> Result<(), Error> {
     let amount_in_dot = api.btc_to_dots(req.amount).await?;
     let griefing_collateral_percentage = api.get_replace_griefing_collateral().await?;
     let griefing_collateral = calculate_for(amount_in_dot, griefing_collateral_percentage)?;
@@ -92,22 +136,25 @@ fn calculate_for(amount: u128, percentage: FixedU128) -> Result<u128, Error> {
         .ok_or(Error::ArithmeticUnderflow)
 }
 
-#[derive(Encode, Decode, Debug)]
+#[derive(Encode, Decode, Deserialize, JsonSchema, Debug)]
 struct RegisterVaultJsonRpcRequest {
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
     collateral: u128,
 }
 
-#[derive(Encode, Decode, Debug)]
+#[derive(Encode, Decode, Serialize, JsonSchema, Debug)]
 struct RegisterVaultJsonRpcResponse {
+    #[serde(with = "json::hex_encoded")]
+    #[schemars(with = "String")]
     public_key: BtcPublicKey,
 }
 
-fn _register_vault<B: BitcoinCoreApi>(
-    api: &Arc<PolkaBtcProvider>,
+fn _register_vault<B: BitcoinCoreApi, P: VaultRegistryPallet>(
+    api: &Arc<P>,
     btc: &Arc<B>,
-    params: Params,
+    req: RegisterVaultJsonRpcRequest,
 ) -> Result<RegisterVaultJsonRpcResponse, Error> {
-    let req = parse_params::<RegisterVaultJsonRpcRequest>(params)?;
     let public_key: BtcPublicKey = block_on(btc.get_new_public_key())?;
     let result = block_on(api.register_vault(req.collateral, public_key.clone()));
     info!(
@@ -117,13 +164,17 @@ fn _register_vault<B: BitcoinCoreApi>(
     Ok(result.map(|_| RegisterVaultJsonRpcResponse { public_key })?)
 }
 
-#[derive(Encode, Decode, Debug)]
+#[derive(Encode, Decode, Deserialize, JsonSchema, Debug)]
 struct ChangeCollateralJsonRpcRequest {
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
     amount: u128,
 }
 
-fn _lock_additional_collateral(api: &Arc<PolkaBtcProvider>, params: Params) -> Result<(), Error> {
-    let req = parse_params::<ChangeCollateralJsonRpcRequest>(params)?;
+fn _lock_additional_collateral<P: VaultRegistryPallet>(
+    api: &Arc<P>,
+    req: ChangeCollateralJsonRpcRequest,
+) -> Result<(), Error> {
     let result = block_on(api.lock_additional_collateral(req.amount));
     info!(
         "Locking additional collateral; amount {}: {:?}",
@@ -132,8 +183,10 @@ fn _lock_additional_collateral(api: &Arc<PolkaBtcProvider>, params: Params) -> R
     Ok(result?)
 }
 
-fn _withdraw_collateral(api: &Arc<PolkaBtcProvider>, params: Params) -> Result<(), Error> {
-    let req = parse_params::<ChangeCollateralJsonRpcRequest>(params)?;
+fn _withdraw_collateral<P: VaultRegistryPallet>(
+    api: &Arc<P>,
+    req: ChangeCollateralJsonRpcRequest,
+) -> Result<(), Error> {
     let result = block_on(api.withdraw_collateral(req.amount));
     info!(
         "Withdrawing collateral with amount {}: {:?}",
@@ -143,12 +196,493 @@ fn _withdraw_collateral(api: &Arc<PolkaBtcProvider>, params: Params) -> Result<(
 }
 
 #[derive(Encode, Decode, Debug)]
+enum VaultOperation {
+    LockAdditionalCollateral(u128),
+    WithdrawCollateral(u128),
+    RegisterAddress(BtcAddress),
+}
+
+#[derive(Encode, Decode, Debug)]
+struct BatchJsonRpcRequest {
+    operations: Vec<VaultOperation>,
+}
+
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+enum JsonVaultOperation {
+    LockAdditionalCollateral {
+        #[serde(with = "json::amount")]
+        #[schemars(with = "String")]
+        amount: u128,
+    },
+    WithdrawCollateral {
+        #[serde(with = "json::amount")]
+        #[schemars(with = "String")]
+        amount: u128,
+    },
+    RegisterAddress {
+        btc_address: String,
+    },
+}
+
+#[derive(Deserialize, JsonSchema, Debug)]
+struct JsonBatchRequest {
+    operations: Vec<JsonVaultOperation>,
+}
+
+impl JsonBatchRequest {
+    fn decode(self) -> Result<BatchJsonRpcRequest, Error> {
+        let operations = self
+            .operations
+            .into_iter()
+            .map(|operation| {
+                Ok(match operation {
+                    JsonVaultOperation::LockAdditionalCollateral { amount } => {
+                        VaultOperation::LockAdditionalCollateral(amount)
+                    }
+                    JsonVaultOperation::WithdrawCollateral { amount } => {
+                        VaultOperation::WithdrawCollateral(amount)
+                    }
+                    JsonVaultOperation::RegisterAddress { btc_address } => {
+                        VaultOperation::RegisterAddress(
+                            BtcAddress::decode_str(&btc_address).map_err(BitcoinError::from)?,
+                        )
+                    }
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(BatchJsonRpcRequest { operations })
+    }
+}
+
+/// Submit the operations in a single extrinsic, such that either all or none of them succeed.
+async fn _batch(api: &Arc<PolkaBtcProvider>, req: BatchJsonRpcRequest) -> Result<(), Error> {
+    let batch = req
+        .operations
+        .iter()
+        .fold(api.batch(), |batch, operation| match operation {
+            VaultOperation::LockAdditionalCollateral(amount) => {
+                batch.add(LockAdditionalCollateralCall { amount: *amount })
+            }
+            VaultOperation::WithdrawCollateral(amount) => {
+                batch.add(WithdrawCollateralCall { amount: *amount })
+            }
+            VaultOperation::RegisterAddress(btc_address) => batch.add(RegisterAddressCall {
+                btc_address: *btc_address,
+            }),
+        });
+    let result = batch.submit().await;
+    info!("Submitting batch {:?}: {:?}", req.operations, result);
+    Ok(result?)
+}
+
+async fn _sla(
+    api: &Arc<PolkaBtcProvider>,
+    history: &SlaHistory,
+) -> Result<SlaJsonRpcResponse, Error> {
+    Ok(history.report(&**api, SlaSubject::Vault).await?)
+}
+
+/// Returns None if the storage cache is disabled.
+fn _cache_stats(api: &Arc<PolkaBtcProvider>) -> Result<Option<CacheStats>, Error> {
+    Ok(api.cache_stats())
+}
+
+fn _service_health(health: &HealthMonitor) -> Result<Vec<ServiceHealth>, Error> {
+    Ok(health.services())
+}
+
+#[derive(Encode, Decode, Debug)]
+struct VaultJsonRpcResponse {
+    vault: PolkaBtcVault,
+    collateral: u128,
+    /// Value of the collateral in BTC relative to the issued and to-be-issued tokens, as
+    /// the parachain computes it for the secure threshold. None if no tokens are issued
+    /// or to be issued.
+    collateralization: Option<FixedU128>,
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+struct JsonVaultResponse {
+    #[serde(with = "json::account_id")]
+    #[schemars(with = "String")]
+    id: AccountId,
+    status: String,
+    banned_until: Option<u32>,
+    #[serde(with = "json::hex_encoded")]
+    #[schemars(with = "String")]
+    public_key: BtcPublicKey,
+    btc_addresses: Vec<String>,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    issued_tokens: u128,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    to_be_issued_tokens: u128,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    to_be_redeemed_tokens: u128,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    collateral: u128,
+    /// Value of the collateral in BTC relative to the issued and to-be-issued tokens.
+    #[schemars(with = "Option<String>")]
+    collateralization: Option<JsonFixedU128>,
+}
+
+/// Wrapper to serialize an optional `FixedU128` as a decimal string.
+#[derive(Serialize, Debug)]
+struct JsonFixedU128(#[serde(with = "json::fixed_u128")] FixedU128);
+
+impl JsonVaultResponse {
+    fn new(resp: VaultJsonRpcResponse, network: Network) -> Result<Self, Error> {
+        let vault = resp.vault;
+        Ok(Self {
+            btc_addresses: vault
+                .wallet
+                .addresses
+                .iter()
+                .map(|btc_address| encode_address(btc_address, network))
+                .collect::<Result<_, _>>()?,
+            status: format!("{:?}", vault.status),
+            banned_until: vault.banned_until,
+            public_key: vault.wallet.public_key,
+            issued_tokens: vault.issued_tokens,
+            to_be_issued_tokens: vault.to_be_issued_tokens,
+            to_be_redeemed_tokens: vault.to_be_redeemed_tokens,
+            id: vault.id,
+            collateral: resp.collateral,
+            collateralization: resp.collateralization.map(JsonFixedU128),
+        })
+    }
+}
+
+async fn _vault<
+    P: VaultRegistryPallet + DotBalancesPallet + ExchangeRateOraclePallet + UtilFuncs,
+>(
+    api: &Arc<P>,
+) -> Result<VaultJsonRpcResponse, Error> {
+    let vault = api.get_vault(api.get_account_id().clone()).await?;
+    let collateral = api.get_reserved_dot_balance().await?;
+    let collateral_in_btc = api.dots_to_btc(collateral).await?;
+    let tokens = vault
+        .issued_tokens
+        .checked_add(vault.to_be_issued_tokens)
+        .ok_or(Error::ArithmeticOverflow)?;
+    Ok(VaultJsonRpcResponse {
+        collateralization: FixedU128::checked_from_rational(collateral_in_btc, tokens),
+        vault,
+        collateral,
+    })
+}
+
+#[derive(Encode, Decode, Serialize, JsonSchema, Debug)]
+struct BalancesJsonRpcResponse {
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    free_dot: u128,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    reserved_dot: u128,
+    /// Confirmed balance of the bitcoin wallet in satoshis.
+    btc: u64,
+}
+
+async fn _balances<B: BitcoinCoreApi, P: DotBalancesPallet>(
+    api: &Arc<P>,
+    btc: &Arc<B>,
+) -> Result<BalancesJsonRpcResponse, Error> {
+    Ok(BalancesJsonRpcResponse {
+        free_dot: api.get_free_dot_balance().await?,
+        reserved_dot: api.get_reserved_dot_balance().await?,
+        btc: btc.get_balance().await?,
+    })
+}
+
+#[derive(Encode, Decode, Debug)]
+struct IssueRequestStatus {
+    id: H256,
+    request: PolkaBtcIssueRequest,
+    /// True if the vault executes the issue once it is paid.
+    awaiting_payment: bool,
+}
+
+#[derive(Encode, Decode, Debug)]
+struct PaymentRequestStatus<T> {
+    id: H256,
+    request: T,
+    /// Progress of the payment by the vault, None if it was not started.
+    payment: Option<PaymentStatus>,
+}
+
+#[derive(Encode, Decode, Debug)]
+struct OpenRequestsJsonRpcResponse {
+    issues: Vec<IssueRequestStatus>,
+    redeems: Vec<PaymentRequestStatus<PolkaBtcRedeemRequest>>,
+    /// Replaces that are paid by this vault.
+    old_vault_replaces: Vec<PaymentRequestStatus<PolkaBtcReplaceRequest>>,
+    /// Replaces that are paid to this vault.
+    new_vault_replaces: Vec<(H256, PolkaBtcReplaceRequest)>,
+    refunds: Vec<PaymentRequestStatus<PolkaBtcRefundRequest>>,
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+struct JsonIssueRequest {
+    #[schemars(with = "String")]
+    id: H256,
+    #[serde(with = "json::account_id")]
+    #[schemars(with = "String")]
+    requester: AccountId,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    amount: u128,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    griefing_collateral: u128,
+    btc_address: String,
+    opentime: u32,
+    awaiting_payment: bool,
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+struct JsonRedeemRequest {
+    #[serde(with = "json::account_id")]
+    #[schemars(with = "String")]
+    redeemer: AccountId,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    amount_polka_btc: u128,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    amount_btc: u128,
+    btc_address: String,
+    opentime: u32,
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+struct JsonReplaceRequest {
+    #[serde(with = "json::account_id")]
+    #[schemars(with = "String")]
+    old_vault: AccountId,
+    /// SS58 address of the new vault, None until the replace is accepted.
+    new_vault: Option<String>,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    amount: u128,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    griefing_collateral: u128,
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    collateral: u128,
+    btc_address: Option<String>,
+    open_time: u32,
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+struct JsonRefundRequest {
+    #[serde(with = "json::amount")]
+    #[schemars(with = "String")]
+    amount_btc: u128,
+    btc_address: String,
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+struct JsonPaymentRequest<T> {
+    #[schemars(with = "String")]
+    id: H256,
+    request: T,
+    payment: Option<PaymentStatus>,
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+struct JsonOpenRequestsResponse {
+    issues: Vec<JsonIssueRequest>,
+    redeems: Vec<JsonPaymentRequest<JsonRedeemRequest>>,
+    old_vault_replaces: Vec<JsonPaymentRequest<JsonReplaceRequest>>,
+    new_vault_replaces: Vec<JsonPaymentRequest<JsonReplaceRequest>>,
+    refunds: Vec<JsonPaymentRequest<JsonRefundRequest>>,
+}
+
+impl JsonReplaceRequest {
+    fn new(request: PolkaBtcReplaceRequest, network: Network) -> Result<Self, Error> {
+        Ok(Self {
+            new_vault: request.new_vault.map(|new_vault| new_vault.to_ss58check()),
+            btc_address: request
+                .btc_address
+                .map(|btc_address| encode_address(&btc_address, network))
+                .transpose()?,
+            old_vault: request.old_vault,
+            amount: request.amount,
+            griefing_collateral: request.griefing_collateral,
+            collateral: request.collateral,
+            open_time: request.open_time,
+        })
+    }
+}
+
+fn json_payment_requests<T, U, F>(
+    requests: Vec<PaymentRequestStatus<T>>,
+    f: F,
+) -> Result<Vec<JsonPaymentRequest<U>>, Error>
+where
+    F: Fn(T) -> Result<U, Error>,
+{
+    requests
+        .into_iter()
+        .map(|status| {
+            Ok(JsonPaymentRequest {
+                id: status.id,
+                request: f(status.request)?,
+                payment: status.payment,
+            })
+        })
+        .collect()
+}
+
+impl JsonOpenRequestsResponse {
+    fn new(resp: OpenRequestsJsonRpcResponse, network: Network) -> Result<Self, Error> {
+        Ok(Self {
+            issues: resp
+                .issues
+                .into_iter()
+                .map(|status| {
+                    Ok(JsonIssueRequest {
+                        id: status.id,
+                        btc_address: encode_address(&status.request.btc_address, network)?,
+                        requester: status.request.requester,
+                        amount: status.request.amount,
+                        griefing_collateral: status.request.griefing_collateral,
+                        opentime: status.request.opentime,
+                        awaiting_payment: status.awaiting_payment,
+                    })
+                })
+                .collect::<Result<_, Error>>()?,
+            redeems: json_payment_requests(resp.redeems, |request| {
+                Ok(JsonRedeemRequest {
+                    btc_address: encode_address(&request.btc_address, network)?,
+                    redeemer: request.redeemer,
+                    amount_polka_btc: request.amount_polka_btc,
+                    amount_btc: request.amount_btc,
+                    opentime: request.opentime,
+                })
+            })?,
+            old_vault_replaces: json_payment_requests(resp.old_vault_replaces, |request| {
+                JsonReplaceRequest::new(request, network)
+            })?,
+            new_vault_replaces: resp
+                .new_vault_replaces
+                .into_iter()
+                .map(|(id, request)| {
+                    Ok(JsonPaymentRequest {
+                        id,
+                        request: JsonReplaceRequest::new(request, network)?,
+                        payment: None,
+                    })
+                })
+                .collect::<Result<_, Error>>()?,
+            refunds: json_payment_requests(resp.refunds, |request| {
+                Ok(JsonRefundRequest {
+                    btc_address: encode_address(&request.btc_address, network)?,
+                    amount_btc: request.amount_btc,
+                })
+            })?,
+        })
+    }
+}
+
+fn with_payment_status<T>(
+    journal: &PaymentJournal,
+    requests: Vec<(H256, T)>,
+) -> Result<Vec<PaymentRequestStatus<T>>, Error> {
+    requests
+        .into_iter()
+        .map(|(id, request)| {
+            Ok(PaymentRequestStatus {
+                id,
+                request,
+                payment: journal.get(id)?.map(|entry| entry.status),
+            })
+        })
+        .collect()
+}
+
+async fn _open_requests<
+    P: IssuePallet + RedeemPallet + ReplacePallet + RefundPallet + UtilFuncs,
+>(
+    api: &Arc<P>,
+    state: &VaultState,
+) -> Result<OpenRequestsJsonRpcResponse, Error> {
+    let vault_id = api.get_account_id().clone();
+
+    let mut issues = Vec::new();
+    for (id, request) in api.get_vault_issue_requests(vault_id.clone()).await? {
+        if !request.completed && !request.cancelled {
+            issues.push(IssueRequestStatus {
+                id,
+                request,
+                awaiting_payment: state.issue_set.contains(&id).await,
+            });
+        }
+    }
+
+    let redeems = api
+        .get_vault_redeem_requests(vault_id.clone())
+        .await?
+        .into_iter()
+        .filter(|(_, request)| !request.completed && !request.cancelled)
+        .collect();
+    let old_vault_replaces = api
+        .get_old_vault_replace_requests(vault_id.clone())
+        .await?
+        .into_iter()
+        .filter(|(_, request)| !request.completed && !request.cancelled)
+        .collect();
+    let new_vault_replaces = api
+        .get_new_vault_replace_requests(vault_id.clone())
+        .await?
+        .into_iter()
+        .filter(|(_, request)| !request.completed && !request.cancelled)
+        .collect();
+    let refunds = api
+        .get_vault_refund_requests(vault_id)
+        .await?
+        .into_iter()
+        .filter(|(_, request)| !request.completed)
+        .collect();
+
+    Ok(OpenRequestsJsonRpcResponse {
+        issues,
+        redeems: with_payment_status(&state.journal, redeems)?,
+        old_vault_replaces: with_payment_status(&state.journal, old_vault_replaces)?,
+        new_vault_replaces,
+        refunds: with_payment_status(&state.journal, refunds)?,
+    })
+}
+
+#[derive(Encode, Decode, Serialize, JsonSchema, Debug)]
+struct CancellationQueueJsonRpcResponse {
+    issues: Vec<ActiveRequest>,
+    replaces: Vec<ActiveRequest>,
+}
+
+fn _cancellation_queue(state: &VaultState) -> Result<CancellationQueueJsonRpcResponse, Error> {
+    Ok(CancellationQueueJsonRpcResponse {
+        issues: state.issue_cancellations.requests(),
+        replaces: state.replace_cancellations.requests(),
+    })
+}
+
+#[derive(Encode, Decode, Deserialize, JsonSchema, Debug)]
 struct WithdrawReplaceJsonRpcRequest {
+    #[schemars(with = "String")]
     replace_id: H256,
 }
 
-fn _withdraw_replace(api: &Arc<PolkaBtcProvider>, params: Params) -> Result<(), Error> {
-    let req = parse_params::<WithdrawReplaceJsonRpcRequest>(params)?;
+fn _withdraw_replace<P: ReplacePallet>(
+    api: &Arc<P>,
+    req: WithdrawReplaceJsonRpcRequest,
+) -> Result<(), Error> {
     let result = block_on(api.withdraw_replace(req.replace_id));
     info!(
         "Withdrawing replace request {}: {:?}",
@@ -157,59 +691,607 @@ fn _withdraw_replace(api: &Arc<PolkaBtcProvider>, params: Params) -> Result<(),
     Ok(result?)
 }
 
+fn _json_schema() -> Result<Vec<MethodSchema>, Error> {
+    Ok(vec![
+        MethodSchema::new::<(), AccountIdJsonRpcResponse>(
+            "json_account_id",
+            "SS58 address of the vault account.",
+        ),
+        MethodSchema::new::<ReplaceRequestJsonRpcRequest, ()>(
+            "json_request_replace",
+            "Request the replacement of the given amount of issued PolkaBTC in satoshis.",
+        ),
+        MethodSchema::new::<RegisterVaultJsonRpcRequest, RegisterVaultJsonRpcResponse>(
+            "json_register_vault",
+            "Register the vault with the given DOT collateral in planck.",
+        ),
+        MethodSchema::new::<ChangeCollateralJsonRpcRequest, ()>(
+            "json_lock_additional_collateral",
+            "Lock additional DOT collateral in planck.",
+        ),
+        MethodSchema::new::<ChangeCollateralJsonRpcRequest, ()>(
+            "json_withdraw_collateral",
+            "Withdraw DOT collateral in planck.",
+        ),
+        MethodSchema::new::<WithdrawReplaceJsonRpcRequest, ()>(
+            "json_withdraw_replace",
+            "Withdraw an open replace request.",
+        ),
+        MethodSchema::new::<JsonBatchRequest, ()>(
+            "json_batch",
+            "Submit the operations in a single extrinsic.",
+        ),
+        SlaJsonRpcResponse::method_schema(),
+        MethodSchema::new::<(), Option<CacheStats>>(
+            "json_cache_stats",
+            "Storage cache hits and misses, null if the cache is disabled.",
+        ),
+        MethodSchema::new::<(), Vec<ServiceHealth>>(
+            "json_service_health",
+            "Status of the services of the vault.",
+        ),
+        MethodSchema::new::<(), JsonVaultResponse>(
+            "json_vault",
+            "Registration and collateralization of the vault.",
+        ),
+        MethodSchema::new::<(), BalancesJsonRpcResponse>(
+            "json_balances",
+            "DOT balances in planck and the bitcoin balance in satoshis.",
+        ),
+        MethodSchema::new::<(), JsonOpenRequestsResponse>(
+            "json_open_requests",
+            "Open requests of the vault with the progress of their payments.",
+        ),
+        MethodSchema::new::<(), CancellationQueueJsonRpcResponse>(
+            "json_cancellation_queue",
+            "Requests that are cancelled once they expire.",
+        ),
+        MethodSchema::new::<(), u32>(
+            "json_bitcoin_confirmations",
+            "Number of bitcoin confirmations required for payments.",
+        ),
+    ])
+}
+
+/// Role required to call the method. Methods that are not listed change the state of the
+/// vault and require the admin role.
+fn required_role(method: &str) -> Option<Role> {
+    match method.trim_start_matches("json_") {
+        "account_id"
+        | "sla"
+        | "cache_stats"
+        | "service_health"
+        | "vault"
+        | "balances"
+        | "open_requests"
+        | "cancellation_queue"
+        | "bitcoin_confirmations"
+        | "schema" => Some(Role::Read),
+        _ => Some(Role::Admin),
+    }
+}
+
+fn authorization(req: &hyper::Request<hyper::Body>) -> Option<&str> {
+    req.headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Respond to a prometheus scrape, which requires a token with the read role.
+fn serve_metrics(
+    auth: &ApiAuth,
+    req: &hyper::Request<hyper::Body>,
+) -> hyper::Response<hyper::Body> {
+    if authorize(
+        auth.authenticate(authorization(req)).as_ref(),
+        Some(Role::Read),
+    )
+    .is_err()
+    {
+        let mut response = hyper::Response::new(hyper::Body::empty());
+        *response.status_mut() = hyper::StatusCode::UNAUTHORIZED;
+        return response;
+    }
+    let mut response = hyper::Response::new(metrics::encode().into());
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static(prometheus::TEXT_FORMAT),
+    );
+    response
+}
+
 pub async fn start<B: BitcoinCoreApi + Send + Sync + 'static>(
     api: Arc<PolkaBtcProvider>,
     btc: Arc<B>,
+    state: VaultState,
     addr: SocketAddr,
-    origin: String,
-) {
-    let mut io = IoHandler::default();
+    origins: Option<String>,
+    auth: ApiAuth,
+    audit: AuditLog,
+) -> Result<(), Error> {
+    if !auth.is_enabled() && !addr.ip().is_loopback() {
+        warn!(
+            "The api listens on {} without authentication, anyone who can reach it can withdraw the collateral",
+            addr
+        );
+    }
+    let mut io = MetaIoHandler::with_middleware(AuthMiddleware::new(required_role, audit));
     {
         let api = api.clone();
         io.add_sync_method("account_id", move |_| handle_resp(_account_id(&api)));
     }
+    {
+        let api = api.clone();
+        io.add_sync_method("json_account_id", move |_| {
+            json::handle_json_resp(_account_id(&api))
+        });
+    }
     {
         let api = api.clone();
         io.add_method("request_replace", move |params| {
             let api = api.clone();
-            async move { handle_resp(_request_replace(&api, params).await) }
+            async move {
+                handle_resp(async { _request_replace(&api, parse_params(params)?).await }.await)
+            }
+        });
+    }
+    {
+        let api = api.clone();
+        io.add_method("json_request_replace", move |params| {
+            let api = api.clone();
+            async move {
+                json::handle_json_resp(record_failure(
+                    async {
+                        _request_replace(&api, json::parse_json_params::<_, Error>(params)?).await
+                    }
+                    .await,
+                ))
+            }
         });
     }
     {
         let api = api.clone();
         let btc = btc.clone();
         io.add_sync_method("register_vault", move |params| {
-            handle_resp(_register_vault(&api, &btc, params))
+            handle_resp(parse_params(params).and_then(|req| _register_vault(&api, &btc, req)))
+        });
+    }
+    {
+        let api = api.clone();
+        let btc = btc.clone();
+        io.add_sync_method("json_register_vault", move |params| {
+            json::handle_json_resp(record_failure(
+                json::parse_json_params(params).and_then(|req| _register_vault(&api, &btc, req)),
+            ))
         });
     }
     {
         let api = api.clone();
         io.add_sync_method("lock_additional_collateral", move |params| {
-            handle_resp(_lock_additional_collateral(&api, params))
+            handle_resp(parse_params(params).and_then(|req| _lock_additional_collateral(&api, req)))
+        });
+    }
+    {
+        let api = api.clone();
+        io.add_sync_method("json_lock_additional_collateral", move |params| {
+            json::handle_json_resp(record_failure(
+                json::parse_json_params(params)
+                    .and_then(|req| _lock_additional_collateral(&api, req)),
+            ))
         });
     }
     {
         let api = api.clone();
         io.add_sync_method("withdraw_collateral", move |params| {
-            handle_resp(_withdraw_collateral(&api, params))
+            handle_resp(parse_params(params).and_then(|req| _withdraw_collateral(&api, req)))
+        });
+    }
+    {
+        let api = api.clone();
+        io.add_sync_method("json_withdraw_collateral", move |params| {
+            json::handle_json_resp(record_failure(
+                json::parse_json_params(params).and_then(|req| _withdraw_collateral(&api, req)),
+            ))
         });
     }
     {
         let api = api.clone();
         io.add_sync_method("withdraw_replace", move |params| {
-            handle_resp(_withdraw_replace(&api, params))
+            handle_resp(parse_params(params).and_then(|req| _withdraw_replace(&api, req)))
+        });
+    }
+    {
+        let api = api.clone();
+        io.add_sync_method("json_withdraw_replace", move |params| {
+            json::handle_json_resp(record_failure(
+                json::parse_json_params(params).and_then(|req| _withdraw_replace(&api, req)),
+            ))
+        });
+    }
+
+    {
+        let api = api.clone();
+        io.add_method("batch", move |params| {
+            let api = api.clone();
+            async move { handle_resp(async { _batch(&api, parse_params(params)?).await }.await) }
+        });
+    }
+    {
+        let api = api.clone();
+        io.add_method("json_batch", move |params| {
+            let api = api.clone();
+            async move {
+                json::handle_json_resp(record_failure(
+                    async {
+                        let req: JsonBatchRequest = json::parse_json_params::<_, Error>(params)?;
+                        _batch(&api, req.decode()?).await
+                    }
+                    .await,
+                ))
+            }
+        });
+    }
+    {
+        let api = api.clone();
+        let sla_history = state.sla_history.clone();
+        io.add_method("sla", move |_| {
+            let api = api.clone();
+            let sla_history = sla_history.clone();
+            async move { handle_resp(_sla(&api, &sla_history).await) }
+        });
+    }
+    {
+        let api = api.clone();
+        let sla_history = state.sla_history.clone();
+        io.add_method("json_sla", move |_| {
+            let api = api.clone();
+            let sla_history = sla_history.clone();
+            async move { json::handle_json_resp(_sla(&api, &sla_history).await) }
+        });
+    }
+
+    {
+        let api = api.clone();
+        io.add_sync_method("cache_stats", move |_| handle_resp(_cache_stats(&api)));
+    }
+    {
+        let api = api.clone();
+        io.add_sync_method("json_cache_stats", move |_| {
+            json::handle_json_resp(_cache_stats(&api))
+        });
+    }
+    {
+        let health = state.health.clone();
+        io.add_sync_method("service_health", move |_| {
+            handle_resp(_service_health(&health))
+        });
+    }
+    {
+        let health = state.health.clone();
+        io.add_sync_method("json_service_health", move |_| {
+            json::handle_json_resp(_service_health(&health))
+        });
+    }
+    {
+        let api = api.clone();
+        io.add_method("vault", move |_| {
+            let api = api.clone();
+            async move { handle_resp(_vault(&api).await) }
+        });
+    }
+    {
+        let api = api.clone();
+        let network = state.network;
+        io.add_method("json_vault", move |_| {
+            let api = api.clone();
+            async move {
+                json::handle_json_resp(
+                    async { JsonVaultResponse::new(_vault(&api).await?, network) }.await,
+                )
+            }
+        });
+    }
+    {
+        let api = api.clone();
+        let btc = btc.clone();
+        io.add_method("balances", move |_| {
+            let api = api.clone();
+            let btc = btc.clone();
+            async move { handle_resp(_balances(&api, &btc).await) }
+        });
+    }
+    {
+        let api = api.clone();
+        let btc = btc.clone();
+        io.add_method("json_balances", move |_| {
+            let api = api.clone();
+            let btc = btc.clone();
+            async move { json::handle_json_resp(_balances(&api, &btc).await) }
+        });
+    }
+    {
+        let api = api.clone();
+        let state = state.clone();
+        io.add_method("open_requests", move |_| {
+            let api = api.clone();
+            let state = state.clone();
+            async move { handle_resp(_open_requests(&api, &state).await) }
+        });
+    }
+    {
+        let api = api.clone();
+        let state = state.clone();
+        io.add_method("json_open_requests", move |_| {
+            let api = api.clone();
+            let state = state.clone();
+            async move {
+                json::handle_json_resp(
+                    async {
+                        JsonOpenRequestsResponse::new(
+                            _open_requests(&api, &state).await?,
+                            state.network,
+                        )
+                    }
+                    .await,
+                )
+            }
+        });
+    }
+    {
+        let state = state.clone();
+        io.add_sync_method("cancellation_queue", move |_| {
+            handle_resp(_cancellation_queue(&state))
+        });
+    }
+    {
+        let state = state.clone();
+        io.add_sync_method("json_cancellation_queue", move |_| {
+            json::handle_json_resp(_cancellation_queue(&state))
+        });
+    }
+    {
+        let num_confirmations = state.num_confirmations;
+        io.add_sync_method("bitcoin_confirmations", move |_| {
+            handle_resp(Ok(num_confirmations))
+        });
+        io.add_sync_method("json_bitcoin_confirmations", move |_| {
+            json::handle_json_resp(Ok::<_, Error>(num_confirmations))
         });
     }
+    io.add_sync_method("json_schema", move |_| {
+        json::handle_json_resp(_json_schema())
+    });
 
-    let server = ServerBuilder::new(io)
-        .rest_api(jsonrpc_http_server::RestApi::Unsecure)
-        .cors(DomainsValidation::AllowOnly(vec![origin.into()]))
-        .start_http(&addr)
-        .expect("Unable to start RPC server");
+    let auth = Arc::new(auth);
+    let metrics_auth = auth.clone();
+    let server =
+        ServerBuilder::with_meta_extractor(io, move |req: &hyper::Request<hyper::Body>| {
+            Meta(auth.authenticate(authorization(req)))
+        })
+        .request_middleware(move |req: hyper::Request<hyper::Body>| {
+            if req.method() == hyper::Method::GET && req.uri().path() == "/metrics" {
+                serve_metrics(&metrics_auth, &req).into()
+            } else {
+                RequestMiddlewareAction::from(req)
+            }
+        })
+        .rest_api(jsonrpc_http_server::RestApi::Secure)
+        .cors(DomainsValidation::AllowOnly(
+            origins
+                .iter()
+                .flat_map(|origins| origins.split(','))
+                .map(|origin| origin.trim().into())
+                .collect(),
+        ))
+        .start_http(&addr)?;
 
+    // the supervisor stops the api by dropping this future, which has to close the server
+    // since it keeps taking calls on its own threads otherwise
+    let _close = CloseOnDrop(server.close_handle());
     tokio::task::spawn_blocking(move || {
         server.wait();
     })
     .await
     .unwrap();
+    Ok(())
+}
+
+/// Closes the server when dropped.
+struct CloseOnDrop(CloseHandle);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        self.0.clone().close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{execution::RequestType, Supervisor};
+    use runtime::testing::MockParachain;
+    use sp_core::H160;
+    use sp_keyring::AccountKeyring;
+    use tempdir::TempDir;
+
+    /// A parachain with Bob registered as vault with 300 collateral, signing as Bob.
+    async fn vault_parachain() -> Arc<MockParachain> {
+        let vault_id = AccountKeyring::Bob.to_account_id();
+        let parachain = MockParachain::new(vault_id.clone());
+        parachain.set_free_dot_balance(vault_id, 1000);
+        parachain
+            .register_vault(300, Default::default())
+            .await
+            .unwrap();
+        Arc::new(parachain)
+    }
+
+    /// Issue `amount` tokens of the vault to Alice, returns Alice's handle.
+    async fn issue_tokens(parachain: &MockParachain, amount: u128) -> MockParachain {
+        let user = parachain.with_account(AccountKeyring::Alice.to_account_id());
+        let issue = user
+            .request_issue(amount, parachain.get_account_id().clone(), 0)
+            .await
+            .unwrap();
+        user.execute_issue(issue.issue_id, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
+        user
+    }
+
+    fn vault_state(tmp_dir: &TempDir) -> VaultState {
+        VaultState {
+            sla_history: Default::default(),
+            health: Supervisor::new().health(),
+            journal: Arc::new(PaymentJournal::open(tmp_dir.path()).unwrap()),
+            issue_set: Arc::new(IssueRequests::new()),
+            issue_cancellations: Default::default(),
+            replace_cancellations: Default::default(),
+            num_confirmations: 1,
+            network: Network::Regtest,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_account_id() {
+        let parachain = vault_parachain().await;
+        assert_eq!(
+            _account_id(&parachain).unwrap().account_id,
+            AccountKeyring::Bob.to_account_id().to_ss58check()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_vault_collateralization_includes_to_be_issued() {
+        let parachain = vault_parachain().await;
+        let resp = _vault(&parachain).await.unwrap();
+        assert_eq!(resp.collateral, 300);
+        assert_eq!(resp.collateralization, None);
+
+        // the collateral backs the pending issue as well
+        let user = parachain.with_account(AccountKeyring::Alice.to_account_id());
+        user.request_issue(100, parachain.get_account_id().clone(), 0)
+            .await
+            .unwrap();
+        let resp = _vault(&parachain).await.unwrap();
+        assert_eq!(resp.vault.issued_tokens, 0);
+        assert_eq!(
+            resp.collateralization,
+            Some(FixedU128::saturating_from_integer(3))
+        );
+
+        issue_tokens(&parachain, 50).await;
+        let resp = _vault(&parachain).await.unwrap();
+        assert_eq!(
+            resp.collateralization,
+            Some(FixedU128::saturating_from_integer(2))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_change_collateral() {
+        let parachain = vault_parachain().await;
+        assert!(_lock_additional_collateral(
+            &parachain,
+            ChangeCollateralJsonRpcRequest { amount: 100 }
+        )
+        .is_ok());
+        assert_eq!(parachain.get_reserved_dot_balance().await.unwrap(), 400);
+
+        // the collateral of the issued tokens can not be withdrawn
+        issue_tokens(&parachain, 200).await;
+        assert!(
+            _withdraw_collateral(&parachain, ChangeCollateralJsonRpcRequest { amount: 150 })
+                .is_err()
+        );
+        assert!(
+            _withdraw_collateral(&parachain, ChangeCollateralJsonRpcRequest { amount: 100 })
+                .is_ok()
+        );
+        assert_eq!(parachain.get_reserved_dot_balance().await.unwrap(), 300);
+    }
+
+    #[tokio::test]
+    async fn test_request_and_withdraw_replace() {
+        let parachain = vault_parachain().await;
+        issue_tokens(&parachain, 100).await;
+
+        _request_replace(&parachain, ReplaceRequestJsonRpcRequest { amount: 50 })
+            .await
+            .unwrap();
+        let replaces = parachain
+            .get_old_vault_replace_requests(parachain.get_account_id().clone())
+            .await
+            .unwrap();
+        assert_eq!(replaces.len(), 1);
+        let (replace_id, request) = &replaces[0];
+        assert_eq!(request.amount, 50);
+        // 10% of the replaced amount in DOT
+        assert_eq!(request.griefing_collateral, 5);
+
+        _withdraw_replace(
+            &parachain,
+            WithdrawReplaceJsonRpcRequest {
+                replace_id: *replace_id,
+            },
+        )
+        .unwrap();
+        assert!(parachain
+            .get_old_vault_replace_requests(parachain.get_account_id().clone())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_open_requests() {
+        let tmp_dir = TempDir::new("api").unwrap();
+        let state = vault_state(&tmp_dir);
+        let parachain = vault_parachain().await;
+        let vault_id = parachain.get_account_id().clone();
+
+        let user = issue_tokens(&parachain, 100).await;
+        let issue = user.request_issue(10, vault_id.clone(), 0).await.unwrap();
+        let redeem_id = user
+            .request_redeem(20, BtcAddress::P2PKH(H160::zero()), vault_id)
+            .await
+            .unwrap();
+        state
+            .journal
+            .record_intent(redeem_id, RequestType::Redeem, 20)
+            .unwrap();
+
+        let resp = _open_requests(&parachain, &state).await.unwrap();
+        // the executed issue is not open
+        assert_eq!(resp.issues.len(), 1);
+        assert_eq!(resp.issues[0].id, issue.issue_id);
+        assert!(!resp.issues[0].awaiting_payment);
+        assert_eq!(resp.redeems.len(), 1);
+        assert_eq!(resp.redeems[0].id, redeem_id);
+        assert_eq!(resp.redeems[0].payment, Some(PaymentStatus::Intent));
+        assert!(resp.old_vault_replaces.is_empty());
+        assert!(resp.new_vault_replaces.is_empty());
+        assert!(resp.refunds.is_empty());
+
+        let json = JsonOpenRequestsResponse::new(resp, state.network).unwrap();
+        assert_eq!(json.redeems[0].request.amount_polka_btc, 20);
+    }
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(required_role("vault"), Some(Role::Read));
+        assert_eq!(required_role("json_open_requests"), Some(Role::Read));
+        assert_eq!(required_role("json_schema"), Some(Role::Read));
+        assert_eq!(required_role("withdraw_collateral"), Some(Role::Admin));
+        assert_eq!(required_role("json_batch"), Some(Role::Admin));
+        assert_eq!(required_role("unknown"), Some(Role::Admin));
+    }
+
+    #[test]
+    fn test_calculate_for() {
+        let percentage = FixedU128::saturating_from_rational(1, 10);
+        assert_eq!(calculate_for(50, percentage).unwrap(), 5);
+        // rounds to the nearest integer
+        assert_eq!(calculate_for(55, percentage).unwrap(), 6);
+    }
 }
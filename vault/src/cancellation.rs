@@ -1,12 +1,16 @@
 use super::Error;
+use crate::metrics::{self, CANCELLATIONS};
 use async_trait::async_trait;
 use futures::channel::mpsc::Receiver;
 use futures::*;
 use log::*;
+use parity_scale_codec::{Decode, Encode};
 use runtime::{AccountId, IssuePallet, PolkaBtcHeader, ReplacePallet, UtilFuncs};
+use schemars::JsonSchema;
+use serde::Serialize;
 use sp_core::H256;
 use std::marker::{Send, Sync};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 pub enum RequestEvent {
     /// new issue requested / replace accepted
@@ -19,12 +23,29 @@ pub struct CancellationScheduler<P: IssuePallet + ReplacePallet + UtilFuncs> {
     provider: Arc<P>,
     vault_id: AccountId,
     period: Option<u32>,
+    queue: CancellationQueue,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-struct ActiveRequest {
-    id: H256,
-    end_time: u32,
+#[derive(Copy, Clone, Debug, PartialEq, Encode, Decode, Serialize, JsonSchema)]
+pub struct ActiveRequest {
+    #[schemars(with = "String")]
+    pub id: H256,
+    /// The parachain height after which the request is cancelled.
+    pub end_time: u32,
+}
+
+/// The requests a `CancellationScheduler` is waiting to cancel, shared with the api.
+#[derive(Clone, Default)]
+pub struct CancellationQueue(Arc<RwLock<Vec<ActiveRequest>>>);
+
+impl CancellationQueue {
+    pub fn requests(&self) -> Vec<ActiveRequest> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn update(&self, requests: &[ActiveRequest]) {
+        *self.0.write().unwrap() = requests.to_vec();
+    }
 }
 
 pub struct UnconvertedOpenTime {
@@ -212,9 +233,14 @@ impl<P: IssuePallet + ReplacePallet + UtilFuncs> CancellationScheduler<P> {
             provider,
             vault_id,
             period: None,
+            queue: Default::default(),
         }
     }
 
+    pub fn queue(&self) -> CancellationQueue {
+        self.queue.clone()
+    }
+
     /// Listens for issuing events (i.e. issue received/executed). When
     /// the issue period has expired without the issue having been executed,
     /// this function will attempt to call cancel_event to get the collateral back.
@@ -241,6 +267,7 @@ impl<P: IssuePallet + ReplacePallet + UtilFuncs> CancellationScheduler<P> {
                     ProductionEventSelector,
                 )
                 .await?;
+            self.queue.update(&active_requests);
         }
     }
 
@@ -265,6 +292,8 @@ impl<P: IssuePallet + ReplacePallet + UtilFuncs> CancellationScheduler<P> {
                     error!("Failed to query open {}s: {}", T::TYPE_NAME, e);
                 }
             }
+            // show the refreshed list while waiting for the next block or event
+            self.queue.update(active_requests);
         }
 
         match selector
@@ -282,8 +311,12 @@ impl<P: IssuePallet + ReplacePallet + UtilFuncs> CancellationScheduler<P> {
 
                 for request in cancellable_requests {
                     match T::cancel_request(self.provider.clone(), request.id).await {
-                        Ok(_) => info!("Canceled {} #{}", T::TYPE_NAME, request.id),
+                        Ok(_) => {
+                            CANCELLATIONS.with_label_values(&[T::TYPE_NAME]).inc();
+                            info!("Canceled {} #{}", T::TYPE_NAME, request.id)
+                        }
                         Err(e) => {
+                            metrics::record_extrinsic_failure(&e);
                             // failed to cancel; get up-to-date request list in next iteration
                             error!("Failed to cancel {}: {}", T::TYPE_NAME, e);
                             return Ok(ListState::Invalid);
@@ -366,14 +399,16 @@ mod tests {
     use async_trait::async_trait;
     use futures::channel::mpsc;
     use runtime::{
+        pallets::issue::CancelIssueEvent,
         sp_runtime::{
             generic::Digest,
             traits::{BlakeTwo256, Hash},
         },
-        AccountId, BtcAddress, Error as RuntimeError, H256Le, PolkaBtcIssueRequest,
-        PolkaBtcReplaceRequest, PolkaBtcRequestIssueEvent,
+        testing::{MockParachain, DEFAULT_REQUEST_PERIOD},
+        PolkaBtcIssueRequest, PolkaBtcRuntime, VaultRegistryPallet,
     };
     use sp_core::H256;
+    use sp_keyring::AccountKeyring;
 
     macro_rules! assert_err {
         ($result:expr, $err:pat) => {{
@@ -417,73 +452,42 @@ mod tests {
         }
     }
 
-    mockall::mock! {
-        Provider {}
-
-        #[async_trait]
-        pub trait IssuePallet {
-            async fn request_issue(
-                &self,
-                amount: u128,
-                vault_id: AccountId,
-                griefing_collateral: u128,
-            ) -> Result<PolkaBtcRequestIssueEvent, RuntimeError>;
-            async fn execute_issue(
-                &self,
-                issue_id: H256,
-                tx_id: H256Le,
-                merkle_proof: Vec<u8>,
-                raw_tx: Vec<u8>,
-            ) -> Result<(), RuntimeError>;
-            async fn cancel_issue(&self, issue_id: H256) -> Result<(), RuntimeError>;
-            async fn get_issue_request(&self, issue_id: H256) -> Result<PolkaBtcIssueRequest, RuntimeError>;
-            async fn get_vault_issue_requests(
-                &self,
-                account_id: AccountId,
-            ) -> Result<Vec<(H256, PolkaBtcIssueRequest)>, RuntimeError>;
-            async fn get_issue_period(&self) -> Result<u32, RuntimeError>;
-            async fn set_issue_period(&self, period: u32) -> Result<(), RuntimeError>;
-        }
+    /// Returns the handle of Bob to a parachain on which Bob is a vault.
+    async fn parachain_with_vault() -> MockParachain {
+        let vault_id = AccountKeyring::Bob.to_account_id();
+        let parachain = MockParachain::new(vault_id.clone());
+        parachain.set_free_dot_balance(vault_id, 1000);
+        parachain
+            .register_vault(1000, Default::default())
+            .await
+            .unwrap();
+        parachain
+    }
 
-        #[async_trait]
-        pub trait ReplacePallet {
-            async fn request_replace(&self, amount: u128, griefing_collateral: u128)
-                -> Result<H256, RuntimeError>;
-            async fn withdraw_replace(&self, replace_id: H256) -> Result<(), RuntimeError>;
-            async fn accept_replace(&self, replace_id: H256, collateral: u128, btc_address: BtcAddress) -> Result<(), RuntimeError>;
-            async fn auction_replace(
-                &self,
-                old_vault: AccountId,
-                btc_amount: u128,
-                collateral: u128,
-                btc_address: BtcAddress,
-            ) -> Result<(), RuntimeError>;
-            async fn execute_replace(
-                &self,
-                replace_id: H256,
-                tx_id: H256Le,
-                merkle_proof: Vec<u8>,
-                raw_tx: Vec<u8>,
-            ) -> Result<(), RuntimeError>;
-            async fn cancel_replace(&self, replace_id: H256) -> Result<(), RuntimeError>;
-            async fn get_replace_request(&self, replace_id: H256) -> Result<PolkaBtcReplaceRequest, RuntimeError>;
-            async fn get_new_vault_replace_requests(
-                &self,
-                account_id: AccountId,
-            ) -> Result<Vec<(H256, PolkaBtcReplaceRequest)>, RuntimeError>;
-            async fn get_old_vault_replace_requests(
-                &self,
-                account_id: AccountId,
-            ) -> Result<Vec<(H256, PolkaBtcReplaceRequest)>, RuntimeError>;
-            async fn get_replace_period(&self) -> Result<u32, RuntimeError>;
-            async fn set_replace_period(&self, period: u32) -> Result<(), RuntimeError>;
-        }
-        #[async_trait]
-        pub trait UtilFuncs {
-            async fn get_current_chain_height(&self) -> Result<u32, RuntimeError>;
-            async fn get_blockchain_height_at(&self, parachain_height: u32) -> Result<u32, RuntimeError>;
-            fn get_account_id(&self) -> &AccountId;
+    /// Alice requests an issue from the vault at each of the given parachain heights,
+    /// returns the ids of the requests.
+    async fn request_issues(parachain: &MockParachain, open_times: &[u32]) -> Vec<H256> {
+        let user = parachain.with_account(AccountKeyring::Alice.to_account_id());
+        let vault_id = parachain.get_account_id().clone();
+        let mut issue_ids = vec![];
+        for open_time in open_times {
+            let height = parachain.get_current_chain_height().await.unwrap();
+            parachain.advance_chain(open_time - height);
+            let event = user.request_issue(1, vault_id.clone(), 0).await.unwrap();
+            issue_ids.push(event.issue_id);
         }
+        issue_ids
+    }
+
+    /// Produce blocks until the given parachain height.
+    async fn advance_to(parachain: &MockParachain, height: u32) {
+        let current_height = parachain.get_current_chain_height().await.unwrap();
+        parachain.advance_chain(height - current_height);
+    }
+
+    fn scheduler(parachain: &MockParachain) -> CancellationScheduler<MockParachain> {
+        let vault_id = parachain.get_account_id().clone();
+        CancellationScheduler::new(Arc::new(parachain.clone()), vault_id)
     }
 
     #[tokio::test]
@@ -491,45 +495,21 @@ mod tests {
         // open_time = 95, current_block = 100, period = 10: remaining = 5 + margin
         // open_time = 10,  current_block = 100, period = 10: remaining = 0
         // open_time = 85,  current_block = 100, period = 10: remaining = -5 + margin
-        let mut provider = MockProvider::default();
-        provider
-            .expect_get_vault_issue_requests()
-            .times(1)
-            .returning(|_| {
-                Ok(vec![
-                    (
-                        H256::from_slice(&[1; 32]),
-                        PolkaBtcIssueRequest {
-                            opentime: 95,
-                            ..Default::default()
-                        },
-                    ),
-                    (
-                        H256::from_slice(&[2; 32]),
-                        PolkaBtcIssueRequest {
-                            opentime: 10,
-                            ..Default::default()
-                        },
-                    ),
-                    (
-                        H256::from_slice(&[3; 32]),
-                        PolkaBtcIssueRequest {
-                            opentime: 85,
-                            ..Default::default()
-                        },
-                    ),
-                ])
-            });
-        provider
-            .expect_get_current_chain_height()
-            .times(1)
-            .returning(|| Ok(100));
-        provider
-            .expect_get_issue_period()
-            .times(1)
-            .returning(|| Ok(10));
-
-        let mut canceller = CancellationScheduler::new(Arc::new(provider), Default::default());
+        let parachain = parachain_with_vault().await;
+        assert_eq!(DEFAULT_REQUEST_PERIOD, 10);
+        for &(byte, opentime) in &[(1, 95), (2, 10), (3, 85)] {
+            parachain.insert_issue_request(
+                H256::from_slice(&[byte; 32]),
+                PolkaBtcIssueRequest {
+                    vault: parachain.get_account_id().clone(),
+                    opentime,
+                    ..Default::default()
+                },
+            );
+        }
+        advance_to(&parachain, 100).await;
+
+        let mut canceller = scheduler(&parachain);
 
         // checks that the delay is calculated correctly, and that the vec is sorted
         assert_eq!(
@@ -556,26 +536,18 @@ mod tests {
     #[tokio::test]
     async fn test_get_open_process_delays_with_invalid_opentime_fails() {
         // if current_block is 5 and the issue was open at 10, something went wrong...
-        let mut provider = MockProvider::default();
-        provider
-            .expect_get_vault_issue_requests()
-            .times(1)
-            .returning(|_| {
-                Ok(vec![(
-                    H256::from_slice(&[1; 32]),
-                    PolkaBtcIssueRequest {
-                        opentime: 10,
-                        ..Default::default()
-                    },
-                )])
-            });
-        provider
-            .expect_get_current_chain_height()
-            .times(1)
-            .returning(|| Ok(5));
-        provider.expect_get_issue_period().returning(|| Ok(10));
-
-        let mut canceller = CancellationScheduler::new(Arc::new(provider), Default::default());
+        let parachain = parachain_with_vault().await;
+        advance_to(&parachain, 5).await;
+        parachain.insert_issue_request(
+            H256::from_slice(&[1; 32]),
+            PolkaBtcIssueRequest {
+                vault: parachain.get_account_id().clone(),
+                opentime: 10,
+                ..Default::default()
+            },
+        );
+
+        let mut canceller = scheduler(&parachain);
         assert_err!(
             canceller.get_open_requests::<IssueCanceller>().await,
             Error::InvalidOpenTime
@@ -585,40 +557,20 @@ mod tests {
     #[tokio::test]
     async fn test_wait_for_event_succeeds() {
         // check that we actually cancel the issue when it expires
-        let mut provider = MockProvider::default();
-        provider
-            .expect_get_vault_issue_requests()
-            .times(1)
-            .returning(|_| {
-                Ok(vec![(
-                    H256::from_slice(&[1; 32]),
-                    PolkaBtcIssueRequest {
-                        opentime: 10,
-                        ..Default::default()
-                    },
-                )])
-            });
-        provider
-            .expect_get_current_chain_height()
-            .times(1)
-            .returning(|| Ok(15));
-        provider.expect_get_issue_period().returning(|| Ok(10));
-
-        // check that it cancels the issue
-        provider
-            .expect_cancel_issue()
-            .times(1)
-            .returning(|_| Ok(()));
+        let parachain = parachain_with_vault().await;
+        let issue_ids = request_issues(&parachain, &[10]).await;
+        advance_to(&parachain, 15).await;
 
         let (_, mut block_listener) = mpsc::channel::<PolkaBtcHeader>(16);
         let (_, mut event_listener) = mpsc::channel::<RequestEvent>(16);
         let mut active_processes: Vec<ActiveRequest> = vec![];
-        let mut cancellation_scheduler =
-            CancellationScheduler::new(Arc::new(provider), AccountId::default());
+        let mut cancellation_scheduler = scheduler(&parachain);
 
         // simulate that we have a a new block
+        let chain = parachain.clone();
         let selector = TestEventSelector {
-            on_event: |_, _| {
+            on_event: move |_, _| {
+                chain.advance_chain(15);
                 Ok(BlockOrEvent::Block(PolkaBtcHeader {
                     parent_hash: BlakeTwo256::hash(&[0; 32]),
                     number: 30,
@@ -643,15 +595,65 @@ mod tests {
             ListState::Valid
         );
 
+        // check that it cancels the issue
+        assert_eq!(
+            parachain
+                .events::<CancelIssueEvent<PolkaBtcRuntime>>()
+                .iter()
+                .map(|event| event.issue_id)
+                .collect::<Vec<_>>(),
+            issue_ids
+        );
         // issue should have been removed from the list after it has been canceled
         assert!(active_processes.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_wait_for_event_updates_queue_before_waiting() {
+        let parachain = parachain_with_vault().await;
+        let issue_ids = request_issues(&parachain, &[10]).await;
+
+        let (_, mut block_listener) = mpsc::channel::<PolkaBtcHeader>(16);
+        let (_, mut event_listener) = mpsc::channel::<RequestEvent>(16);
+        let mut active_processes: Vec<ActiveRequest> = vec![];
+        let mut cancellation_scheduler = scheduler(&parachain);
+
+        // the refreshed list is queued before the scheduler waits for the next block
+        let queue = cancellation_scheduler.queue();
+        let selector = TestEventSelector {
+            on_event: move |_, _| {
+                assert_eq!(
+                    queue
+                        .requests()
+                        .iter()
+                        .map(|request| request.id)
+                        .collect::<Vec<_>>(),
+                    issue_ids
+                );
+                Ok(BlockOrEvent::Event(RequestEvent::Opened))
+            },
+        };
+
+        assert_eq!(
+            cancellation_scheduler
+                .wait_for_event::<IssueCanceller, _>(
+                    &mut block_listener,
+                    &mut event_listener,
+                    &mut active_processes,
+                    ListState::Invalid,
+                    selector
+                )
+                .await
+                .unwrap(),
+            ListState::Invalid
+        );
+    }
+
     #[tokio::test]
     async fn test_wait_for_event_remove_from_list() {
         // checks that we don't query for new issues, and that when the issue gets executed, it
         // is removed from the list
-        let provider = MockProvider::default();
+        let parachain = parachain_with_vault().await;
 
         let (_, mut block_listener) = mpsc::channel::<PolkaBtcHeader>(16);
         let (_, mut event_listener) = mpsc::channel::<RequestEvent>(16);
@@ -670,8 +672,7 @@ mod tests {
             },
         ];
 
-        let mut cancellation_scheduler =
-            CancellationScheduler::new(Arc::new(provider), AccountId::default());
+        let mut cancellation_scheduler = scheduler(&parachain);
 
         // simulate that the issue gets executed
         let selector = TestEventSelector {
@@ -711,38 +712,19 @@ mod tests {
     async fn test_wait_for_event_get_new_list() {
         // checks that we query for new issues, and that when the issue gets executed, it
         // is removed from the list
-        let mut provider = MockProvider::default();
-        provider
-            .expect_get_vault_issue_requests()
-            .times(1)
-            .returning(|_| {
-                Ok(vec![(
-                    H256::from_slice(&[1; 32]),
-                    PolkaBtcIssueRequest {
-                        opentime: 10,
-                        ..Default::default()
-                    },
-                )])
-            });
-        provider
-            .expect_get_current_chain_height()
-            .times(1)
-            .returning(|| Ok(15));
-        provider.expect_get_issue_period().returning(|| Ok(10));
+        let parachain = parachain_with_vault().await;
+        let issue_ids = request_issues(&parachain, &[10]).await;
+        advance_to(&parachain, 15).await;
 
         let (_, mut block_listener) = mpsc::channel::<PolkaBtcHeader>(16);
         let (_, mut event_listener) = mpsc::channel::<RequestEvent>(16);
         let mut active_processes: Vec<ActiveRequest> = vec![];
-        let mut cancellation_scheduler =
-            CancellationScheduler::new(Arc::new(provider), AccountId::default());
+        let mut cancellation_scheduler = scheduler(&parachain);
 
         // simulate that the issue gets executed
+        let issue_id = issue_ids[0];
         let selector = TestEventSelector {
-            on_event: |_, _| {
-                Ok(BlockOrEvent::Event(RequestEvent::Executed(
-                    H256::from_slice(&[1; 32]),
-                )))
-            },
+            on_event: move |_, _| Ok(BlockOrEvent::Event(RequestEvent::Executed(issue_id))),
         };
 
         assert_eq!(
@@ -766,17 +748,21 @@ mod tests {
     #[tokio::test]
     async fn test_wait_for_event_timeout() {
         // check that if we fail to get the issue list, we return Invalid, but not Err
-        let mut provider = MockProvider::default();
-        provider
-            .expect_get_vault_issue_requests()
-            .times(1)
-            .returning(|_| Err(RuntimeError::BlockNotFound));
+        let parachain = parachain_with_vault().await;
+        // an issue opened after the current height fails the query
+        parachain.insert_issue_request(
+            H256::from_slice(&[1; 32]),
+            PolkaBtcIssueRequest {
+                vault: parachain.get_account_id().clone(),
+                opentime: 10,
+                ..Default::default()
+            },
+        );
 
         let (_, mut block_listener) = mpsc::channel::<PolkaBtcHeader>(16);
         let (_, mut event_listener) = mpsc::channel::<RequestEvent>(16);
         let mut active_processes: Vec<ActiveRequest> = vec![];
-        let mut cancellation_scheduler =
-            CancellationScheduler::new(Arc::new(provider), AccountId::default());
+        let mut cancellation_scheduler = scheduler(&parachain);
 
         // simulate that we have a timeout (new issue request opened)
         let selector = TestEventSelector {
@@ -802,13 +788,12 @@ mod tests {
     #[tokio::test]
     async fn test_wait_for_event_shutdown() {
         // check that if the selector fails, the error is propagated
-        let provider = MockProvider::default();
+        let parachain = parachain_with_vault().await;
 
         let (_, mut block_listener) = mpsc::channel::<PolkaBtcHeader>(16);
         let (_, mut event_listener) = mpsc::channel::<RequestEvent>(16);
         let mut active_processes: Vec<ActiveRequest> = vec![];
-        let mut cancellation_scheduler =
-            CancellationScheduler::new(Arc::new(provider), AccountId::default());
+        let mut cancellation_scheduler = scheduler(&parachain);
 
         // simulate that we have a timeout
         let selector = TestEventSelector {
@@ -1,39 +1,81 @@
 use crate::error::Error;
+use crate::metrics;
+use futures::{pin_mut, select, FutureExt};
 use log::*;
 use runtime::{
-    pallets::exchange_rate_oracle::SetExchangeRateEvent, pallets::vault_registry::VaultStatus,
-    AccountId, DotBalancesPallet, PolkaBtcProvider, PolkaBtcRuntime, UtilFuncs,
-    VaultRegistryPallet,
+    pallets::vault_registry::VaultStatus, AccountId, DotBalancesPallet, PolkaBtcProvider,
+    StatusCode, UtilFuncs, VaultRegistryPallet,
 };
 use std::sync::Arc;
+use tokio::sync::Mutex;
 
+/// Locks additional collateral when the exchange rate changes, when the vault changes (e.g.
+/// its collateral is slashed) and when the parachain is running again after an error, since
+/// collateral can not be locked while it is in error.
+///
+/// # Arguments
+///
+/// * `provider` - the parachain RPC handle
+/// * `maximum_collateral` - the upperbound of total collateral that is allowed to be placed
 pub async fn maintain_collateralization_rate(
     provider: Arc<PolkaBtcProvider>,
     maximum_collateral: u128,
 ) -> Result<(), runtime::Error> {
     let provider = &provider;
-    provider
-        .on_event::<SetExchangeRateEvent<PolkaBtcRuntime>, _, _, _>(
-            |_| async move {
-                info!("Received SetExchangeRateEvent");
-                // todo: implement retrying
-
-                match lock_required_collateral(
-                    provider.clone(),
-                    provider.get_account_id().clone(),
-                    maximum_collateral,
-                )
-                .await
-                {
-                    // vault not being registered is ok, no need to log it
-                    Err(Error::RuntimeError(runtime::Error::VaultNotFound)) => {}
-                    Err(e) => error!("Failed to maintain collateral level: {}", e),
-                    _ => {} // success
-                }
-            },
-            |error| error!("Error reading SetExchangeRate event: {}", error.to_string()),
+    // the listeners run concurrently, but must not lock collateral concurrently
+    let locking = &Mutex::new(());
+    let check = || async move {
+        let _locking = locking.lock().await;
+        // todo: implement retrying
+        match lock_required_collateral(
+            provider.clone(),
+            provider.get_account_id().clone(),
+            maximum_collateral,
         )
         .await
+        {
+            // vault not being registered is ok, no need to log it
+            Err(Error::RuntimeError(runtime::Error::VaultNotFound)) => {}
+            Err(e) => {
+                metrics::record_extrinsic_failure(&e);
+                error!("Failed to maintain collateral level: {}", e)
+            }
+            _ => {} // success
+        }
+    };
+
+    let on_exchange_rate_change = provider
+        .on_exchange_rate_change(|exchange_rate| async move {
+            info!("Exchange rate changed to {}", exchange_rate);
+            check().await;
+            Ok(())
+        })
+        .fuse();
+    let on_vault_change = provider
+        .on_vault_change(provider.get_account_id().clone(), |_| async move {
+            check().await;
+            Ok(())
+        })
+        .fuse();
+    let on_parachain_status_change = provider
+        .on_parachain_status_change(|status| async move {
+            if status == StatusCode::Running {
+                check().await;
+            }
+            Ok(())
+        })
+        .fuse();
+    pin_mut!(
+        on_exchange_rate_change,
+        on_vault_change,
+        on_parachain_status_change
+    );
+
+    select! {
+        result = on_exchange_rate_change => result,
+        result = on_vault_change => result,
+        result = on_parachain_status_change => result,
+    }
 }
 
 /// Gets the required collateral for this vault, and if it is more than the actual
@@ -114,11 +156,12 @@ pub async fn lock_required_collateral<P: VaultRegistryPallet + DotBalancesPallet
 #[cfg(test)]
 mod tests {
     use super::*;
-    use async_trait::async_trait;
     use runtime::{
-        pallets::Core, AccountId, BtcAddress, BtcPublicKey, Error as RuntimeError, PolkaBtcRuntime,
-        PolkaBtcVault,
+        pallets::vault_registry::LockAdditionalCollateralEvent, testing::MockParachain,
+        ExchangeRateOraclePallet, FixedPointNumber, FixedU128, IssuePallet, PolkaBtcRuntime,
+        StakedRelayerPallet, MINIMUM_STAKE,
     };
+    use sp_keyring::AccountKeyring;
 
     macro_rules! assert_ok {
         ( $x:expr $(,)? ) => {
@@ -143,253 +186,181 @@ mod tests {
         }};
     }
 
-    mockall::mock! {
-        Provider {}
-
-        #[async_trait]
-        pub trait VaultRegistryPallet {
-            async fn get_vault(&self, vault_id: AccountId) -> Result<PolkaBtcVault, RuntimeError>;
-            async fn get_all_vaults(&self) -> Result<Vec<PolkaBtcVault>, RuntimeError>;
-            async fn register_vault(&self, collateral: u128, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
-            async fn lock_additional_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
-            async fn withdraw_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
-            async fn update_public_key(&self, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
-            async fn register_address(&self, btc_address: BtcAddress) -> Result<(), RuntimeError>;
-            async fn get_required_collateral_for_polkabtc(&self, amount_btc: u128) -> Result<u128, RuntimeError>;
-            async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<u128, RuntimeError>;
-            async fn is_vault_below_auction_threshold(&self, vault_id: AccountId) -> Result<bool, RuntimeError>;
-        }
+    /// Returns Bob's handle to a parachain on which Bob is a vault with the given
+    /// collateral, backing 100 issued tokens that require a collateral of 150.
+    async fn parachain_with_vault(collateral: u128) -> MockParachain {
+        let vault_id = AccountKeyring::Bob.to_account_id();
+        let parachain = MockParachain::new(vault_id.clone());
+        parachain.set_free_dot_balance(vault_id.clone(), 1000);
+        parachain
+            .register_vault(collateral, Default::default())
+            .await
+            .unwrap();
+
+        // issue at a tenth of the exchange rate such that any collateral suffices
+        parachain
+            .set_exchange_rate_info(FixedU128::saturating_from_rational(1, 10))
+            .await
+            .unwrap();
+        let user = parachain.with_account(AccountKeyring::Alice.to_account_id());
+        let issue = user.request_issue(100, vault_id, 0).await.unwrap();
+        user.execute_issue(issue.issue_id, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
+        parachain
+            .set_exchange_rate_info(FixedU128::one())
+            .await
+            .unwrap();
+        parachain
+    }
 
-        #[async_trait]
-        pub trait DotBalancesPallet {
-            async fn get_free_dot_balance(&self) -> Result<<PolkaBtcRuntime as Core>::Balance, RuntimeError>;
-            async fn get_free_dot_balance_for_id(&self, id: AccountId) -> Result<<PolkaBtcRuntime as Core>::Balance, RuntimeError>;
-            async fn get_reserved_dot_balance(&self) -> Result<<PolkaBtcRuntime as Core>::Balance, RuntimeError>;
-            async fn transfer_to(&self, destination: AccountId, amount: u128) -> Result<(), RuntimeError>;
-        }
+    fn locked_collateral(parachain: &MockParachain) -> Vec<u128> {
+        parachain
+            .events::<LockAdditionalCollateralEvent<PolkaBtcRuntime>>()
+            .into_iter()
+            .map(|event| event.new_collateral)
+            .collect()
+    }
+
+    async fn lock_up_to(parachain: &MockParachain, maximum_collateral: u128) -> Result<(), Error> {
+        let vault_id = parachain.get_account_id().clone();
+        lock_required_collateral(Arc::new(parachain.clone()), vault_id, maximum_collateral).await
     }
 
     #[tokio::test]
     async fn test_lock_required_collateral_case_1() {
         // case 1: required <= actual <= limit -- do nothing (already enough)
-        // required = 50, actual = 75, max = 100:
+        // required = 150, actual = 200, max = 250:
         // check that lock_additional_collateral is not called
-        let mut provider = MockProvider::default();
-        provider.expect_get_vault().returning(|x| {
-            Ok(PolkaBtcVault {
-                id: x,
-                status: VaultStatus::Active,
-                ..Default::default()
-            })
-        });
-        provider
-            .expect_get_required_collateral_for_vault()
-            .returning(|_| Ok(50));
-        provider
-            .expect_get_reserved_dot_balance()
-            .returning(|| Ok(75));
+        let parachain = parachain_with_vault(200).await;
 
-        let vault_id = AccountId::default();
-        assert_ok!(lock_required_collateral(Arc::new(provider), vault_id, 100).await);
+        assert_ok!(lock_up_to(&parachain, 250).await);
+        assert_eq!(locked_collateral(&parachain), vec![]);
     }
 
     #[tokio::test]
     async fn test_lock_required_collateral_case_2() {
         // case 2: required <= limit <= actual -- do nothing (already enough)
-        // required = 100, actual = 200, max = 150:
+        // required = 150, actual = 300, max = 200:
         // check that lock_additional_collateral is not called
-        let mut provider = MockProvider::default();
-        provider.expect_get_vault().returning(|x| {
-            Ok(PolkaBtcVault {
-                id: x,
-                status: VaultStatus::Active,
-                ..Default::default()
-            })
-        });
-        provider
-            .expect_get_required_collateral_for_vault()
-            .returning(|_| Ok(100));
-        provider
-            .expect_get_reserved_dot_balance()
-            .returning(|| Ok(200));
+        let parachain = parachain_with_vault(300).await;
 
-        let vault_id = AccountId::default();
-        assert_ok!(lock_required_collateral(Arc::new(provider), vault_id, 150).await);
+        assert_ok!(lock_up_to(&parachain, 200).await);
+        assert_eq!(locked_collateral(&parachain), vec![]);
     }
 
     #[tokio::test]
     async fn test_lock_required_collateral_case_3() {
         // case 3: limit <= required <= actual -- do nothing (already enough)
-        // required = 100, actual = 150, max = 75:
+        // required = 150, actual = 200, max = 100:
         // check that lock_additional_collateral is not called
-        let mut provider = MockProvider::default();
-        provider.expect_get_vault().returning(|x| {
-            Ok(PolkaBtcVault {
-                id: x,
-                status: VaultStatus::Active,
-                ..Default::default()
-            })
-        });
-        provider
-            .expect_get_required_collateral_for_vault()
-            .returning(|_| Ok(100));
-        provider
-            .expect_get_reserved_dot_balance()
-            .returning(|| Ok(150));
+        let parachain = parachain_with_vault(200).await;
 
-        let vault_id = AccountId::default();
-        assert_ok!(lock_required_collateral(Arc::new(provider), vault_id, 75).await);
+        assert_ok!(lock_up_to(&parachain, 100).await);
+        assert_eq!(locked_collateral(&parachain), vec![]);
     }
 
     #[tokio::test]
     async fn test_lock_required_collateral_case_4() {
         // case 4: limit <= actual <= required -- do nothing (return error)
-        // required = 100, actual = 75, max = 50:
+        // required = 150, actual = 100, max = 50:
         // check that lock_additional_collateral is not called
-        let mut provider = MockProvider::default();
-        provider.expect_get_vault().returning(|x| {
-            Ok(PolkaBtcVault {
-                id: x,
-                status: VaultStatus::Active,
-                ..Default::default()
-            })
-        });
-        provider
-            .expect_get_required_collateral_for_vault()
-            .returning(|_| Ok(100));
-        provider
-            .expect_get_reserved_dot_balance()
-            .returning(|| Ok(75));
+        let parachain = parachain_with_vault(100).await;
 
-        let vault_id = AccountId::default();
-        assert_err!(
-            lock_required_collateral(Arc::new(provider), vault_id, 50).await,
-            Error::InsufficientFunds
-        );
+        assert_err!(lock_up_to(&parachain, 50).await, Error::InsufficientFunds);
+        assert_eq!(locked_collateral(&parachain), vec![]);
     }
 
     #[tokio::test]
     async fn test_lock_required_collateral_case_5() {
         // case 5: actual <= limit <= required -- increase to limit (return error)
-        // required = 100, actual = 25, max = 75: should add 50, but return err
-        let mut provider = MockProvider::default();
-        provider.expect_get_vault().returning(|x| {
-            Ok(PolkaBtcVault {
-                id: x,
-                status: VaultStatus::Active,
-                ..Default::default()
-            })
-        });
-        provider
-            .expect_get_required_collateral_for_vault()
-            .returning(|_| Ok(100));
-        provider
-            .expect_get_reserved_dot_balance()
-            .returning(|| Ok(25));
-        provider
-            .expect_lock_additional_collateral()
-            .withf(|&amount| amount == 50)
-            .times(1)
-            .returning(|_| Ok(()));
+        // required = 150, actual = 50, max = 100: should add 50, but return err
+        let parachain = parachain_with_vault(50).await;
 
-        let vault_id = AccountId::default();
-        assert_err!(
-            lock_required_collateral(Arc::new(provider), vault_id, 75).await,
-            Error::InsufficientFunds
-        );
+        assert_err!(lock_up_to(&parachain, 100).await, Error::InsufficientFunds);
+        assert_eq!(locked_collateral(&parachain), vec![50]);
     }
     #[tokio::test]
     async fn test_lock_required_collateral_case_6() {
         // case 6: actual <= required <= limit -- increase to required (return ok)
-        // required = 100, actual = 25, max = 200: should add 75
-        let mut provider = MockProvider::default();
-        provider.expect_get_vault().returning(|x| {
-            Ok(PolkaBtcVault {
-                id: x,
-                status: VaultStatus::Active,
-                ..Default::default()
-            })
-        });
-        provider
-            .expect_get_required_collateral_for_vault()
-            .returning(|_| Ok(100));
-        provider
-            .expect_get_reserved_dot_balance()
-            .returning(|| Ok(25));
-        provider
-            .expect_lock_additional_collateral()
-            .withf(|&amount| amount == 75)
-            .times(1)
-            .returning(|_| Ok(()));
+        // required = 150, actual = 50, max = 300: should add 100
+        let parachain = parachain_with_vault(50).await;
 
-        let vault_id = AccountId::default();
-        assert_ok!(lock_required_collateral(Arc::new(provider), vault_id, 200).await);
+        assert_ok!(lock_up_to(&parachain, 300).await);
+        assert_eq!(locked_collateral(&parachain), vec![100]);
     }
 
     #[tokio::test]
     async fn test_lock_required_collateral_at_max_fails() {
-        // required = 100, actual = 25, max = 25:
+        // required = 150, actual = 50, max = 50:
         // check that lock_additional_collateral is not called with amount 0
-        let mut provider = MockProvider::default();
-        provider.expect_get_vault().returning(|x| {
-            Ok(PolkaBtcVault {
-                id: x,
-                status: VaultStatus::Active,
-                ..Default::default()
-            })
-        });
-        provider
-            .expect_get_required_collateral_for_vault()
-            .returning(|_| Ok(100));
-        provider
-            .expect_get_reserved_dot_balance()
-            .returning(|| Ok(25));
+        let parachain = parachain_with_vault(50).await;
 
-        let vault_id = AccountId::default();
-        assert_err!(
-            lock_required_collateral(Arc::new(provider), vault_id, 25).await,
-            Error::InsufficientFunds
-        );
+        assert_err!(lock_up_to(&parachain, 50).await, Error::InsufficientFunds);
+        assert_eq!(locked_collateral(&parachain), vec![]);
     }
 
     #[tokio::test]
     async fn test_lock_required_collateral_at_required_succeeds() {
-        // required = 100, actual = 100, max = 200:
+        // required = 150, actual = 150, max = 300:
         // check that lock_additional_collateral is not called with amount 0
-        let mut provider = MockProvider::default();
-        provider.expect_get_vault().returning(|x| {
-            Ok(PolkaBtcVault {
-                id: x,
-                status: VaultStatus::Active,
-                ..Default::default()
-            })
-        });
-        provider
-            .expect_get_required_collateral_for_vault()
-            .returning(|_| Ok(100));
-        provider
-            .expect_get_reserved_dot_balance()
-            .returning(|| Ok(100));
+        let parachain = parachain_with_vault(150).await;
 
-        let vault_id = AccountId::default();
-        assert_ok!(lock_required_collateral(Arc::new(provider), vault_id, 200).await);
+        assert_ok!(lock_up_to(&parachain, 300).await);
+        assert_eq!(locked_collateral(&parachain), vec![]);
     }
 
     #[tokio::test]
     async fn test_lock_required_collateral_with_unregistered_vault_fails() {
-        let mut provider = MockProvider::default();
-        provider.expect_get_vault().returning(|x| {
-            Ok(PolkaBtcVault {
-                id: x,
-                status: VaultStatus::CommittedTheft,
-                ..Default::default()
-            })
-        });
-
-        let vault_id = AccountId::default();
+        let parachain = parachain_with_vault(50).await;
+        let relayer_id = AccountKeyring::Alice.to_account_id();
+        let relayer = parachain.with_account(relayer_id.clone());
+        relayer.set_free_dot_balance(relayer_id, MINIMUM_STAKE as u128);
+        relayer
+            .register_staked_relayer(MINIMUM_STAKE as u128)
+            .await
+            .unwrap();
+        relayer
+            .report_vault_theft(
+                parachain.get_account_id().clone(),
+                Default::default(),
+                vec![],
+                vec![],
+            )
+            .await
+            .unwrap();
+
         assert_err!(
-            lock_required_collateral(Arc::new(provider), vault_id, 75).await,
+            lock_up_to(&parachain, 300).await,
             Error::RuntimeError(runtime::Error::VaultNotFound)
         );
+        assert_eq!(locked_collateral(&parachain), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_lock_required_collateral_after_exchange_rate_change() {
+        let vault_id = AccountKeyring::Bob.to_account_id();
+        let parachain = MockParachain::new(vault_id.clone());
+        parachain.set_free_dot_balance(vault_id.clone(), 1000);
+        parachain
+            .register_vault(300, Default::default())
+            .await
+            .unwrap();
+
+        let user = parachain.with_account(AccountKeyring::Alice.to_account_id());
+        let issue = user.request_issue(100, vault_id.clone(), 0).await.unwrap();
+        user.execute_issue(issue.issue_id, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
+
+        // the 100 issued tokens now require 3 * 100 * 150% = 450 collateral
+        parachain
+            .set_exchange_rate_info(FixedU128::saturating_from_integer(3))
+            .await
+            .unwrap();
+
+        let provider = Arc::new(parachain);
+        assert_ok!(lock_required_collateral(provider.clone(), vault_id, 1000).await);
+        assert_eq!(provider.get_reserved_dot_balance().await.unwrap(), 450);
     }
 }
@@ -8,6 +8,9 @@ pub const BITCOIN_MAX_RETRYING_TIME: Duration = Duration::from_secs(24 * 60 * 60
 /// This constant defines the rate at which we check whether the chain height has increased.
 pub const CHAIN_HEIGHT_POLLING_INTERVAL: Duration = Duration::from_millis(500);
 
+/// Rate at which the balances and open requests exposed as metrics are queried.
+pub const METRICS_UPDATE_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Gets the default retrying policy. This should be used for unexpected errors, not for operations
 /// that are expected to take a while to succeed. That is, it is unsuitable for e.g. awaiting bitcoin
 /// confirmation proof, due to potentially high retrying time.
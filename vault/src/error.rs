@@ -1,7 +1,14 @@
 use bitcoin::Error as BitcoinError;
 use jsonrpc_http_server::jsonrpc_core::Error as JsonRpcError;
+use kv::Error as KVError;
 use parity_scale_codec::Error as CodecError;
-use runtime::{substrate_subxt::Error as XtError, Error as RuntimeError};
+use runtime::{
+    json::{ApiErrorData, ApiErrorKind},
+    substrate_subxt::Error as XtError,
+    Error as RuntimeError,
+};
+use serde_json::Error as SerdeJsonError;
+use std::io::Error as IoError;
 use std::net::AddrParseError;
 use thiserror::Error;
 
@@ -25,6 +32,12 @@ pub enum Error {
     ArithmeticOverflow,
     #[error("Mathematical operation caused an underflow")]
     ArithmeticUnderflow,
+    #[error("Invalid or missing payment journal entry")]
+    InvalidJournalEntry,
+    #[error("Service {0} failed: {1}")]
+    ServiceFailed(&'static str, String),
+    #[error("Vault is shutting down")]
+    ShuttingDown,
 
     #[error("BitcoinError: {0}")]
     BitcoinError(#[from] BitcoinError),
@@ -38,4 +51,30 @@ pub enum Error {
     CodecError(#[from] CodecError),
     #[error("AddrParseError: {0}")]
     AddrParseError(#[from] AddrParseError),
+    #[error("KV store error: {0}")]
+    KVError(#[from] KVError),
+    #[error("SerdeJsonError: {0}")]
+    SerdeJsonError(#[from] SerdeJsonError),
+    #[error("IoError: {0}")]
+    IoError(#[from] IoError),
+}
+
+impl From<&Error> for ApiErrorData {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::RuntimeError(err) => err.into(),
+            Error::SubXtError(err) => err.into(),
+            Error::InsufficientFunds => ApiErrorData::new(ApiErrorKind::InsufficientFunds),
+            Error::ShuttingDown => ApiErrorData::new(ApiErrorKind::Rejected),
+            Error::JsonRpcError(_)
+            | Error::CodecError(_)
+            | Error::BitcoinError(BitcoinError::ConversionError(_)) => {
+                ApiErrorData::new(ApiErrorKind::InvalidParams)
+            }
+            Error::BitcoinError(_) | Error::WalletInitializationFailure(_) => {
+                ApiErrorData::new(ApiErrorKind::Bitcoin)
+            }
+            _ => ApiErrorData::new(ApiErrorKind::Internal),
+        }
+    }
 }
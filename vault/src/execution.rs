@@ -1,8 +1,13 @@
 use crate::constants::*;
 use crate::error::Error;
 use crate::issue::{process_issue_requests, IssueRequests};
+use crate::journal::PaymentJournal;
+use crate::metrics::{self, request_type_label, EXECUTE_RETRIES};
 use backoff::{future::FutureOperation as _, ExponentialBackoff};
-use bitcoin::{BitcoinCoreApi, Transaction, TransactionExt, TransactionMetadata};
+use bitcoin::{
+    deserialize, BitcoinCoreApi, Error as BitcoinError, LockedTransaction, Transaction,
+    TransactionExt, TransactionMetadata,
+};
 use futures::stream::StreamExt;
 use log::*;
 use runtime::{
@@ -11,12 +16,18 @@ use runtime::{
         refund::RequestRefundEvent,
         replace::{AcceptReplaceEvent, AuctionReplaceEvent},
     },
+    sp_runtime::traits::Header as _,
     BtcAddress, H256Le, PolkaBtcProvider, PolkaBtcRedeemRequest, PolkaBtcRefundRequest,
     PolkaBtcReplaceRequest, PolkaBtcRuntime, RedeemPallet, RefundPallet, ReplacePallet, UtilFuncs,
     VaultRegistryPallet,
 };
+use serde::{Deserialize, Serialize};
 use sp_core::H256;
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 #[derive(Debug, Clone)]
 pub struct Request {
@@ -25,9 +36,12 @@ pub struct Request {
     amount: u128,
     btc_address: BtcAddress,
     request_type: RequestType,
+    /// When the event of the request was received, the start of its payment latency. None
+    /// for requests loaded from the parachain, whose event time is unknown.
+    received: Option<Instant>,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RequestType {
     Redeem,
     Replace,
@@ -43,6 +57,7 @@ impl Request {
             amount: request.amount_polka_btc,
             btc_address: request.btc_address,
             request_type: RequestType::Redeem,
+            received: None,
         }
     }
     /// Constructs a Request for the given PolkaBtcReplaceRequest
@@ -53,6 +68,7 @@ impl Request {
             amount: request.amount,
             btc_address: request.btc_address?,
             request_type: RequestType::Replace,
+            received: None,
         })
     }
     /// Constructs a Request for the given PolkaBtcRefundRequest
@@ -63,6 +79,7 @@ impl Request {
             amount: request.amount_btc,
             btc_address: request.btc_address,
             request_type: RequestType::Refund,
+            received: None,
         }
     }
     /// Constructs a Request for the given RequestRefundEvent
@@ -73,6 +90,7 @@ impl Request {
             hash: request.refund_id,
             open_time: None,
             request_type: RequestType::Refund,
+            received: Some(Instant::now()),
         }
     }
     /// Constructs a Request for the given AcceptReplaceEvent
@@ -83,6 +101,7 @@ impl Request {
             hash: request.replace_id,
             open_time: None,
             request_type: RequestType::Replace,
+            received: Some(Instant::now()),
         }
     }
     /// Constructs a Request for the given AuctionReplaceEvent
@@ -93,6 +112,7 @@ impl Request {
             hash: request.replace_id,
             open_time: None,
             request_type: RequestType::Replace,
+            received: Some(Instant::now()),
         }
     }
     /// Constructs a Request for the given RequestRedeemEvent
@@ -103,10 +123,12 @@ impl Request {
             hash: request.redeem_id,
             open_time: None,
             request_type: RequestType::Redeem,
+            received: Some(Instant::now()),
         }
     }
 
-    /// Makes the bitcoin transfer and executes the request
+    /// Makes the bitcoin transfer and executes the request. If the journal contains a
+    /// payment for the request, that payment is resumed instead of paying again.
     pub async fn pay_and_execute<
         B: BitcoinCoreApi,
         P: ReplacePallet + RefundPallet + RedeemPallet + VaultRegistryPallet + UtilFuncs + Send + Sync,
@@ -114,85 +136,182 @@ impl Request {
         &self,
         provider: Arc<P>,
         btc_rpc: Arc<B>,
+        journal: Arc<PaymentJournal>,
         num_confirmations: u32,
     ) -> Result<(), Error> {
-        let tx_metadata = self
-            .transfer_btc(provider.clone(), btc_rpc, num_confirmations)
-            .await?;
-        self.execute(provider, tx_metadata).await
+        let journaled_payment = match journal.get(self.hash)? {
+            Some(entry) => entry.transaction()?,
+            None => None,
+        };
+        let tx_metadata = match journaled_payment {
+            Some(transaction) => {
+                self.resume_payment(btc_rpc, transaction, num_confirmations)
+                    .await?
+            }
+            None => {
+                self.transfer_btc(btc_rpc, &journal, num_confirmations)
+                    .await?
+            }
+        };
+        self.execute(provider, tx_metadata).await?;
+        self.observe_payment_latency("executed");
+        journal.record_executed(self.hash)
+    }
+
+    /// Record the time since the event of the request was received, if it is known.
+    fn observe_payment_latency(&self, stage: &str) {
+        if let Some(received) = self.received {
+            metrics::observe_payment_latency(self.request_type, stage, received);
+        }
     }
 
     /// Make a bitcoin transfer to fulfil the request
-    async fn transfer_btc<B: BitcoinCoreApi, P: VaultRegistryPallet + UtilFuncs + Send + Sync>(
+    async fn transfer_btc<B: BitcoinCoreApi>(
         &self,
-        provider: Arc<P>,
         btc_rpc: Arc<B>,
+        journal: &PaymentJournal,
         num_confirmations: u32,
     ) -> Result<TransactionMetadata, Error> {
+        // a shutdown waits for the payment until it is broadcast
+        let payment = journal.begin_payment()?;
+        journal.record_intent(self.hash, self.request_type, self.amount)?;
         info!("Sending bitcoin to {}", self.btc_address);
 
         let tx = btc_rpc
             .create_transaction(self.btc_address, self.amount as u64, Some(self.hash))
             .await?;
+        // the return-to-self address is registered together with the execution
+        self.return_to_self_address(&tx.transaction)?;
+
+        // after a crash, the journaled payment is broadcast again rather than creating another
+        journal.record_signed(self.hash, &tx.transaction)?;
+        let txid = btc_rpc.send_transaction(tx).await?;
+        journal.record_broadcast(self.hash)?;
+        drop(payment);
+        self.observe_payment_latency("broadcast");
 
-        let return_to_self_addresses = tx
-            .transaction
+        let tx_metadata = btc_rpc
+            .wait_for_transaction_metadata(txid, BITCOIN_MAX_RETRYING_TIME, num_confirmations)
+            .await?;
+
+        info!("Bitcoin successfully sent to {}", self.btc_address);
+        Ok(tx_metadata)
+    }
+
+    /// Returns the output address of the payment other than the requested one, if any.
+    fn return_to_self_address(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<Option<BtcAddress>, Error> {
+        let return_to_self_addresses = transaction
             .extract_output_addresses()
             .into_iter()
             .filter(|x| x != &self.btc_address)
             .collect::<Vec<_>>();
 
-        // register return-to-self address if it exists
         match return_to_self_addresses.as_slice() {
-            [] => {} // no return-to-self
-            [address] => {
-                // one return-to-self address, make sure it is registered
-                let vault_id = provider.get_account_id().clone();
-                let wallet = provider.get_vault(vault_id).await?.wallet;
-                if !wallet.has_btc_address(&address) {
-                    info!("Registering address {}", address);
-                    provider.register_address(address.clone()).await?;
-                }
-            }
-            _ => return Err(Error::TooManyReturnToSelfAddresses),
+            [] => Ok(None),
+            [address] => Ok(Some(*address)),
+            _ => Err(Error::TooManyReturnToSelfAddresses),
+        }
+    }
+
+    /// Returns the return-to-self address of the payment, if it is not registered yet.
+    async fn unregistered_address<P: VaultRegistryPallet + UtilFuncs>(
+        &self,
+        provider: &P,
+        tx_metadata: &TransactionMetadata,
+    ) -> Result<Option<BtcAddress>, Error> {
+        let transaction: Transaction =
+            deserialize(&tx_metadata.raw_tx).map_err(BitcoinError::from)?;
+        let address = match self.return_to_self_address(&transaction)? {
+            Some(address) => address,
+            None => return Ok(None),
         };
+        // the cache lags behind, so it may not contain an address registered just now
+        let vault_id = provider.get_account_id().clone();
+        let wallet = provider.get_vault_uncached(vault_id).await?.wallet;
+        Ok(if wallet.has_btc_address(&address) {
+            None
+        } else {
+            Some(address)
+        })
+    }
 
-        let txid = btc_rpc.send_transaction(tx).await?;
-        let tx_metadata = btc_rpc
-            .wait_for_transaction_metadata(txid, BITCOIN_MAX_RETRYING_TIME, num_confirmations)
-            .await?;
+    /// Broadcast the journaled payment again, in case it never reached the mempool or was
+    /// evicted from it, and wait for its confirmation.
+    async fn resume_payment<B: BitcoinCoreApi>(
+        &self,
+        btc_rpc: Arc<B>,
+        transaction: Transaction,
+        num_confirmations: u32,
+    ) -> Result<TransactionMetadata, Error> {
+        let txid = transaction.txid();
+        info!(
+            "Resuming journaled payment {} for {:?} request #{}",
+            txid, self.request_type, self.hash
+        );
+        // fails if the transaction is already in the mempool or in the chain
+        if let Err(e) = btc_rpc
+            .send_transaction(LockedTransaction::signed(transaction))
+            .await
+        {
+            info!("Did not broadcast journaled payment {}: {}", txid, e);
+        }
 
-        info!("Bitcoin successfully sent to {}", self.btc_address);
-        Ok(tx_metadata)
+        Ok(btc_rpc
+            .wait_for_transaction_metadata(txid, BITCOIN_MAX_RETRYING_TIME, num_confirmations)
+            .await?)
     }
 
-    /// Executes the request. Upon failure it will retry
-    async fn execute<P: ReplacePallet + RedeemPallet + RefundPallet>(
+    /// Executes the request, registering the return-to-self address of the payment in the
+    /// same batch if needed. Upon failure it will retry
+    async fn execute<
+        P: ReplacePallet + RedeemPallet + RefundPallet + VaultRegistryPallet + UtilFuncs,
+    >(
         &self,
         provider: Arc<P>,
         tx_metadata: TransactionMetadata,
     ) -> Result<(), Error> {
-        // select the execute function based on request_type
+        // select the execute functions based on request_type
         let execute = match self.request_type {
             RequestType::Redeem => RedeemPallet::execute_redeem,
             RequestType::Replace => ReplacePallet::execute_replace,
             RequestType::Refund => RefundPallet::execute_refund,
         };
+        let register_address_and_execute = match self.request_type {
+            RequestType::Redeem => RedeemPallet::register_address_and_execute_redeem,
+            RequestType::Replace => ReplacePallet::register_address_and_execute_replace,
+            RequestType::Refund => RefundPallet::register_address_and_execute_refund,
+        };
 
         // Retry until success or timeout
         (|| async {
+            let tx_id = H256Le::from_bytes_le(tx_metadata.txid.as_ref());
+            let proof = tx_metadata.proof.clone();
+            let raw_tx = tx_metadata.raw_tx.clone();
             // call the selected function
-            (execute)(
-                &*provider,
-                self.hash,
-                H256Le::from_bytes_le(tx_metadata.txid.as_ref()),
-                tx_metadata.proof.clone(),
-                tx_metadata.raw_tx.clone(),
-            )
-            .await
-            .map_err(|x| x.into())
+            let unregistered_address = self
+                .unregistered_address(&*provider, &tx_metadata)
+                .await
+                .map_err(backoff::Error::Transient)?;
+            let result = match unregistered_address {
+                Some(address) => {
+                    info!("Registering address {}", address);
+                    (register_address_and_execute)(
+                        &*provider, address, self.hash, tx_id, proof, raw_tx,
+                    )
+                    .await
+                }
+                None => (execute)(&*provider, self.hash, tx_id, proof, raw_tx).await,
+            };
+            result.map_err(|x| backoff::Error::Transient(Error::from(x)))
         })
         .retry_notify(get_retry_policy(), |e, dur: Duration| {
+            EXECUTE_RETRIES
+                .with_label_values(&[request_type_label(self.request_type)])
+                .inc();
+            metrics::record_extrinsic_failure(&e);
             warn!(
                 "{:?} execution of request {} failed: {} - next retry in {:.3} s",
                 self.request_type,
@@ -207,17 +326,12 @@ impl Request {
     }
 }
 
-/// Queries the parachain for open requests/replaces and executes them. It checks the
-/// bitcoin blockchain to see if a payment has already been made.
-pub async fn execute_open_requests<B: BitcoinCoreApi + Send + Sync + 'static>(
-    provider: Arc<PolkaBtcProvider>,
-    btc_rpc: Arc<B>,
-    num_confirmations: u32,
-) -> Result<(), Error> {
+/// Returns the open redeem, replace and refund requests of this vault.
+async fn get_open_requests<P: RedeemPallet + ReplacePallet + RefundPallet + UtilFuncs>(
+    provider: &P,
+) -> Result<Vec<Request>, Error> {
     let vault_id = provider.get_account_id().clone();
-    // get all open redeem/replaces and map them to the shared Request type
     let open_redeems = provider
-        .clone()
         .get_vault_redeem_requests(vault_id.clone())
         .await?
         .into_iter()
@@ -235,14 +349,99 @@ pub async fn execute_open_requests<B: BitcoinCoreApi + Send + Sync + 'static>(
         .into_iter()
         .filter(|(_, request)| !request.completed)
         .map(|(hash, request)| Request::from_refund_request(hash, request));
-
-    // Place all redeems,replaces&refunds into a hashmap, indexed by their redeemid/replaceid
-    let mut hash_map = open_redeems
+    Ok(open_redeems
         .chain(open_replaces)
         .chain(open_refunds)
+        .collect())
+}
+
+/// Removes the journal entries of executed requests once they are no longer open as of the
+/// latest finalized block, such that the journal does not grow without bound.
+///
+/// # Arguments
+///
+/// * `provider` - the parachain RPC handle
+/// * `journal` - the journal that records the bitcoin payments
+pub async fn prune_payment_journal(
+    provider: Arc<PolkaBtcProvider>,
+    journal: Arc<PaymentJournal>,
+) -> Result<(), runtime::Error> {
+    let provider = &provider;
+    let journal = &journal;
+    provider
+        .on_block(|header| async move {
+            if let Err(e) = prune_executed_payments(&provider.at(header.hash()), journal).await {
+                error!("Failed to prune the payment journal: {}", e);
+            }
+            Ok(())
+        })
+        .await
+}
+
+/// Removes the journal entries of executed requests that are no longer open.
+async fn prune_executed_payments<P: RedeemPallet + ReplacePallet + RefundPallet + UtilFuncs>(
+    provider: &P,
+    journal: &PaymentJournal,
+) -> Result<(), Error> {
+    let executed = journal.executed()?;
+    if executed.is_empty() {
+        return Ok(());
+    }
+    // an execution that was not finalized yet leaves the request open
+    let open_requests = get_open_requests(provider)
+        .await?
+        .into_iter()
+        .map(|request| request.hash)
+        .collect::<HashSet<_>>();
+    for request_id in executed {
+        if !open_requests.contains(&request_id) {
+            journal.remove(request_id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Queries the parachain for open requests/replaces and executes them. Payments recorded in
+/// the journal are resumed, otherwise it checks the bitcoin blockchain to see if a payment
+/// has already been made.
+pub async fn execute_open_requests<B: BitcoinCoreApi + Send + Sync + 'static>(
+    provider: Arc<PolkaBtcProvider>,
+    btc_rpc: Arc<B>,
+    journal: Arc<PaymentJournal>,
+    num_confirmations: u32,
+) -> Result<(), Error> {
+    // Place all redeems,replaces&refunds into a hashmap, indexed by their redeemid/replaceid
+    let mut hash_map = get_open_requests(&*provider)
+        .await?
+        .into_iter()
         .map(|x| (x.hash, x))
         .collect::<HashMap<_, _>>();
 
+    // resume the payments in the journal, these need not be searched in the chain
+    for (hash, request) in hash_map.clone() {
+        let has_payment = match journal.get(hash)? {
+            Some(entry) => entry.transaction()?.is_some(),
+            None => false,
+        };
+        if !has_payment {
+            continue;
+        }
+        hash_map.remove(&hash);
+
+        let provider = provider.clone();
+        let btc_rpc = btc_rpc.clone();
+        let journal = journal.clone();
+        journal.clone().spawn(async move {
+            match request
+                .pay_and_execute(provider, btc_rpc, journal, num_confirmations)
+                .await
+            {
+                Ok(_) => info!("Executed request #{}", request.hash),
+                Err(e) => error!("Failed to execute request #{}: {}", request.hash, e),
+            }
+        });
+    }
+
     // find the height of bitcoin chain corresponding to the earliest open_time
     let btc_start_height = match hash_map
         .iter()
@@ -273,7 +472,7 @@ pub async fn execute_open_requests<B: BitcoinCoreApi + Send + Sync + 'static>(
             // make copies of the variables we move into the task
             let provider = provider.clone();
             let btc_rpc = btc_rpc.clone();
-            tokio::spawn(async move {
+            journal.spawn(async move {
                 // Payment has been made, but it might not have been confirmed enough times yet
                 let tx_metadata = btc_rpc
                     .clone()
@@ -309,14 +508,15 @@ pub async fn execute_open_requests<B: BitcoinCoreApi + Send + Sync + 'static>(
         // make copies of the variables we move into the task
         let provider = provider.clone();
         let btc_rpc = btc_rpc.clone();
-        tokio::spawn(async move {
+        let journal = journal.clone();
+        journal.clone().spawn(async move {
             info!(
                 "{:?} request #{} found without bitcoin payment - processing...",
                 request.request_type, request.hash
             );
 
             match request
-                .pay_and_execute(provider, btc_rpc, num_confirmations)
+                .pay_and_execute(provider, btc_rpc, journal, num_confirmations)
                 .await
             {
                 Ok(_) => info!(
@@ -368,256 +568,355 @@ fn get_request_for_btc_tx(tx: &Transaction, hash_map: &HashMap<H256, Request>) -
 
 #[cfg(test)]
 mod tests {
-    //     use super::*;
-    //     use async_trait::async_trait;
-    //     use bitcoin::{
-    //         Block, BlockHash, Error as BitcoinError, GetBlockResult, GetRawTransactionResult,
-    //         LockedTransaction, Network, PartialAddress, Transaction, TransactionMetadata, Txid,
-    //     };
-    //     use runtime::{AccountId, Error as RuntimeError, PolkaBtcVault};
-    //     use sp_core::H160;
-    //     use std::future::Future;
-    //     macro_rules! assert_ok {
-    //         ( $x:expr $(,)? ) => {
-    //             let is = $x;
-    //             match is {
-    //                 Ok(_) => (),
-    //                 _ => assert!(false, "Expected Ok(_). Got {:#?}", is),
-    //             }
-    //         };
-    //         ( $x:expr, $y:expr $(,)? ) => {
-    //             assert_eq!($x, Ok($y));
-    //         };
-    //     }
-    //     macro_rules! assert_err {
-    //         ($result:expr, $err:pat) => {{
-    //             match $result {
-    //                 Err($err) => (),
-    //                 Ok(v) => panic!("assertion failed: Ok({:?})", v),
-    //                 _ => panic!("expected: Err($err)"),
-    //             }
-    //         }};
-    //     }
-
-    //     mockall::mock! {
-    //         Provider {}
-
-    //         #[async_trait]
-    //         pub trait UtilFuncs {
-    //             async fn get_current_chain_height(&self) -> Result<u32, RuntimeError>;
-    //             async fn get_blockchain_height_at(&self, parachain_height: u32) -> Result<u32, RuntimeError>;
-    //             fn get_account_id(&self) -> &AccountId;
-    //         }
-    //         #[async_trait]
-    //         pub trait VaultRegistryPallet {
-    //             async fn get_vault(&self, vault_id: AccountId) -> Result<PolkaBtcVault, RuntimeError>;
-    //             async fn get_all_vaults(&self) -> Result<Vec<PolkaBtcVault>, RuntimeError>;
-    //             async fn register_vault(&self, collateral: u128, btc_address: BtcAddress) -> Result<(), RuntimeError>;
-    //             async fn lock_additional_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
-    //             async fn withdraw_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
-    //             async fn update_btc_address(&self, address: BtcAddress) -> Result<(), RuntimeError>;
-    //             async fn get_required_collateral_for_polkabtc(&self, amount_btc: u128) -> Result<u128, RuntimeError>;
-    //             async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<u128, RuntimeError>;
-    //             async fn is_vault_below_auction_threshold(&self, vault_id: AccountId) -> Result<bool, RuntimeError>;
-    //         }
-    //         #[async_trait]
-    //         pub trait RedeemPallet {
-    //             async fn get_redeem_request(&self, redeem_id: H256) -> Result<PolkaBtcRedeemRequest, RuntimeError>;
-    //             async fn request_redeem(
-    //                 &self,
-    //                 amount_polka_btc: u128,
-    //                 btc_address: BtcAddress,
-    //                 vault_id: AccountId,
-    //             ) -> Result<H256, RuntimeError>;
-    //             async fn execute_redeem(
-    //                 &self,
-    //                 redeem_id: H256,
-    //                 tx_id: H256Le,
-    //                 merkle_proof: Vec<u8>,
-    //                 raw_tx: Vec<u8>,
-    //             ) -> Result<(), RuntimeError>;
-    //             async fn cancel_redeem(&self, redeem_id: H256, reimburse: bool) -> Result<(), RuntimeError>;
-    //             async fn get_vault_redeem_requests(
-    //                 &self,
-    //                 account_id: AccountId,
-    //             ) -> Result<Vec<(H256, PolkaBtcRedeemRequest)>, RuntimeError>;
-    //             async fn set_redeem_period(&self, period: u32) -> Result<(), RuntimeError>;
-    //         }
-    //         #[async_trait]
-    //         pub trait ReplacePallet {
-    //             async fn request_replace(&self, amount: u128, griefing_collateral: u128)
-    //                 -> Result<H256, RuntimeError>;
-    //             async fn withdraw_replace(&self, replace_id: H256) -> Result<(), RuntimeError>;
-    //             async fn accept_replace(&self, replace_id: H256, collateral: u128) -> Result<(), RuntimeError>;
-    //             async fn auction_replace(
-    //                 &self,
-    //                 old_vault: AccountId,
-    //                 btc_amount: u128,
-    //                 collateral: u128,
-    //             ) -> Result<(), RuntimeError>;
-    //             async fn execute_replace(
-    //                 &self,
-    //                 replace_id: H256,
-    //                 tx_id: H256Le,
-    //                 merkle_proof: Vec<u8>,
-    //                 raw_tx: Vec<u8>,
-    //             ) -> Result<(), RuntimeError>;
-    //             async fn cancel_replace(&self, replace_id: H256) -> Result<(), RuntimeError>;
-    //             async fn get_new_vault_replace_requests(
-    //                 &self,
-    //                 account_id: AccountId,
-    //             ) -> Result<Vec<(H256, PolkaBtcReplaceRequest)>, RuntimeError>;
-    //             async fn get_old_vault_replace_requests(
-    //                 &self,
-    //                 account_id: AccountId,
-    //             ) -> Result<Vec<(H256, PolkaBtcReplaceRequest)>, RuntimeError>;
-    //             async fn get_replace_period(&self) -> Result<u32, RuntimeError>;
-    //             async fn set_replace_period(&self, period: u32) -> Result<(), RuntimeError>;
-    //             async fn get_replace_request(&self, replace_id: H256) -> Result<PolkaBtcReplaceRequest, RuntimeError>;
-    //         }
-    //     }
-
-    //     mockall::mock! {
-    //         Bitcoin {}
-
-    //         #[async_trait]
-    //         trait BitcoinCoreApi {
-    //             async fn wait_for_block(&self, height: u32, delay: Duration) -> Result<BlockHash, BitcoinError>;
-    //             async fn get_block_count(&self) -> Result<u64, BitcoinError>;
-    //             async fn get_raw_tx_for(&self, txid: &Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
-    //             async fn get_proof_for(&self, txid: Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
-    //            async  fn get_block_hash_for(&self, height: u32) -> Result<BlockHash, BitcoinError>;
-    //             async fn is_block_known(&self, block_hash: BlockHash) -> Result<bool, BitcoinError>;
-    //             async fn get_new_address<A: PartialAddress + Send + 'static>(&self) -> Result<A, BitcoinError>;
-    //             async fn get_best_block_hash(&self) -> Result<BlockHash, BitcoinError>;
-    //             async fn get_block(&self, hash: &BlockHash) -> Result<Block, BitcoinError>;
-    //             async fn get_block_info(&self, hash: &BlockHash) -> Result<GetBlockResult, BitcoinError>;
-    //             async fn get_mempool_transactions<'a>(
-    //                 self: Arc<Self>,
-    //             ) -> Result<Box<dyn Iterator<Item = Result<Transaction, BitcoinError>> + 'a>, BitcoinError>;
-    //             async fn wait_for_transaction_metadata(
-    //                 &self,
-    //                 txid: Txid,
-    //                 op_timeout: Duration,
-    //                 num_confirmations: u32,
-    //             ) -> Result<TransactionMetadata, BitcoinError>;
-    //             async fn create_transaction<A: PartialAddress + Send + 'static>(
-    //                 &self,
-    //                 address: A,
-    //                 sat: u64,
-    //                 request_id: Option<H256>,
-    //             ) -> Result<LockedTransaction, BitcoinError>;
-    //             async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, BitcoinError>;
-    //             async fn create_and_send_transaction<A: PartialAddress + Send + 'static>(
-    //                 &self,
-    //                 address: A,
-    //                 sat: u64,
-    //                 request_id: Option<H256>,
-    //             ) -> Result<Txid, BitcoinError>;
-    //             async fn send_to_address<A: PartialAddress + Send + 'static>(
-    //                 &self,
-    //                 address: A,
-    //                 sat: u64,
-    //                 request_id: Option<H256>,
-    //                 op_timeout: Duration,
-    //                 num_confirmations: u32,
-    //             ) -> Result<TransactionMetadata, BitcoinError>;
-    //             async fn create_wallet(&self, wallet: &str) -> Result<(), BitcoinError>;
-    //         }
-    //     }
-
-    //     fn dummy_transaction_metadata() -> TransactionMetadata {
-    //         TransactionMetadata {
-    //             block_hash: Default::default(),
-    //             block_height: Default::default(),
-    //             proof: Default::default(),
-    //             raw_tx: Default::default(),
-    //             txid: Default::default(),
-    //         }
-    //     }
-
-    // TODO: re-enable these tests
-
-    // #[tokio::test]
-    // async fn test_pay_and_execute_redeem_succeeds() {
-    //     let mut provider = MockProvider::default();
-    //     let mut btc_rpc = MockBitcoin::default();
-    //     btc_rpc
-    //         .expect_send_to_address::<BtcAddress>()
-    //         .times(1) // checks that this function is not retried
-    //         .returning(|_, _, _, _, _| Ok(dummy_transaction_metadata()));
-
-    //     provider
-    //         .expect_execute_redeem()
-    //         .times(1)
-    //         .returning(|_, _, _, _| Ok(()));
-
-    //     let request = Request {
-    //         amount: 100,
-    //         btc_address: BtcAddress::P2SH(H160::from_slice(&[1; 20])),
-    //         hash: H256::from_slice(&[1; 32]),
-    //         open_time: None,
-    //         request_type: RequestType::Redeem,
-    //     };
-
-    //     assert_ok!(
-    //         request
-    //             .pay_and_execute(Arc::new(provider), Arc::new(btc_rpc), 6)
-    //             .await
-    //     );
-    // }
-
-    // #[tokio::test]
-    // async fn test_pay_and_execute_replace_succeeds() {
-    //     let mut provider = MockProvider::default();
-    //     let mut btc_rpc = MockBitcoin::default();
-    //     btc_rpc
-    //         .expect_send_to_address::<BtcAddress>()
-    //         .times(1) // checks that this function is not retried
-    //         .returning(|_, _, _, _, _| Ok(dummy_transaction_metadata()));
-
-    //     provider
-    //         .expect_execute_replace()
-    //         .times(1)
-    //         .returning(|_, _, _, _| Ok(()));
-
-    //     let request = Request {
-    //         amount: 100,
-    //         btc_address: BtcAddress::P2SH(H160::from_slice(&[1; 20])),
-    //         hash: H256::from_slice(&[1; 32]),
-    //         open_time: None,
-    //         request_type: RequestType::Replace,
-    //     };
-
-    //     assert_ok!(
-    //         request
-    //             .pay_and_execute(Arc::new(provider), Arc::new(btc_rpc), 6)
-    //             .await
-    //     );
-    // }
-
-    // #[tokio::test]
-    // async fn test_pay_and_execute_no_bitcoin_retry() {
-    //     let provider = MockProvider::default();
-    //     let mut btc_rpc = MockBitcoin::default();
-    //     btc_rpc
-    //         .expect_send_to_address::<BtcAddress>()
-    //         .times(1) // checks that this function is not retried
-    //         .returning(|_, _, _, _, _| Err(BitcoinError::ConfirmationError));
-
-    //     let request = Request {
-    //         amount: 100,
-    //         btc_address: BtcAddress::P2SH(H160::from_slice(&[1; 20])),
-    //         hash: H256::from_slice(&[1; 32]),
-    //         open_time: None,
-    //         request_type: RequestType::Replace,
-    //     };
-
-    //     assert_err!(
-    //         request
-    //             .pay_and_execute(Arc::new(provider), Arc::new(btc_rpc), 6)
-    //             .await,
-    //         Error::BitcoinError(BitcoinError::ConfirmationError)
-    //     );
-    // }
+    use super::*;
+    use crate::journal::PaymentStatus;
+    use async_trait::async_trait;
+    use bitcoin::{
+        serialize, Block, BlockHash, GetBlockResult, OutPoint, PartialAddress, TxIn, TxOut, Txid,
+        PUBLIC_KEY_SIZE,
+    };
+    use runtime::{
+        pallets::{redeem::ExecuteRedeemEvent, replace::ExecuteReplaceEvent},
+        testing::MockParachain,
+        AccountId, IssuePallet,
+    };
+    use sp_core::H160;
+    use sp_keyring::AccountKeyring;
+    use tempdir::TempDir;
+
+    macro_rules! assert_ok {
+        ( $x:expr $(,)? ) => {
+            let is = $x;
+            match is {
+                Ok(_) => (),
+                _ => assert!(false, "Expected Ok(_). Got {:#?}", is),
+            }
+        };
+    }
+    macro_rules! assert_err {
+        ($result:expr, $err:pat) => {{
+            match $result {
+                Err($err) => (),
+                Ok(v) => panic!("assertion failed: Ok({:?})", v),
+                _ => panic!("expected: Err($err)"),
+            }
+        }};
+    }
+
+    mockall::mock! {
+        Bitcoin {}
+
+        #[async_trait]
+        trait BitcoinCoreApi {
+            async fn wait_for_block(&self, height: u32, delay: Duration, num_confirmations: u32) -> Result<BlockHash, BitcoinError>;
+            async fn get_block_count(&self) -> Result<u64, BitcoinError>;
+            async fn get_balance(&self) -> Result<u64, BitcoinError>;
+            async fn get_raw_tx_for(&self, txid: &Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
+            async fn get_proof_for(&self, txid: Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
+            async fn get_block_hash_for(&self, height: u32) -> Result<BlockHash, BitcoinError>;
+            async fn is_block_known(&self, block_hash: BlockHash) -> Result<bool, BitcoinError>;
+            async fn get_new_address<A: PartialAddress + Send + 'static>(&self) -> Result<A, BitcoinError>;
+            async fn get_new_public_key<P: From<[u8; PUBLIC_KEY_SIZE]> + 'static>(&self) -> Result<P, BitcoinError>;
+            async fn add_new_deposit_key<P: Into<[u8; PUBLIC_KEY_SIZE]> + Send + Sync + 'static>(
+                &self,
+                public_key: P,
+                secret_key: Vec<u8>,
+                rescan: bool,
+            ) -> Result<bool, BitcoinError>;
+            async fn rescan_blockchain(&self, start_height: u32) -> Result<(), BitcoinError>;
+            async fn get_best_block_hash(&self) -> Result<BlockHash, BitcoinError>;
+            async fn get_block(&self, hash: &BlockHash) -> Result<Block, BitcoinError>;
+            async fn get_block_info(&self, hash: &BlockHash) -> Result<GetBlockResult, BitcoinError>;
+            async fn get_mempool_transactions<'a>(
+                self: Arc<Self>,
+            ) -> Result<Box<dyn Iterator<Item = Result<Transaction, BitcoinError>> + Send + 'a>, BitcoinError>;
+            async fn wait_for_transaction_metadata(
+                &self,
+                txid: Txid,
+                op_timeout: Duration,
+                num_confirmations: u32,
+            ) -> Result<TransactionMetadata, BitcoinError>;
+            async fn create_transaction<A: PartialAddress + Send + 'static>(
+                &self,
+                address: A,
+                sat: u64,
+                request_id: Option<H256>,
+            ) -> Result<LockedTransaction, BitcoinError>;
+            async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, BitcoinError>;
+            async fn create_and_send_transaction<A: PartialAddress + Send + 'static>(
+                &self,
+                address: A,
+                sat: u64,
+                request_id: Option<H256>,
+            ) -> Result<Txid, BitcoinError>;
+            async fn send_to_address<A: PartialAddress + Send + 'static>(
+                &self,
+                address: A,
+                sat: u64,
+                request_id: Option<H256>,
+                op_timeout: Duration,
+                num_confirmations: u32,
+            ) -> Result<TransactionMetadata, BitcoinError>;
+            async fn create_wallet(&self, wallet: &str) -> Result<(), BitcoinError>;
+            async fn wallet_has_public_key<P>(&self, public_key: P) -> Result<bool, BitcoinError>
+                where
+                    P: Into<[u8; PUBLIC_KEY_SIZE]> + From<[u8; PUBLIC_KEY_SIZE]> + Clone + PartialEq + Send + Sync + 'static;
+        }
+    }
+
+    /// A payment without a return-to-self output, so no address needs to be registered.
+    fn dummy_transaction() -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Default::default(),
+                sequence: 0,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 100,
+                script_pubkey: Default::default(),
+            }],
+        }
+    }
+
+    fn dummy_transaction_metadata() -> TransactionMetadata {
+        let transaction = dummy_transaction();
+        TransactionMetadata {
+            txid: transaction.txid(),
+            proof: Default::default(),
+            raw_tx: serialize(&transaction),
+            block_height: Default::default(),
+            block_hash: Default::default(),
+        }
+    }
+
+    fn dummy_request(request_type: RequestType) -> Request {
+        Request {
+            amount: 100,
+            btc_address: dummy_address(),
+            hash: H256::from_slice(&[1; 32]),
+            open_time: None,
+            request_type,
+            received: None,
+        }
+    }
+
+    fn dummy_address() -> BtcAddress {
+        BtcAddress::P2SH(H160::from_slice(&[1; 20]))
+    }
+
+    /// Returns Bob's handle to a parachain on which Bob is a vault that issued 200 tokens
+    /// to Alice.
+    async fn parachain_with_vault() -> MockParachain {
+        let vault_id = AccountKeyring::Bob.to_account_id();
+        let parachain = MockParachain::new(vault_id.clone());
+        parachain.set_free_dot_balance(vault_id.clone(), 1000);
+        parachain
+            .register_vault(1000, Default::default())
+            .await
+            .unwrap();
+
+        let user = parachain.with_account(AccountKeyring::Alice.to_account_id());
+        let issue = user.request_issue(200, vault_id, 0).await.unwrap();
+        user.execute_issue(issue.issue_id, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
+        parachain
+    }
+
+    /// Redeems 100 of Alice's tokens at the vault.
+    async fn request_redeem(parachain: &MockParachain) -> Request {
+        let user = parachain.with_account(AccountKeyring::Alice.to_account_id());
+        user.request_redeem(100, dummy_address(), parachain.get_account_id().clone())
+            .await
+            .unwrap();
+        let event = parachain
+            .events::<RequestRedeemEvent<PolkaBtcRuntime>>()
+            .pop()
+            .unwrap();
+        Request::from_redeem_request_event(&event)
+    }
+
+    /// A bitcoin client that makes the payment exactly once.
+    fn paying_bitcoin() -> MockBitcoin {
+        let mut btc_rpc = MockBitcoin::default();
+        btc_rpc
+            .expect_create_transaction::<BtcAddress>()
+            .times(1) // checks that this function is not retried
+            .returning(|_, _, _| Ok(LockedTransaction::signed(dummy_transaction())));
+        btc_rpc
+            .expect_send_transaction()
+            .times(1)
+            .returning(|tx| Ok(tx.transaction.txid()));
+        btc_rpc
+            .expect_wait_for_transaction_metadata()
+            .returning(|_, _, _| Ok(dummy_transaction_metadata()));
+        btc_rpc
+    }
+
+    #[tokio::test]
+    async fn test_pay_and_execute_redeem_succeeds() {
+        let tmp_dir = TempDir::new("payment-journal").unwrap();
+        let journal = Arc::new(PaymentJournal::open(tmp_dir.path()).unwrap());
+        let parachain = parachain_with_vault().await;
+
+        let request = request_redeem(&parachain).await;
+        assert_ok!(
+            request
+                .pay_and_execute(
+                    Arc::new(parachain.clone()),
+                    Arc::new(paying_bitcoin()),
+                    journal.clone(),
+                    6
+                )
+                .await
+        );
+        assert_eq!(
+            parachain
+                .events::<ExecuteRedeemEvent<PolkaBtcRuntime>>()
+                .len(),
+            1
+        );
+        let entry = journal.get(request.hash).unwrap().unwrap();
+        assert_eq!(entry.status, PaymentStatus::Executed);
+    }
+
+    #[tokio::test]
+    async fn test_pay_and_execute_replace_succeeds() {
+        let tmp_dir = TempDir::new("payment-journal").unwrap();
+        let journal = Arc::new(PaymentJournal::open(tmp_dir.path()).unwrap());
+        let parachain = parachain_with_vault().await;
+        let replace_id = parachain.request_replace(100, 0).await.unwrap();
+
+        let new_vault_id = AccountKeyring::Charlie.to_account_id();
+        let new_vault = parachain.with_account(new_vault_id.clone());
+        new_vault.set_free_dot_balance(new_vault_id, 1000);
+        new_vault
+            .register_vault(0, Default::default())
+            .await
+            .unwrap();
+        new_vault
+            .accept_replace(replace_id, 150, dummy_address())
+            .await
+            .unwrap();
+        let event = parachain
+            .events::<AcceptReplaceEvent<PolkaBtcRuntime>>()
+            .pop()
+            .unwrap();
+
+        let request = Request::from_accept_replace_event(&event);
+        assert_ok!(
+            request
+                .pay_and_execute(
+                    Arc::new(parachain.clone()),
+                    Arc::new(paying_bitcoin()),
+                    journal,
+                    6
+                )
+                .await
+        );
+        assert_eq!(
+            parachain
+                .events::<ExecuteReplaceEvent<PolkaBtcRuntime>>()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pay_and_execute_no_bitcoin_retry() {
+        let tmp_dir = TempDir::new("payment-journal").unwrap();
+        let journal = Arc::new(PaymentJournal::open(tmp_dir.path()).unwrap());
+        let parachain = MockParachain::new(AccountId::default());
+        let mut btc_rpc = MockBitcoin::default();
+        btc_rpc
+            .expect_create_transaction::<BtcAddress>()
+            .times(1) // checks that this function is not retried
+            .returning(|_, _, _| Err(BitcoinError::ConfirmationError));
+
+        let request = dummy_request(RequestType::Replace);
+        assert_err!(
+            request
+                .pay_and_execute(Arc::new(parachain), Arc::new(btc_rpc), journal, 6)
+                .await,
+            Error::BitcoinError(BitcoinError::ConfirmationError)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pay_and_execute_resumes_journaled_payment() {
+        let tmp_dir = TempDir::new("payment-journal").unwrap();
+        let journal = Arc::new(PaymentJournal::open(tmp_dir.path()).unwrap());
+        let parachain = parachain_with_vault().await;
+        let request = request_redeem(&parachain).await;
+        // the vault crashed after signing the payment
+        journal
+            .record_intent(request.hash, request.request_type, request.amount)
+            .unwrap();
+        journal
+            .record_signed(request.hash, &dummy_transaction())
+            .unwrap();
+
+        let mut btc_rpc = MockBitcoin::default();
+        btc_rpc.expect_create_transaction::<BtcAddress>().times(0);
+        btc_rpc.expect_send_to_address::<BtcAddress>().times(0);
+        btc_rpc
+            .expect_send_transaction()
+            .times(1)
+            .withf(|tx| tx.transaction == dummy_transaction())
+            .returning(|tx| Ok(tx.transaction.txid()));
+        btc_rpc
+            .expect_wait_for_transaction_metadata()
+            .returning(|_, _, _| Ok(dummy_transaction_metadata()));
+
+        assert_ok!(
+            request
+                .pay_and_execute(
+                    Arc::new(parachain.clone()),
+                    Arc::new(btc_rpc),
+                    journal.clone(),
+                    6
+                )
+                .await
+        );
+        assert_eq!(
+            parachain
+                .events::<ExecuteRedeemEvent<PolkaBtcRuntime>>()
+                .len(),
+            1
+        );
+        let entry = journal.get(request.hash).unwrap().unwrap();
+        assert_eq!(entry.status, PaymentStatus::Executed);
+    }
+
+    #[tokio::test]
+    async fn test_prune_executed_payments() {
+        let tmp_dir = TempDir::new("payment-journal").unwrap();
+        let journal = PaymentJournal::open(tmp_dir.path()).unwrap();
+        let parachain = parachain_with_vault().await;
+        let finalized = request_redeem(&parachain).await.hash;
+        let pending = request_redeem(&parachain).await.hash;
+        for request_id in [finalized, pending].iter().copied() {
+            journal
+                .record_intent(request_id, RequestType::Redeem, 100)
+                .unwrap();
+            journal
+                .record_signed(request_id, &dummy_transaction())
+                .unwrap();
+            journal.record_executed(request_id).unwrap();
+        }
+
+        // the execution of the pending request is not finalized yet
+        parachain
+            .execute_redeem(finalized, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
+
+        prune_executed_payments(&parachain, &journal).await.unwrap();
+        assert_eq!(journal.get(finalized).unwrap(), None);
+        assert_eq!(journal.executed().unwrap(), vec![pending]);
+    }
 }
@@ -1,13 +1,15 @@
 use crate::cancellation::RequestEvent;
+use crate::constants::BITCOIN_MAX_RETRYING_TIME;
+use crate::metrics;
 use crate::Error;
 use bitcoin::{BitcoinCoreApi, BlockHash, Transaction, TransactionExt};
 use futures::channel::mpsc::Sender;
-use futures::{SinkExt, StreamExt};
-use log::{error, info};
+use futures::{future::join_all, Future, SinkExt, StreamExt};
+use log::{error, info, warn};
 use runtime::{
     pallets::issue::{CancelIssueEvent, ExecuteIssueEvent, RequestIssueEvent},
     BtcAddress, BtcPublicKey, BtcRelayPallet, H256Le, IssuePallet, PolkaBtcProvider,
-    PolkaBtcRuntime, UtilFuncs,
+    PolkaBtcRuntime, UtilFuncs, VaultRegistryPallet,
 };
 use sha2::{Digest, Sha256};
 use sp_core::H256;
@@ -63,6 +65,20 @@ where
         }
     }
 
+    /// Returns a copy of all keys.
+    pub fn keys(&self) -> Vec<K> {
+        self.0 .0.keys().copied().collect()
+    }
+
+    /// Search the reversible map by key.
+    pub fn contains_key<Q: ?Sized>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        self.0 .0.contains_key(k)
+    }
+
     /// Search the reversible map by value.
     pub fn contains_value<Q: ?Sized>(&self, v: &Q) -> bool
     where
@@ -77,9 +93,130 @@ pub struct IssueRequests(Mutex<ReversibleHashMap<H256, BtcAddress>>);
 
 impl IssueRequests {
     pub fn new() -> Self {
-        // TODO: fetch active issue ids from storage
         IssueRequests(Mutex::new(ReversibleHashMap::new()))
     }
+
+    /// Returns true if the issue is executed once its payment is found.
+    pub async fn contains(&self, issue_id: &H256) -> bool {
+        self.0.lock().await.contains_key(issue_id)
+    }
+
+    /// Add the open issue requests of all vaults, such that requests made before the vault
+    /// (re)started are executed as well. Imports the deposit keys of this vault's requests that
+    /// the wallet is missing, then rescans the blocks once for the imported keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - the parachain RPC handle
+    /// * `btc_rpc` - the bitcoin RPC handle
+    pub async fn populate<
+        B: BitcoinCoreApi + Send + Sync + 'static,
+        P: IssuePallet + VaultRegistryPallet + UtilFuncs,
+    >(
+        &self,
+        provider: &Arc<P>,
+        btc_rpc: &Arc<B>,
+    ) -> Result<(), Error> {
+        // the opentime of the earliest request whose deposit key was imported
+        let mut rescan_opentime = None;
+        for vault in provider.get_all_vaults().await? {
+            let is_own_vault = &vault.id == provider.get_account_id();
+            let open_requests = provider
+                .get_vault_issue_requests(vault.id.clone())
+                .await?
+                .into_iter()
+                .filter(|(_, request)| !request.completed && !request.cancelled);
+
+            for (issue_id, request) in open_requests {
+                if is_own_vault {
+                    match add_new_deposit_key(
+                        btc_rpc,
+                        issue_id,
+                        vault.wallet.public_key.clone(),
+                        false,
+                    )
+                    .await
+                    {
+                        Ok(true) => {
+                            rescan_opentime = rescan_opentime
+                                .map(|opentime: u32| opentime.min(request.opentime))
+                                .or(Some(request.opentime));
+                        }
+                        Ok(false) => {}
+                        Err(e) => {
+                            error!("Failed to add deposit key #{}: {}", issue_id, e.to_string())
+                        }
+                    }
+                }
+                self.0.lock().await.insert(issue_id, request.btc_address);
+            }
+        }
+
+        if let Some(opentime) = rescan_opentime {
+            let start_height = provider.get_blockchain_height_at(opentime).await?;
+            info!(
+                "Rescanning bitcoin blocks from height {} for imported deposit keys",
+                start_height
+            );
+            if let Err(e) = btc_rpc.rescan_blockchain(start_height).await {
+                error!("Failed to rescan bitcoin blocks: {}", e.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Execute the open issues that were paid while the vault was offline, found by scanning the
+/// mempool and the blocks back to the point where the earliest open issue was requested.
+/// Returns once the issues of all payments found are executed or failed to execute.
+///
+/// # Arguments
+///
+/// * `provider` - the parachain RPC handle
+/// * `btc_rpc` - the bitcoin RPC handle
+/// * `issue_set` - all open issue ids
+/// * `num_confirmations` - the number of bitcoin confirmation to await
+pub async fn scan_for_issue_payments<
+    B: BitcoinCoreApi + Send + Sync + 'static,
+    P: IssuePallet + BtcRelayPallet + UtilFuncs + Send + Sync + 'static,
+>(
+    provider: Arc<P>,
+    btc_rpc: Arc<B>,
+    issue_set: Arc<IssueRequests>,
+    num_confirmations: u32,
+) -> Result<(), Error> {
+    let start_height = match get_issue_scan_start_height(&provider, &issue_set).await? {
+        Some(start_height) => start_height,
+        None => return Ok(()),
+    };
+    info!(
+        "Scanning bitcoin blocks from height {} for issue payments",
+        start_height
+    );
+
+    let mut executions = Vec::new();
+    let mut transaction_stream = bitcoin::get_transactions(btc_rpc.clone(), start_height).await?;
+    while let Some(transaction) = transaction_stream.next().await {
+        match transaction {
+            Ok(transaction) => {
+                if let Some(execution) = execute_issue_for_unconfirmed_transaction(
+                    &provider,
+                    &btc_rpc,
+                    &issue_set,
+                    num_confirmations,
+                    transaction,
+                )
+                .await
+                {
+                    executions.push(execution);
+                }
+            }
+            // the payments in the skipped transaction are found once they are confirmed
+            Err(e) => error!("Failed to scan bitcoin transaction: {}", e.to_string()),
+        }
+    }
+    join_all(executions).await;
+    Ok(())
 }
 
 /// execute issue requests on best-effort (i.e. don't retry on error),
@@ -90,12 +227,9 @@ pub async fn process_issue_requests<B: BitcoinCoreApi + Send + Sync + 'static>(
     issue_set: &Arc<IssueRequests>,
     num_confirmations: u32,
 ) -> Result<(), Error> {
-    let mut stream = bitcoin::stream_in_chain_transactions(
-        btc_rpc.clone(),
-        btc_rpc.get_block_count().await? as u32,
-        num_confirmations,
-    )
-    .await;
+    let tip_height = btc_rpc.get_block_count().await? as u32;
+    let mut stream =
+        bitcoin::stream_in_chain_transactions(btc_rpc.clone(), tip_height, num_confirmations).await;
 
     while let Some(Ok((block_hash, transaction))) = stream.next().await {
         if let Err(e) = process_transaction_and_execute_issue(
@@ -108,6 +242,7 @@ pub async fn process_issue_requests<B: BitcoinCoreApi + Send + Sync + 'static>(
         )
         .await
         {
+            metrics::record_extrinsic_failure(&e);
             error!("Error executing issue request: {}", e.to_string());
         }
     }
@@ -115,6 +250,100 @@ pub async fn process_issue_requests<B: BitcoinCoreApi + Send + Sync + 'static>(
     Err(Error::NoIncomingBlocks)
 }
 
+/// Returns the bitcoin height at the time the earliest open issue was requested, None if there
+/// are no open issues. Issues that were executed or cancelled in the meantime are removed.
+async fn get_issue_scan_start_height<P: IssuePallet + UtilFuncs>(
+    provider: &Arc<P>,
+    issue_set: &Arc<IssueRequests>,
+) -> Result<Option<u32>, Error> {
+    let issue_ids = issue_set.0.lock().await.keys();
+
+    let mut earliest_opentime = None;
+    for issue_id in issue_ids {
+        match provider.get_issue_request(issue_id).await {
+            Ok(request) if request.completed || request.cancelled => {
+                issue_set.0.lock().await.remove_key(&issue_id);
+            }
+            Ok(request) => {
+                earliest_opentime = earliest_opentime
+                    .map(|opentime: u32| opentime.min(request.opentime))
+                    .or(Some(request.opentime));
+            }
+            Err(e) => warn!("Failed to fetch issue request #{}: {}", issue_id, e),
+        }
+    }
+
+    match earliest_opentime {
+        Some(opentime) => Ok(Some(provider.get_blockchain_height_at(opentime).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Check whether the transaction pays an open issue. If so, the issue is removed from the set,
+/// such that it is not executed again once the payment is found in a block, and the returned
+/// future awaits the confirmation of the transaction and executes the issue. Returns None if
+/// no open issue is paid.
+async fn execute_issue_for_unconfirmed_transaction<
+    B: BitcoinCoreApi + Send + Sync + 'static,
+    P: IssuePallet + BtcRelayPallet + Send + Sync + 'static,
+>(
+    provider: &Arc<P>,
+    btc_rpc: &Arc<B>,
+    issue_set: &Arc<IssueRequests>,
+    num_confirmations: u32,
+    transaction: Transaction,
+) -> Option<impl Future<Output = ()>> {
+    let addresses = transaction.extract_output_addresses::<BtcAddress>();
+    let issue_id = {
+        let mut issue_requests = issue_set.0.lock().await;
+        addresses
+            .iter()
+            .find_map(|address| issue_requests.remove_value(address))?
+    };
+    info!("Found payment for issue with id {}", issue_id);
+
+    let provider = provider.clone();
+    let btc_rpc = btc_rpc.clone();
+    Some(async move {
+        let result: Result<(), Error> = async {
+            // the payment might not have been confirmed enough times yet
+            let tx_metadata = btc_rpc
+                .wait_for_transaction_metadata(
+                    transaction.txid(),
+                    BITCOIN_MAX_RETRYING_TIME,
+                    num_confirmations,
+                )
+                .await?;
+            provider
+                .wait_for_block_in_relay(
+                    H256Le::from_bytes_le(&tx_metadata.block_hash.to_vec()),
+                    num_confirmations,
+                )
+                .await?;
+
+            // this will error if someone else executes the issue first
+            provider
+                .execute_issue(
+                    issue_id,
+                    H256Le::from_bytes_le(&tx_metadata.txid.as_hash()),
+                    tx_metadata.proof,
+                    tx_metadata.raw_tx,
+                )
+                .await?;
+            Ok(())
+        }
+        .await;
+
+        match result {
+            Ok(_) => info!("Executed issue with id {}", issue_id),
+            Err(e) => {
+                metrics::record_extrinsic_failure(&e);
+                error!("Failed to execute issue #{}: {}", issue_id, e.to_string())
+            }
+        }
+    })
+}
+
 /// extract op_return output and check corresponding issue ids
 async fn process_transaction_and_execute_issue<B: BitcoinCoreApi + Send + Sync + 'static>(
     provider: &Arc<PolkaBtcProvider>,
@@ -164,21 +393,22 @@ async fn process_transaction_and_execute_issue<B: BitcoinCoreApi + Send + Sync +
     Ok(())
 }
 
-/// Import the deposit key using the on-chain key derivation scheme
+/// Import the deposit key using the on-chain key derivation scheme, unless the wallet
+/// already has it. Returns whether the key was imported.
 async fn add_new_deposit_key<B: BitcoinCoreApi + Send + Sync + 'static>(
     btc_rpc: &Arc<B>,
     secure_id: H256,
     public_key: BtcPublicKey,
-) -> Result<(), Error> {
+    rescan: bool,
+) -> Result<bool, Error> {
     let mut hasher = Sha256::default();
     // input compressed public key
     hasher.input(public_key.0.to_vec());
     // input issue id
     hasher.input(secure_id.as_bytes());
-    btc_rpc
-        .add_new_deposit_key(public_key, hasher.result().as_slice().to_vec())
-        .await?;
-    Ok(())
+    Ok(btc_rpc
+        .add_new_deposit_key(public_key, hasher.result().as_slice().to_vec(), rescan)
+        .await?)
 }
 
 /// Listen for RequestIssueEvent directed at this vault. Schedules a cancellation of
@@ -188,7 +418,7 @@ async fn add_new_deposit_key<B: BitcoinCoreApi + Send + Sync + 'static>(
 ///
 /// * `provider` - the parachain RPC handle
 /// * `event_channel` - the channel over which to signal events
-/// * `issue_set` - all issue ids observed since vault started
+/// * `issue_set` - all open issue ids
 pub async fn listen_for_issue_requests<B: BitcoinCoreApi + Send + Sync + 'static>(
     provider: Arc<PolkaBtcProvider>,
     btc_rpc: Arc<B>,
@@ -209,7 +439,7 @@ pub async fn listen_for_issue_requests<B: BitcoinCoreApi + Send + Sync + 'static
                     let _ = event_channel.clone().send(RequestEvent::Opened).await;
 
                     if let Err(e) =
-                        add_new_deposit_key(btc_rpc, event.issue_id, event.public_key).await
+                        add_new_deposit_key(btc_rpc, event.issue_id, event.public_key, true).await
                     {
                         error!(
                             "Failed to add new deposit key #{}: {}",
@@ -237,7 +467,7 @@ pub async fn listen_for_issue_requests<B: BitcoinCoreApi + Send + Sync + 'static
 ///
 /// * `provider` - the parachain RPC handle
 /// * `event_channel` - the channel over which to signal events
-/// * `issue_set` - all issue ids observed since vault started
+/// * `issue_set` - all open issue ids
 pub async fn listen_for_issue_executes(
     provider: Arc<PolkaBtcProvider>,
     event_channel: Sender<RequestEvent>,
@@ -270,7 +500,7 @@ pub async fn listen_for_issue_executes(
 /// # Arguments
 ///
 /// * `provider` - the parachain RPC handle
-/// * `issue_set` - all issue ids observed since vault started
+/// * `issue_set` - all open issue ids
 pub async fn listen_for_issue_cancels(
     provider: Arc<PolkaBtcProvider>,
     issue_set: Arc<IssueRequests>,
@@ -285,3 +515,366 @@ pub async fn listen_for_issue_cancels(
         )
         .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bitcoin::{
+        Block, Error as BitcoinError, GetBlockResult, Hash as _, LockedTransaction, PartialAddress,
+        TransactionMetadata, Txid, PUBLIC_KEY_SIZE,
+    };
+    use bitcoin::{OutPoint, Script, TxIn, TxOut};
+    use runtime::{
+        pallets::issue::ExecuteIssueEvent,
+        testing::{MockParachain, DEFAULT_REQUEST_PERIOD},
+        AccountId, PolkaBtcRequestIssueEvent, RawBlockHeader,
+    };
+    use sp_core::H160;
+    use sp_keyring::AccountKeyring;
+    use std::time::Duration;
+
+    mockall::mock! {
+        Bitcoin {}
+
+        #[async_trait]
+        trait BitcoinCoreApi {
+            async fn wait_for_block(&self, height: u32, delay: Duration, num_confirmations: u32) -> Result<BlockHash, BitcoinError>;
+            async fn get_block_count(&self) -> Result<u64, BitcoinError>;
+            async fn get_balance(&self) -> Result<u64, BitcoinError>;
+            async fn get_raw_tx_for(&self, txid: &Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
+            async fn get_proof_for(&self, txid: Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
+            async fn get_block_hash_for(&self, height: u32) -> Result<BlockHash, BitcoinError>;
+            async fn is_block_known(&self, block_hash: BlockHash) -> Result<bool, BitcoinError>;
+            async fn get_new_address<A: PartialAddress + Send + 'static>(&self) -> Result<A, BitcoinError>;
+            async fn get_new_public_key<P: From<[u8; PUBLIC_KEY_SIZE]> + 'static>(&self) -> Result<P, BitcoinError>;
+            async fn add_new_deposit_key<P: Into<[u8; PUBLIC_KEY_SIZE]> + Send + Sync + 'static>(
+                &self,
+                public_key: P,
+                secret_key: Vec<u8>,
+                rescan: bool,
+            ) -> Result<bool, BitcoinError>;
+            async fn rescan_blockchain(&self, start_height: u32) -> Result<(), BitcoinError>;
+            async fn get_best_block_hash(&self) -> Result<BlockHash, BitcoinError>;
+            async fn get_block(&self, hash: &BlockHash) -> Result<Block, BitcoinError>;
+            async fn get_block_info(&self, hash: &BlockHash) -> Result<GetBlockResult, BitcoinError>;
+            async fn get_mempool_transactions<'a>(
+                self: Arc<Self>,
+            ) -> Result<Box<dyn Iterator<Item = Result<Transaction, BitcoinError>> + Send + 'a>, BitcoinError>;
+            async fn wait_for_transaction_metadata(
+                &self,
+                txid: Txid,
+                op_timeout: Duration,
+                num_confirmations: u32,
+            ) -> Result<TransactionMetadata, BitcoinError>;
+            async fn create_transaction<A: PartialAddress + Send + 'static>(
+                &self,
+                address: A,
+                sat: u64,
+                request_id: Option<H256>,
+            ) -> Result<LockedTransaction, BitcoinError>;
+            async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, BitcoinError>;
+            async fn create_and_send_transaction<A: PartialAddress + Send + 'static>(
+                &self,
+                address: A,
+                sat: u64,
+                request_id: Option<H256>,
+            ) -> Result<Txid, BitcoinError>;
+            async fn send_to_address<A: PartialAddress + Send + 'static>(
+                &self,
+                address: A,
+                sat: u64,
+                request_id: Option<H256>,
+                op_timeout: Duration,
+                num_confirmations: u32,
+            ) -> Result<TransactionMetadata, BitcoinError>;
+            async fn create_wallet(&self, wallet: &str) -> Result<(), BitcoinError>;
+            async fn wallet_has_public_key<P>(&self, public_key: P) -> Result<bool, BitcoinError>
+                where
+                    P: Into<[u8; PUBLIC_KEY_SIZE]> + From<[u8; PUBLIC_KEY_SIZE]> + Clone + PartialEq + Send + Sync + 'static;
+        }
+    }
+
+    /// Returns Bob's handle to a parachain on which Bob is a vault with the given public key.
+    async fn parachain_with_vault(public_key: BtcPublicKey) -> MockParachain {
+        let vault_id = AccountKeyring::Bob.to_account_id();
+        let parachain = MockParachain::new(vault_id.clone());
+        parachain.set_free_dot_balance(vault_id, 1000);
+        parachain.register_vault(1000, public_key).await.unwrap();
+        parachain
+    }
+
+    /// Alice requests to issue a single token at the given vault.
+    async fn request_issue(
+        parachain: &MockParachain,
+        vault_id: AccountId,
+    ) -> PolkaBtcRequestIssueEvent {
+        parachain
+            .with_account(AccountKeyring::Alice.to_account_id())
+            .request_issue(1, vault_id, 0)
+            .await
+            .unwrap()
+    }
+
+    /// Initializes the relay with a single block at the given height, returns its hash.
+    async fn relay_block(parachain: &MockParachain, height: u32) -> BlockHash {
+        let header = RawBlockHeader::from_bytes(&[0; 80][..]).unwrap();
+        let block_hash = BlockHash::from_slice(&header.hash().to_bytes_le()).unwrap();
+        parachain
+            .initialize_btc_relay(header, height)
+            .await
+            .unwrap();
+        block_hash
+    }
+
+    #[tokio::test]
+    async fn test_populate_loads_open_issues() {
+        let public_key = BtcPublicKey([2; 33]);
+        let parachain = parachain_with_vault(public_key.clone()).await;
+        let vault_id = parachain.get_account_id().clone();
+
+        let other_vault_id = AccountKeyring::Charlie.to_account_id();
+        let other_vault = parachain.with_account(other_vault_id.clone());
+        other_vault.set_free_dot_balance(other_vault_id.clone(), 1000);
+        other_vault
+            .register_vault(1000, BtcPublicKey([3; 33]))
+            .await
+            .unwrap();
+
+        let open = request_issue(&parachain, vault_id.clone()).await.issue_id;
+        let completed = request_issue(&parachain, vault_id.clone()).await.issue_id;
+        let cancelled = request_issue(&parachain, vault_id).await.issue_id;
+        let other_open = request_issue(&parachain, other_vault_id).await.issue_id;
+        parachain
+            .execute_issue(completed, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
+        parachain.advance_chain(DEFAULT_REQUEST_PERIOD + 1);
+        parachain.cancel_issue(cancelled).await.unwrap();
+        relay_block(&parachain, 100).await;
+
+        // only the deposit key of this vault's open issue is imported, without a rescan per key
+        let mut btc_rpc = MockBitcoin::default();
+        btc_rpc
+            .expect_add_new_deposit_key::<BtcPublicKey>()
+            .times(1)
+            .withf(move |key, _, &rescan| key == &public_key && !rescan)
+            .returning(|_, _, _| Ok(true));
+        btc_rpc
+            .expect_rescan_blockchain()
+            .times(1)
+            .withf(|&start_height| start_height == 100)
+            .returning(|_| Ok(()));
+
+        let issue_set = IssueRequests::new();
+        issue_set
+            .populate(&Arc::new(parachain), &Arc::new(btc_rpc))
+            .await
+            .unwrap();
+
+        assert!(issue_set.contains(&open).await);
+        assert!(issue_set.contains(&other_open).await);
+        assert!(!issue_set.contains(&completed).await);
+        assert!(!issue_set.contains(&cancelled).await);
+    }
+
+    #[tokio::test]
+    async fn test_populate_does_not_rescan_without_missing_keys() {
+        let parachain = parachain_with_vault(Default::default()).await;
+        let issue = request_issue(&parachain, parachain.get_account_id().clone()).await;
+
+        // the wallet already has the key
+        let mut btc_rpc = MockBitcoin::default();
+        btc_rpc
+            .expect_add_new_deposit_key::<BtcPublicKey>()
+            .times(1)
+            .returning(|_, _, _| Ok(false));
+        btc_rpc.expect_rescan_blockchain().times(0);
+
+        let issue_set = IssueRequests::new();
+        issue_set
+            .populate(&Arc::new(parachain), &Arc::new(btc_rpc))
+            .await
+            .unwrap();
+        assert!(issue_set.contains(&issue.issue_id).await);
+    }
+
+    fn issue_set_with(issues: &[(H256, BtcAddress)]) -> Arc<IssueRequests> {
+        let issue_set = IssueRequests::new();
+        {
+            let mut issue_requests = issue_set.0.try_lock().unwrap();
+            for (issue_id, address) in issues {
+                issue_requests.insert(*issue_id, *address);
+            }
+        }
+        Arc::new(issue_set)
+    }
+
+    #[tokio::test]
+    async fn test_get_issue_scan_start_height() {
+        let parachain = parachain_with_vault(Default::default()).await;
+        let vault_id = parachain.get_account_id().clone();
+        let early = request_issue(&parachain, vault_id.clone()).await;
+        parachain.advance_chain(5);
+        let late = request_issue(&parachain, vault_id.clone()).await;
+        let completed = request_issue(&parachain, vault_id).await;
+        parachain
+            .execute_issue(completed.issue_id, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
+        relay_block(&parachain, 100).await;
+
+        let issue_set = issue_set_with(&[
+            (early.issue_id, early.btc_address),
+            (late.issue_id, late.btc_address),
+            (completed.issue_id, completed.btc_address),
+        ]);
+        let start_height = get_issue_scan_start_height(&Arc::new(parachain), &issue_set)
+            .await
+            .unwrap();
+        assert_eq!(start_height, Some(100));
+        // completed issues are removed
+        assert!(!issue_set.contains(&completed.issue_id).await);
+        assert!(issue_set.contains(&early.issue_id).await);
+        assert!(issue_set.contains(&late.issue_id).await);
+    }
+
+    #[tokio::test]
+    async fn test_get_issue_scan_start_height_without_open_issues() {
+        let parachain = MockParachain::new(AccountId::default());
+        let start_height =
+            get_issue_scan_start_height(&Arc::new(parachain), &Arc::new(IssueRequests::new()))
+                .await
+                .unwrap();
+        assert_eq!(start_height, None);
+    }
+
+    #[tokio::test]
+    async fn test_scan_for_issue_payments_without_open_issues() {
+        // the bitcoin client panics if it is called
+        let parachain = MockParachain::new(AccountId::default());
+        scan_for_issue_payments(
+            Arc::new(parachain),
+            Arc::new(MockBitcoin::default()),
+            Arc::new(IssueRequests::new()),
+            6,
+        )
+        .await
+        .unwrap();
+    }
+
+    fn payment_to(address: H160) -> Transaction {
+        // OP_DUP OP_HASH160 <address> OP_EQUALVERIFY OP_CHECKSIG
+        let mut script = vec![0x76, 0xa9, 20];
+        script.extend_from_slice(address.as_bytes());
+        script.extend_from_slice(&[0x88, 0xac]);
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Default::default(),
+                sequence: 0,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 100,
+                script_pubkey: Script::from(script),
+            }],
+        }
+    }
+
+    fn confirming_bitcoin(block_hash: BlockHash) -> MockBitcoin {
+        let mut btc_rpc = MockBitcoin::default();
+        btc_rpc
+            .expect_wait_for_transaction_metadata()
+            .returning(move |txid, _, _| {
+                Ok(TransactionMetadata {
+                    txid,
+                    proof: vec![],
+                    raw_tx: vec![],
+                    block_height: 0,
+                    block_hash,
+                })
+            });
+        btc_rpc
+    }
+
+    /// Returns the parachain with an open issue, the set containing the issue and the
+    /// address that pays it.
+    async fn parachain_with_open_issue() -> (MockParachain, Arc<IssueRequests>, H160) {
+        let parachain = parachain_with_vault(Default::default()).await;
+        let issue = request_issue(&parachain, parachain.get_account_id().clone()).await;
+        let issue_set = issue_set_with(&[(issue.issue_id, issue.btc_address)]);
+        match issue.btc_address {
+            BtcAddress::P2PKH(address) => (parachain, issue_set, address),
+            _ => unreachable!("the mock pays issues to P2PKH addresses"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_issue_for_unconfirmed_transaction() {
+        let (parachain, issue_set, address) = parachain_with_open_issue().await;
+        let block_hash = relay_block(&parachain, 1).await;
+        let (provider, btc_rpc) = (
+            Arc::new(parachain.clone()),
+            Arc::new(confirming_bitcoin(block_hash)),
+        );
+
+        // transactions that pay no open issue are ignored
+        let unrelated = payment_to(H160::from_low_u64_be(u64::MAX));
+        assert!(execute_issue_for_unconfirmed_transaction(
+            &provider, &btc_rpc, &issue_set, 0, unrelated
+        )
+        .await
+        .is_none());
+
+        execute_issue_for_unconfirmed_transaction(
+            &provider,
+            &btc_rpc,
+            &issue_set,
+            0,
+            payment_to(address),
+        )
+        .await
+        .unwrap()
+        .await;
+        assert_eq!(
+            parachain
+                .events::<ExecuteIssueEvent<PolkaBtcRuntime>>()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_issue_for_unconfirmed_transaction_removes_issue_before_execution() {
+        let (parachain, issue_set, address) = parachain_with_open_issue().await;
+        let (provider, btc_rpc) = (
+            Arc::new(parachain.clone()),
+            Arc::new(MockBitcoin::default()),
+        );
+
+        // the execution is not started, yet the payment is not found a second time, neither
+        // by the back-scan nor by the scan of new blocks
+        let execution = execute_issue_for_unconfirmed_transaction(
+            &provider,
+            &btc_rpc,
+            &issue_set,
+            0,
+            payment_to(address),
+        )
+        .await;
+        assert!(execution.is_some());
+        assert!(execute_issue_for_unconfirmed_transaction(
+            &provider,
+            &btc_rpc,
+            &issue_set,
+            0,
+            payment_to(address)
+        )
+        .await
+        .is_none());
+        assert!(issue_set.0.lock().await.keys().is_empty());
+    }
+}
@@ -0,0 +1,377 @@
+use crate::error::Error;
+use crate::execution::RequestType;
+use bitcoin::{deserialize, serialize, Transaction};
+use kv::{Bucket, Config, Store};
+use parity_scale_codec::{Decode, Encode};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sp_core::H256;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+const JOURNAL_BUCKET_NAME: &str = "payments";
+
+/// Progress of the bitcoin payment for a redeem, replace or refund request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Encode, Decode, JsonSchema)]
+pub enum PaymentStatus {
+    /// The payment is about to be created, nothing was sent yet.
+    Intent,
+    /// The payment was signed and is about to be broadcast.
+    Signed,
+    /// The payment was placed into the mempool.
+    Broadcast,
+    /// The request was executed on the parachain.
+    Executed,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaymentEntry {
+    pub request_type: RequestType,
+    pub amount: u128,
+    /// Hex encoded signed transaction, set from `PaymentStatus::Signed` onwards.
+    pub raw_tx: Option<String>,
+    pub txid: Option<String>,
+    pub status: PaymentStatus,
+}
+
+impl PaymentEntry {
+    /// Returns the signed payment, None if it was not created yet. Once it exists, the
+    /// request must never be paid with another transaction.
+    pub fn transaction(&self) -> Result<Option<Transaction>, Error> {
+        match &self.raw_tx {
+            Some(raw_tx) => {
+                let raw_tx = hex::decode(raw_tx).map_err(|_| Error::InvalidJournalEntry)?;
+                let transaction = deserialize(&raw_tx).map_err(|_| Error::InvalidJournalEntry)?;
+                Ok(Some(transaction))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+struct InFlightPayments {
+    count: AtomicUsize,
+    draining: AtomicBool,
+    notify: Notify,
+    /// The tasks that pay and execute requests, each removes itself once it completes.
+    tasks: Mutex<HashMap<u64, JoinHandle<()>>>,
+    next_task: AtomicU64,
+}
+
+/// Held while a payment is made, until it is broadcast. See `PaymentJournal::begin_payment`.
+pub struct PaymentGuard(Arc<InFlightPayments>);
+
+impl Drop for PaymentGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.notify.notify();
+        }
+    }
+}
+
+/// Durable record of the bitcoin payments made by the vault, indexed by request id. The
+/// signed transaction is written before it is broadcast, such that after a crash the vault
+/// resumes the payment rather than paying a second time.
+#[derive(Clone)]
+pub struct PaymentJournal {
+    store: Store,
+    in_flight: Arc<InFlightPayments>,
+}
+
+impl PaymentJournal {
+    /// Open the journal in the given directory, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(Self {
+            store: Store::new(Config::new(path.as_ref()))?,
+            in_flight: Arc::new(InFlightPayments {
+                count: AtomicUsize::new(0),
+                draining: AtomicBool::new(false),
+                notify: Notify::new(),
+                tasks: Mutex::new(HashMap::new()),
+                next_task: AtomicU64::new(0),
+            }),
+        })
+    }
+
+    /// Register a payment that is about to be made, fails once the journal is drained.
+    /// The payment counts as in flight until the returned guard is dropped.
+    pub fn begin_payment(&self) -> Result<PaymentGuard, Error> {
+        self.in_flight.count.fetch_add(1, Ordering::SeqCst);
+        let guard = PaymentGuard(self.in_flight.clone());
+        if self.in_flight.draining.load(Ordering::SeqCst) {
+            return Err(Error::ShuttingDown);
+        }
+        Ok(guard)
+    }
+
+    /// Spawn a task that pays and executes a request, `drain` waits for it to complete.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let in_flight = self.in_flight.clone();
+        // hold the lock until the handle is inserted, such that a task that completes
+        // immediately does not leave its handle behind
+        let mut tasks = self.in_flight.tasks.lock().unwrap();
+        let id = self.in_flight.next_task.fetch_add(1, Ordering::SeqCst);
+        let handle = tokio::spawn(async move {
+            task.await;
+            in_flight.tasks.lock().unwrap().remove(&id);
+        });
+        tasks.insert(id, handle);
+    }
+
+    /// Refuse new payments and wait until all spawned tasks completed and all payments in
+    /// flight have been released.
+    pub async fn drain(&self) {
+        self.in_flight.draining.store(true, Ordering::SeqCst);
+        loop {
+            let tasks = self
+                .in_flight
+                .tasks
+                .lock()
+                .unwrap()
+                .drain()
+                .map(|(_, handle)| handle)
+                .collect::<Vec<_>>();
+            if tasks.is_empty() {
+                break;
+            }
+            // a task that panicked is complete as well
+            let _ = futures::future::join_all(tasks).await;
+        }
+        while self.in_flight.count.load(Ordering::SeqCst) > 0 {
+            self.in_flight.notify.notified().await;
+        }
+    }
+
+    /// Wait until all entries are persisted.
+    pub fn flush(&self) -> Result<(), Error> {
+        self.bucket()?.flush()?;
+        Ok(())
+    }
+
+    fn bucket(&self) -> Result<Bucket<String, String>, Error> {
+        Ok(self
+            .store
+            .bucket::<String, String>(Some(JOURNAL_BUCKET_NAME))?)
+    }
+
+    fn key(request_id: H256) -> String {
+        hex::encode(request_id)
+    }
+
+    pub fn get(&self, request_id: H256) -> Result<Option<PaymentEntry>, Error> {
+        match self.bucket()?.get(Self::key(request_id))? {
+            Some(entry) => Ok(Some(serde_json::from_str(&entry)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Write the entry and wait until it is persisted.
+    fn set(&self, request_id: H256, entry: &PaymentEntry) -> Result<(), Error> {
+        let bucket = self.bucket()?;
+        bucket.set(Self::key(request_id), serde_json::to_string(entry)?)?;
+        bucket.flush()?;
+        Ok(())
+    }
+
+    fn update_status(&self, request_id: H256, status: PaymentStatus) -> Result<(), Error> {
+        let mut entry = self.get(request_id)?.ok_or(Error::InvalidJournalEntry)?;
+        entry.status = status;
+        self.set(request_id, &entry)
+    }
+
+    /// Record that the request is about to be paid. Does not overwrite an existing payment.
+    pub fn record_intent(
+        &self,
+        request_id: H256,
+        request_type: RequestType,
+        amount: u128,
+    ) -> Result<(), Error> {
+        if let Some(entry) = self.get(request_id)? {
+            if entry.status != PaymentStatus::Intent {
+                return Ok(());
+            }
+        }
+        self.set(
+            request_id,
+            &PaymentEntry {
+                request_type,
+                amount,
+                raw_tx: None,
+                txid: None,
+                status: PaymentStatus::Intent,
+            },
+        )
+    }
+
+    /// Record the signed payment, must be called before it is broadcast.
+    pub fn record_signed(&self, request_id: H256, transaction: &Transaction) -> Result<(), Error> {
+        let mut entry = self.get(request_id)?.ok_or(Error::InvalidJournalEntry)?;
+        entry.raw_tx = Some(hex::encode(serialize(transaction)));
+        entry.txid = Some(transaction.txid().to_string());
+        entry.status = PaymentStatus::Signed;
+        self.set(request_id, &entry)
+    }
+
+    pub fn record_broadcast(&self, request_id: H256) -> Result<(), Error> {
+        self.update_status(request_id, PaymentStatus::Broadcast)
+    }
+
+    pub fn record_executed(&self, request_id: H256) -> Result<(), Error> {
+        self.update_status(request_id, PaymentStatus::Executed)
+    }
+
+    /// Returns the ids of the requests whose execution was recorded.
+    pub fn executed(&self) -> Result<Vec<H256>, Error> {
+        let mut executed = vec![];
+        for item in self.bucket()?.iter() {
+            let item = item?;
+            let entry: PaymentEntry = serde_json::from_str(&item.value::<String>()?)?;
+            if entry.status != PaymentStatus::Executed {
+                continue;
+            }
+            let key = hex::decode(item.key::<String>()?).map_err(|_| Error::InvalidJournalEntry)?;
+            if key.len() != H256::len_bytes() {
+                return Err(Error::InvalidJournalEntry);
+            }
+            executed.push(H256::from_slice(&key));
+        }
+        Ok(executed)
+    }
+
+    /// Remove the entry of the request and wait until this is persisted. Must only be called
+    /// once the execution of the request is finalized.
+    pub fn remove(&self, request_id: H256) -> Result<(), Error> {
+        let bucket = self.bucket()?;
+        bucket.remove(Self::key(request_id))?;
+        bucket.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{OutPoint, TxIn, TxOut};
+    use tempdir::TempDir;
+
+    fn dummy_transaction() -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Default::default(),
+                sequence: 0,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 100,
+                script_pubkey: Default::default(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_payment_survives_reopening() {
+        let tmp_dir = TempDir::new("payment-journal").unwrap();
+        let request_id = H256::repeat_byte(1);
+        let transaction = dummy_transaction();
+
+        {
+            let journal = PaymentJournal::open(tmp_dir.path()).unwrap();
+            journal
+                .record_intent(request_id, RequestType::Redeem, 100)
+                .unwrap();
+            journal.record_signed(request_id, &transaction).unwrap();
+        }
+
+        let journal = PaymentJournal::open(tmp_dir.path()).unwrap();
+        let entry = journal.get(request_id).unwrap().unwrap();
+        assert_eq!(entry.status, PaymentStatus::Signed);
+        assert_eq!(entry.transaction().unwrap(), Some(transaction));
+
+        // the signed payment is kept if the request is processed again
+        journal
+            .record_intent(request_id, RequestType::Redeem, 100)
+            .unwrap();
+        journal.record_broadcast(request_id).unwrap();
+        let entry = journal.get(request_id).unwrap().unwrap();
+        assert_eq!(entry.status, PaymentStatus::Broadcast);
+        assert!(entry.raw_tx.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_payments() {
+        let tmp_dir = TempDir::new("payment-journal").unwrap();
+        let journal = PaymentJournal::open(tmp_dir.path()).unwrap();
+        let payment = journal.begin_payment().unwrap();
+
+        let draining_journal = journal.clone();
+        let drained = tokio::spawn(async move { draining_journal.drain().await });
+        tokio::task::yield_now().await;
+        // no new payments are started while draining
+        assert!(matches!(journal.begin_payment(), Err(Error::ShuttingDown)));
+
+        drop(payment);
+        drained.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_tasks() {
+        let tmp_dir = TempDir::new("payment-journal").unwrap();
+        let journal = PaymentJournal::open(tmp_dir.path()).unwrap();
+        let (executed_tx, mut executed_rx) = futures::channel::oneshot::channel();
+        let (release_tx, release_rx) = futures::channel::oneshot::channel::<()>();
+        journal.spawn(async move {
+            let _ = release_rx.await;
+            executed_tx.send(()).unwrap();
+        });
+
+        let draining_journal = journal.clone();
+        let drained = tokio::spawn(async move { draining_journal.drain().await });
+        tokio::task::yield_now().await;
+        release_tx.send(()).unwrap();
+        drained.await.unwrap();
+        // the task completed before the drain
+        assert_eq!(executed_rx.try_recv(), Ok(Some(())));
+    }
+
+    #[test]
+    fn test_remove_executed() {
+        let tmp_dir = TempDir::new("payment-journal").unwrap();
+        let journal = PaymentJournal::open(tmp_dir.path()).unwrap();
+        let (executed, broadcast) = (H256::repeat_byte(1), H256::repeat_byte(2));
+        for request_id in [executed, broadcast].iter().copied() {
+            journal
+                .record_intent(request_id, RequestType::Redeem, 100)
+                .unwrap();
+            journal
+                .record_signed(request_id, &dummy_transaction())
+                .unwrap();
+            journal.record_broadcast(request_id).unwrap();
+        }
+        journal.record_executed(executed).unwrap();
+        assert_eq!(journal.executed().unwrap(), vec![executed]);
+
+        journal.remove(executed).unwrap();
+        assert_eq!(journal.get(executed).unwrap(), None);
+        assert!(journal.executed().unwrap().is_empty());
+        assert!(journal.get(broadcast).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_unknown_request() {
+        let tmp_dir = TempDir::new("payment-journal").unwrap();
+        let journal = PaymentJournal::open(tmp_dir.path()).unwrap();
+        assert_eq!(journal.get(H256::zero()).unwrap(), None);
+        assert!(journal.record_executed(H256::zero()).is_err());
+    }
+}
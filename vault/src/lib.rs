@@ -7,9 +7,12 @@ mod constants;
 mod error;
 mod execution;
 mod issue;
+mod journal;
+mod metrics;
 mod redeem;
 mod refund;
 mod replace;
+mod supervisor;
 
 use crate::{
     cancellation::{CancellationScheduler, IssueCanceller, ReplaceCanceller},
@@ -25,17 +28,18 @@ use futures::SinkExt;
 use log::*;
 use runtime::{
     pallets::sla::UpdateVaultSLAEvent, BtcRelayPallet, Error as RuntimeError, PolkaBtcHeader,
-    PolkaBtcProvider, PolkaBtcRuntime, UtilFuncs, VaultRegistryPallet,
+    PolkaBtcProvider, PolkaBtcRuntime, SlaHistory, UtilFuncs, VaultRegistryPallet,
 };
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::time::delay_for;
+use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::{delay_for, timeout};
 
 pub use crate::error::Error;
 
 pub mod service {
     pub use crate::execution::execute_open_requests;
     pub use crate::execution::execute_open_issue_requests;
+    pub use crate::execution::prune_payment_journal;
     pub use crate::redeem::listen_for_redeem_requests;
     pub use crate::refund::listen_for_refund_requests;
     pub use crate::replace::listen_for_replace_requests;
@@ -46,9 +50,14 @@ pub mod service {
     pub use crate::issue::listen_for_issue_requests;
     pub use crate::issue::listen_for_issue_executes;
     pub use crate::issue::listen_for_issue_cancels;
+    pub use crate::issue::scan_for_issue_payments;
 }
 pub use crate::issue::IssueRequests;
-pub use crate::cancellation::RequestEvent;
+pub use crate::cancellation::{ActiveRequest, CancellationQueue, RequestEvent};
+pub use crate::journal::{PaymentEntry, PaymentJournal, PaymentStatus};
+pub use crate::supervisor::{
+    HealthMonitor, RestartPolicy, ServiceHealth, ServiceStatus, Supervisor,
+};
 use service::*;
 
 #[derive(Debug, Copy, Clone)]
@@ -71,17 +80,23 @@ impl FromStr for BitcoinNetwork {
 #[derive(Clap, Debug, Clone)]
 #[clap(version = "0.1", author = "Interlay <contact@interlay.io>")]
 pub struct Opts {
-    /// Parachain URL, can be over WebSockets or HTTP.
-    #[clap(long, default_value = "ws://127.0.0.1:9944")]
-    pub polka_btc_url: String,
+    /// Comma separated list of parachain URLs, can be over WebSockets or HTTP.
+    /// Switches to another URL if the current one becomes unhealthy.
+    #[clap(long, default_value = "ws://127.0.0.1:9944", use_delimiter = true)]
+    pub polka_btc_url: Vec<String>,
 
     /// Address to listen on for JSON-RPC requests.
-    #[clap(long, default_value = "[::0]:3031")]
+    #[clap(long, default_value = "127.0.0.1:3031")]
     pub http_addr: String,
 
-    /// Comma separated list of allowed origins.
-    #[clap(long, default_value = "*")]
-    pub rpc_cors_domain: String,
+    /// Comma separated list of origins that may call the api from a browser. If not set,
+    /// cross-origin requests are refused.
+    #[clap(long)]
+    pub rpc_cors_domain: Option<String>,
+
+    /// Authentication of the JSON-RPC api.
+    #[clap(flatten)]
+    pub api_auth: runtime::cli::ApiAuthOpts,
 
     /// Automatically register the vault with the given amount of collateral and a newly generated address.
     #[clap(long)]
@@ -111,10 +126,37 @@ pub struct Opts {
     #[clap(long, default_value = "1000000")]
     pub max_collateral: u128,
 
-    /// Timeout in milliseconds to repeat collateralization checks.
-    #[clap(long, default_value = "5000")]
+    /// Timeout in milliseconds to repeat collateralization checks. Vaults are also
+    /// checked whenever the exchange rate changes.
+    #[clap(long, default_value = "60000")]
     pub collateral_timeout_ms: u64,
 
+    /// Cache frequently read parachain storage, such as the vault and the exchange rate,
+    /// as of the latest finalized block.
+    #[clap(long)]
+    pub storage_cache: bool,
+
+    /// Directory of the journal that records the bitcoin payments of the vault, such that
+    /// they are resumed rather than repeated after a restart.
+    #[clap(long, default_value = "./payment-journal")]
+    pub payment_journal: String,
+
+    /// Maximum time in seconds to wait on shutdown for bitcoin payments that are in flight,
+    /// and the execution of their requests. Payments that did not finish in time are
+    /// resumed after a restart.
+    #[clap(long, default_value = "60")]
+    pub shutdown_timeout: u64,
+
+    /// Maximum time in seconds to wait before restarting a failed service, such as the
+    /// issue or redeem listener. The delay doubles with every consecutive failure.
+    #[clap(long, default_value = "3600")]
+    pub restart_max_interval: u64,
+
+    /// Time in seconds after which a service that keeps failing is given up and the
+    /// vault shuts down. Set to 0 to restart failed services indefinitely.
+    #[clap(long, default_value = "86400")]
+    pub restart_max_elapsed: u64,
+
     /// How many bitcoin confirmations to wait for. If not specified, the
     /// parachain settings will be used (recommended).
     #[clap(long)]
@@ -138,6 +180,9 @@ pub async fn start<B: BitcoinCoreApi + Send + Sync + 'static>(
     arc_provider: Arc<PolkaBtcProvider>,
     btc_rpc: Arc<B>,
 ) -> Result<(), Error> {
+    // handle termination from here on, since the startup already pays open requests
+    let shutdown = shutdown_signal()?;
+
     let vault_id = arc_provider.clone().get_account_id().clone();
 
     let num_confirmations = match opts.btc_confirmations {
@@ -167,7 +212,7 @@ pub async fn start<B: BitcoinCoreApi + Send + Sync + 'static>(
         }
     }
 
-    if let Ok(vault) = arc_provider.clone().get_vault(vault_id.clone()).await {
+    if let Ok(vault) = arc_provider.get_vault(vault_id.clone()).await {
         if !btc_rpc
             .wallet_has_public_key(vault.wallet.public_key)
             .await?
@@ -176,8 +221,14 @@ pub async fn start<B: BitcoinCoreApi + Send + Sync + 'static>(
         }
     }
 
-    let open_request_executor =
-        execute_open_requests(arc_provider.clone(), btc_rpc.clone(), num_confirmations);
+    let journal = Arc::new(PaymentJournal::open(&opts.payment_journal)?);
+
+    let open_request_executor = execute_open_requests(
+        arc_provider.clone(),
+        btc_rpc.clone(),
+        journal.clone(),
+        num_confirmations,
+    );
     tokio::spawn(async move {
         info!("Checking for open replace/redeem requests..");
         match open_request_executor.await {
@@ -197,9 +248,6 @@ pub async fn start<B: BitcoinCoreApi + Send + Sync + 'static>(
         };
     }
 
-    let collateral_maintainer =
-        maintain_collateralization_rate(arc_provider.clone(), opts.max_collateral);
-
     // wait for a new block to arrive, to prevent processing an event that potentially
     // has been processed already prior to restarting
     info!("Waiting for new block..");
@@ -211,117 +259,151 @@ pub async fn start<B: BitcoinCoreApi + Send + Sync + 'static>(
 
     let (issue_block_tx, issue_block_rx) = mpsc::channel::<PolkaBtcHeader>(16);
     let (replace_block_tx, replace_block_rx) = mpsc::channel::<PolkaBtcHeader>(16);
-    let block_listener = arc_provider.clone();
 
     // Issue handling
     let issue_set = Arc::new(IssueRequests::new());
+    issue_set.populate(&arc_provider, &btc_rpc).await?;
     let (issue_event_tx, issue_event_rx) = mpsc::channel::<RequestEvent>(16);
     let mut issue_cancellation_scheduler =
         CancellationScheduler::new(arc_provider.clone(), vault_id.clone());
-    let issue_request_listener = listen_for_issue_requests(
-        arc_provider.clone(),
-        btc_rpc.clone(),
-        issue_event_tx.clone(),
-        issue_set.clone(),
-    );
-    let issue_execute_listener = listen_for_issue_executes(
-        arc_provider.clone(),
-        issue_event_tx.clone(),
-        issue_set.clone(),
-    );
-    let issue_cancel_listener = listen_for_issue_cancels(arc_provider.clone(), issue_set.clone());
-    let issue_executor = execute_open_issue_requests(
-        arc_provider.clone(),
-        btc_rpc.clone(),
-        issue_set.clone(),
-        num_confirmations,
-    );
 
     // replace handling
     let (replace_event_tx, replace_event_rx) = mpsc::channel::<RequestEvent>(16);
     let mut replace_cancellation_scheduler =
         CancellationScheduler::new(arc_provider.clone(), vault_id.clone());
-    let request_replace_listener = listen_for_replace_requests(
-        arc_provider.clone(),
-        btc_rpc.clone(),
-        replace_event_tx.clone(),
-        !opts.no_auto_replace,
-    );
-    let accept_replace_listener =
-        listen_for_accept_replace(arc_provider.clone(), btc_rpc.clone(), num_confirmations);
-    let execute_replace_listener =
-        listen_for_execute_replace(arc_provider.clone(), replace_event_tx.clone());
-    let auction_replace_listener =
-        listen_for_auction_replace(arc_provider.clone(), btc_rpc.clone(), num_confirmations);
-    let third_party_collateral_listener = monitor_collateral_of_vaults(
-        arc_provider.clone(),
-        btc_rpc.clone(),
-        replace_event_tx.clone(),
-        Duration::from_millis(opts.collateral_timeout_ms)
+
+    // recent changes of our SLA score, served by the api
+    let sla_history = Arc::new(SlaHistory::default());
+
+    let supervisor = Supervisor::new();
+    // the api and the cancellation schedulers are not restarted, other services are
+    // restarted with backoff until they keep failing for too long
+    let policy = RestartPolicy::backoff(
+        Duration::from_secs(opts.restart_max_interval),
+        match opts.restart_max_elapsed {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        },
     );
 
+    if !opts.no_api {
+        let api_provider = arc_provider.clone();
+        let api_btc_rpc = btc_rpc.clone();
+        let api_state = api::VaultState {
+            sla_history: sla_history.clone(),
+            health: supervisor.health(),
+            journal: journal.clone(),
+            issue_set: issue_set.clone(),
+            issue_cancellations: issue_cancellation_scheduler.queue(),
+            replace_cancellations: replace_cancellation_scheduler.queue(),
+            num_confirmations,
+            network: opts.network.0,
+        };
+        let http_addr: SocketAddr = opts.http_addr.parse()?;
+        let rpc_cors_domain = opts.rpc_cors_domain.clone();
+        let (api_auth, audit_log) = opts.api_auth.load()?;
+        // the server can not be restarted if it fails to bind the address
+        supervisor.spawn_once("api", async move {
+            api::start(
+                api_provider,
+                api_btc_rpc,
+                api_state,
+                http_addr,
+                rpc_cors_domain,
+                api_auth,
+                audit_log,
+            )
+            .await
+        });
+    }
 
-    // redeem handling
-    let redeem_listener =
-        listen_for_redeem_requests(arc_provider.clone(), btc_rpc.clone(), num_confirmations);
+    // switches to another parachain endpoint if the current one fails
+    let endpoint_monitor = arc_provider.clone();
+    supervisor.spawn("endpoint_monitor", policy.clone(), move || {
+        let endpoint_monitor = endpoint_monitor.clone();
+        async move {
+            endpoint_monitor.monitor_endpoints().await;
+            Ok::<_, Error>(())
+        }
+    });
 
-    // refund handling
-    let refund_listener =
-        listen_for_refund_requests(arc_provider.clone(), btc_rpc.clone(), num_confirmations);
+    // queries the balances and open requests exposed on /metrics
+    let metrics_provider = arc_provider.clone();
+    let metrics_btc_rpc = btc_rpc.clone();
+    supervisor.spawn("metrics_updater", policy.clone(), move || {
+        let metrics_provider = metrics_provider.clone();
+        let metrics_btc_rpc = metrics_btc_rpc.clone();
+        async move {
+            metrics::update_metrics(metrics_provider, metrics_btc_rpc).await;
+            Ok::<_, Error>(())
+        }
+    });
 
-    let api_listener = if opts.no_api {
-        None
-    } else {
-        Some(api::start(
-            arc_provider.clone(),
-            btc_rpc.clone(),
-            opts.http_addr.parse()?,
-            opts.rpc_cors_domain,
-        ))
-    };
+    // invalidates the storage cache on new blocks, if enabled
+    let cache_maintainer = arc_provider.clone();
+    supervisor.spawn("cache_maintainer", policy.clone(), move || {
+        let cache_maintainer = cache_maintainer.clone();
+        async move { cache_maintainer.maintain_cache().await }
+    });
 
-    // misc copies of variables to move into spawn closures
-    let no_auto_auction = opts.no_auto_auction;
-    let no_issue_execution = opts.no_issue_execution;
-    let sla_event_provider = arc_provider.clone();
+    // removes the journal entries of requests whose execution is finalized
+    let (journal_provider, pruned_journal) = (arc_provider.clone(), journal.clone());
+    supervisor.spawn("payment_journal_pruner", policy.clone(), move || {
+        prune_payment_journal(journal_provider.clone(), pruned_journal.clone())
+    });
 
-    // starts all the tasks
-    let result = tokio::try_join!(
-        tokio::spawn(async move {
-            if let Some(api_listener) = api_listener {
-                api_listener.await;
-            }
-        }),
-        tokio::spawn(async move {
-            arc_provider
+    let error_event_listener = arc_provider.clone();
+    supervisor.spawn("error_event_listener", policy.clone(), move || {
+        let error_event_listener = error_event_listener.clone();
+        async move {
+            error_event_listener
                 .on_event_error(|e| error!("Received error event: {}", e))
                 .await
-                .unwrap();
-        }),
-        tokio::spawn(async move {
+        }
+    });
+
+    let sla_event_provider = arc_provider.clone();
+    supervisor.spawn("sla_listener", policy.clone(), move || {
+        let sla_event_provider = sla_event_provider.clone();
+        let sla_history = sla_history.clone();
+        async move {
             let vault_id = sla_event_provider.get_account_id();
+            let sla_history = &sla_history;
             sla_event_provider
-                .on_event::<UpdateVaultSLAEvent<PolkaBtcRuntime>, _, _, _>(
-                    |event| async move {
+                .on_block_event::<UpdateVaultSLAEvent<PolkaBtcRuntime>, _, _, _>(
+                    |event, header| async move {
                         if &event.vault_id == vault_id {
-                            info!("Received event: new total SLA score = {:?}", event.new_sla);
+                            info!(
+                                "Received event: new total SLA score = {:?} at block {}",
+                                event.new_sla, header.number
+                            );
+                            sla_history.record(&header, event.new_sla);
                         }
+                        Ok(())
                     },
-                    |err| error!("Error (UpdateVaultSLAEvent): {}", err.to_string()),
+                    |error| error!("Failed to decode SLA event: {}", error),
                 )
                 .await
-                .unwrap();
-        }),
-        tokio::spawn(async move {
-            collateral_maintainer.await.unwrap();
-        }),
-        tokio::spawn(async move {
+        }
+    });
+
+    let collateral_provider = arc_provider.clone();
+    let max_collateral = opts.max_collateral;
+    supervisor.spawn("collateral_maintainer", policy.clone(), move || {
+        maintain_collateralization_rate(collateral_provider.clone(), max_collateral)
+    });
+
+    let block_listener = arc_provider.clone();
+    supervisor.spawn("block_listener", policy.clone(), move || {
+        let block_listener = block_listener.clone();
+        let issue_block_tx = issue_block_tx.clone();
+        let replace_block_tx = replace_block_tx.clone();
+        async move {
             let issue_block_tx = &issue_block_tx;
             let replace_block_tx = &replace_block_tx;
-
             block_listener
-                .clone()
                 .on_block(move |header| async move {
+                    metrics::PARACHAIN_HEIGHT.set(header.number.into());
                     issue_block_tx
                         .clone()
                         .send(header.clone())
@@ -335,70 +417,186 @@ pub async fn start<B: BitcoinCoreApi + Send + Sync + 'static>(
                     Ok(())
                 })
                 .await
-                .unwrap();
-        }),
-        tokio::spawn(async move {
-            issue_request_listener.await.unwrap();
-        }),
-        tokio::spawn(async move {
-            issue_execute_listener.await.unwrap();
-        }),
-        tokio::spawn(async move {
-            issue_cancellation_scheduler
-                .handle_cancellation::<IssueCanceller>(issue_block_rx, issue_event_rx)
-                .await
-                .unwrap();
-        }),
-        tokio::spawn(async move {
-            issue_cancel_listener.await.unwrap();
-        }),
-        tokio::spawn(async move {
-            if !no_issue_execution {
-                issue_executor.await.unwrap();
-            }
-        }),
-        // redeem handling
-        tokio::spawn(async move {
-            redeem_listener.await.unwrap();
-        }),
-        // refund handling
-        tokio::spawn(async move {
-            refund_listener.await.unwrap();
-        }),
-        // replace handling
-        tokio::spawn(async move {
-            request_replace_listener.await.unwrap();
-        }),
-        tokio::spawn(async move {
-            accept_replace_listener.await.unwrap();
-        }),
-        tokio::spawn(async move {
-            auction_replace_listener.await.unwrap();
-        }),
-        tokio::spawn(async move {
-            execute_replace_listener.await.unwrap();
-        }),
-        tokio::spawn(async move {
-            if !no_auto_auction {
-                third_party_collateral_listener.await.unwrap();
-            }
-        }),
-        tokio::spawn(async move {
-            replace_cancellation_scheduler
-                .handle_cancellation::<ReplaceCanceller>(replace_block_rx, replace_event_rx)
-                .await
-                .unwrap();
-        }),
-    );
-    match result {
-        Ok(_) => {
-            println!("Done");
-        }
-        Err(err) => {
-            println!("Error: {}", err);
-            std::process::exit(1);
         }
-    };
+    });
+
+    // issue handling
+    {
+        let (provider, btc_rpc, issue_event_tx, issue_set) = (
+            arc_provider.clone(),
+            btc_rpc.clone(),
+            issue_event_tx.clone(),
+            issue_set.clone(),
+        );
+        supervisor.spawn("issue_request_listener", policy.clone(), move || {
+            listen_for_issue_requests(
+                provider.clone(),
+                btc_rpc.clone(),
+                issue_event_tx.clone(),
+                issue_set.clone(),
+            )
+        });
+    }
+    {
+        let (provider, issue_set) = (arc_provider.clone(), issue_set.clone());
+        supervisor.spawn("issue_execute_listener", policy.clone(), move || {
+            listen_for_issue_executes(provider.clone(), issue_event_tx.clone(), issue_set.clone())
+        });
+    }
+    {
+        let (provider, issue_set) = (arc_provider.clone(), issue_set.clone());
+        supervisor.spawn("issue_cancel_listener", policy.clone(), move || {
+            listen_for_issue_cancels(provider.clone(), issue_set.clone())
+        });
+    }
+    // the channels of the scheduler can not be recreated
+    supervisor.spawn_once("issue_cancellation_scheduler", async move {
+        issue_cancellation_scheduler
+            .handle_cancellation::<IssueCanceller>(issue_block_rx, issue_event_rx)
+            .await
+    });
+    if !opts.no_issue_execution {
+        // the scan stops once the payments made while the vault was offline are executed
+        let (provider, btc_rpc, issue_set) =
+            (arc_provider.clone(), btc_rpc.clone(), issue_set.clone());
+        supervisor.spawn("issue_payment_scanner", policy.clone(), move || {
+            scan_for_issue_payments(
+                provider.clone(),
+                btc_rpc.clone(),
+                issue_set.clone(),
+                num_confirmations,
+            )
+        });
+        let (provider, btc_rpc) = (arc_provider.clone(), btc_rpc.clone());
+        supervisor.spawn("issue_executor", policy.clone(), move || {
+            execute_open_issue_requests(
+                provider.clone(),
+                btc_rpc.clone(),
+                issue_set.clone(),
+                num_confirmations,
+            )
+        });
+    }
+
+    // redeem handling
+    {
+        let (provider, btc_rpc, journal) = (arc_provider.clone(), btc_rpc.clone(), journal.clone());
+        supervisor.spawn("redeem_listener", policy.clone(), move || {
+            listen_for_redeem_requests(
+                provider.clone(),
+                btc_rpc.clone(),
+                journal.clone(),
+                num_confirmations,
+            )
+        });
+    }
+
+    // refund handling
+    {
+        let (provider, btc_rpc, journal) = (arc_provider.clone(), btc_rpc.clone(), journal.clone());
+        supervisor.spawn("refund_listener", policy.clone(), move || {
+            listen_for_refund_requests(
+                provider.clone(),
+                btc_rpc.clone(),
+                journal.clone(),
+                num_confirmations,
+            )
+        });
+    }
+
+    // replace handling
+    {
+        let (provider, btc_rpc, replace_event_tx) = (
+            arc_provider.clone(),
+            btc_rpc.clone(),
+            replace_event_tx.clone(),
+        );
+        let accept_replace_requests = !opts.no_auto_replace;
+        supervisor.spawn("replace_request_listener", policy.clone(), move || {
+            listen_for_replace_requests(
+                provider.clone(),
+                btc_rpc.clone(),
+                replace_event_tx.clone(),
+                accept_replace_requests,
+            )
+        });
+    }
+    {
+        let (provider, btc_rpc, journal) = (arc_provider.clone(), btc_rpc.clone(), journal.clone());
+        supervisor.spawn("accept_replace_listener", policy.clone(), move || {
+            listen_for_accept_replace(
+                provider.clone(),
+                btc_rpc.clone(),
+                journal.clone(),
+                num_confirmations,
+            )
+        });
+    }
+    {
+        let (provider, btc_rpc, journal) = (arc_provider.clone(), btc_rpc.clone(), journal.clone());
+        supervisor.spawn("auction_replace_listener", policy.clone(), move || {
+            listen_for_auction_replace(
+                provider.clone(),
+                btc_rpc.clone(),
+                journal.clone(),
+                num_confirmations,
+            )
+        });
+    }
+    {
+        let (provider, replace_event_tx) = (arc_provider.clone(), replace_event_tx.clone());
+        supervisor.spawn("execute_replace_listener", policy.clone(), move || {
+            listen_for_execute_replace(provider.clone(), replace_event_tx.clone())
+        });
+    }
+    if !opts.no_auto_auction {
+        let (provider, btc_rpc) = (arc_provider.clone(), btc_rpc.clone());
+        supervisor.spawn("vault_collateral_monitor", policy.clone(), move || {
+            monitor_collateral_of_vaults(
+                provider.clone(),
+                btc_rpc.clone(),
+                replace_event_tx.clone(),
+                Duration::from_millis(opts.collateral_timeout_ms),
+            )
+        });
+    }
+    supervisor.spawn_once("replace_cancellation_scheduler", async move {
+        replace_cancellation_scheduler
+            .handle_cancellation::<ReplaceCanceller>(replace_block_rx, replace_event_rx)
+            .await
+    });
+
+    // runs until a service fails and can not be restarted, or the vault is stopped
+    let result = supervisor
+        .wait(async move {
+            shutdown.await;
+            info!("Received shutdown signal, stopping services..");
+        })
+        .await;
+
+    // let the payment tasks complete, including the execution of their requests, otherwise
+    // they are resumed from the journal after a restart
+    info!("Waiting for bitcoin payments in flight..");
+    if timeout(Duration::from_secs(opts.shutdown_timeout), journal.drain())
+        .await
+        .is_err()
+    {
+        warn!("Timed out waiting for bitcoin payments in flight");
+    }
+    journal.flush()?;
+    info!("Vault stopped");
+
+    result
+}
 
-    Ok(())
+/// Completes on ctrl-c, or once the process is terminated.
+fn shutdown_signal() -> Result<impl Future<Output = ()>, Error> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    Ok(async move {
+        futures::future::select(
+            Box::pin(tokio::signal::ctrl_c()),
+            Box::pin(terminate.recv()),
+        )
+        .await;
+    })
 }
@@ -1,8 +1,7 @@
 use bitcoin::{BitcoinCore, BitcoinCoreApi};
 use clap::Clap;
 use log::*;
-use runtime::substrate_subxt::PairSigner;
-use runtime::{PolkaBtcProvider, PolkaBtcRuntime};
+use runtime::PolkaBtcProvider;
 use std::sync::Arc;
 use vault::{start, Error, Opts};
 
@@ -14,9 +13,11 @@ async fn main() -> Result<(), Error> {
 
     info!("Command line arguments: {:?}", opts.clone());
 
-    let (pair, wallet) = opts.account_info.get_key_pair()?;
-    let signer = PairSigner::<PolkaBtcRuntime, _>::new(pair);
-    let provider = PolkaBtcProvider::from_url(opts.polka_btc_url.clone(), signer).await?;
+    let (signer, wallet) = opts.account_info.get_signer().await?;
+    let mut provider = PolkaBtcProvider::from_urls(opts.polka_btc_url.clone(), signer).await?;
+    if opts.storage_cache {
+        provider = provider.with_cache();
+    }
     let arc_provider = Arc::new(provider.clone());
 
     let btc_rpc = Arc::new(BitcoinCore::new(
@@ -0,0 +1,278 @@
+use crate::constants::METRICS_UPDATE_INTERVAL;
+use crate::execution::RequestType;
+use crate::Error;
+use bitcoin::BitcoinCoreApi;
+use lazy_static::lazy_static;
+use log::warn;
+use prometheus::{
+    register_gauge, register_histogram_vec, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Encoder, Gauge, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+    TextEncoder,
+};
+use runtime::{
+    json::{ApiErrorData, ApiErrorKind},
+    DotBalancesPallet, Error as RuntimeError, ExchangeRateOraclePallet, FixedPointNumber,
+    IssuePallet, PolkaBtcProvider, RedeemPallet, RefundPallet, ReplacePallet, UtilFuncs,
+    VaultRegistryPallet,
+};
+use sp_arithmetic::FixedU128;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::time::delay_for;
+
+lazy_static! {
+    pub static ref OPEN_REQUESTS: IntGaugeVec = register_int_gauge_vec!(
+        "vault_open_requests",
+        "Number of open requests of the vault",
+        &["type"]
+    )
+    .unwrap();
+    pub static ref PAYMENT_LATENCY: HistogramVec = register_histogram_vec!(
+        "vault_payment_latency_seconds",
+        "Time from receiving the event of a request until its bitcoin payment is broadcast and executed",
+        &["type", "stage"],
+        // payments wait for bitcoin confirmations, which take up to a few hours
+        vec![1.0, 10.0, 60.0, 300.0, 600.0, 1800.0, 3600.0, 7200.0, 14400.0]
+    )
+    .unwrap();
+    pub static ref EXECUTE_RETRIES: IntCounterVec = register_int_counter_vec!(
+        "vault_request_execute_retries_total",
+        "Number of retried executions of paid requests",
+        &["type"]
+    )
+    .unwrap();
+    pub static ref CANCELLATIONS: IntCounterVec = register_int_counter_vec!(
+        "vault_cancellations_total",
+        "Number of expired requests cancelled by the vault",
+        &["type"]
+    )
+    .unwrap();
+    pub static ref EXTRINSIC_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "vault_extrinsic_failures_total",
+        "Number of extrinsics rejected by the parachain, by dispatch error",
+        &["module", "error"]
+    )
+    .unwrap();
+    pub static ref COLLATERALIZATION: Gauge = register_gauge!(
+        "vault_collateralization",
+        "Ratio of the collateral of the vault to its issued and to-be-issued tokens, 0 if there are none"
+    )
+    .unwrap();
+    pub static ref FREE_DOT: Gauge =
+        register_gauge!("vault_free_dot", "Free DOT balance of the vault in planck").unwrap();
+    pub static ref RESERVED_DOT: Gauge = register_gauge!(
+        "vault_reserved_dot",
+        "Reserved DOT balance, i.e. the collateral, of the vault in planck"
+    )
+    .unwrap();
+    pub static ref BTC_BALANCE: IntGauge = register_int_gauge!(
+        "vault_btc_balance",
+        "Confirmed balance of the bitcoin wallet in satoshis"
+    )
+    .unwrap();
+    pub static ref PARACHAIN_HEIGHT: IntGauge = register_int_gauge!(
+        "vault_parachain_height",
+        "Height of the last parachain block processed by the vault"
+    )
+    .unwrap();
+    pub static ref BITCOIN_HEIGHT: IntGauge = register_int_gauge!(
+        "vault_bitcoin_height",
+        "Height of the best bitcoin block known to bitcoin core"
+    )
+    .unwrap();
+}
+
+pub fn request_type_label(request_type: RequestType) -> &'static str {
+    match request_type {
+        RequestType::Redeem => "redeem",
+        RequestType::Replace => "replace",
+        RequestType::Refund => "refund",
+    }
+}
+
+/// Record the time since the payment of a request was started.
+pub fn observe_payment_latency(request_type: RequestType, stage: &str, started: Instant) {
+    PAYMENT_LATENCY
+        .with_label_values(&[request_type_label(request_type), stage])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+/// Count the error if it is a dispatch error of an extrinsic, other errors are ignored.
+pub fn record_extrinsic_failure<'a, E>(err: &'a E)
+where
+    ApiErrorData: From<&'a E>,
+{
+    count_dispatch_error(&ApiErrorData::from(err))
+}
+
+fn count_dispatch_error(data: &ApiErrorData) {
+    if data.kind == ApiErrorKind::Dispatch {
+        EXTRINSIC_FAILURES
+            .with_label_values(&[
+                data.module.as_deref().unwrap_or_default(),
+                data.error.as_deref().unwrap_or_default(),
+            ])
+            .inc();
+    }
+}
+
+/// Encode all metrics in the prometheus text format.
+pub fn encode() -> Vec<u8> {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        warn!("Failed to encode metrics: {}", e);
+    }
+    buffer
+}
+
+/// Periodically update the metrics that are queried from the parachain and bitcoin core,
+/// rather than recorded when the vault processes events.
+pub async fn update_metrics<B: BitcoinCoreApi>(provider: Arc<PolkaBtcProvider>, btc_rpc: Arc<B>) {
+    loop {
+        query_metrics(&provider, &btc_rpc).await;
+        delay_for(METRICS_UPDATE_INTERVAL).await;
+    }
+}
+
+/// Log the failed query of a metric, which then keeps its previous value.
+fn log_failure<T, E: Display>(metric: &str, result: Result<T, E>) -> Option<T> {
+    result
+        .map_err(|e| warn!("Failed to update {}: {}", metric, e))
+        .ok()
+}
+
+async fn query_metrics<B: BitcoinCoreApi>(provider: &Arc<PolkaBtcProvider>, btc_rpc: &Arc<B>) {
+    // the metrics are queried independently, such that a failing query only leaves its own
+    // metric stale
+    log_failure("vault_open_requests", update_open_requests(provider).await);
+    if let Some(balance) = log_failure("vault_free_dot", provider.get_free_dot_balance().await) {
+        FREE_DOT.set(balance as f64);
+    }
+    if let Some(balance) = log_failure("vault_btc_balance", btc_rpc.get_balance().await) {
+        BTC_BALANCE.set(balance as i64);
+    }
+    if let Some(height) = log_failure("vault_bitcoin_height", btc_rpc.get_block_count().await) {
+        BITCOIN_HEIGHT.set(height as i64);
+    }
+    if let Some(reserved_dot) = log_failure(
+        "vault_reserved_dot",
+        provider.get_reserved_dot_balance().await,
+    ) {
+        RESERVED_DOT.set(reserved_dot as f64);
+        log_failure(
+            "vault_collateralization",
+            update_collateralization(provider, reserved_dot).await,
+        );
+    }
+}
+
+async fn update_collateralization(
+    provider: &Arc<PolkaBtcProvider>,
+    reserved_dot: u128,
+) -> Result<(), Error> {
+    let vault = match provider.get_vault(provider.get_account_id().clone()).await {
+        Ok(vault) => vault,
+        // the vault has no collateralization until it is registered
+        Err(RuntimeError::VaultNotFound) => {
+            COLLATERALIZATION.set(0.0);
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let collateral_in_btc = provider.dots_to_btc(reserved_dot).await?;
+    let tokens = vault
+        .issued_tokens
+        .checked_add(vault.to_be_issued_tokens)
+        .ok_or(Error::ArithmeticOverflow)?;
+    COLLATERALIZATION.set(collateralization(collateral_in_btc, tokens));
+    Ok(())
+}
+
+/// Ratio of the collateral to the tokens, 0 if there are no tokens.
+fn collateralization(collateral_in_btc: u128, tokens: u128) -> f64 {
+    match FixedU128::checked_from_rational(collateral_in_btc, tokens) {
+        Some(ratio) => ratio.into_inner() as f64 / FixedU128::accuracy() as f64,
+        None => 0.0,
+    }
+}
+
+async fn update_open_requests(provider: &Arc<PolkaBtcProvider>) -> Result<(), Error> {
+    let vault_id = provider.get_account_id().clone();
+
+    let issues = provider
+        .get_vault_issue_requests(vault_id.clone())
+        .await?
+        .into_iter()
+        .filter(|(_, request)| !request.completed && !request.cancelled)
+        .count();
+    let redeems = provider
+        .get_vault_redeem_requests(vault_id.clone())
+        .await?
+        .into_iter()
+        .filter(|(_, request)| !request.completed && !request.cancelled)
+        .count();
+    let old_vault_replaces = provider
+        .get_old_vault_replace_requests(vault_id.clone())
+        .await?
+        .into_iter()
+        .filter(|(_, request)| !request.completed && !request.cancelled)
+        .count();
+    let new_vault_replaces = provider
+        .get_new_vault_replace_requests(vault_id.clone())
+        .await?
+        .into_iter()
+        .filter(|(_, request)| !request.completed && !request.cancelled)
+        .count();
+    let refunds = provider
+        .get_vault_refund_requests(vault_id)
+        .await?
+        .into_iter()
+        .filter(|(_, request)| !request.completed)
+        .count();
+
+    for (request_type, count) in &[
+        ("issue", issues),
+        ("redeem", redeems),
+        ("old_vault_replace", old_vault_replaces),
+        ("new_vault_replace", new_vault_replaces),
+        ("refund", refunds),
+    ] {
+        OPEN_REQUESTS
+            .with_label_values(&[*request_type])
+            .set(*count as i64);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collateralization() {
+        assert_eq!(collateralization(300, 200), 1.5);
+        // reset once all tokens are redeemed
+        assert_eq!(collateralization(300, 0), 0.0);
+    }
+
+    #[test]
+    fn test_record_extrinsic_failure() {
+        let dispatch_error = ApiErrorData {
+            kind: ApiErrorKind::Dispatch,
+            module: Some("VaultRegistry".to_string()),
+            error: Some("InsufficientCollateral".to_string()),
+        };
+        let counter =
+            EXTRINSIC_FAILURES.with_label_values(&["VaultRegistry", "InsufficientCollateral"]);
+        let before = counter.get();
+
+        count_dispatch_error(&dispatch_error);
+        count_dispatch_error(&ApiErrorData::new(ApiErrorKind::Bitcoin));
+        assert_eq!(counter.get(), before + 1);
+
+        let encoded = String::from_utf8(encode()).unwrap();
+        assert!(encoded.contains("# TYPE vault_extrinsic_failures_total counter"));
+        assert!(encoded.contains("module=\"VaultRegistry\""));
+    }
+}
@@ -1,4 +1,6 @@
 use crate::execution::*;
+use crate::journal::PaymentJournal;
+use crate::metrics;
 use bitcoin::BitcoinCoreApi;
 use log::{error, info};
 use runtime::{pallets::refund::RequestRefundEvent, PolkaBtcProvider, PolkaBtcRuntime, UtilFuncs};
@@ -11,11 +13,13 @@ use std::sync::Arc;
 ///
 /// * `provider` - the parachain RPC handle
 /// * `btc_rpc` - the bitcoin RPC handle
+/// * `journal` - the journal that records the bitcoin payments
 /// * `network` - network the bitcoin network used (i.e. regtest/testnet/mainnet)
 /// * `num_confirmations` - the number of bitcoin confirmation to await
 pub async fn listen_for_refund_requests<B: BitcoinCoreApi + Send + Sync + 'static>(
     provider: Arc<PolkaBtcProvider>,
     btc_rpc: Arc<B>,
+    journal: Arc<PaymentJournal>,
     num_confirmations: u32,
 ) -> Result<(), runtime::Error> {
     provider
@@ -31,12 +35,14 @@ pub async fn listen_for_refund_requests<B: BitcoinCoreApi + Send + Sync + 'stati
                 // arguments by value rather than by reference, so clone these:
                 let provider = provider.clone();
                 let btc_rpc = btc_rpc.clone();
-                // Spawn a new task so that we handle these events concurrently
-                tokio::spawn(async move {
+                let journal = journal.clone();
+                // Spawn a new task so that we handle these events concurrently, a shutdown
+                // waits for it to complete
+                journal.clone().spawn(async move {
                     // prepare the action that will be executed after the bitcoin transfer
                     let request = Request::from_refund_request_event(&event);
                     let result = request
-                        .pay_and_execute(provider, btc_rpc, num_confirmations)
+                        .pay_and_execute(provider, btc_rpc, journal, num_confirmations)
                         .await;
 
                     match result {
@@ -44,11 +50,14 @@ pub async fn listen_for_refund_requests<B: BitcoinCoreApi + Send + Sync + 'stati
                             "Completed refund request #{} with amount {}",
                             event.refund_id, event.amount_polka_btc
                         ),
-                        Err(e) => error!(
-                            "Failed to process refund request #{}: {}",
-                            event.refund_id,
-                            e.to_string()
-                        ),
+                        Err(e) => {
+                            metrics::record_extrinsic_failure(&e);
+                            error!(
+                                "Failed to process refund request #{}: {}",
+                                event.refund_id,
+                                e.to_string()
+                            )
+                        }
                     }
                 });
             },
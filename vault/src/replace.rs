@@ -1,17 +1,22 @@
 use crate::cancellation::RequestEvent;
 use crate::error::Error;
 use crate::execution::Request;
+use crate::journal::PaymentJournal;
+use crate::metrics;
 use bitcoin::BitcoinCoreApi;
 use futures::channel::mpsc::Sender;
-use futures::SinkExt;
+use futures::{pin_mut, select, FutureExt, SinkExt};
 use log::*;
 use runtime::{
-    pallets::replace::{AcceptReplaceEvent, ExecuteReplaceEvent, RequestReplaceEvent, AuctionReplaceEvent},
-    DotBalancesPallet, PolkaBtcProvider, PolkaBtcRuntime, PolkaBtcVault, ReplacePallet, UtilFuncs,
-    VaultRegistryPallet,
+    pallets::replace::{
+        AcceptReplaceCall, AcceptReplaceEvent, AuctionReplaceCall, AuctionReplaceEvent,
+        ExecuteReplaceEvent, RequestReplaceEvent,
+    },
+    substrate_subxt::Call,
+    BtcAddress, DotBalancesPallet, ExtrinsicFuncs, PolkaBtcProvider, PolkaBtcRuntime,
+    PolkaBtcVault, ReplacePallet, UtilFuncs, VaultRegistryPallet,
 };
-use std::sync::Arc;
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use tokio::time::delay_for;
 
 /// Listen for AcceptReplaceEvent directed at this vault and continue the replacement
@@ -21,14 +26,17 @@ use tokio::time::delay_for;
 ///
 /// * `provider` - the parachain RPC handle
 /// * `btc_rpc` - the bitcoin RPC handle
+/// * `journal` - the journal that records the bitcoin payments
 /// * `num_confirmations` - the number of bitcoin confirmation to await
 pub async fn listen_for_accept_replace<B: BitcoinCoreApi + Send + Sync + 'static>(
     provider: Arc<PolkaBtcProvider>,
     btc_rpc: Arc<B>,
+    journal: Arc<PaymentJournal>,
     num_confirmations: u32,
 ) -> Result<(), runtime::Error> {
     let provider = &provider;
     let btc_rpc = &btc_rpc;
+    let journal = &journal;
     provider
         .on_event::<AcceptReplaceEvent<PolkaBtcRuntime>, _, _, _>(
             |event| async move {
@@ -42,11 +50,13 @@ pub async fn listen_for_accept_replace<B: BitcoinCoreApi + Send + Sync + 'static
                 // arguments by value rather than by reference, so clone these:
                 let provider = provider.clone();
                 let btc_rpc = btc_rpc.clone();
-                // Spawn a new task so that we handle these events concurrently
-                tokio::spawn(async move {
+                let journal = journal.clone();
+                // Spawn a new task so that we handle these events concurrently, a shutdown
+                // waits for it to complete
+                journal.clone().spawn(async move {
                     let request = Request::from_accept_replace_event(&event);
                     let result = request
-                        .pay_and_execute(provider, btc_rpc, num_confirmations)
+                        .pay_and_execute(provider, btc_rpc, journal, num_confirmations)
                         .await;
 
                     match result {
@@ -54,11 +64,14 @@ pub async fn listen_for_accept_replace<B: BitcoinCoreApi + Send + Sync + 'static
                             "Successfully Executed replace #{} with amount {}",
                             event.replace_id, event.btc_amount
                         ),
-                        Err(e) => error!(
-                            "Failed to process replace request #{}: {}",
-                            event.replace_id,
-                            e.to_string()
-                        ),
+                        Err(e) => {
+                            metrics::record_extrinsic_failure(&e);
+                            error!(
+                                "Failed to process replace request #{}: {}",
+                                event.replace_id,
+                                e.to_string()
+                            )
+                        }
                     }
                 });
             },
@@ -74,14 +87,17 @@ pub async fn listen_for_accept_replace<B: BitcoinCoreApi + Send + Sync + 'static
 ///
 /// * `provider` - the parachain RPC handle
 /// * `btc_rpc` - the bitcoin RPC handle
+/// * `journal` - the journal that records the bitcoin payments
 /// * `num_confirmations` - the number of bitcoin confirmation to await
 pub async fn listen_for_auction_replace<B: BitcoinCoreApi + Send + Sync + 'static>(
     provider: Arc<PolkaBtcProvider>,
     btc_rpc: Arc<B>,
+    journal: Arc<PaymentJournal>,
     num_confirmations: u32,
 ) -> Result<(), runtime::Error> {
     let provider = &provider;
     let btc_rpc = &btc_rpc;
+    let journal = &journal;
     provider
         .on_event::<AuctionReplaceEvent<PolkaBtcRuntime>, _, _, _>(
             |event| async move {
@@ -95,11 +111,13 @@ pub async fn listen_for_auction_replace<B: BitcoinCoreApi + Send + Sync + 'stati
                 // arguments by value rather than by reference, so clone these:
                 let provider = provider.clone();
                 let btc_rpc = btc_rpc.clone();
-                // Spawn a new task so that we handle these events concurrently
-                tokio::spawn(async move {
+                let journal = journal.clone();
+                // Spawn a new task so that we handle these events concurrently, a shutdown
+                // waits for it to complete
+                journal.clone().spawn(async move {
                     let request = Request::from_auction_replace_event(&event);
                     let result = request
-                        .pay_and_execute(provider, btc_rpc, num_confirmations)
+                        .pay_and_execute(provider, btc_rpc, journal, num_confirmations)
                         .await;
 
                     match result {
@@ -107,11 +125,14 @@ pub async fn listen_for_auction_replace<B: BitcoinCoreApi + Send + Sync + 'stati
                             "Successfully executed auctioned replace #{} with amount {}",
                             event.replace_id, event.btc_amount
                         ),
-                        Err(e) => error!(
-                            "Failed to process auctioned replace request #{}: {}",
-                            event.replace_id,
-                            e.to_string()
-                        ),
+                        Err(e) => {
+                            metrics::record_extrinsic_failure(&e);
+                            error!(
+                                "Failed to process auctioned replace request #{}: {}",
+                                event.replace_id,
+                                e.to_string()
+                            )
+                        }
                     }
                 });
             },
@@ -157,11 +178,14 @@ pub async fn listen_for_replace_requests<B: BitcoinCoreApi>(
                             // the only way it can fail is if the channel is closed
                             let _ = event_channel.clone().send(RequestEvent::Opened).await;
                         }
-                        Err(e) => error!(
-                            "Failed to accept replace request #{}: {}",
-                            event.replace_id,
-                            e.to_string()
-                        ),
+                        Err(e) => {
+                            metrics::record_extrinsic_failure(&e);
+                            error!(
+                                "Failed to accept replace request #{}: {}",
+                                event.replace_id,
+                                e.to_string()
+                            )
+                        }
                     }
                 }
             },
@@ -174,7 +198,7 @@ pub async fn listen_for_replace_requests<B: BitcoinCoreApi>(
 /// failure, since nothing is at stake at this point
 pub async fn handle_replace_request<
     B: BitcoinCoreApi,
-    P: DotBalancesPallet + ReplacePallet + VaultRegistryPallet,
+    P: DotBalancesPallet + ReplacePallet + VaultRegistryPallet + ExtrinsicFuncs,
 >(
     provider: Arc<P>,
     btc_rpc: Arc<B>,
@@ -187,16 +211,51 @@ pub async fn handle_replace_request<
     let free_balance = provider.get_free_dot_balance().await?;
 
     if free_balance < required_collateral {
-        Err(Error::InsufficientFunds)
-    } else {
-        Ok(provider
-            .accept_replace(
-                event.replace_id,
-                required_collateral,
-                btc_rpc.get_new_address().await?,
-            )
-            .await?)
+        return Err(Error::InsufficientFunds);
     }
+
+    let call = AcceptReplaceCall {
+        replace_id: event.replace_id,
+        collateral: required_collateral,
+        btc_address: BtcAddress::default(),
+    };
+    check_affordable(&*provider, call, required_collateral, free_balance).await?;
+
+    let btc_address = btc_rpc.get_new_address().await?;
+    Ok(provider
+        .accept_replace(event.replace_id, required_collateral, btc_address)
+        .await?)
+}
+
+/// Checks that we can pay the transaction fee of the call on top of the collateral
+/// it locks, and that the call would currently succeed. The call is checked with a
+/// placeholder btc address, so that no address is derived from the wallet for calls
+/// that are not submitted.
+///
+/// # Arguments
+///
+/// * `provider` - the parachain RPC handle
+/// * `call` - the call we intend to submit
+/// * `collateral` - the collateral that the call will lock
+/// * `free_balance` - our current free DOT balance
+async fn check_affordable<
+    P: ExtrinsicFuncs,
+    C: Call<PolkaBtcRuntime> + Clone + Send + Sync + 'static,
+>(
+    provider: &P,
+    call: C,
+    collateral: u128,
+    free_balance: u128,
+) -> Result<(), Error> {
+    let fee = provider.estimate_fee(call.clone()).await?;
+    let required_balance = collateral
+        .checked_add(fee)
+        .ok_or(Error::ArithmeticOverflow)?;
+    if free_balance < required_balance {
+        return Err(Error::InsufficientFunds);
+    }
+    provider.dry_run(call).await?;
+    Ok(())
 }
 
 /// Monitor the collateralization rate of all vaults and request auctions and auction_replace them
@@ -206,27 +265,47 @@ pub async fn handle_replace_request<
 ///
 /// * `provider` - the parachain RPC handle
 /// * `btc_rpc` - the bitcoin RPC handle
-/// * `num_confirmations` - the number of bitcoin confirmation to await
-/// * `interval` - interval between checks
+/// * `event_channel` - channel to notify the cancellation scheduler of opened replace requests
+/// * `poll_interval` - the time between periodic checks
 pub async fn monitor_collateral_of_vaults<B: BitcoinCoreApi>(
     provider: Arc<PolkaBtcProvider>,
     btc_rpc: Arc<B>,
-    mut event_channel: Sender<RequestEvent>,
-    interval: Duration,
-) -> Result<(), runtime::Error>{
-    // we could automatically check vault collateralization rates on events
-    // that affect this (e.g. `SetExchangeRate`, `WithdrawCollateral`) but
-    // polling is easier for now
-    loop {
-        if let Err(e) =
-            check_collateral_of_vaults(&provider, &btc_rpc, &mut event_channel).await
-        {
+    event_channel: Sender<RequestEvent>,
+    poll_interval: Duration,
+) -> Result<(), runtime::Error> {
+    let provider = &provider;
+    let btc_rpc = &btc_rpc;
+    let event_channel = &event_channel;
+    let check = || async move {
+        let mut event_channel = event_channel.clone();
+        if let Err(e) = check_collateral_of_vaults(provider, btc_rpc, &mut event_channel).await {
             error!(
                 "Error while monitoring collateral of vaults: {}",
                 e.to_string()
             );
         }
-        delay_for(interval).await
+    };
+
+    // most vaults fall below the auction threshold when the exchange rate changes, but the
+    // collateral of a vault is also lowered when it is slashed, so we additionally poll
+    let on_exchange_rate_change = provider
+        .on_exchange_rate_change(|_| async move {
+            check().await;
+            Ok(())
+        })
+        .fuse();
+    let poll = async {
+        loop {
+            delay_for(poll_interval).await;
+            check().await;
+        }
+    }
+    .fuse();
+    pin_mut!(on_exchange_rate_change, poll);
+
+    select! {
+        result = on_exchange_rate_change => result,
+        _ = poll => Ok(()),
     }
 }
 /// Monitor the collateralization rate of all vaults and request auctions.
@@ -258,8 +337,11 @@ pub async fn check_collateral_of_vaults<B: BitcoinCoreApi>(
                     // try to send the event, but ignore the returned result since
                     // the only way it can fail is if the channel is closed
                     let _ = event_channel.send(RequestEvent::Opened).await;
-                },
-                Err(e) => error!("Failed to auction vault {}: {}", vault.id, e.to_string()),
+                }
+                Err(e) => {
+                    metrics::record_extrinsic_failure(&e);
+                    error!("Failed to auction vault {}: {}", vault.id, e.to_string())
+                }
             };
         }
     }
@@ -268,7 +350,7 @@ pub async fn check_collateral_of_vaults<B: BitcoinCoreApi>(
 
 async fn auction_replace<
     B: BitcoinCoreApi,
-    P: DotBalancesPallet + ReplacePallet + VaultRegistryPallet,
+    P: DotBalancesPallet + ReplacePallet + VaultRegistryPallet + ExtrinsicFuncs,
 >(
     provider: &Arc<P>,
     btc_rpc: &Arc<B>,
@@ -280,10 +362,21 @@ async fn auction_replace<
         .await?;
 
     // don't auction vault if we can't afford to replace it
-    if collateral > provider.get_free_dot_balance().await? {
+    let free_balance = provider.get_free_dot_balance().await?;
+    if collateral > free_balance {
         return Err(Error::InsufficientFunds);
     }
 
+    let call = AuctionReplaceCall {
+        old_vault: vault.id.clone(),
+        btc_amount,
+        collateral,
+        btc_address: BtcAddress::default(),
+    };
+    check_affordable(&**provider, call, collateral, free_balance).await?;
+
+    let btc_address = btc_rpc.get_new_address().await?;
+
     info!(
         "Vault {} is below auction threshold; replacing {} BTC with {} DOT",
         vault.id, btc_amount, collateral
@@ -291,12 +384,7 @@ async fn auction_replace<
 
     // TODO: retry auctioning?
     provider
-        .auction_replace(
-            vault.id.clone(),
-            btc_amount,
-            collateral,
-            btc_rpc.get_new_address().await?,
-        )
+        .auction_replace(vault.id.clone(), btc_amount, collateral, btc_address)
         .await?;
 
     Ok(())
@@ -340,12 +428,9 @@ mod tests {
         Block, BlockHash, Error as BitcoinError, GetBlockResult, LockedTransaction, PartialAddress,
         Transaction, TransactionMetadata, Txid, PUBLIC_KEY_SIZE,
     };
-    use runtime::{
-        pallets::Core, AccountId, BtcAddress, BtcPublicKey, Error as RuntimeError, H256Le,
-        PolkaBtcReplaceRequest, PolkaBtcRuntime, PolkaBtcVault,
-    };
+    use runtime::{testing::MockParachain, IssuePallet};
     use sp_core::H256;
-    use std::time::Duration;
+    use sp_keyring::AccountKeyring;
 
     macro_rules! assert_err {
         ($result:expr, $err:pat) => {{
@@ -364,6 +449,7 @@ mod tests {
         trait BitcoinCoreApi {
             async fn wait_for_block(&self, height: u32, delay: Duration, num_confirmations: u32) -> Result<BlockHash, BitcoinError>;
             async fn get_block_count(&self) -> Result<u64, BitcoinError>;
+            async fn get_balance(&self) -> Result<u64, BitcoinError>;
             async fn get_raw_tx_for(&self, txid: &Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
             async fn get_proof_for(&self, txid: Txid, block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError>;
            async  fn get_block_hash_for(&self, height: u32) -> Result<BlockHash, BitcoinError>;
@@ -374,7 +460,9 @@ mod tests {
                 &self,
                 public_key: P,
                 secret_key: Vec<u8>,
-            ) -> Result<(), BitcoinError>;
+                rescan: bool,
+            ) -> Result<bool, BitcoinError>;
+            async fn rescan_blockchain(&self, start_height: u32) -> Result<(), BitcoinError>;
             async fn get_best_block_hash(&self) -> Result<BlockHash, BitcoinError>;
             async fn get_block(&self, hash: &BlockHash) -> Result<Block, BitcoinError>;
             async fn get_block_info(&self, hash: &BlockHash) -> Result<GetBlockResult, BitcoinError>;
@@ -415,64 +503,40 @@ mod tests {
         }
     }
 
-    mockall::mock! {
-        Provider {}
-
-        #[async_trait]
-        pub trait VaultRegistryPallet {
-            async fn get_vault(&self, vault_id: AccountId) -> Result<PolkaBtcVault, RuntimeError>;
-            async fn get_all_vaults(&self) -> Result<Vec<PolkaBtcVault>, RuntimeError>;
-            async fn register_vault(&self, collateral: u128, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
-            async fn lock_additional_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
-            async fn withdraw_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
-            async fn update_public_key(&self, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
-            async fn register_address(&self, btc_address: BtcAddress) -> Result<(), RuntimeError>;
-            async fn get_required_collateral_for_polkabtc(&self, amount_btc: u128) -> Result<u128, RuntimeError>;
-            async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<u128, RuntimeError>;
-            async fn is_vault_below_auction_threshold(&self, vault_id: AccountId) -> Result<bool, RuntimeError>;
-        }
+    /// Returns Alice's handle to a parachain on which Bob is a vault with 100 issued tokens,
+    /// which require a collateral of 150, and Alice is a vault with the given free balance.
+    async fn parachain_with_vaults(free_balance: u128) -> MockParachain {
+        let old_vault_id = AccountKeyring::Bob.to_account_id();
+        let old_vault = MockParachain::new(old_vault_id.clone());
+        old_vault.set_free_dot_balance(old_vault_id.clone(), 150);
+        old_vault
+            .register_vault(150, Default::default())
+            .await
+            .unwrap();
+        let user = old_vault.with_account(AccountKeyring::Charlie.to_account_id());
+        let issue = user.request_issue(100, old_vault_id, 0).await.unwrap();
+        user.execute_issue(issue.issue_id, Default::default(), vec![], vec![])
+            .await
+            .unwrap();
 
-        #[async_trait]
-        pub trait ReplacePallet {
-            async fn request_replace(&self, amount: u128, griefing_collateral: u128)
-                -> Result<H256, RuntimeError>;
-            async fn withdraw_replace(&self, replace_id: H256) -> Result<(), RuntimeError>;
-            async fn accept_replace(&self, replace_id: H256, collateral: u128, btc_address: BtcAddress) -> Result<(), RuntimeError>;
-            async fn auction_replace(
-                &self,
-                old_vault: AccountId,
-                btc_amount: u128,
-                collateral: u128,
-                btc_address: BtcAddress,
-            ) -> Result<(), RuntimeError>;
-            async fn execute_replace(
-                &self,
-                replace_id: H256,
-                tx_id: H256Le,
-                merkle_proof: Vec<u8>,
-                raw_tx: Vec<u8>,
-            ) -> Result<(), RuntimeError>;
-            async fn cancel_replace(&self, replace_id: H256) -> Result<(), RuntimeError>;
-            async fn get_new_vault_replace_requests(
-                &self,
-                account_id: AccountId,
-            ) -> Result<Vec<(H256, PolkaBtcReplaceRequest)>, RuntimeError>;
-            async fn get_old_vault_replace_requests(
-                &self,
-                account_id: AccountId,
-            ) -> Result<Vec<(H256, PolkaBtcReplaceRequest)>, RuntimeError>;
-            async fn get_replace_period(&self) -> Result<u32, RuntimeError>;
-            async fn set_replace_period(&self, period: u32) -> Result<(), RuntimeError>;
-            async fn get_replace_request(&self, replace_id: H256) -> Result<PolkaBtcReplaceRequest, RuntimeError>;
-        }
+        let vault_id = AccountKeyring::Alice.to_account_id();
+        let parachain = old_vault.with_account(vault_id.clone());
+        parachain
+            .register_vault(0, Default::default())
+            .await
+            .unwrap();
+        parachain.set_free_dot_balance(vault_id, free_balance);
+        parachain
+    }
 
-        #[async_trait]
-        pub trait DotBalancesPallet {
-            async fn get_free_dot_balance(&self) -> Result<<PolkaBtcRuntime as Core>::Balance, RuntimeError>;
-            async fn get_free_dot_balance_for_id(&self, id: AccountId) -> Result<<PolkaBtcRuntime as Core>::Balance, RuntimeError>;
-            async fn get_reserved_dot_balance(&self) -> Result<<PolkaBtcRuntime as Core>::Balance, RuntimeError>;
-            async fn transfer_to(&self, destination: AccountId, amount: u128) -> Result<(), RuntimeError>;
-        }
+    /// Bob requests to be replaced for all of his issued tokens.
+    async fn request_replace(parachain: &MockParachain) -> RequestReplaceEvent<PolkaBtcRuntime> {
+        let old_vault = parachain.with_account(AccountKeyring::Bob.to_account_id());
+        old_vault.request_replace(100, 0).await.unwrap();
+        parachain
+            .events::<RequestReplaceEvent<PolkaBtcRuntime>>()
+            .pop()
+            .unwrap()
     }
 
     #[tokio::test]
@@ -482,15 +546,13 @@ mod tests {
             .expect_get_new_address()
             .returning(|| Ok(BtcAddress::default()));
 
-        let mut provider = MockProvider::default();
-        provider
-            .expect_get_required_collateral_for_polkabtc()
-            .returning(|_| Ok(100));
-        provider.expect_get_free_dot_balance().returning(|| Ok(50));
-
-        let vault = PolkaBtcVault::default();
+        let parachain = parachain_with_vaults(50).await;
+        let vault = parachain
+            .get_vault(AccountKeyring::Bob.to_account_id())
+            .await
+            .unwrap();
         assert_err!(
-            auction_replace(&Arc::new(provider), &Arc::new(bitcoin), &vault).await,
+            auction_replace(&Arc::new(parachain), &Arc::new(bitcoin), &vault).await,
             Error::InsufficientFunds
         );
     }
@@ -502,20 +564,43 @@ mod tests {
             .expect_get_new_address()
             .returning(|| Ok(BtcAddress::default()));
 
-        let mut provider = MockProvider::default();
-        provider
-            .expect_get_required_collateral_for_polkabtc()
-            .returning(|_| Ok(100));
-        provider.expect_get_free_dot_balance().returning(|| Ok(50));
-
-        let event = RequestReplaceEvent {
-            amount: Default::default(),
-            old_vault_id: Default::default(),
-            replace_id: Default::default(),
-        };
+        let parachain = parachain_with_vaults(50).await;
+        let event = request_replace(&parachain).await;
         assert_err!(
-            handle_replace_request(Arc::new(provider), Arc::new(bitcoin), &event).await,
+            handle_replace_request(Arc::new(parachain), Arc::new(bitcoin), &event).await,
             Error::InsufficientFunds
         );
     }
+
+    #[tokio::test]
+    async fn test_handle_replace_request_checks_fee() {
+        // (free balance, fee, whether the request is accepted)
+        for &(free_balance, fee, accepted) in &[(155, 10, false), (160, 10, true)] {
+            let mut bitcoin = MockBitcoin::default();
+            // an address is only derived for requests that are accepted
+            bitcoin
+                .expect_get_new_address()
+                .times(accepted as usize)
+                .returning(|| Ok(BtcAddress::default()));
+
+            let parachain = parachain_with_vaults(free_balance).await;
+            parachain.set_extrinsic_fee(fee);
+
+            let event = request_replace(&parachain).await;
+            let result =
+                handle_replace_request(Arc::new(parachain.clone()), Arc::new(bitcoin), &event)
+                    .await;
+            assert_eq!(
+                parachain
+                    .events::<AcceptReplaceEvent<PolkaBtcRuntime>>()
+                    .len(),
+                accepted as usize
+            );
+            if accepted {
+                assert!(result.is_ok());
+            } else {
+                assert_err!(result, Error::InsufficientFunds);
+            }
+        }
+    }
 }
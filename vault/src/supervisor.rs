@@ -0,0 +1,364 @@
+use crate::constants::get_retry_policy;
+use crate::Error;
+use backoff::{backoff::Backoff, ExponentialBackoff};
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::future::{select, Either};
+use futures::{pin_mut, Future, StreamExt};
+use log::{error, info, warn};
+use parity_scale_codec::{Decode, Encode};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::time::delay_for;
+
+/// A service that ran for this long before failing is considered to have recovered, so
+/// its backoff starts over.
+const SERVICE_RECOVERY_TIME: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, Serialize, JsonSchema)]
+pub enum ServiceStatus {
+    Running,
+    /// The service failed and is waiting to be restarted.
+    Restarting,
+    /// The service completed, it is not restarted.
+    Stopped,
+    /// The service failed and is not restarted, the vault shuts down.
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, Serialize, JsonSchema)]
+pub struct ServiceHealth {
+    pub name: String,
+    pub status: ServiceStatus,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+}
+
+/// The health of all supervised services, shared with the api.
+#[derive(Clone, Default)]
+pub struct HealthMonitor(Arc<RwLock<BTreeMap<&'static str, ServiceHealth>>>);
+
+impl HealthMonitor {
+    /// Returns the health of every service, ordered by name.
+    pub fn services(&self) -> Vec<ServiceHealth> {
+        self.0.read().unwrap().values().cloned().collect()
+    }
+
+    /// Returns true if no service is failed or waiting to be restarted.
+    pub fn is_healthy(&self) -> bool {
+        self.0.read().unwrap().values().all(|service| {
+            matches!(
+                service.status,
+                ServiceStatus::Running | ServiceStatus::Stopped
+            )
+        })
+    }
+
+    fn register(&self, name: &'static str) {
+        self.0.write().unwrap().insert(
+            name,
+            ServiceHealth {
+                name: name.to_string(),
+                status: ServiceStatus::Running,
+                restarts: 0,
+                last_error: None,
+            },
+        );
+    }
+
+    fn update<F: FnOnce(&mut ServiceHealth)>(&self, name: &'static str, f: F) {
+        if let Some(service) = self.0.write().unwrap().get_mut(name) {
+            f(service);
+        }
+    }
+}
+
+/// Completes once the supervisor stops its services.
+async fn stopped(mut shutdown: watch::Receiver<bool>) {
+    while let Some(false) = shutdown.recv().await {}
+}
+
+/// What to do when a service fails.
+#[derive(Clone)]
+pub enum RestartPolicy {
+    /// Shut down the vault.
+    Fatal,
+    /// Restart the service after the delay given by the backoff, which grows with every
+    /// consecutive failure. Shut down the vault once the backoff expires.
+    Backoff(ExponentialBackoff),
+}
+
+impl RestartPolicy {
+    /// Restart with the default retrying policy, see `get_retry_policy`, but wait at most
+    /// `max_interval` between restarts and give up after `max_elapsed_time` of failures.
+    /// Without a `max_elapsed_time` the service is restarted indefinitely.
+    pub fn backoff(max_interval: Duration, max_elapsed_time: Option<Duration>) -> Self {
+        RestartPolicy::Backoff(ExponentialBackoff {
+            max_interval,
+            max_elapsed_time,
+            ..get_retry_policy()
+        })
+    }
+}
+
+/// Runs the services of the vault, restarts them according to their `RestartPolicy` and
+/// tracks their health.
+pub struct Supervisor {
+    health: HealthMonitor,
+    fatal_tx: UnboundedSender<Error>,
+    fatal_rx: UnboundedReceiver<Error>,
+    shutdown_tx: watch::Sender<bool>,
+    shutdown_rx: watch::Receiver<bool>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        let (fatal_tx, fatal_rx) = mpsc::unbounded();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        Self {
+            health: Default::default(),
+            fatal_tx,
+            fatal_rx,
+            shutdown_tx,
+            shutdown_rx,
+        }
+    }
+
+    pub fn health(&self) -> HealthMonitor {
+        self.health.clone()
+    }
+
+    /// Run the service created by `service`, which is called again on every restart.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - unique name of the service, used in the logs and the health report
+    /// * `policy` - how to handle failures of the service
+    /// * `service` - creates the future that runs the service
+    pub fn spawn<F, R, E>(&self, name: &'static str, policy: RestartPolicy, service: F)
+    where
+        F: Fn() -> R + Send + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Error> + Send + 'static,
+    {
+        let health = self.health.clone();
+        let fatal_tx = self.fatal_tx.clone();
+        let shutdown = self.shutdown_rx.clone();
+        health.register(name);
+
+        tokio::spawn(async move {
+            let mut backoff = match policy {
+                RestartPolicy::Fatal => None,
+                RestartPolicy::Backoff(backoff) => Some(backoff),
+            };
+
+            loop {
+                let started = Instant::now();
+                let service = service();
+                let stopped = stopped(shutdown.clone());
+                // run the service in its own task such that panics are caught
+                let error = match tokio::spawn(async move {
+                    pin_mut!(service);
+                    pin_mut!(stopped);
+                    match select(service, stopped).await {
+                        Either::Left((result, _)) => result,
+                        Either::Right(_) => Ok(()),
+                    }
+                })
+                .await
+                {
+                    Ok(Ok(())) => {
+                        info!("Service {} stopped", name);
+                        health.update(name, |service| service.status = ServiceStatus::Stopped);
+                        return;
+                    }
+                    Ok(Err(e)) => {
+                        let e: Error = e.into();
+                        e.to_string()
+                    }
+                    Err(e) => format!("panicked: {}", e),
+                };
+                error!("Service {} failed: {}", name, error);
+                health.update(name, |service| service.last_error = Some(error.clone()));
+                if *shutdown.borrow() {
+                    health.update(name, |service| service.status = ServiceStatus::Stopped);
+                    return;
+                }
+
+                let delay = backoff.as_mut().and_then(|backoff| {
+                    if started.elapsed() >= SERVICE_RECOVERY_TIME {
+                        backoff.reset();
+                    }
+                    backoff.next_backoff()
+                });
+                match delay {
+                    Some(delay) => {
+                        warn!("Restarting service {} in {:?}", name, delay);
+                        health.update(name, |service| service.status = ServiceStatus::Restarting);
+                        delay_for(delay).await;
+                        health.update(name, |service| {
+                            service.status = ServiceStatus::Running;
+                            service.restarts += 1;
+                        });
+                    }
+                    None => {
+                        health.update(name, |service| service.status = ServiceStatus::Failed);
+                        // the receiver only closes once the vault shuts down
+                        let _ = fatal_tx.unbounded_send(Error::ServiceFailed(name, error));
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Run a service that can not be restarted, its failure shuts down the vault.
+    pub fn spawn_once<R, E>(&self, name: &'static str, service: R)
+    where
+        R: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Error> + Send + 'static,
+    {
+        let service = std::sync::Mutex::new(Some(service));
+        self.spawn(name, RestartPolicy::Fatal, move || {
+            // the service is only taken once, since the policy never restarts it
+            let service = service.lock().unwrap().take();
+            async move {
+                match service {
+                    Some(service) => service.await,
+                    None => Ok(()),
+                }
+            }
+        });
+    }
+
+    /// Wait until a service failed fatally or `shutdown` completes, then stop all services.
+    /// Returns Ok unless a service failed.
+    pub async fn wait<F: Future<Output = ()>>(self, shutdown: F) -> Result<(), Error> {
+        let Self {
+            fatal_tx,
+            fatal_rx,
+            shutdown_tx,
+            ..
+        } = self;
+        // the spawned services hold the remaining senders
+        drop(fatal_tx);
+        pin_mut!(shutdown);
+        let result = match select(fatal_rx.into_future(), shutdown).await {
+            Either::Left(((Some(error), _), _)) => Err(error),
+            _ => Ok(()),
+        };
+        // fails if all services stopped already
+        let _ = shutdown_tx.broadcast(true);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn fast_backoff(max_elapsed_time: Option<Duration>) -> RestartPolicy {
+        RestartPolicy::Backoff(ExponentialBackoff {
+            initial_interval: Duration::from_millis(1),
+            current_interval: Duration::from_millis(1),
+            max_elapsed_time,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_service_is_restarted() {
+        let supervisor = Supervisor::new();
+        let health = supervisor.health();
+        let attempts = Arc::new(AtomicU32::new(0));
+
+        let service_attempts = attempts.clone();
+        supervisor.spawn("flaky", fast_backoff(None), move || {
+            let attempt = service_attempts.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match attempt {
+                    0 => Err(Error::ChannelClosed),
+                    1 => panic!("crashed"),
+                    _ => Ok(()),
+                }
+            }
+        });
+
+        assert!(supervisor.wait(future::pending()).await.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        let service = &health.services()[0];
+        assert_eq!(service.status, ServiceStatus::Stopped);
+        assert_eq!(service.restarts, 2);
+        assert!(service.last_error.as_ref().unwrap().contains("panicked"));
+        assert!(health.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_fatal_failure_stops_vault() {
+        let supervisor = Supervisor::new();
+        let health = supervisor.health();
+        supervisor.spawn_once("scheduler", async { Err(Error::ChannelClosed) });
+        supervisor.spawn("listener", fast_backoff(None), || {
+            future::pending::<Result<(), Error>>()
+        });
+
+        assert!(matches!(
+            supervisor.wait(future::pending()).await,
+            Err(Error::ServiceFailed("scheduler", _))
+        ));
+        assert!(!health.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_expired_backoff_is_fatal() {
+        let supervisor = Supervisor::new();
+        supervisor.spawn(
+            "listener",
+            fast_backoff(Some(Duration::from_millis(0))),
+            || async { Err::<(), _>(Error::ChannelClosed) },
+        );
+        assert!(supervisor.wait(future::pending()).await.is_err());
+    }
+
+    #[test]
+    fn test_backoff_policy() {
+        let max_interval = Duration::from_secs(60);
+        match RestartPolicy::backoff(max_interval, None) {
+            RestartPolicy::Backoff(backoff) => {
+                assert_eq!(backoff.max_interval, max_interval);
+                assert_eq!(backoff.max_elapsed_time, None);
+                assert_eq!(
+                    backoff.initial_interval,
+                    get_retry_policy().initial_interval
+                );
+            }
+            RestartPolicy::Fatal => panic!("expected backoff"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_services() {
+        let supervisor = Supervisor::new();
+        let health = supervisor.health();
+        supervisor.spawn("listener", fast_backoff(None), || {
+            future::pending::<Result<(), Error>>()
+        });
+
+        assert!(supervisor.wait(future::ready(())).await.is_ok());
+        // the service observes the shutdown asynchronously
+        while health.services()[0].status != ServiceStatus::Stopped {
+            tokio::task::yield_now().await;
+        }
+    }
+}
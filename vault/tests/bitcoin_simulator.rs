@@ -240,6 +240,10 @@ impl BitcoinCoreApi for MockBitcoinCore {
     async fn get_block_count(&self) -> Result<u64, BitcoinError> {
         Ok(self.blocks.read().await.len().try_into().unwrap())
     }
+    async fn get_balance(&self) -> Result<u64, BitcoinError> {
+        // the simulated wallet does not track its funds
+        Ok(0)
+    }
     async fn get_raw_tx_for(
         &self,
         txid: &Txid,
@@ -293,7 +297,11 @@ impl BitcoinCoreApi for MockBitcoinCore {
         &self,
         public_key: P,
         secret_key: Vec<u8>,
-    ) -> Result<(), BitcoinError> {
+        rescan: bool,
+    ) -> Result<bool, BitcoinError> {
+        Ok(true)
+    }
+    async fn rescan_blockchain(&self, start_height: u32) -> Result<(), BitcoinError> {
         Ok(())
     }
     async fn get_best_block_hash(&self) -> Result<BlockHash, BitcoinError> {
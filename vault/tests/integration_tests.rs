@@ -53,7 +53,7 @@ use tempdir::TempDir;
 use tokio::sync::{RwLock, Mutex, OwnedMutexGuard};
 use tokio::time::delay_for;
 use vault;
-use vault::{IssueRequests, RequestEvent};
+use vault::{IssueRequests, PaymentJournal, RequestEvent};
 use futures::future::{join, try_join};
 use tokio::time::timeout;
 use futures::Future;
@@ -73,9 +73,13 @@ impl Translate for Txid {
 
 fn default_vault_args() -> vault::Opts {
     vault::Opts {
-        polka_btc_url: "".to_string(), // only used by bin
+        polka_btc_url: vec![],         // only used by bin
         http_addr: "".to_string(),     // only used by bin
-        rpc_cors_domain: "*".to_string(),
+        rpc_cors_domain: None,
+        api_auth: runtime::cli::ApiAuthOpts {
+            rpc_auth_file: None,
+            rpc_audit_log: None,
+        },
         auto_register_with_collateral: Some(50000000),
         no_auto_auction: false,
         no_auto_replace: false,
@@ -87,7 +91,16 @@ fn default_vault_args() -> vault::Opts {
             keyname: None,
             keyfile: None,
             keyring: Some(AccountKeyring::Bob),
+            keystore: None,
+            password_env: None,
+            password_fd: None,
+            remote_signer: None,
         },
+        storage_cache: false,
+        payment_journal: "".to_string(), // only used by bin
+        shutdown_timeout: 60,
+        restart_max_interval: 3600,
+        restart_max_elapsed: 86400,
         btc_confirmations: None,
         no_issue_execution: false,
         bitcoin: bitcoin::cli::BitcoinOpts {
@@ -124,6 +137,12 @@ async fn default_provider_client(key: AccountKeyring) -> (JsonRpseeClient, TempD
     return (client, tmp);
 }
 
+fn setup_journal(tmp: &TempDir) -> Arc<PaymentJournal> {
+    let journal = PaymentJournal::open(tmp.path().join("payment-journal"))
+        .expect("Error opening payment journal");
+    Arc::new(journal)
+}
+
 async fn setup_provider(client: JsonRpseeClient, key: AccountKeyring) -> Arc<PolkaBtcProvider> {
     let signer = PairSigner::<PolkaBtcRuntime, _>::new(key.pair());
     let ret = PolkaBtcProvider::new(client, signer)
@@ -171,7 +190,7 @@ async fn test_redeem_succeeds() {
     let address = BtcAddress::P2PKH(H160::from_slice(&[2;20]));
     let vault_id = vault_provider.get_account_id().clone();
     let fut = test_service(
-        vault::service::listen_for_redeem_requests(vault_provider, btc_rpc, 0),
+        vault::service::listen_for_redeem_requests(vault_provider, btc_rpc, setup_journal(&_tmp_dir), 0),
         async {
             let redeem_id = user_provider.request_redeem(10000, address, vault_id).await.unwrap();
             assert_redeem_event(Duration::from_secs(30), user_provider, redeem_id).await;
@@ -228,6 +247,7 @@ async fn test_replace_succeeds() {
             vault::service::listen_for_accept_replace(
                 old_vault_provider.clone(), 
                 btc_rpc.clone(), 
+                setup_journal(&_tmp_dir),
                 0
             )
         ),
@@ -308,6 +328,7 @@ async fn test_auction_replace_succeeds() {
             vault::service::listen_for_auction_replace(
                 old_vault_provider.clone(), 
                 btc_rpc.clone(), 
+                setup_journal(&_tmp_dir),
                 0
             )
         ),
@@ -344,7 +365,7 @@ async fn test_refund_succeeds() {
 
     relayer_provider.set_exchange_rate_info(FixedU128::saturating_from_rational(1u128, 100)).await.unwrap();
     
-    let refund_service = vault::service::listen_for_refund_requests(vault_provider.clone(), btc_rpc.clone(), 0);
+    let refund_service = vault::service::listen_for_refund_requests(vault_provider.clone(), btc_rpc.clone(), setup_journal(&_tmp_dir), 0);
 
     let issue_amount = 100000;
     let fee = user_provider.get_issue_fee().await.unwrap();
@@ -421,7 +442,7 @@ async fn test_issue_overpayment_succeeds() {
 
     relayer_provider.set_exchange_rate_info(FixedU128::saturating_from_rational(1u128, 100)).await.unwrap();
     
-    let refund_service = vault::service::listen_for_refund_requests(vault_provider.clone(), btc_rpc.clone(), 0);
+    let refund_service = vault::service::listen_for_refund_requests(vault_provider.clone(), btc_rpc.clone(), setup_journal(&_tmp_dir), 0);
 
     let issue_amount = 100000;
     let over_payment_factor = 3;
@@ -598,7 +619,7 @@ async fn test_execute_open_requests_succeeds() {
     let redeem_id = user_provider.request_redeem(10000, address, vault_provider.get_account_id().clone()).await.unwrap();
 
     let ret = join(
-        vault::service::execute_open_requests(vault_provider, Arc::new(btc_rpc), 0),
+        vault::service::execute_open_requests(vault_provider, Arc::new(btc_rpc), setup_journal(&_tmp_dir), 0),
         assert_redeem_event(Duration::from_secs(30), user_provider, redeem_id)
     ).await;
     ret.0.unwrap();